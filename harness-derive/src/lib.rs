@@ -0,0 +1,340 @@
+//! Derive macros for harness tools
+//!
+//! `#[derive(ToolArgs)]` turns a struct of tool arguments into the JSON
+//! schema the model sees, so a tool only declares its arguments once:
+//!
+//! ```ignore
+//! use harness::tools::ToolArgs;
+//! use serde::Deserialize;
+//!
+//! /// Read the contents of a file.
+//! #[derive(Deserialize, ToolArgs)]
+//! struct ReadFileArgs {
+//!     /// Path to the file (relative to workspace root)
+//!     path: String,
+//!     /// Starting line number (1-indexed)
+//!     #[tool(minimum = 1)]
+//!     start_line: Option<usize>,
+//! }
+//! ```
+//!
+//! Field doc comments become property descriptions and the struct doc comment
+//! becomes the tool description. `Option` fields and fields marked
+//! `#[serde(default)]` are optional, and `#[serde(rename = "...")]` is
+//! honoured. `#[tool(...)]` adds constraints: `enum_values = [..]`,
+//! `minimum = N` and `maximum = N`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, Lit, LitStr, Token};
+
+#[proc_macro_derive(ToolArgs, attributes(tool))]
+pub fn derive_tool_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "ToolArgs can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "ToolArgs can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut arg_fields = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let serde = SerdeField::parse(&field.attrs)?;
+        let constraints = ToolField::parse(&field.attrs)?;
+
+        let key = serde.rename.unwrap_or_else(|| ident.to_string());
+        let description = doc_comment(&field.attrs);
+        let mut arg = quote! {
+            ::harness::tools::ArgField::new::<#ty>(#key, #description)
+        };
+        if serde.default {
+            arg = quote! { #arg.optional() };
+        }
+        if let Some(values) = constraints.enum_values {
+            arg = quote! { #arg.with_enum([#(#values),*]) };
+        }
+        if constraints.minimum.is_some() || constraints.maximum.is_some() {
+            let minimum = option_f64(constraints.minimum);
+            let maximum = option_f64(constraints.maximum);
+            arg = quote! { #arg.with_range(#minimum, #maximum) };
+        }
+        arg_fields.push(arg);
+    }
+
+    let description = doc_comment(&input.attrs);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::harness::tools::ToolArgs for #name #ty_generics #where_clause {
+            fn description() -> &'static str {
+                #description
+            }
+
+            fn fields() -> ::std::vec::Vec<::harness::tools::ArgField> {
+                ::std::vec![#(#arg_fields),*]
+            }
+        }
+
+        impl #impl_generics ::harness::tools::ArgSchema for #name #ty_generics #where_clause {
+            fn property_schema() -> ::harness::tools::PropertySchema {
+                <Self as ::harness::tools::ToolArgs>::property_schema()
+            }
+        }
+    })
+}
+
+fn option_f64(value: Option<Expr>) -> TokenStream2 {
+    match value {
+        Some(expr) => quote! { ::std::option::Option::Some((#expr) as f64) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+/// Concatenated `///` lines, trimmed and joined with spaces.
+fn doc_comment(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(s) => Some(s.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join(" ")
+}
+
+/// The parts of `#[serde(...)]` that change the schema.
+#[derive(Default)]
+struct SerdeField {
+    rename: Option<String>,
+    default: bool,
+}
+
+impl SerdeField {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                    if meta.input.peek(Token![=]) {
+                        meta.value()?.parse::<Expr>()?;
+                    }
+                } else if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|nested| {
+                        if nested.input.peek(Token![=]) {
+                            nested.value()?.parse::<Expr>()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Constraints from `#[tool(...)]`.
+#[derive(Default)]
+struct ToolField {
+    enum_values: Option<Vec<Lit>>,
+    minimum: Option<Expr>,
+    maximum: Option<Expr>,
+}
+
+impl ToolField {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("tool")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("enum_values") {
+                    let input = meta.value()?;
+                    let content;
+                    syn::bracketed!(content in input);
+                    let values = Punctuated::<Lit, Token![,]>::parse_terminated(&content)?;
+                    parsed.enum_values = Some(values.into_iter().collect());
+                } else if meta.path.is_ident("minimum") {
+                    parsed.minimum = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("maximum") {
+                    parsed.maximum = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `enum_values`, `minimum` or `maximum`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    /// The whole expansion, as a token string.
+    fn expand_to_string(input: DeriveInput) -> String {
+        expand(input).unwrap().to_string()
+    }
+
+    fn assert_expands_to(expanded: &str, expected: TokenStream2) {
+        let expected = expected.to_string();
+        assert!(
+            expanded.contains(&expected),
+            "expected `{}` in `{}`",
+            expected,
+            expanded
+        );
+    }
+
+    #[test]
+    fn test_doc_comments_become_descriptions() {
+        let expanded = expand_to_string(parse_quote! {
+            /// Read a file.
+            ///
+            ///   Lines are numbered.
+            struct ReadArgs {
+                /// Path to the file
+                path: String,
+                line: Option<usize>,
+            }
+        });
+
+        assert_expands_to(
+            &expanded,
+            quote! {
+                fn description() -> &'static str {
+                    "Read a file. Lines are numbered."
+                }
+            },
+        );
+        assert_expands_to(
+            &expanded,
+            quote! {
+                ::std::vec![
+                    ::harness::tools::ArgField::new::<String>("path", "Path to the file"),
+                    ::harness::tools::ArgField::new::<Option<usize> >("line", "")
+                ]
+            },
+        );
+    }
+
+    #[test]
+    fn test_serde_attributes_rename_and_make_optional() {
+        let expanded = expand_to_string(parse_quote! {
+            struct FindArgs {
+                #[serde(rename = "name", skip_serializing_if = "String::is_empty")]
+                file_name: String,
+                #[serde(default = "default_roots")]
+                roots: Vec<String>,
+            }
+        });
+
+        assert_expands_to(
+            &expanded,
+            quote! { ::harness::tools::ArgField::new::<String>("name", "") },
+        );
+        assert_expands_to(
+            &expanded,
+            quote! { ::harness::tools::ArgField::new::<Vec<String> >("roots", "").optional() },
+        );
+    }
+
+    #[test]
+    fn test_tool_attributes_add_constraints() {
+        let expanded = expand_to_string(parse_quote! {
+            struct ListArgs {
+                #[tool(enum_values = ["file", "dir"])]
+                kind: String,
+                #[tool(minimum = 1, maximum = 100)]
+                limit: u32,
+                #[tool(maximum = 8)]
+                depth: u8,
+            }
+        });
+
+        assert_expands_to(
+            &expanded,
+            quote! { ::harness::tools::ArgField::new::<String>("kind", "").with_enum(["file", "dir"]) },
+        );
+        assert_expands_to(
+            &expanded,
+            quote! {
+                ::harness::tools::ArgField::new::<u32>("limit", "").with_range(
+                    ::std::option::Option::Some((1) as f64),
+                    ::std::option::Option::Some((100) as f64)
+                )
+            },
+        );
+        assert_expands_to(
+            &expanded,
+            quote! {
+                ::harness::tools::ArgField::new::<u8>("depth", "").with_range(
+                    ::std::option::Option::None,
+                    ::std::option::Option::Some((8) as f64)
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsupported_input() {
+        let cases: [(DeriveInput, &str); 3] = [
+            (
+                parse_quote! { struct Pair(String, u32); },
+                "ToolArgs can only be derived for structs with named fields",
+            ),
+            (
+                parse_quote! { enum Level { L0, L1 } },
+                "ToolArgs can only be derived for structs",
+            ),
+            (
+                parse_quote! {
+                    struct Args {
+                        #[tool(pattern = "*.rs")]
+                        glob: String,
+                    }
+                },
+                "expected `enum_values`, `minimum` or `maximum`",
+            ),
+        ];
+        for (input, expected) in cases {
+            let error = expand(input).unwrap_err();
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Context-window management for the agent conversation
+//!
+//! Long tool-heavy runs grow `AgentLoop::conversation_history` until it no
+//! longer fits the model's context window. Once the estimated size crosses
+//! [`ContextBudget::trigger_tokens`], the agent compacts the history in two
+//! stages:
+//!
+//! 1. Cut old tool outputs down to [`ContextBudget::max_tool_output_chars`].
+//! 2. If that is not enough, replace older turns with a summary written by the
+//!    model (or a short omission note when no summary can be produced).
+//!
+//! System prompts, the original user request and the current plan are pinned
+//! and never removed, and the most recent [`ContextBudget::keep_recent`]
+//! messages are left untouched.
+
+use model::config::ModelDefaults;
+use model::tokens::estimate_conversation_tokens;
+use model::types::{ChatMessage, MessageRole};
+
+/// How much of the context window the conversation may use.
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    /// Context window of the model, in tokens
+    pub max_tokens: usize,
+    /// Fraction of `max_tokens` at which compaction starts
+    pub compact_threshold: f32,
+    /// Number of most recent messages that are never compacted
+    pub keep_recent: usize,
+    /// Older tool outputs are cut to this many characters
+    pub max_tool_output_chars: usize,
+    /// Ask the model to summarize older turns when truncation is not enough
+    pub summarize: bool,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            max_tokens: ModelDefaults::default().context_length as usize,
+            compact_threshold: 0.8,
+            keep_recent: 6,
+            max_tool_output_chars: 1_000,
+            summarize: true,
+        }
+    }
+}
+
+impl ContextBudget {
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Estimated size at which compaction kicks in.
+    pub fn trigger_tokens(&self) -> usize {
+        (self.max_tokens as f64 * self.compact_threshold as f64) as usize
+    }
+
+    pub fn needs_compaction(&self, history: &[ChatMessage]) -> bool {
+        estimate_conversation_tokens(history) > self.trigger_tokens()
+    }
+
+    /// Index where the untouchable tail of recent messages starts.
+    ///
+    /// Moved back past tool results so they are never separated from the
+    /// assistant message that requested them.
+    fn tail_start(&self, history: &[ChatMessage]) -> usize {
+        let mut start = history.len().saturating_sub(self.keep_recent);
+        while start > 0 && start < history.len() && history[start].role == MessageRole::Tool {
+            start -= 1;
+        }
+        start
+    }
+}
+
+/// Whether a message must survive compaction.
+pub fn is_pinned(history: &[ChatMessage], index: usize, plan: Option<&str>) -> bool {
+    let message = &history[index];
+    if message.role == MessageRole::System {
+        return true;
+    }
+    let first_user = history.iter().position(|m| m.role == MessageRole::User);
+    if first_user == Some(index) {
+        return true;
+    }
+    match (plan, message.content.as_deref()) {
+        (Some(plan), Some(content)) => !plan.is_empty() && content.contains(plan),
+        _ => false,
+    }
+}
+
+/// Cut tool outputs outside the recent tail. Returns how many were shortened.
+pub fn truncate_tool_outputs(history: &mut [ChatMessage], budget: &ContextBudget) -> usize {
+    let tail_start = budget.tail_start(history);
+    let mut truncated = 0;
+    for message in &mut history[..tail_start] {
+        if message.role != MessageRole::Tool {
+            continue;
+        }
+        let Some(content) = &message.content else {
+            continue;
+        };
+        if let Some(short) = truncate_chars(content, budget.max_tool_output_chars) {
+            message.content = Some(short);
+            truncated += 1;
+        }
+    }
+    truncated
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> Option<String> {
+    let (cut, _) = text.char_indices().nth(max_chars)?;
+    let omitted = text[cut..].chars().count();
+    Some(format!(
+        "{}\n[... {} characters truncated]",
+        &text[..cut],
+        omitted
+    ))
+}
+
+/// History split into the parts compaction keeps and the part it replaces.
+#[derive(Debug, Clone)]
+pub struct CompactionPlan {
+    /// Pinned messages from before the recent tail, in order
+    pub pinned: Vec<ChatMessage>,
+    /// Number of pinned messages that came before the first removed one
+    pub pinned_before: usize,
+    /// Messages to be summarized away
+    pub removed: Vec<ChatMessage>,
+    /// Most recent messages, kept verbatim
+    pub recent: Vec<ChatMessage>,
+}
+
+impl CompactionPlan {
+    /// Decide what to drop. `None` if there is nothing that can be removed.
+    pub fn new(
+        history: &[ChatMessage],
+        budget: &ContextBudget,
+        plan: Option<&str>,
+    ) -> Option<Self> {
+        let tail_start = budget.tail_start(history);
+        let mut pinned = Vec::new();
+        let mut removed = Vec::new();
+        let mut pinned_before = 0;
+        for (index, message) in history[..tail_start].iter().enumerate() {
+            if is_pinned(history, index, plan) {
+                pinned.push(message.clone());
+                if removed.is_empty() {
+                    pinned_before += 1;
+                }
+            } else {
+                removed.push(message.clone());
+            }
+        }
+        if removed.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pinned,
+            pinned_before,
+            removed,
+            recent: history[tail_start..].to_vec(),
+        })
+    }
+
+    /// Plain-text rendering of the removed turns, for the summarization prompt.
+    pub fn transcript(&self, max_tool_output_chars: usize) -> String {
+        let mut lines = Vec::new();
+        for message in &self.removed {
+            let role = format!("{:?}", message.role).to_uppercase();
+            if let Some(content) = message.content.as_deref().filter(|c| !c.is_empty()) {
+                let content = truncate_chars(content, max_tool_output_chars)
+                    .unwrap_or_else(|| content.to_string());
+                lines.push(format!("{}: {}", role, content));
+            }
+            for call in message.tool_calls.iter().flatten() {
+                lines.push(format!(
+                    "{}: called {}({})",
+                    role, call.function.name, call.function.arguments
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Reassemble the history with `replacement` standing in for the removed
+    /// turns, where the first of them was. Pinned messages keep their order
+    /// and their side of it.
+    pub fn apply(self, replacement: ChatMessage) -> Vec<ChatMessage> {
+        let mut history = self.pinned;
+        history.insert(self.pinned_before, replacement);
+        history.extend(self.recent);
+        history
+    }
+
+    /// Replacement used when no summary is available.
+    pub fn omission_note(&self) -> ChatMessage {
+        ChatMessage::user(format!(
+            "[{} earlier messages were omitted to fit the context window]",
+            self.removed.len()
+        ))
+    }
+}
+
+/// Compact `history` without asking the model for a summary: truncate old
+/// tool outputs, then replace older turns with an omission note if it is
+/// still over budget. Returns whether anything changed.
+pub fn compact_without_summary(
+    history: &mut Vec<ChatMessage>,
+    budget: &ContextBudget,
+    plan: Option<&str>,
+) -> bool {
+    if !budget.needs_compaction(history) {
+        return false;
+    }
+    truncate_tool_outputs(history, budget);
+    if budget.needs_compaction(history) {
+        if let Some(compaction) = CompactionPlan::new(history, budget, plan) {
+            let note = compaction.omission_note();
+            *history = compaction.apply(note);
+        }
+    }
+    true
+}
+
+/// Message carrying a model-written summary of earlier turns.
+pub fn summary_message(summary: &str) -> ChatMessage {
+    ChatMessage::user(format!(
+        "Summary of the earlier conversation:\n{}",
+        summary.trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::types::{FunctionCall, ToolCall};
+
+    fn tool_exchange(id: &str, output: &str) -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::assistant_with_tools(
+                None,
+                vec![ToolCall {
+                    id: id.to_string(),
+                    function: FunctionCall {
+                        name: "read_file".to_string(),
+                        arguments: serde_json::json!({"path": "src/lib.rs"}),
+                    },
+                }],
+            ),
+            ChatMessage::tool_response(id, output),
+        ]
+    }
+
+    fn history() -> Vec<ChatMessage> {
+        let mut history = vec![
+            ChatMessage::system("You are a coding agent"),
+            ChatMessage::user("Fix the build"),
+            ChatMessage::user("Execute the following plan using the available tools: fix lib.rs"),
+        ];
+        for i in 0..4 {
+            history.extend(tool_exchange(&format!("call_{}", i), &"x".repeat(5_000)));
+        }
+        history.push(ChatMessage::assistant("Done"));
+        history
+    }
+
+    #[test]
+    fn test_trigger_threshold() {
+        let budget = ContextBudget::default().with_max_tokens(1_000);
+        assert_eq!(budget.trigger_tokens(), 800);
+        assert!(budget.needs_compaction(&history()));
+        assert!(!budget.needs_compaction(&history()[..3]));
+    }
+
+    #[test]
+    fn test_truncation_spares_recent_messages() {
+        let budget = ContextBudget {
+            keep_recent: 3,
+            max_tool_output_chars: 100,
+            ..Default::default()
+        };
+        let mut history = history();
+
+        let truncated = truncate_tool_outputs(&mut history, &budget);
+
+        // The last exchange plus the final answer form the recent tail
+        assert_eq!(truncated, 3);
+        let last_tool = &history[history.len() - 2];
+        assert_eq!(last_tool.content.as_ref().unwrap().len(), 5_000);
+        let first_tool = history[4].content.as_ref().unwrap();
+        assert!(first_tool.starts_with(&"x".repeat(100)));
+        assert!(first_tool.ends_with("[... 4900 characters truncated]"));
+    }
+
+    #[test]
+    fn test_plan_keeps_pinned_messages_and_whole_exchanges() {
+        let budget = ContextBudget {
+            keep_recent: 2,
+            ..Default::default()
+        };
+        let history = history();
+
+        let plan = CompactionPlan::new(&history, &budget, Some("fix lib.rs")).unwrap();
+
+        assert_eq!(plan.pinned.len(), 3);
+        assert_eq!(plan.pinned[0].role, MessageRole::System);
+        // keep_recent = 2 would start on a tool result; the tail is widened to
+        // include the assistant call that produced it.
+        assert_eq!(plan.recent.len(), 3);
+        assert!(plan.recent[0].tool_calls.is_some());
+        assert_eq!(plan.removed.len(), 6);
+        assert!(plan.transcript(10).contains("ASSISTANT: called read_file"));
+
+        let compacted = plan.apply(summary_message("read three files"));
+        assert_eq!(compacted.len(), 7);
+        assert!(compacted[3]
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("read three files"));
+    }
+
+    #[test]
+    fn test_summary_takes_the_place_of_the_removed_turns() {
+        let budget = ContextBudget {
+            keep_recent: 1,
+            ..Default::default()
+        };
+        let mut history = vec![
+            ChatMessage::system("You are a coding agent"),
+            ChatMessage::user("Fix the build"),
+        ];
+        history.extend(tool_exchange("call_0", "first"));
+        history.push(ChatMessage::user(
+            "Execute the following plan using the available tools: fix lib.rs",
+        ));
+        history.extend(tool_exchange("call_1", "second"));
+        history.push(ChatMessage::assistant("Done"));
+
+        let plan = CompactionPlan::new(&history, &budget, Some("fix lib.rs")).unwrap();
+        assert_eq!(plan.pinned_before, 2);
+        let compacted = plan.apply(summary_message("read lib.rs twice"));
+
+        let contents: Vec<&str> = compacted
+            .iter()
+            .map(|m| m.content.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(contents.len(), 5);
+        assert_eq!(contents[1], "Fix the build");
+        assert!(contents[2].contains("read lib.rs twice"));
+        assert!(contents[3].contains("fix lib.rs"));
+        assert_eq!(contents[4], "Done");
+    }
+
+    #[test]
+    fn test_nothing_to_compact() {
+        let history = history()[..3].to_vec();
+        let budget = ContextBudget {
+            keep_recent: 0,
+            ..Default::default()
+        };
+        assert!(CompactionPlan::new(&history, &budget, Some("fix lib.rs")).is_none());
+        assert!(CompactionPlan::new(&history, &budget, None).is_some());
+    }
+
+    #[test]
+    fn test_compact_without_summary_falls_back_to_omission_note() {
+        let budget = ContextBudget {
+            max_tokens: 1_000,
+            keep_recent: 3,
+            max_tool_output_chars: 100,
+            ..Default::default()
+        };
+        let mut short = history()[..3].to_vec();
+        assert!(!compact_without_summary(&mut short, &budget, None));
+        assert_eq!(short.len(), 3);
+
+        let mut history = history();
+        assert!(compact_without_summary(&mut history, &budget, None));
+
+        // System prompt and first request survive, older turns become a note
+        assert_eq!(history[0].role, MessageRole::System);
+        assert_eq!(history[1].content.as_deref(), Some("Fix the build"));
+        assert!(history[2]
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("earlier messages were omitted"));
+        assert_eq!(history.last().unwrap().content.as_deref(), Some("Done"));
+    }
+
+    #[test]
+    fn test_truncate_chars_respects_char_boundaries() {
+        assert_eq!(truncate_chars("héllo", 10), None);
+        let short = truncate_chars("héllo wörld", 2).unwrap();
+        assert!(short.starts_with("hé\n"));
+        assert!(short.ends_with("[... 9 characters truncated]"));
+    }
+}
@@ -57,7 +57,7 @@ use crate::agent::{AgentConfig, AgentContext, AgentLoop, AgentState};
 use crate::entities::{EntityQuery, EntityStore, EntityType, InMemoryEntityStore};
 use crate::monitoring::SystemMetrics;
 use crate::observability::ObservabilitySystem;
-use model::judge::{ValidationCriteria, ValidationResult};
+use model::judge::{LlmJudge, Rubric, ValidationCriteria, ValidationMetrics, ValidationResult};
 use model::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -89,6 +89,9 @@ pub enum EvaluationError {
 
 pub type EvaluationResult<T> = Result<T, EvaluationError>;
 
+/// Seed used by default so repeated evaluations sample the same way
+pub const DEFAULT_EVAL_SEED: u64 = 42;
+
 /// Configuration for agent evaluation
 #[derive(Debug, Clone)]
 pub struct EvaluationConfig {
@@ -109,6 +112,10 @@ pub struct EvaluationConfig {
 
     /// Validation criteria for LLM outputs
     pub validation_criteria: ValidationCriteria,
+
+    /// Sampling seed given to the agent so runs can be compared; `None`
+    /// samples freshly each run
+    pub seed: Option<u64>,
 }
 
 impl Default for EvaluationConfig {
@@ -129,6 +136,7 @@ impl Default for EvaluationConfig {
                 require_factual_accuracy: true,
                 custom_validators: vec![],
             },
+            seed: Some(DEFAULT_EVAL_SEED),
         }
     }
 }
@@ -148,6 +156,11 @@ impl EvaluationConfig {
         self.verbose = verbose;
         self
     }
+
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
 }
 
 /// Evaluation scenario defines a test case for the agent
@@ -428,6 +441,9 @@ pub struct AgentEvaluator {
 
     /// Observability system (optional)
     observability: Option<ObservabilitySystem>,
+
+    /// Judge model grading the agent's answer, and what it grades against
+    judge: Option<(LlmJudge, Rubric)>,
 }
 
 impl AgentEvaluator {
@@ -449,9 +465,17 @@ impl AgentEvaluator {
         Ok(Self {
             config,
             observability,
+            judge: None,
         })
     }
 
+    /// Have `judge` grade the agent's final answer against `rubric`; a grade
+    /// below the rubric's pass threshold fails the scenario.
+    pub fn with_judge(mut self, judge: LlmJudge, rubric: Rubric) -> Self {
+        self.judge = Some((judge, rubric));
+        self
+    }
+
     /// Evaluate an agent scenario
     pub async fn evaluate(
         &mut self,
@@ -473,6 +497,8 @@ impl AgentEvaluator {
             verbose: self.config.verbose,
             system_prompt: String::new(),
             model_name: self.config.model.clone(),
+            seed: self.config.seed,
+            ..Default::default()
         };
 
         let mut agent = AgentLoop::with_entity_store(agent_config, entity_store);
@@ -547,6 +573,36 @@ impl AgentEvaluator {
         let decision_quality = self.calculate_decision_quality(&run_result, &scenario);
         metrics.decision_quality = decision_quality;
 
+        // Grade the answer with the judge model
+        if let Some((judge, rubric)) = &self.judge {
+            let judge_start = Instant::now();
+            let answer = &run_result.result_summary;
+            match judge.grade(&scenario.user_prompt, answer, rubric).await {
+                Ok(grade) => {
+                    for (name, score) in &grade.scores {
+                        metrics
+                            .custom_metrics
+                            .insert(format!("judge_{}", name), *score);
+                    }
+                    metrics
+                        .custom_metrics
+                        .insert("judge_overall".to_string(), grade.overall);
+                    if !grade.passed {
+                        failures.push(format!(
+                            "Judge score too low: {:.2} < {:.2}",
+                            grade.overall, rubric.pass_threshold
+                        ));
+                    }
+                    let judged = ValidationMetrics::with_duration(judge_start.elapsed())
+                        .with_response_length(answer.len());
+                    metrics
+                        .validation_results
+                        .push(grade.to_validation_result(judged));
+                }
+                Err(e) => warnings.push(format!("Judge could not grade the answer: {}", e)),
+            }
+        }
+
         // Check expected outcomes
         self.validate_outcomes(
             &scenario,
@@ -957,4 +1013,57 @@ mod tests {
             );
         }
     }
+
+    fn scripted_judge(reply: &str) -> LlmJudge {
+        let response = ChatResponse {
+            choices: vec![Choice {
+                message: ChatMessage::assistant(reply),
+                finish_reason: Some(FinishReason::Stop),
+            }],
+            usage: None,
+            served_by: None,
+        };
+        LlmJudge::new(
+            std::sync::Arc::new(model::ReplayProvider::scripted(vec![response])),
+            LlmJudgeConfig::new("judge").with_samples(1),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_judge_grades_the_answer() {
+        let config = EvaluationConfig {
+            collect_observability: false,
+            timeout: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let rubric = Rubric::new().with_criterion("helpfulness", "Does it address the task");
+
+        let mut evaluator = AgentEvaluator::new(config.clone())
+            .await
+            .unwrap()
+            .with_judge(
+                scripted_judge(r#"{"scores": {"helpfulness": 9}, "rationale": "Done"}"#),
+                rubric.clone(),
+            );
+        let result = evaluator
+            .evaluate(EvaluationScenario::simple_entity_creation())
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.failures);
+        assert!((result.metrics.custom_metrics["judge_helpfulness"] - 0.9).abs() < 1e-9);
+        assert!(result.metrics.validation_results[0].is_success());
+
+        let mut evaluator = AgentEvaluator::new(config).await.unwrap().with_judge(
+            scripted_judge(r#"{"scores": {"helpfulness": 2}, "rationale": "Off topic"}"#),
+            rubric,
+        );
+        let result = evaluator
+            .evaluate(EvaluationScenario::simple_entity_creation())
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.failures[0].starts_with("Judge score too low"));
+        assert!(result.metrics.validation_results[0].is_failure());
+    }
 }
@@ -12,25 +12,40 @@
 //! 8. Decision → **Query Entities (RAG)** → back to Decision
 //! 9. Decision → **Plan Entity Modification** (loop)
 
+pub mod compaction;
 pub mod decision;
 pub mod eval;
 pub mod eval_case;
 pub mod prompts;
 pub mod rag;
 
+use crate::agent::compaction::{
+    summary_message, truncate_tool_outputs, CompactionPlan, ContextBudget,
+};
+use crate::agent::prompts::CompactionPrompt;
 use crate::entities::context::types::{ContextEntity, ToolCallRecord};
 use crate::entities::{EntityStore, InMemoryEntityStore};
-use crate::tools::ToolRegistry;
+use crate::tools::{tool_response_message, ToolRegistry};
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
-use model::provider::ModelProvider;
-use model::types::{ChatMessage, ChatRequest, ChatResponse, FinishReason, MessageRole};
-
-const MAX_LLM_RESPONSE_LENGTH: usize = 2000;
+use model::capabilities::{CapabilityRegistry, ModelCapabilities};
+use model::config::RetryConfig;
+use model::provider::{CancellationToken, ModelError, ModelProvider};
+use model::reasoning::ThinkTagFilter;
+use model::retry::{CircuitBreaker, RetryingProvider};
+use model::stream::collect_stream;
+use model::structured::{chat_json_with, UnparsedReply};
+use model::tokens::estimate_conversation_tokens;
+use model::tool_parser::{TextToolFormat, ToolCallParser};
+use model::types::{ChatMessage, ChatRequest, ChatResponse, MessageRole, Usage};
+
+/// Repair prompts allowed when a structured reply does not parse
+const STRUCTURED_REPLY_REPAIRS: usize = 1;
 const DEFAULT_PLANNING_RAG_LIMIT: usize = 10;
 const DEFAULT_QUERY_RAG_LIMIT: usize = 5;
 const PLANNING_TEMPERATURE: f32 = 0.7;
@@ -39,6 +54,9 @@ const DECISION_TEMPERATURE: f32 = 0.3;
 const DEFAULT_MODEL: &str = "qwen2.5:0.5b";
 const MAX_TOOL_ITERATIONS: usize = 10;
 
+/// System prompt for tool-using agents started from the CLI and MCP tasks
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful coding assistant. Use the available tools to accomplish tasks. When you have completed the task, respond with a summary.";
+
 /// Errors that can occur in the agent
 #[derive(Error, Debug)]
 pub enum AgentError {
@@ -65,6 +83,15 @@ pub enum AgentError {
         conversation_snapshot: Vec<ChatMessage>,
         last_agent_state: AgentState,
     },
+    #[error("Agent run cancelled after {iterations_completed} iterations")]
+    Cancelled {
+        /// Text the model had generated when its request was abandoned
+        partial_output: Option<String>,
+        iterations_completed: usize,
+        tool_calls_made: Vec<ToolCallRecord>,
+        conversation_snapshot: Vec<ChatMessage>,
+        last_agent_state: AgentState,
+    },
 }
 
 impl AgentError {
@@ -105,6 +132,26 @@ impl AgentError {
                 *iterations_completed,
                 last_agent_state,
             ),
+            AgentError::Cancelled {
+                tool_calls_made,
+                conversation_snapshot,
+                iterations_completed,
+                last_agent_state,
+                ..
+            } => (
+                tool_calls_made,
+                conversation_snapshot,
+                *iterations_completed,
+                last_agent_state,
+            ),
+        }
+    }
+
+    /// Model output that was cut short by cancellation, if any.
+    pub fn partial_output(&self) -> Option<&str> {
+        match self {
+            AgentError::Cancelled { partial_output, .. } => partial_output.as_deref(),
+            _ => None,
         }
     }
 }
@@ -140,6 +187,19 @@ fn bare_max_iterations(iterations_completed: usize) -> AgentError {
     }
 }
 
+/// Response recorded for tool calls abandoned by cancellation.
+const CANCELLED_TOOL_RESPONSE: &str = "Error: cancelled before the tool call finished";
+
+fn bare_cancelled(partial_output: Option<String>) -> AgentError {
+    AgentError::Cancelled {
+        partial_output,
+        iterations_completed: 0,
+        tool_calls_made: vec![],
+        conversation_snapshot: vec![],
+        last_agent_state: AgentState::PlanningEntityModification,
+    }
+}
+
 /// State of the agent in the control loop
 ///
 /// States and transitions mirror the Harness Control Flow in ARCHITECTURE.md:
@@ -183,6 +243,27 @@ pub struct AgentConfig {
     pub verbose: bool,
     pub system_prompt: String,
     pub model_name: String,
+    /// When and how to compact the conversation history
+    pub context_budget: ContextBudget,
+    /// Turn thinking on or off for reasoning models; `None` uses the model default
+    pub think: Option<bool>,
+    /// Recovers tool calls the model wrote into its reply text; `None` only
+    /// accepts native tool calls. Set from the model's capabilities by
+    /// [`AgentConfig::with_capabilities`]
+    pub text_tool_calls: Option<ToolCallParser>,
+    /// How failed LLM calls are retried
+    pub retry: RetryConfig,
+    /// Shared breaker that stops calls while the backend is down
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Sampling seed sent with every request, making runs reproducible on
+    /// backends that honour it
+    pub seed: Option<u64>,
+    /// Sampling temperature for tool-using turns; `None` uses the model's
+    /// recommended temperature
+    pub temperature: Option<f32>,
+    /// What the model supports; `None` resolves it for `model_name` when the
+    /// agent first runs
+    pub capabilities: Option<ModelCapabilities>,
 }
 
 impl Default for AgentConfig {
@@ -192,7 +273,52 @@ impl Default for AgentConfig {
             verbose: false,
             system_prompt: String::new(),
             model_name: DEFAULT_MODEL.to_string(),
+            context_budget: ContextBudget::default(),
+            think: None,
+            text_tool_calls: None,
+            retry: RetryConfig::default(),
+            circuit_breaker: None,
+            seed: None,
+            temperature: None,
+            capabilities: None,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Adapt the context budget, thinking mode and tool-call parsing to what
+    /// the model is known to support.
+    ///
+    /// Tool calls written into the reply text are only recovered for models
+    /// known to need it. Models without native tool calling get every text
+    /// format; models with it only get the `<tool_call>` fallback, since a
+    /// plain JSON answer from them is more likely meant as an answer.
+    /// Thinking is not requested from models that cannot think, which Ollama
+    /// would reject. The model's recommended temperature is used unless one
+    /// was set.
+    pub fn with_capabilities(mut self, caps: &ModelCapabilities) -> Self {
+        if let Some(context_length) = caps.context_length {
+            self.context_budget.max_tokens = context_length as usize;
+        }
+        match caps.tools {
+            Some(true) => {
+                let parser = self.text_tool_calls.unwrap_or_default();
+                self.text_tool_calls = Some(parser.with_formats([TextToolFormat::Hermes]));
+            }
+            Some(false) => {
+                self.text_tool_calls
+                    .get_or_insert_with(ToolCallParser::default);
+            }
+            None => {}
         }
+        if caps.thinking == Some(false) {
+            self.think = None;
+        }
+        self.temperature = self
+            .temperature
+            .or_else(|| caps.recommended_temperature(self.think == Some(true)));
+        self.capabilities = Some(caps.clone());
+        self
     }
 }
 
@@ -219,6 +345,10 @@ pub struct AgentRunResult {
     pub tool_calls_made: Vec<ToolCallRecord>,
     /// Snapshot of the full conversation
     pub conversation_snapshot: Vec<ChatMessage>,
+    /// Token usage summed over every LLM call in the run, if the provider reported any
+    pub usage: Option<Usage>,
+    /// Backend that answered the last LLM call, when the provider is a router
+    pub served_by: Option<String>,
 }
 
 fn extract_tool_calls_from_history(history: &[ChatMessage]) -> Vec<ToolCallRecord> {
@@ -269,6 +399,9 @@ fn extract_result_summary(history: &[ChatMessage]) -> String {
         .to_string()
 }
 
+/// Callback receiving content deltas as the model streams them.
+pub type TokenSink = Arc<dyn Fn(&str) + Send + Sync>;
+
 pub struct AgentLoop {
     state: AgentState,
     config: AgentConfig,
@@ -280,6 +413,14 @@ pub struct AgentLoop {
     tool_registry: Option<ToolRegistry>,
     conversation_history: Vec<ChatMessage>,
     progress_counter: Option<Arc<AtomicUsize>>,
+    token_sink: Option<TokenSink>,
+    cancellation: Option<CancellationToken>,
+    /// Task the run belongs to, passed on with every LLM request
+    task_id: Option<String>,
+    /// Content streamed so far by the LLM call in progress
+    partial_output: Mutex<String>,
+    usage: Mutex<Option<Usage>>,
+    served_by: Mutex<Option<String>>,
     state_history: Vec<AgentState>,
 }
 
@@ -297,6 +438,12 @@ impl AgentLoop {
             tool_registry: None,
             conversation_history: Vec::new(),
             progress_counter: None,
+            token_sink: None,
+            cancellation: None,
+            task_id: None,
+            partial_output: Mutex::new(String::new()),
+            usage: Mutex::new(None),
+            served_by: Mutex::new(None),
             state_history: Vec::new(),
         }
     }
@@ -314,6 +461,12 @@ impl AgentLoop {
             tool_registry: None,
             conversation_history: Vec::new(),
             progress_counter: None,
+            token_sink: None,
+            cancellation: None,
+            task_id: None,
+            partial_output: Mutex::new(String::new()),
+            usage: Mutex::new(None),
+            served_by: Mutex::new(None),
             state_history: Vec::new(),
         }
     }
@@ -335,6 +488,12 @@ impl AgentLoop {
             tool_registry: None,
             conversation_history: Vec::new(),
             progress_counter: None,
+            token_sink: None,
+            cancellation: None,
+            task_id: None,
+            partial_output: Mutex::new(String::new()),
+            usage: Mutex::new(None),
+            served_by: Mutex::new(None),
             state_history: Vec::new(),
         }
     }
@@ -357,6 +516,12 @@ impl AgentLoop {
             tool_registry: Some(tool_registry),
             conversation_history: Vec::new(),
             progress_counter: None,
+            token_sink: None,
+            cancellation: None,
+            task_id: None,
+            partial_output: Mutex::new(String::new()),
+            usage: Mutex::new(None),
+            served_by: Mutex::new(None),
             state_history: Vec::new(),
         }
     }
@@ -365,6 +530,32 @@ impl AgentLoop {
         self.progress_counter = Some(counter);
     }
 
+    /// Stream LLM output through `sink` when the provider supports streaming.
+    pub fn set_token_sink(&mut self, sink: TokenSink) {
+        self.token_sink = Some(sink);
+    }
+
+    /// Stop the run when `token` is cancelled, abandoning any LLM request or
+    /// tool call in flight. Requests are streamed when the provider supports
+    /// it, so the text generated before cancellation ends up in
+    /// [`AgentError::Cancelled`]; a non-streaming reply that arrives after
+    /// cancellation is kept there whole.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    /// Send every LLM request on behalf of task `task_id`, so a governed
+    /// backend can take turns between tasks.
+    pub fn set_task_id(&mut self, task_id: impl Into<String>) {
+        self.task_id = Some(task_id.into());
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
     pub fn conversation_history(&self) -> &[ChatMessage] {
         &self.conversation_history
     }
@@ -423,15 +614,34 @@ impl AgentLoop {
                 conversation_snapshot: conversation,
                 last_agent_state: state,
             },
+            AgentError::Cancelled { partial_output, .. } => AgentError::Cancelled {
+                partial_output,
+                iterations_completed: iterations,
+                tool_calls_made: tool_calls,
+                conversation_snapshot: conversation,
+                last_agent_state: state,
+            },
         }
     }
 
-    fn extract_response_content(response: &ChatResponse) -> &str {
-        response
-            .choices
-            .first()
-            .and_then(|c| c.message.content.as_deref())
-            .unwrap_or("")
+    /// Look up what the configured model supports, once, and adapt the config
+    /// to it. Probing the provider is best effort; the built-in table and the
+    /// user's overrides still apply when it cannot answer.
+    async fn resolve_capabilities(&mut self) {
+        if self.config.capabilities.is_some() {
+            return;
+        }
+        let mut registry = CapabilityRegistry::configured();
+        if let Some(provider) = &self.llm_provider {
+            registry
+                .probe(provider.as_ref(), &self.config.model_name)
+                .await;
+        }
+        let caps = registry.resolve(&self.config.model_name);
+        if self.config.verbose {
+            tracing::info!("Model capabilities: {:?}", caps);
+        }
+        self.config = std::mem::take(&mut self.config).with_capabilities(&caps);
     }
 
     /// Run the agent loop with the given context.
@@ -441,6 +651,9 @@ impl AgentLoop {
     pub async fn run(&mut self, context: AgentContext) -> AgentResult<AgentRunResult> {
         self.iterations = 0;
         self.state_history.clear();
+        self.take_usage();
+        self.take_served_by();
+        self.resolve_capabilities().await;
 
         // Initialize conversation history from context
         self.conversation_history.clear();
@@ -456,6 +669,9 @@ impl AgentLoop {
             if self.iterations >= self.config.max_iterations {
                 return Err(self.enrich_error(bare_max_iterations(self.iterations)));
             }
+            if self.is_cancelled() {
+                return Err(self.enrich_error(bare_cancelled(None)));
+            }
 
             if self.config.verbose {
                 tracing::info!("Agent iteration {}: {:?}", self.iterations, self.state);
@@ -484,6 +700,8 @@ impl AgentLoop {
                     result_summary,
                     tool_calls_made,
                     conversation_snapshot: conversation,
+                    usage: self.take_usage(),
+                    served_by: self.take_served_by(),
                 });
             }
 
@@ -555,59 +773,140 @@ impl AgentLoop {
         self.state = new_state;
     }
 
-    /// Call LLM with retry logic and exponential backoff
+    /// Add a response's token usage to the running total for this run and
+    /// remember which backend served it.
+    fn record_usage(&self, response: &ChatResponse) {
+        if let Some(usage) = &response.usage {
+            let mut total = self.usage.lock().unwrap();
+            total.get_or_insert_with(Usage::default).accumulate(usage);
+        }
+        if response.served_by.is_some() {
+            *self.served_by.lock().unwrap() = response.served_by.clone();
+        }
+    }
+
+    /// Take the accumulated usage, resetting the running total.
+    fn take_usage(&self) -> Option<Usage> {
+        self.usage.lock().unwrap().take()
+    }
+
+    /// Take the backend that served the last call, resetting it.
+    fn take_served_by(&self) -> Option<String> {
+        self.served_by.lock().unwrap().take()
+    }
+
+    /// Apply the configured thinking mode, seed, context window, task and
+    /// cancellation token unless the request sets its own.
+    fn with_request_defaults(&self, mut request: ChatRequest) -> ChatRequest {
+        request.think = request.think.or(self.config.think);
+        request.seed = request.seed.or(self.config.seed);
+        request.num_ctx = request.num_ctx.or_else(|| {
+            self.config
+                .capabilities
+                .as_ref()
+                .and_then(|caps| caps.context_length)
+        });
+        request.task = request.task.or_else(|| self.task_id.clone());
+        request.cancellation = request.cancellation.or_else(|| self.cancellation.clone());
+        request
+    }
+
+    /// Take the content streamed by the last LLM call, if there was any.
+    fn take_partial_output(&self) -> Option<String> {
+        let partial = std::mem::take(&mut *self.partial_output.lock().unwrap());
+        (!partial.is_empty()).then_some(partial)
+    }
+
+    /// Pass streamed answer text to the token sink and the partial output.
+    fn emit_answer(&self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.partial_output.lock().unwrap().push_str(text);
+        if let Some(sink) = &self.token_sink {
+            sink(text);
+        }
+    }
+
+    /// Call the LLM through a [`RetryingProvider`] configured from
+    /// `config.retry` and `config.circuit_breaker`.
     async fn call_llm_with_retry(
         &self,
         provider: &Arc<dyn ModelProvider>,
         request: ChatRequest,
         operation: &str,
     ) -> AgentResult<ChatResponse> {
-        use model::judge::JudgeConfig;
-
-        let judge_config = JudgeConfig::default();
-
-        for attempt in 0..judge_config.max_retries {
-            match provider.chat(request.clone()).await {
-                Ok(response) => return Ok(response),
-                Err(e) => {
-                    if attempt < judge_config.max_retries - 1 {
-                        let delay = judge_config.calculate_retry_delay(attempt);
-                        if self.config.verbose {
-                            tracing::warn!(
-                                "LLM {} failed (attempt {}), retrying in {:?}: {}",
-                                operation,
-                                attempt + 1,
-                                delay,
-                                e
-                            );
+        let request = self.with_request_defaults(request);
+        let failed = |e: ModelError| bare_state_error(format!("LLM {} failed: {}", operation, e));
+
+        let mut retrying = RetryingProvider::new(Arc::clone(provider), self.config.retry.clone())
+            .map_err(failed)?;
+        if let Some(breaker) = &self.config.circuit_breaker {
+            retrying = retrying.with_circuit_breaker(Arc::clone(breaker));
+        }
+
+        self.partial_output.lock().unwrap().clear();
+        let wants_stream = self.token_sink.is_some() || self.cancellation.is_some();
+        let result = match retrying.as_streaming().filter(|_| wants_stream) {
+            Some(streaming) => match streaming.chat_stream(request).await {
+                Ok(stream) => {
+                    // Inline reasoning is kept out of the sink and partial output
+                    let mut think_filter = ThinkTagFilter::new();
+                    let result = collect_stream(stream, |chunk| {
+                        if let Some(delta) = &chunk.delta {
+                            self.emit_answer(&think_filter.push(delta));
                         }
-                        tokio::time::sleep(delay).await;
-                    } else {
-                        return Err(bare_state_error(format!(
-                            "LLM {} failed after {} attempts: {}",
-                            operation, judge_config.max_retries, e
-                        )));
-                    }
+                    })
+                    .await;
+                    self.emit_answer(&think_filter.finish());
+                    result
                 }
+                Err(e) => Err(e),
+            },
+            None => {
+                let result = retrying.chat(request).await;
+                if let Ok(response) = &result {
+                    let content = response
+                        .choices
+                        .first()
+                        .and_then(|choice| choice.message.content.as_deref())
+                        .unwrap_or("");
+                    let mut think_filter = ThinkTagFilter::new();
+                    let mut partial = self.partial_output.lock().unwrap();
+                    partial.push_str(&think_filter.push(content));
+                    partial.push_str(&think_filter.finish());
+                }
+                result
             }
+        };
+        if self.is_cancelled() && result.is_ok() {
+            // The reply arrived, but the run was cancelled while waiting for it
+            return Err(bare_cancelled(self.take_partial_output()));
         }
-        unreachable!()
-    }
 
-    /// Validate LLM response meets basic criteria
-    fn validate_llm_response(&self, response: &str, expected_keywords: &[&str]) -> bool {
-        if response.trim().is_empty() || response.len() > MAX_LLM_RESPONSE_LENGTH {
-            return false;
-        }
+        let response = result.map_err(|e| match e {
+            ModelError::Cancelled => bare_cancelled(self.take_partial_output()),
+            e => failed(e),
+        })?;
+        self.partial_output.lock().unwrap().clear();
+        self.record_usage(&response);
+        Ok(response)
+    }
 
-        if !expected_keywords.is_empty() {
-            let response_upper = response.to_uppercase();
-            expected_keywords
-                .iter()
-                .any(|kw| response_upper.contains(&kw.to_uppercase()))
-        } else {
-            true
-        }
+    /// Ask for a reply in the request's JSON schema, sending every attempt
+    /// (including repair prompts) through [`Self::call_llm_with_retry`].
+    async fn call_llm_json<T: DeserializeOwned>(
+        &self,
+        provider: &Arc<dyn ModelProvider>,
+        request: ChatRequest,
+        operation: &str,
+    ) -> AgentResult<Result<T, UnparsedReply>> {
+        chat_json_with(
+            |request| self.call_llm_with_retry(provider, request, operation),
+            request,
+            STRUCTURED_REPLY_REPAIRS,
+        )
+        .await
     }
 
     /// Entity Enrichment (ARCHITECTURE.md) — scan and enrich entities from
@@ -687,19 +986,28 @@ impl AgentLoop {
                 &self.config.model_name,
                 vec![ChatMessage::user(&prompt_text)],
             )
-            .with_temperature(PLANNING_TEMPERATURE);
-
-            let response = self
-                .call_llm_with_retry(provider, request, "planning")
-                .await?;
-
-            if response.choices.is_empty() {
-                return Err(bare_state_error(
-                    "LLM returned empty choices array for planning",
-                ));
-            }
-
-            self.plan_cache = response.choices[0].message.content.clone();
+            .with_temperature(PLANNING_TEMPERATURE)
+            .with_response_format(prompts::PlanningPrompt::response_format());
+
+            let plan = match self
+                .call_llm_json::<prompts::PlanReply>(provider, request, "planning")
+                .await?
+            {
+                Ok(reply) => reply.plan,
+                Err(unparsed) if unparsed.content.trim().is_empty() => {
+                    return Err(bare_state_error("LLM returned an empty plan"));
+                }
+                Err(unparsed) => {
+                    if self.config.verbose {
+                        tracing::warn!(
+                            "Plan is not valid JSON ({}), using it as-is",
+                            unparsed.error
+                        );
+                    }
+                    unparsed.content
+                }
+            };
+            self.plan_cache = Some(plan);
 
             if self.config.verbose {
                 tracing::info!("LLM Plan: {:?}", self.plan_cache);
@@ -736,34 +1044,18 @@ impl AgentLoop {
                 &self.config.model_name,
                 vec![ChatMessage::user(&prompt_text)],
             )
-            .with_temperature(COMPLETION_TEMPERATURE);
+            .with_temperature(COMPLETION_TEMPERATURE)
+            .with_response_format(prompts::CompletionPrompt::response_format());
 
-            let response = self
-                .call_llm_with_retry(provider, request, "completion check")
+            let reply = self
+                .call_llm_json::<prompts::CompletionReply>(provider, request, "completion check")
                 .await?;
 
-            if response.choices.is_empty() {
-                if self.config.verbose {
-                    tracing::warn!("LLM returned empty choices, falling back to action count");
-                }
-                return Ok(self.performed_actions > 0);
-            }
-
-            let status_text = Self::extract_response_content(&response);
-
-            if !self.validate_llm_response(status_text, &["COMPLETE", "INCOMPLETE"]) {
-                if self.config.verbose {
-                    tracing::warn!("Invalid completion response, falling back to action count");
-                }
-                return Ok(self.performed_actions > 0);
-            }
-
-            match prompts::CompletionPrompt::parse_response(status_text) {
-                Some(true) => Ok(true),
-                Some(false) => Ok(false),
+            match reply.ok().and_then(|reply| reply.is_complete()) {
+                Some(complete) => Ok(complete),
                 None => {
                     if self.config.verbose {
-                        tracing::warn!("Ambiguous completion status, falling back");
+                        tracing::warn!("Invalid completion reply, falling back to action count");
                     }
                     Ok(self.performed_actions > 0)
                 }
@@ -798,34 +1090,18 @@ impl AgentLoop {
                 &self.config.model_name,
                 vec![ChatMessage::user(&prompt_text)],
             )
-            .with_temperature(DECISION_TEMPERATURE);
+            .with_temperature(DECISION_TEMPERATURE)
+            .with_response_format(prompts::DecisionPrompt::response_format());
 
-            let response = self
-                .call_llm_with_retry(provider, request, "decision")
+            let reply = self
+                .call_llm_json::<prompts::DecisionReply>(provider, request, "decision")
                 .await?;
 
-            if response.choices.is_empty() {
-                if self.config.verbose {
-                    tracing::warn!("LLM returned empty choices, defaulting to PROCEED");
-                }
-                return Ok(false);
-            }
-
-            let decision_text = Self::extract_response_content(&response);
-
-            if !self.validate_llm_response(decision_text, &["QUERY", "PROCEED"]) {
-                if self.config.verbose {
-                    tracing::warn!("Invalid decision response, defaulting to PROCEED");
-                }
-                return Ok(false);
-            }
-
-            match prompts::DecisionPrompt::parse_response(decision_text) {
-                Some(true) => Ok(true),
-                Some(false) => Ok(false),
+            match reply.ok().and_then(|reply| reply.wants_query()) {
+                Some(query) => Ok(query),
                 None => {
                     if self.config.verbose {
-                        tracing::warn!("Ambiguous decision, defaulting to PROCEED");
+                        tracing::warn!("Invalid decision reply, defaulting to PROCEED");
                     }
                     Ok(false)
                 }
@@ -913,127 +1189,67 @@ impl AgentLoop {
         Ok(())
     }
 
-    /// Full tool-calling run loop — formerly used when tool_registry is set.
+    /// Shrink the conversation history once it approaches the context budget.
     ///
-    /// Deprecated: The state machine `run()` method now handles tools via
-    /// `perform_entity_modification_with_tools()` in the Performing state, following the architecture.
-    #[deprecated(note = "run() now handles tools via the state machine")]
-    #[allow(dead_code)]
-    async fn run_tool_loop(&mut self, context: AgentContext) -> AgentResult<AgentRunResult> {
-        self.conversation_history.clear();
-
-        if !self.config.system_prompt.is_empty() {
-            let sp = self.config.system_prompt.clone();
-            self.conversation_history.push(ChatMessage::system(&sp));
-        }
-
-        for msg in context.conversation_history {
-            self.conversation_history.push(msg);
+    /// Old tool outputs are truncated first; if the history is still too large,
+    /// older turns are replaced by a model-written summary, or by an omission
+    /// note when summarization is disabled or fails.
+    async fn compact_history_if_needed(
+        &mut self,
+        context: &AgentContext,
+        provider: &Arc<dyn ModelProvider>,
+    ) {
+        let budget = self.config.context_budget.clone();
+        if !budget.needs_compaction(&self.conversation_history) {
+            return;
         }
 
-        let tool_defs = self
-            .tool_registry
-            .as_ref()
-            .map(|r| r.get_definitions())
-            .unwrap_or_default();
-
-        self.iterations = 0;
-
-        loop {
-            if self.iterations >= self.config.max_iterations {
-                return Err(self.enrich_error(bare_max_iterations(self.iterations)));
-            }
-
-            let model_name = self.config.model_name.clone();
-            let messages = self.conversation_history.clone();
-            let request = ChatRequest::new(&model_name, messages).with_tools(tool_defs.clone());
-
-            let provider = match self.llm_provider.clone() {
-                Some(p) => p,
-                None => {
-                    return Err(self.enrich_error(bare_state_error("No provider configured")));
-                }
-            };
-
-            let response = provider.chat(request).await.map_err(|e| {
-                self.enrich_error(bare_state_error(format!("LLM call failed: {}", e)))
-            })?;
-
-            if response.choices.is_empty() {
-                return Err(self.enrich_error(bare_state_error("Empty response from model")));
-            }
-
-            let choice = response.choices.into_iter().next().unwrap();
-            let finish_reason = choice.finish_reason.clone();
-            let tool_calls = choice.message.tool_calls.clone();
-            self.conversation_history.push(choice.message);
-
-            match finish_reason {
-                Some(FinishReason::Stop) | None => {
-                    self.transition_to(AgentState::Completed);
-                    let task_description = context.user_prompt.clone();
-                    let conversation = self.conversation_history.clone();
-                    let tool_calls_made = extract_tool_calls_from_history(&conversation);
-                    let result_summary = extract_result_summary(&conversation);
-                    let model_used = self.config.model_name.clone();
-                    let entity = ContextEntity::new(
-                        task_description,
-                        conversation.clone(),
-                        tool_calls_made.clone(),
-                        result_summary.clone(),
-                        model_used,
+        let before = estimate_conversation_tokens(&self.conversation_history);
+        truncate_tool_outputs(&mut self.conversation_history, &budget);
+
+        if budget.needs_compaction(&self.conversation_history) {
+            if let Some(plan) = CompactionPlan::new(
+                &self.conversation_history,
+                &budget,
+                self.plan_cache.as_deref(),
+            ) {
+                let replacement = if budget.summarize {
+                    let prompt = CompactionPrompt::build(
+                        &context.user_prompt,
+                        &plan.transcript(budget.max_tool_output_chars),
                     );
-                    if let Err(e) = self.entity_store.store(Box::new(entity)).await {
-                        tracing::warn!("Failed to store context entity: {}", e);
-                    }
-                    return Ok(AgentRunResult {
-                        final_state: AgentState::Completed,
-                        iterations: self.iterations,
-                        task_completed: true,
-                        result_summary,
-                        tool_calls_made,
-                        conversation_snapshot: conversation,
-                    });
-                }
-                Some(FinishReason::ToolCalls) => {
-                    if let Some(calls) = tool_calls {
-                        for tool_call in &calls {
-                            let name = tool_call.function.name.clone();
-                            let args = tool_call.function.arguments.clone();
-                            let call_id = tool_call.id.clone();
-
-                            let response_content = {
-                                let registry = self.tool_registry.as_ref();
-                                match registry {
-                                    None => {
-                                        return Err(
-                                            self.enrich_error(bare_state_error("No tool registry"))
-                                        );
-                                    }
-                                    Some(r) => {
-                                        let result = r.execute(&name, args).await;
-                                        match result {
-                                            Ok(v) => v.to_string(),
-                                            Err(e) => format!("Error: {}", e),
-                                        }
-                                    }
-                                }
-                            };
-
-                            self.conversation_history
-                                .push(ChatMessage::tool_response(call_id, response_content));
+                    let request =
+                        ChatRequest::new(&self.config.model_name, vec![ChatMessage::user(prompt)])
+                            .with_temperature(COMPLETION_TEMPERATURE);
+                    match self
+                        .call_llm_with_retry(provider, request, "compaction")
+                        .await
+                    {
+                        Ok(response) => response
+                            .choices
+                            .first()
+                            .and_then(|c| c.message.content.as_deref())
+                            .filter(|summary| !summary.trim().is_empty())
+                            .map(summary_message)
+                            .unwrap_or_else(|| plan.omission_note()),
+                        Err(e) => {
+                            tracing::warn!("Conversation summarization failed: {}", e);
+                            plan.omission_note()
                         }
                     }
-                }
-                Some(_) => {
-                    return Err(self.enrich_error(bare_state_error("Unexpected finish reason")));
-                }
+                } else {
+                    plan.omission_note()
+                };
+                self.conversation_history = plan.apply(replacement);
             }
+        }
 
-            self.iterations += 1;
-            if let Some(ref counter) = self.progress_counter {
-                counter.fetch_add(1, Ordering::Relaxed);
-            }
+        if self.config.verbose {
+            tracing::info!(
+                "Compacted conversation from ~{} to ~{} tokens",
+                before,
+                estimate_conversation_tokens(&self.conversation_history)
+            );
         }
     }
 
@@ -1048,6 +1264,11 @@ impl AgentLoop {
         provider: &Arc<dyn ModelProvider>,
     ) -> AgentResult<()> {
         let tool_defs = self.tool_registry.as_ref().unwrap().get_definitions();
+        let text_tool_calls = self
+            .config
+            .text_tool_calls
+            .clone()
+            .map(|parser| parser.for_tools(&tool_defs));
 
         // Add plan context if available, rather than resetting the conversation
         if let Some(plan) = &self.plan_cache {
@@ -1061,15 +1282,24 @@ impl AgentLoop {
         }
 
         for _ in 0..MAX_TOOL_ITERATIONS {
+            self.compact_history_if_needed(context, provider).await;
+
             let request =
                 ChatRequest::new(&self.config.model_name, self.conversation_history.clone())
                     .with_tools(tool_defs.clone())
-                    .with_temperature(COMPLETION_TEMPERATURE);
+                    .with_temperature(self.config.temperature.unwrap_or(COMPLETION_TEMPERATURE));
 
-            let response = self
+            let mut response = self
                 .call_llm_with_retry(provider, request, "perform")
                 .await?;
 
+            if let Some(parser) = &text_tool_calls {
+                let recovered = parser.recover(&mut response);
+                if recovered > 0 && self.config.verbose {
+                    tracing::info!("Recovered {} tool call(s) from reply text", recovered);
+                }
+            }
+
             if response.choices.is_empty() {
                 break;
             }
@@ -1087,21 +1317,32 @@ impl AgentLoop {
                 let tool_calls = choice.message.tool_calls.clone().unwrap();
                 self.conversation_history.push(choice.message.clone());
 
-                for tc in &tool_calls {
-                    let result = self
-                        .tool_registry
-                        .as_ref()
-                        .unwrap()
-                        .execute(&tc.function.name, tc.function.arguments.clone())
-                        .await;
-
-                    let content = match result {
-                        Ok(val) => val.to_string(),
-                        Err(e) => format!("Error: {}", e),
+                let registry = self.tool_registry.as_ref().unwrap();
+                for (i, tc) in tool_calls.iter().enumerate() {
+                    let call = registry.execute(&tc.function.name, tc.function.arguments.clone());
+                    // A cancelled run abandons the tool call in flight. Every
+                    // call still gets a response, so the history stays valid
+                    let result = match &self.cancellation {
+                        Some(token) => tokio::select! {
+                            result = call => result,
+                            _ = token.cancelled() => {
+                                for pending in &tool_calls[i..] {
+                                    self.conversation_history.push(ChatMessage::tool_response(
+                                        pending.id.clone(),
+                                        CANCELLED_TOOL_RESPONSE,
+                                    ));
+                                }
+                                return Err(bare_cancelled(None));
+                            }
+                        },
+                        None => call.await,
                     };
 
-                    self.conversation_history
-                        .push(ChatMessage::tool_response(tc.id.clone(), content));
+                    self.conversation_history.push(tool_response_message(
+                        tc.id.clone(),
+                        result,
+                        registry.workspace_root(),
+                    ));
                 }
             } else {
                 self.conversation_history.push(choice.message);
@@ -1137,43 +1378,13 @@ mod tests {
     use model::types::{
         ChatResponse, Choice, FinishReason, FunctionCall, MessageRole, ModelInfo, ToolCall,
     };
+    use model::ReplayProvider;
     use std::sync::Mutex;
 
-    struct MockProvider {
-        responses: Mutex<Vec<ChatResponse>>,
-    }
-
-    impl MockProvider {
-        fn new(responses: Vec<ChatResponse>) -> Arc<Self> {
-            Arc::new(Self {
-                responses: Mutex::new(responses),
-            })
-        }
-    }
-
-    #[async_trait]
-    impl ModelProvider for MockProvider {
-        async fn chat(&self, _request: ChatRequest) -> ModelResult<ChatResponse> {
-            let mut responses = self.responses.lock().unwrap();
-            if responses.is_empty() {
-                return Err(ModelError::Unknown {
-                    message: "No more responses".to_string(),
-                });
-            }
-            Ok(responses.remove(0))
-        }
-
-        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
-            Ok(vec![])
-        }
-
-        async fn health_check(&self) -> ModelResult<()> {
-            Ok(())
-        }
-
-        fn provider_name(&self) -> &'static str {
-            "mock"
-        }
+    /// Replays `responses` in order, like a cassette recorded from a run
+    /// whose prompts have since changed.
+    fn scripted(responses: Vec<ChatResponse>) -> Arc<ReplayProvider> {
+        Arc::new(ReplayProvider::scripted(responses))
     }
 
     fn plain_response(content: &str) -> ChatResponse {
@@ -1184,10 +1395,14 @@ mod tests {
                     content: Some(content.to_string()),
                     tool_calls: None,
                     tool_call_id: None,
+                    reasoning: None,
+                    reasoning_signature: None,
+                    images: Vec::new(),
                 },
                 finish_reason: Some(FinishReason::Stop),
             }],
             usage: None,
+            served_by: None,
         }
     }
 
@@ -1205,10 +1420,14 @@ mod tests {
                         },
                     }]),
                     tool_call_id: None,
+                    reasoning: None,
+                    reasoning_signature: None,
+                    images: Vec::new(),
                 },
                 finish_reason: Some(FinishReason::ToolCalls),
             }],
             usage: None,
+            served_by: None,
         }
     }
 
@@ -1218,11 +1437,13 @@ mod tests {
     fn wrap_with_state_machine_responses(tool_responses: Vec<ChatResponse>) -> Vec<ChatResponse> {
         // EnrichingEntities: no LLM call
         let mut responses = vec![
-            plain_response("Plan: execute the task"), // PlanningEntityModification
+            plain_response(r#"{"plan": "execute the task"}"#), // PlanningEntityModification
         ];
         responses.extend(tool_responses); // PerformingEntityModification
                                           // UpdatingEntities: no LLM call
-        responses.push(plain_response("COMPLETE - task done")); // CheckingTaskCompletion
+        responses.push(plain_response(
+            r#"{"status": "COMPLETE", "reasoning": "task done"}"#,
+        )); // CheckingTaskCompletion
         responses
     }
 
@@ -1230,7 +1451,7 @@ mod tests {
     fn test_agent_loop_with_tools_creation() {
         let config = AgentConfig::default();
         let store = InMemoryEntityStore::new();
-        let provider = MockProvider::new(vec![]);
+        let provider = scripted(vec![]);
         let mut registry = ToolRegistry::new();
         registry.register(Box::new(EchoTool::new()));
 
@@ -1253,7 +1474,7 @@ mod tests {
         assert!(agent.llm_provider.is_none());
 
         let store = InMemoryEntityStore::new();
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let agent = AgentLoop::with_llm(AgentConfig::default(), store, provider);
         assert!(agent.tool_registry().is_none());
         assert!(agent.llm_provider.is_some());
@@ -1261,7 +1482,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_perform_entity_modification_with_tools_executes_calls() {
-        let provider = MockProvider::new(vec![
+        let provider = scripted(vec![
             tool_call_response("echo", serde_json::json!({"message": "hello"})),
             plain_response("Done! I echoed the message."),
         ]);
@@ -1305,69 +1526,228 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_perform_entity_modification_with_tools_handles_errors() {
-        let provider = MockProvider::new(vec![
-            tool_call_response("nonexistent_tool", serde_json::json!({})),
-            plain_response("I couldn't find that tool."),
+    async fn test_perform_entity_modification_recovers_text_tool_calls() {
+        let provider = scripted(vec![
+            plain_response(
+                "<tool_call>{\"name\": \"echo\", \"arguments\": {\"message\": \"hi\"}}</tool_call>",
+            ),
+            plain_response("Done."),
         ]);
 
-        let registry = ToolRegistry::new();
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool::new()));
 
-        let config = AgentConfig::default();
+        let config = AgentConfig {
+            text_tool_calls: Some(ToolCallParser::default()),
+            ..Default::default()
+        };
         let store = InMemoryEntityStore::new();
         let mut agent = AgentLoop::with_tools(config, store, provider, registry);
-
-        // Pre-populate conversation history (normally done by run())
         agent
             .conversation_history
-            .push(ChatMessage::user("Do something"));
+            .push(ChatMessage::user("Echo hi"));
 
         let context = AgentContext {
-            user_prompt: "Do something".to_string(),
+            user_prompt: "Echo hi".to_string(),
             conversation_history: vec![],
             app_state_id: "test".to_string(),
         };
-
         let provider_clone = agent.llm_provider.as_ref().unwrap().clone();
-        let result = agent
+        agent
             .perform_entity_modification_with_tools(&context, &provider_clone)
-            .await;
-        assert!(result.is_ok(), "Should handle tool errors gracefully");
+            .await
+            .unwrap();
 
-        let has_error_response = agent.conversation_history.iter().any(|m| {
-            m.role == MessageRole::Tool
-                && m.content
-                    .as_ref()
-                    .map(|c| c.starts_with("Error:"))
-                    .unwrap_or(false)
-        });
-        assert!(has_error_response, "Should have an error tool response");
+        let records = extract_tool_calls_from_history(&agent.conversation_history);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].tool_name, "echo");
+        assert!(records[0].result.contains("hi"));
     }
 
-    #[tokio::test]
-    #[allow(deprecated)]
-    async fn test_perform_entity_modification_without_tools_mvp_fallback() {
-        let config = AgentConfig {
-            max_iterations: 10,
+    #[test]
+    fn test_config_with_capabilities() {
+        let caps = ModelCapabilities {
+            context_length: Some(8_192),
+            tools: Some(true),
+            thinking: Some(false),
             ..Default::default()
         };
-        let store = InMemoryEntityStore::new();
-        let mut agent = AgentLoop::with_entity_store(config, store);
+        let config = AgentConfig {
+            think: Some(true),
+            ..Default::default()
+        }
+        .with_capabilities(&caps);
+
+        assert_eq!(config.context_budget.max_tokens, 8_192);
+        assert!(config.think.is_none());
+        let parser = config.text_tool_calls.unwrap();
+        assert!(parser
+            .parse(r#"{"name": "echo", "arguments": {}}"#)
+            .is_none());
+        assert!(parser
+            .parse(r#"<tool_call>{"name": "echo", "arguments": {}}</tool_call>"#)
+            .is_some());
+
+        let without_tools = AgentConfig::default().with_capabilities(&ModelCapabilities {
+            tools: Some(false),
+            ..Default::default()
+        });
+        assert!(without_tools
+            .text_tool_calls
+            .unwrap()
+            .parse(r#"{"name": "echo", "arguments": {}}"#)
+            .is_some());
 
-        assert!(agent.tool_registry().is_none());
+        let unknown = AgentConfig::default().with_capabilities(&ModelCapabilities::default());
+        assert_eq!(
+            unknown.context_budget.max_tokens,
+            ContextBudget::default().max_tokens
+        );
+        assert!(unknown.text_tool_calls.is_none());
+        assert!(unknown.temperature.is_none());
+    }
 
-        let context = AgentContext {
-            user_prompt: "Create entity".to_string(),
-            conversation_history: vec![],
-            app_state_id: "test".to_string(),
+    #[test]
+    fn test_config_takes_recommended_temperature_unless_set() {
+        let caps = ModelCapabilities {
+            thinking: Some(true),
+            temperature: Some(0.7),
+            thinking_temperature: Some(0.6),
+            ..Default::default()
         };
 
-        let result = agent.perform_entity_modification_mvp(&context).await;
-        assert!(result.is_ok());
-        assert_eq!(agent.performed_actions, 1);
+        let recommended = AgentConfig::default().with_capabilities(&caps);
+        assert_eq!(recommended.temperature, Some(0.7));
 
-        let entities = agent
-            .entity_store()
+        let thinking = AgentConfig {
+            think: Some(true),
+            ..Default::default()
+        }
+        .with_capabilities(&caps);
+        assert_eq!(thinking.temperature, Some(0.6));
+
+        let explicit = AgentConfig {
+            temperature: Some(0.1),
+            ..Default::default()
+        }
+        .with_capabilities(&caps);
+        assert_eq!(explicit.temperature, Some(0.1));
+    }
+
+    #[test]
+    fn test_requests_use_the_resolved_context_window() {
+        let config = AgentConfig::default().with_capabilities(&ModelCapabilities {
+            context_length: Some(8_192),
+            ..Default::default()
+        });
+        let agent = AgentLoop::new(config);
+        let request = ChatRequest::new(DEFAULT_MODEL, vec![ChatMessage::user("hi")]);
+
+        assert_eq!(
+            agent.with_request_defaults(request.clone()).num_ctx,
+            Some(8_192)
+        );
+        assert_eq!(
+            agent
+                .with_request_defaults(request.with_num_ctx(2_048))
+                .num_ctx,
+            Some(2_048)
+        );
+        let unknown = AgentLoop::new(AgentConfig::default());
+        assert_eq!(
+            unknown
+                .with_request_defaults(ChatRequest::new(DEFAULT_MODEL, vec![]))
+                .num_ctx,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_resolves_capabilities_on_run() {
+        let config = AgentConfig {
+            max_iterations: 10,
+            model_name: "qwen2.5:0.5b".to_string(),
+            ..Default::default()
+        };
+        let mut agent = AgentLoop::new(config);
+        let context = AgentContext {
+            user_prompt: "test prompt".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test_state".to_string(),
+        };
+
+        agent.run(context).await.unwrap();
+
+        assert!(agent.config.capabilities.is_some());
+        assert_eq!(agent.config.context_budget.max_tokens, 32_768);
+        assert_eq!(agent.config.temperature, Some(0.7));
+        assert!(agent.config.text_tool_calls.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_perform_entity_modification_with_tools_handles_errors() {
+        let provider = scripted(vec![
+            tool_call_response("nonexistent_tool", serde_json::json!({})),
+            plain_response("I couldn't find that tool."),
+        ]);
+
+        let registry = ToolRegistry::new();
+
+        let config = AgentConfig::default();
+        let store = InMemoryEntityStore::new();
+        let mut agent = AgentLoop::with_tools(config, store, provider, registry);
+
+        // Pre-populate conversation history (normally done by run())
+        agent
+            .conversation_history
+            .push(ChatMessage::user("Do something"));
+
+        let context = AgentContext {
+            user_prompt: "Do something".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+
+        let provider_clone = agent.llm_provider.as_ref().unwrap().clone();
+        let result = agent
+            .perform_entity_modification_with_tools(&context, &provider_clone)
+            .await;
+        assert!(result.is_ok(), "Should handle tool errors gracefully");
+
+        let has_error_response = agent.conversation_history.iter().any(|m| {
+            m.role == MessageRole::Tool
+                && m.content
+                    .as_ref()
+                    .map(|c| c.starts_with("Error:"))
+                    .unwrap_or(false)
+        });
+        assert!(has_error_response, "Should have an error tool response");
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_perform_entity_modification_without_tools_mvp_fallback() {
+        let config = AgentConfig {
+            max_iterations: 10,
+            ..Default::default()
+        };
+        let store = InMemoryEntityStore::new();
+        let mut agent = AgentLoop::with_entity_store(config, store);
+
+        assert!(agent.tool_registry().is_none());
+
+        let context = AgentContext {
+            user_prompt: "Create entity".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+
+        let result = agent.perform_entity_modification_mvp(&context).await;
+        assert!(result.is_ok());
+        assert_eq!(agent.performed_actions, 1);
+
+        let entities = agent
+            .entity_store()
             .query(&EntityQuery::default())
             .await
             .unwrap();
@@ -1384,7 +1764,7 @@ mod tests {
             .map(|_| tool_call_response("echo", serde_json::json!({"message": "loop"})))
             .collect();
 
-        let provider = MockProvider::new(responses);
+        let provider = scripted(responses);
 
         let mut registry = ToolRegistry::new();
         registry.register(Box::new(EchoTool::new()));
@@ -1481,10 +1861,537 @@ mod tests {
         assert!(result.is_ok() || matches!(result, Err(AgentError::MaxIterationsExceeded { .. })));
     }
 
+    /// Streams each queued response as one delta per whitespace-separated word.
+    struct StreamingMockProvider {
+        inner: Arc<ReplayProvider>,
+    }
+
+    #[async_trait]
+    impl ModelProvider for StreamingMockProvider {
+        async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+            self.inner.chat(request).await
+        }
+
+        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> ModelResult<()> {
+            Ok(())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "mock-streaming"
+        }
+
+        fn as_streaming(&self) -> Option<&dyn model::provider::StreamingModelProvider> {
+            Some(self)
+        }
+    }
+
+    #[async_trait]
+    impl model::provider::StreamingModelProvider for StreamingMockProvider {
+        async fn chat_stream(
+            &self,
+            request: ChatRequest,
+        ) -> ModelResult<model::stream::ChatStream> {
+            let response = self.inner.chat(request).await?;
+            let content = response.choices[0]
+                .message
+                .content
+                .clone()
+                .unwrap_or_default();
+            let chunks: Vec<ModelResult<model::stream::ChatStreamChunk>> = content
+                .split_inclusive(' ')
+                .map(|word| {
+                    Ok(model::stream::ChatStreamChunk {
+                        delta: Some(word.to_string()),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            Ok(Box::pin(futures::stream::iter(chunks)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_sink_receives_streamed_deltas() {
+        let provider = Arc::new(StreamingMockProvider {
+            inner: scripted(vec![
+                plain_response(r#"{"plan": "say hello"}"#),
+                plain_response(r#"{"status": "COMPLETE", "reasoning": "done"}"#),
+            ]),
+        });
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            provider,
+        );
+
+        let received = Arc::new(Mutex::new(Vec::<String>::new()));
+        let sink_received = Arc::clone(&received);
+        agent.set_token_sink(Arc::new(move |delta: &str| {
+            sink_received.lock().unwrap().push(delta.to_string());
+        }));
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let result = agent.run(context).await.unwrap();
+
+        assert!(result.task_completed);
+        let received = received.lock().unwrap();
+        assert_eq!(received[..3], [r#"{"plan": "#, r#""say "#, r#"hello"}"#]);
+        assert_eq!(agent.plan_cache.as_deref(), Some("say hello"));
+    }
+
+    #[tokio::test]
+    async fn test_token_sink_skips_inline_reasoning() {
+        let provider = Arc::new(StreamingMockProvider {
+            inner: scripted(vec![
+                plain_response(r#"<think>weigh the request</think> {"plan": "say hello"}"#),
+                plain_response(r#"{"status": "COMPLETE", "reasoning": "done"}"#),
+            ]),
+        });
+        let mut agent =
+            AgentLoop::with_llm(AgentConfig::default(), InMemoryEntityStore::new(), provider);
+
+        let received = Arc::new(Mutex::new(String::new()));
+        let sink_received = Arc::clone(&received);
+        agent.set_token_sink(Arc::new(move |delta: &str| {
+            sink_received.lock().unwrap().push_str(delta);
+        }));
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        agent.run(context).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert!(
+            received.starts_with(r#"{"plan": "say hello"}"#),
+            "{}",
+            received
+        );
+        assert!(!received.contains("weigh"));
+        assert_eq!(agent.plan_cache.as_deref(), Some("say hello"));
+    }
+
+    /// Streams the start of a plan, then generates until the request is
+    /// cancelled.
+    struct StallingProvider;
+
+    #[async_trait]
+    impl ModelProvider for StallingProvider {
+        async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+            if let Some(token) = request.cancellation {
+                token.cancelled().await;
+            }
+            Err(ModelError::Cancelled)
+        }
+
+        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> ModelResult<()> {
+            Ok(())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "stalling"
+        }
+
+        fn as_streaming(&self) -> Option<&dyn model::provider::StreamingModelProvider> {
+            Some(self)
+        }
+    }
+
+    #[async_trait]
+    impl model::provider::StreamingModelProvider for StallingProvider {
+        async fn chat_stream(
+            &self,
+            request: ChatRequest,
+        ) -> ModelResult<model::stream::ChatStream> {
+            let token = request
+                .cancellation
+                .expect("request carries the agent's token");
+            let first = futures::stream::iter(vec![Ok(model::stream::ChatStreamChunk {
+                delta: Some("Plan: step one".to_string()),
+                ..Default::default()
+            })]);
+            let rest = futures::stream::once(async move {
+                token.cancelled().await;
+                Err(ModelError::Cancelled)
+            });
+            Ok(Box::pin(futures::StreamExt::chain(first, rest)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_stops_run_and_keeps_partial_output() {
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            Arc::new(StallingProvider),
+        );
+        let token = CancellationToken::new();
+        agent.set_cancellation_token(token.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            token.cancel();
+        });
+
+        let context = AgentContext {
+            user_prompt: "plan something".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let err = agent.run(context).await.unwrap_err();
+
+        assert!(matches!(err, AgentError::Cancelled { .. }), "{}", err);
+        assert_eq!(err.partial_output(), Some("Plan: step one"));
+        let (_, _, _, state) = err.diagnostics();
+        assert_eq!(state, &AgentState::PlanningEntityModification);
+    }
+
+    /// Answers without streaming, but only after the run has been cancelled.
+    struct LateReplyProvider;
+
+    #[async_trait]
+    impl ModelProvider for LateReplyProvider {
+        async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+            request
+                .cancellation
+                .expect("request carries the agent's token")
+                .cancel();
+            Ok(plain_response("<think>hmm</think>Plan: step one"))
+        }
+
+        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> ModelResult<()> {
+            Ok(())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "late-reply"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_keeps_non_streaming_reply() {
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig::default(),
+            InMemoryEntityStore::new(),
+            Arc::new(LateReplyProvider),
+        );
+        agent.set_cancellation_token(CancellationToken::new());
+
+        let context = AgentContext {
+            user_prompt: "plan something".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let err = agent.run(context).await.unwrap_err();
+
+        assert!(matches!(err, AgentError::Cancelled { .. }), "{}", err);
+        assert_eq!(err.partial_output(), Some("Plan: step one"));
+    }
+
+    /// Never finishes on its own
+    #[derive(Debug, Deserialize, crate::tools::ToolArgs)]
+    struct HangArgs {}
+
+    struct HangTool;
+
+    #[async_trait]
+    impl crate::tools::TypedTool for HangTool {
+        type Args = HangArgs;
+
+        const NAME: &'static str = "hang";
+
+        async fn run(&self, _args: HangArgs) -> crate::tools::ToolResult<serde_json::Value> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_interrupts_tool_call() {
+        let provider = scripted(wrap_with_state_machine_responses(vec![tool_call_response(
+            "hang",
+            serde_json::json!({}),
+        )]));
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(HangTool));
+        let mut agent = AgentLoop::with_tools(
+            AgentConfig::default(),
+            InMemoryEntityStore::new(),
+            provider,
+            registry,
+        );
+        let token = CancellationToken::new();
+        agent.set_cancellation_token(token.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            token.cancel();
+        });
+
+        let context = AgentContext {
+            user_prompt: "wait forever".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let err = tokio::time::timeout(std::time::Duration::from_secs(5), agent.run(context))
+            .await
+            .expect("the tool call is interrupted")
+            .unwrap_err();
+
+        assert!(matches!(err, AgentError::Cancelled { .. }), "{}", err);
+        assert_eq!(
+            err.diagnostics().3,
+            &AgentState::PerformingEntityModification
+        );
+        let conversation = err.diagnostics().1;
+        let last = conversation.last().unwrap();
+        assert_eq!(last.role, MessageRole::Tool);
+        assert_eq!(last.tool_call_id.as_deref(), Some("call_0"));
+        assert_eq!(last.content.as_deref(), Some(CANCELLED_TOOL_RESPONSE));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_agent_stops_before_next_step() {
+        let provider = scripted(vec![plain_response(r#"{"plan": "say hello"}"#)]);
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            provider,
+        );
+        let token = CancellationToken::new();
+        token.cancel();
+        agent.set_cancellation_token(token);
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let err = agent.run(context).await.unwrap_err();
+
+        assert!(matches!(err, AgentError::Cancelled { .. }));
+        assert_eq!(err.partial_output(), None);
+        assert_eq!(err.diagnostics().2, 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_result_reports_serving_backend() {
+        let served = |content: &str, backend: &str| ChatResponse {
+            served_by: Some(backend.to_string()),
+            ..plain_response(content)
+        };
+        let provider = scripted(vec![
+            served(r#"{"plan": "say hello"}"#, "gpu-a"),
+            served(r#"{"status": "COMPLETE", "reasoning": "done"}"#, "gpu-b"),
+        ]);
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            provider,
+        );
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let result = agent.run(context).await.unwrap();
+
+        assert_eq!(result.served_by.as_deref(), Some("gpu-b"));
+    }
+
+    #[tokio::test]
+    async fn test_run_result_sums_usage_across_calls() {
+        let with_usage = |content: &str, usage: Usage| ChatResponse {
+            usage: Some(usage),
+            ..plain_response(content)
+        };
+        let provider = scripted(vec![
+            with_usage(r#"{"plan": "say hello"}"#, Usage::new(40, 10)),
+            with_usage(
+                r#"{"status": "COMPLETE", "reasoning": "done"}"#,
+                Usage::new(60, 5),
+            ),
+        ]);
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            provider,
+        );
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let result = agent.run(context).await.unwrap();
+
+        let usage = result.usage.expect("usage should be reported");
+        assert_eq!(usage.prompt_tokens, 100);
+        assert_eq!(usage.completion_tokens, 15);
+        assert_eq!(usage.total_tokens, 115);
+    }
+
+    #[tokio::test]
+    async fn test_structured_replies_drive_planning_and_completion() {
+        let provider = scripted(vec![
+            plain_response(r#"{"plan": "Say hello"}"#),
+            plain_response(r#"{"status": "COMPLETE", "reasoning": "no INCOMPLETE steps remain"}"#),
+        ]);
+        let mut agent = AgentLoop::with_llm(
+            AgentConfig {
+                max_iterations: 10,
+                ..Default::default()
+            },
+            InMemoryEntityStore::new(),
+            provider,
+        );
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        let result = agent.run(context).await.unwrap();
+
+        assert!(result.task_completed);
+        assert_eq!(agent.plan_cache.as_deref(), Some("Say hello"));
+    }
+
+    #[tokio::test]
+    async fn test_unparsed_completion_reply_falls_back_to_action_count() {
+        // The prose reply and its repair both say COMPLETE, but neither parses
+        // and no actions were performed, so the task is not complete
+        let provider = scripted(vec![
+            plain_response("The task is COMPLETE."),
+            plain_response("Still COMPLETE, honestly."),
+        ]);
+        let agent =
+            AgentLoop::with_llm(AgentConfig::default(), InMemoryEntityStore::new(), provider);
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        assert!(!agent.check_task_completion(&context).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_structured_reply_is_repaired() {
+        let provider = scripted(vec![
+            plain_response("QUERY, I need more context"),
+            plain_response(r#"{"decision": "QUERY", "reasoning": "need more context"}"#),
+        ]);
+        let agent =
+            AgentLoop::with_llm(AgentConfig::default(), InMemoryEntityStore::new(), provider);
+
+        let context = AgentContext {
+            user_prompt: "say hello".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        assert!(agent.entity_modification_decision(&context).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_history_compaction_summarizes_old_turns() {
+        let provider: Arc<dyn ModelProvider> =
+            scripted(vec![plain_response("Read lib.rs; it defines parse()")]);
+        let config = AgentConfig {
+            context_budget: ContextBudget {
+                max_tokens: 150,
+                keep_recent: 1,
+                max_tool_output_chars: 200,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut agent = AgentLoop::with_llm(config, InMemoryEntityStore::new(), provider.clone());
+        agent.plan_cache = Some("Plan: inspect lib.rs".to_string());
+
+        agent.conversation_history = vec![
+            ChatMessage::system("You are a coding agent"),
+            ChatMessage::user("Explain lib.rs"),
+            ChatMessage::user(
+                "Execute the following plan using the available tools: Plan: inspect lib.rs",
+            ),
+        ];
+        for i in 0..3 {
+            agent.conversation_history.push(
+                tool_call_response("read_file", serde_json::json!({"path": "src/lib.rs"})).choices
+                    [0]
+                .message
+                .clone(),
+            );
+            agent.conversation_history.push(ChatMessage::tool_response(
+                format!("call_{}", i),
+                "fn parse() {}\n".repeat(100),
+            ));
+        }
+        agent
+            .conversation_history
+            .push(ChatMessage::assistant("lib.rs defines parse"));
+
+        let context = AgentContext {
+            user_prompt: "Explain lib.rs".to_string(),
+            conversation_history: vec![],
+            app_state_id: "test".to_string(),
+        };
+        agent.compact_history_if_needed(&context, &provider).await;
+
+        let history = agent.conversation_history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[0].role, MessageRole::System);
+        assert_eq!(history[1].content.as_deref(), Some("Explain lib.rs"));
+        assert!(history[2]
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("Plan: inspect lib.rs"));
+        assert!(history[3]
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("it defines parse()"));
+        assert_eq!(history[4].content.as_deref(), Some("lib.rs defines parse"));
+        assert!(!agent.config.context_budget.needs_compaction(history));
+    }
+
     #[tokio::test]
     async fn test_enriched_error_carries_diagnostics() {
         let responses: Vec<ChatResponse> = (0..5).map(|_| plain_response("not done yet")).collect();
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(responses);
+        let provider: Arc<dyn ModelProvider> = scripted(responses);
         let config = AgentConfig {
             max_iterations: 0,
             ..Default::default()
@@ -1950,7 +2857,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_tool_loop_run_stores_context_entity() {
-        let provider = MockProvider::new(wrap_with_state_machine_responses(vec![plain_response(
+        let provider = scripted(wrap_with_state_machine_responses(vec![plain_response(
             "Task complete!",
         )]));
 
@@ -2029,7 +2936,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_tool_loop_stores_tool_calls_made() {
-        let provider = MockProvider::new(wrap_with_state_machine_responses(vec![
+        let provider = scripted(wrap_with_state_machine_responses(vec![
             tool_call_response("echo", serde_json::json!({"message": "ping"})),
             plain_response("All done."),
         ]));
@@ -2080,4 +2987,83 @@ mod tests {
             "Entity should contain result_summary"
         );
     }
+
+    #[tokio::test]
+    async fn test_llm_calls_retry_only_transient_errors() {
+        struct FailingProvider {
+            calls: AtomicUsize,
+            error: fn() -> ModelError,
+        }
+
+        #[async_trait]
+        impl ModelProvider for FailingProvider {
+            async fn chat(&self, _request: ChatRequest) -> ModelResult<ChatResponse> {
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                Err((self.error)())
+            }
+
+            async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+                Ok(vec![])
+            }
+
+            async fn health_check(&self) -> ModelResult<()> {
+                Ok(())
+            }
+
+            fn provider_name(&self) -> &'static str {
+                "failing"
+            }
+        }
+
+        let config = AgentConfig {
+            retry: RetryConfig::new()
+                .with_max_attempts(3)
+                .with_backoff(std::time::Duration::ZERO, std::time::Duration::ZERO),
+            ..Default::default()
+        };
+        let agent = AgentLoop::new(config);
+        let request = ChatRequest::new("m", vec![ChatMessage::user("hi")]);
+
+        for (error, expected_calls) in [
+            (
+                (|| ModelError::ModelNotFound {
+                    model: "m".to_string(),
+                }) as fn() -> ModelError,
+                1,
+            ),
+            (
+                || ModelError::ServiceUnavailable {
+                    message: "down".to_string(),
+                },
+                3,
+            ),
+        ] {
+            let failing = Arc::new(FailingProvider {
+                calls: AtomicUsize::new(0),
+                error,
+            });
+            let provider: Arc<dyn ModelProvider> = failing.clone();
+            let result = agent
+                .call_llm_with_retry(&provider, request.clone(), "test")
+                .await;
+            assert!(result.is_err());
+            assert_eq!(failing.calls.load(Ordering::Relaxed), expected_calls);
+        }
+    }
+
+    #[test]
+    fn test_request_defaults_fill_think_and_seed() {
+        let agent = AgentLoop::new(AgentConfig {
+            think: Some(false),
+            seed: Some(42),
+            ..Default::default()
+        });
+
+        let request = agent.with_request_defaults(ChatRequest::new("m", vec![]));
+        assert_eq!(request.think, Some(false));
+        assert_eq!(request.seed, Some(42));
+
+        let explicit = agent.with_request_defaults(ChatRequest::new("m", vec![]).with_seed(7));
+        assert_eq!(explicit.seed, Some(7));
+    }
 }
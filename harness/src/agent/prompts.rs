@@ -7,12 +7,16 @@
 //! 2. **Entity Modification Decision**: Decide whether to QUERY entities (RAG) or PROCEED to plan
 //! 3. **Task Complete?**: Determine if task is COMPLETE or INCOMPLETE
 //!
+//! Plus a summarization prompt used to compact long conversations.
+//!
 //! # Design Philosophy
 //!
 //! - Simple, clear prompts that request specific output formats
-//! - Favor keywords over JSON for MVP simplicity
+//! - Request JSON through a schema-constrained [`ResponseFormat`] and read the
+//!   reply into [`PlanReply`], [`DecisionReply`] or [`CompletionReply`]; the
+//!   agent asks the model to repair replies that do not parse
+//! - `parse_response` also accepts bare keywords, for callers holding free text
 //! - Provide sufficient context without overwhelming the LLM
-//! - Format outputs for easy parsing (uppercase keywords)
 //!
 //! # Security Considerations
 //!
@@ -25,11 +29,77 @@
 //! See: <https://owasp.org/www-project-top-10-for-large-language-model-applications/>
 
 use crate::entities::QueryResult;
+use model::structured::parse_json;
+use model::types::ResponseFormat;
+use serde::Deserialize;
+
+/// Reply to a [`PlanningPrompt`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanReply {
+    pub plan: String,
+}
+
+/// Reply to a [`DecisionPrompt`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecisionReply {
+    pub decision: String,
+    #[serde(default)]
+    pub reasoning: String,
+}
+
+impl DecisionReply {
+    /// `Some(true)` to query, `Some(false)` to proceed, `None` for any other
+    /// decision.
+    pub fn wants_query(&self) -> Option<bool> {
+        match self.decision.trim().to_uppercase().as_str() {
+            "QUERY" => Some(true),
+            "PROCEED" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Reply to a [`CompletionPrompt`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionReply {
+    pub status: String,
+    #[serde(default)]
+    pub reasoning: String,
+}
+
+impl CompletionReply {
+    /// `Some(true)` when complete, `Some(false)` when not, `None` for any
+    /// other status.
+    pub fn is_complete(&self) -> Option<bool> {
+        match self.status.trim().to_uppercase().as_str() {
+            "COMPLETE" => Some(true),
+            "INCOMPLETE" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Schema for a `{"<field>": <one of values>, "reasoning": "..."}` reply.
+fn keyword_schema(name: &str, field: &str, values: &[&str]) -> ResponseFormat {
+    ResponseFormat::json_schema(
+        name,
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                field: {"type": "string", "enum": values},
+                "reasoning": {"type": "string"}
+            },
+            "required": [field, "reasoning"],
+            "additionalProperties": false
+        }),
+    )
+}
 
 /// Planning prompt - Asks LLM to analyze user request and create execution plan
 ///
 /// # Output Format
-/// Expected LLM response should be 1-2 sentences describing the next action.
+/// Expected LLM response is `{"plan": "..."}` with 1-2 sentences describing
+/// the next action.
 ///
 /// # Example
 /// ```
@@ -61,11 +131,36 @@ impl PlanningPrompt {
              USER REQUEST: {}\n\
              WORKSPACE: {} entities\n\
              RELEVANT: {}\n\n\
-             Plan the next action in 1-2 sentences.",
+             Plan the next action in 1-2 sentences.\n\
+             Respond with JSON: {{\"plan\": \"<the plan>\"}}",
             user_prompt, entity_count, rag_results
         )
     }
 
+    /// Response format constraining the reply to `{"plan": string}`
+    pub fn response_format() -> ResponseFormat {
+        ResponseFormat::json_schema(
+            "plan",
+            serde_json::json!({
+                "type": "object",
+                "properties": {"plan": {"type": "string"}},
+                "required": ["plan"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    /// Extract the plan from LLM response
+    ///
+    /// Returns the `plan` field of a JSON reply, or the reply unchanged if it
+    /// is not JSON.
+    pub fn parse_response(response: &str) -> String {
+        match parse_json::<PlanReply>(response) {
+            Ok(reply) => reply.plan,
+            Err(_) => response.to_string(),
+        }
+    }
+
     /// Build planning prompt from QueryResult vector
     ///
     /// # Arguments
@@ -102,7 +197,7 @@ impl PlanningPrompt {
 /// Decision prompt - Asks LLM to decide "QUERY" or "PROCEED"
 ///
 /// # Output Format
-/// Expected LLM response should start with either:
+/// Expected LLM response is `{"decision": "QUERY" | "PROCEED", "reasoning": "..."}`:
 /// - "QUERY" - Need more context from RAG
 /// - "PROCEED" - Ready to perform action
 ///
@@ -144,11 +239,16 @@ impl DecisionPrompt {
              WORKSPACE: {} entities\n\
              ACTIONS PERFORMED: {}\n\n\
              Do you need more context (QUERY) or are you ready to act (PROCEED)?\n\
-             Respond with QUERY or PROCEED followed by brief reasoning.",
+             Respond with JSON: {{\"decision\": \"QUERY or PROCEED\", \"reasoning\": \"<brief reasoning>\"}}",
             user_prompt, current_plan, entity_count, performed_actions
         )
     }
 
+    /// Response format constraining the reply to a QUERY/PROCEED decision
+    pub fn response_format() -> ResponseFormat {
+        keyword_schema("decision", "decision", &["QUERY", "PROCEED"])
+    }
+
     /// Parse decision from LLM response
     ///
     /// # Arguments
@@ -158,7 +258,14 @@ impl DecisionPrompt {
     /// * `Some(true)` - Need to query (QUERY found)
     /// * `Some(false)` - Ready to proceed (PROCEED found)
     /// * `None` - Could not parse response
+    ///
+    /// JSON replies are read from their `decision` field; anything else falls
+    /// back to keyword matching.
     pub fn parse_response(response: &str) -> Option<bool> {
+        if let Ok(reply) = parse_json::<DecisionReply>(response) {
+            return reply.wants_query();
+        }
+
         let upper = response.to_uppercase();
         let has_query = upper.contains("QUERY");
         let has_proceed = upper.contains("PROCEED");
@@ -178,7 +285,7 @@ impl DecisionPrompt {
 /// Completion prompt - Asks LLM to determine "COMPLETE" or "INCOMPLETE"
 ///
 /// # Output Format
-/// Expected LLM response should start with either:
+/// Expected LLM response is `{"status": "COMPLETE" | "INCOMPLETE", "reasoning": "..."}`:
 /// - "COMPLETE" - Task is finished
 /// - "INCOMPLETE" - More work needed
 ///
@@ -218,11 +325,16 @@ impl CompletionPrompt {
              ACTIONS PERFORMED: {}\n\
              CURRENT ENTITIES: {}\n\n\
              Is the user's request complete (COMPLETE) or does more work need to be done (INCOMPLETE)?\n\
-             Respond with COMPLETE or INCOMPLETE followed by brief reasoning.",
+             Respond with JSON: {{\"status\": \"COMPLETE or INCOMPLETE\", \"reasoning\": \"<brief reasoning>\"}}",
             user_prompt, actions_performed, entities_text
         )
     }
 
+    /// Response format constraining the reply to a COMPLETE/INCOMPLETE status
+    pub fn response_format() -> ResponseFormat {
+        keyword_schema("completion", "status", &["COMPLETE", "INCOMPLETE"])
+    }
+
     /// Parse completion status from LLM response
     ///
     /// # Arguments
@@ -232,7 +344,14 @@ impl CompletionPrompt {
     /// * `Some(true)` - Task is complete (COMPLETE found)
     /// * `Some(false)` - Task is incomplete (INCOMPLETE found)
     /// * `None` - Could not parse response
+    ///
+    /// JSON replies are read from their `status` field, so reasoning that
+    /// mentions either keyword does not make them ambiguous.
     pub fn parse_response(response: &str) -> Option<bool> {
+        if let Ok(reply) = parse_json::<CompletionReply>(response) {
+            return reply.is_complete();
+        }
+
         let upper = response.to_uppercase();
 
         // Check for standalone "COMPLETE" (not part of "INCOMPLETE")
@@ -252,6 +371,42 @@ impl CompletionPrompt {
     }
 }
 
+/// Compaction prompt - Asks LLM to summarize earlier conversation turns
+///
+/// # Output Format
+/// Expected LLM response is a short plain-text summary.
+///
+/// # Example
+/// ```
+/// use harness::agent::prompts::CompactionPrompt;
+///
+/// let prompt = CompactionPrompt::build("Fix the build", "TOOL: error[E0308]");
+/// assert!(prompt.contains("Fix the build"));
+/// assert!(prompt.contains("E0308"));
+/// ```
+pub struct CompactionPrompt;
+
+impl CompactionPrompt {
+    /// Build a compaction prompt
+    ///
+    /// # Arguments
+    /// * `user_prompt` - The user's request
+    /// * `transcript` - Rendered conversation turns to summarize
+    ///
+    /// # Returns
+    /// Formatted prompt string for LLM
+    pub fn build(user_prompt: &str, transcript: &str) -> String {
+        format!(
+            "You are a code assistant condensing your working notes.\n\
+             USER REQUEST: {}\n\
+             EARLIER CONVERSATION:\n{}\n\n\
+             Summarize the conversation above in a few sentences. Keep file paths, \
+             tool results and decisions that later steps depend on.",
+            user_prompt, transcript
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -551,6 +706,59 @@ mod tests {
         );
     }
 
+    // ===== Structured Output Tests =====
+
+    #[test]
+    fn test_parse_structured_replies() {
+        assert_eq!(
+            CompletionPrompt::parse_response(
+                r#"{"status": "COMPLETE", "reasoning": "nothing is INCOMPLETE"}"#
+            ),
+            Some(true),
+            "Reasoning should not make a JSON reply ambiguous"
+        );
+        assert_eq!(
+            CompletionPrompt::parse_response(r#"{"status": "incomplete", "reasoning": ""}"#),
+            Some(false)
+        );
+        assert_eq!(
+            DecisionPrompt::parse_response(
+                "```json\n{\"decision\": \"QUERY\", \"reasoning\": \"then PROCEED\"}\n```"
+            ),
+            Some(true)
+        );
+        assert_eq!(
+            DecisionPrompt::parse_response(r#"{"decision": "WAIT", "reasoning": "PROCEED"}"#),
+            None
+        );
+        assert_eq!(
+            PlanningPrompt::parse_response(r#"{"plan": "Read lib.rs"}"#),
+            "Read lib.rs"
+        );
+        assert_eq!(
+            PlanningPrompt::parse_response("Plan: read lib.rs"),
+            "Plan: read lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_response_formats_enumerate_keywords() {
+        match CompletionPrompt::response_format() {
+            ResponseFormat::JsonSchema { schema, .. } => {
+                assert_eq!(
+                    schema["properties"]["status"]["enum"],
+                    serde_json::json!(["COMPLETE", "INCOMPLETE"])
+                );
+                assert_eq!(schema["required"][0], "status");
+            }
+            other => panic!("expected a JSON schema, got {:?}", other),
+        }
+        assert!(matches!(
+            DecisionPrompt::response_format(),
+            ResponseFormat::JsonSchema { .. }
+        ));
+    }
+
     // ===== Integration Tests =====
 
     #[test]
@@ -572,11 +780,13 @@ mod tests {
         let planning = PlanningPrompt::build("", 0, "");
         let decision = DecisionPrompt::build("", "", 0, 0);
         let completion = CompletionPrompt::build("", 0, &[]);
+        let compaction = CompactionPrompt::build("", "");
 
         // Should not panic with empty inputs
         assert!(planning.contains("USER REQUEST:"));
         assert!(decision.contains("USER REQUEST:"));
         assert!(completion.contains("USER REQUEST:"));
+        assert!(compaction.contains("USER REQUEST:"));
     }
 
     #[test]
@@ -0,0 +1,943 @@
+//! Layered configuration
+//!
+//! Settings come from, lowest precedence first: built-in defaults, the user
+//! file `$XDG_CONFIG_HOME/nanna/nanna.toml` (or `~/.config/nanna/nanna.toml`),
+//! the repository's `nanna.toml`, and `NANNA_*` environment variables.
+//! Command-line flags override all of them. Each value is checked against the
+//! layer it came from, so errors name the file or variable and the dotted key
+//! at fault.
+//!
+//! ```toml
+//! [provider]
+//! kind = "openai"
+//! base_urls = ["http://gpu-1:8000", "http://gpu-2:8000"]
+//! routing = "round-robin"
+//! timeout_secs = 120
+//!
+//! [models]
+//! agent = "qwen3:8b"
+//!
+//! [tools]
+//! deny = ["write_file"]
+//! ```
+
+use crate::agent::DEFAULT_SYSTEM_PROMPT;
+use crate::task::DEFAULT_MAX_CONCURRENT_TASKS;
+use crate::tools::ToolRegistry;
+use clap::ValueEnum;
+use model::config::{CircuitBreakerConfig, GovernorConfig, RetryConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Name of the repository and user configuration files
+pub const CONFIG_FILE_NAME: &str = "nanna.toml";
+
+/// Environment variables read by [`ConfigLoader::with_env`], the key each
+/// one sets, and how its value is parsed.
+const ENV_VARS: &[(&str, &str, EnvKind)] = &[
+    ("NANNA_PROVIDER", "provider.kind", EnvKind::String),
+    ("NANNA_BASE_URLS", "provider.base_urls", EnvKind::List),
+    ("NANNA_ROUTING", "provider.routing", EnvKind::String),
+    (
+        "NANNA_TIMEOUT_SECS",
+        "provider.timeout_secs",
+        EnvKind::Integer,
+    ),
+    (
+        "NANNA_MAX_IN_FLIGHT",
+        "provider.max_in_flight",
+        EnvKind::Integer,
+    ),
+    (
+        "NANNA_TOKENS_PER_MINUTE",
+        "provider.tokens_per_minute",
+        EnvKind::Integer,
+    ),
+    ("NANNA_CHAT_MODEL", "models.chat", EnvKind::String),
+    ("NANNA_AGENT_MODEL", "models.agent", EnvKind::String),
+    ("NANNA_MCP_MODEL", "models.mcp", EnvKind::String),
+    (
+        "NANNA_MAX_ITERATIONS",
+        "agent.max_iterations",
+        EnvKind::Integer,
+    ),
+    (
+        "NANNA_SYSTEM_PROMPT",
+        "agent.system_prompt",
+        EnvKind::String,
+    ),
+    ("NANNA_SEED", "agent.seed", EnvKind::Integer),
+    (
+        "NANNA_AGENT_TEMPERATURE",
+        "agent.temperature",
+        EnvKind::Float,
+    ),
+    ("NANNA_TEMPERATURE", "chat.temperature", EnvKind::Float),
+    ("NANNA_STREAM", "chat.stream", EnvKind::Bool),
+    ("NANNA_TOOLS", "tools.enabled", EnvKind::Bool),
+    ("NANNA_TOOLS_ALLOW", "tools.allow", EnvKind::List),
+    ("NANNA_TOOLS_DENY", "tools.deny", EnvKind::List),
+    ("NANNA_MAX_ATTEMPTS", "retry.max_attempts", EnvKind::Integer),
+    (
+        "NANNA_BASE_DELAY_MS",
+        "retry.base_delay_ms",
+        EnvKind::Integer,
+    ),
+    ("NANNA_MAX_DELAY_MS", "retry.max_delay_ms", EnvKind::Integer),
+    (
+        "NANNA_DEADLINE_SECS",
+        "retry.deadline_secs",
+        EnvKind::Integer,
+    ),
+    (
+        "NANNA_MAX_CONCURRENT_TASKS",
+        "tasks.max_concurrent",
+        EnvKind::Integer,
+    ),
+];
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("{origin}: {message}")]
+    Parse { origin: String, message: String },
+
+    #[error("{origin}: invalid `{key}`: {message}")]
+    Invalid {
+        origin: String,
+        key: String,
+        message: String,
+    },
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderKind {
+    /// Ollama native API
+    Ollama,
+    /// OpenAI-compatible /v1/chat/completions server (vLLM, llama.cpp, ...)
+    Openai,
+    /// Anthropic Messages API
+    Anthropic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoutingKind {
+    /// Use the first server, moving on only when it is unavailable
+    Fallback,
+    /// Rotate between servers, skipping unavailable ones
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NannaConfig {
+    pub provider: ProviderSection,
+    pub models: ModelsSection,
+    pub agent: AgentSection,
+    pub chat: ChatSection,
+    pub tools: ToolsSection,
+    pub tasks: TasksSection,
+    pub retry: RetrySection,
+    /// Where each key set by a layer came from
+    #[serde(skip)]
+    origins: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderSection {
+    pub kind: ProviderKind,
+    /// Model servers; empty uses the provider's default URL
+    pub base_urls: Vec<String>,
+    /// How to pick between servers when several are configured
+    pub routing: RoutingKind,
+    /// Per-request HTTP timeout
+    pub timeout_secs: u64,
+    /// Requests sent to each server at the same time; others wait in line
+    pub max_in_flight: usize,
+    /// Tokens sent to each server per minute; 0 for no limit
+    pub tokens_per_minute: u32,
+}
+
+impl Default for ProviderSection {
+    fn default() -> Self {
+        Self {
+            kind: ProviderKind::Ollama,
+            base_urls: Vec::new(),
+            routing: RoutingKind::Fallback,
+            timeout_secs: 30,
+            max_in_flight: 4,
+            tokens_per_minute: 0,
+        }
+    }
+}
+
+impl ProviderSection {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn governor_config(&self) -> GovernorConfig {
+        let config = GovernorConfig::new().with_max_in_flight(self.max_in_flight);
+        match self.tokens_per_minute {
+            0 => config,
+            limit => config.with_tokens_per_minute(limit),
+        }
+    }
+}
+
+/// Default model of each command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelsSection {
+    pub chat: String,
+    pub agent: String,
+    pub mcp: String,
+}
+
+impl Default for ModelsSection {
+    fn default() -> Self {
+        Self {
+            chat: "llama3.1:8b".to_string(),
+            agent: "qwen3:0.6b".to_string(),
+            mcp: "qwen3:0.6b".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentSection {
+    pub max_iterations: usize,
+    pub system_prompt: String,
+    /// Sampling seed for reproducible runs; 0 samples freshly
+    pub seed: u64,
+    /// Sampling temperature for tool-using turns; unset uses the model's
+    /// recommended temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+impl Default for AgentSection {
+    fn default() -> Self {
+        Self {
+            max_iterations: 100,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            seed: 0,
+            temperature: None,
+        }
+    }
+}
+
+impl AgentSection {
+    pub fn seed(&self) -> Option<u64> {
+        (self.seed != 0).then_some(self.seed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatSection {
+    pub temperature: f32,
+    /// Print replies as they are generated
+    pub stream: bool,
+}
+
+impl Default for ChatSection {
+    fn default() -> Self {
+        Self {
+            temperature: 0.7,
+            stream: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsSection {
+    /// Offer tools to the model without passing `--tools`
+    pub enabled: bool,
+    /// Tools to keep; empty keeps every tool
+    pub allow: Vec<String>,
+    /// Tools to remove, applied after `allow`
+    pub deny: Vec<String>,
+}
+
+impl ToolsSection {
+    /// Remove the tools excluded by `allow` and `deny` from `registry`,
+    /// ignoring names it does not have.
+    pub fn apply(&self, registry: &mut ToolRegistry) {
+        registry.retain(|name| {
+            (self.allow.is_empty() || self.allow.iter().any(|a| a == name))
+                && !self.deny.iter().any(|d| d == name)
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TasksSection {
+    /// MCP tasks run at the same time; further tasks wait
+    pub max_concurrent: usize,
+}
+
+impl Default for TasksSection {
+    fn default() -> Self {
+        Self {
+            max_concurrent: DEFAULT_MAX_CONCURRENT_TASKS,
+        }
+    }
+}
+
+/// Retries and circuit breaking for agent model calls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySection {
+    /// Attempts per model call, including the first
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Time allowed for a model call across all attempts
+    pub deadline_secs: u64,
+    /// Consecutive backend failures after which calls fail fast
+    pub breaker_threshold: u32,
+    /// How long calls fail fast before the backend is tried again
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for RetrySection {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 100,
+            max_delay_ms: 5000,
+            deadline_secs: 300,
+            breaker_threshold: 5,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl RetrySection {
+    pub fn retry_config(&self) -> RetryConfig {
+        RetryConfig::new()
+            .with_max_attempts(self.max_attempts)
+            .with_backoff(
+                Duration::from_millis(self.base_delay_ms),
+                Duration::from_millis(self.max_delay_ms),
+            )
+            .with_deadline(Duration::from_secs(self.deadline_secs))
+    }
+
+    pub fn circuit_breaker_config(&self) -> CircuitBreakerConfig {
+        CircuitBreakerConfig::new()
+            .with_failure_threshold(self.breaker_threshold)
+            .with_cool_down(Duration::from_secs(self.breaker_cooldown_secs))
+    }
+}
+
+impl NannaConfig {
+    /// Load the user file, the `nanna.toml` of the repository containing
+    /// `workspace_root`, and the process environment.
+    pub fn load(workspace_root: &Path) -> ConfigResult<Self> {
+        let mut loader = ConfigLoader::new();
+        if let Some(path) = Self::user_config_path() {
+            loader = loader.with_file(&path)?;
+        }
+        if let Some(path) = Self::repo_config_path(workspace_root) {
+            loader = loader.with_file(&path)?;
+        }
+        loader.with_env(std::env::vars())?.build()
+    }
+
+    /// `$XDG_CONFIG_HOME/nanna/nanna.toml`, falling back to `~/.config`.
+    pub fn user_config_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("nanna").join(CONFIG_FILE_NAME))
+    }
+
+    /// The nearest `nanna.toml` at or above `start`, not looking past the
+    /// root of the git repository.
+    pub fn repo_config_path(start: &Path) -> Option<PathBuf> {
+        for dir in start.ancestors() {
+            let candidate = dir.join(CONFIG_FILE_NAME);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+
+    /// File or environment variable that set `key`, or `"default"`.
+    pub fn origin_of(&self, key: &str) -> &str {
+        self.origins
+            .get(key)
+            .map(String::as_str)
+            .unwrap_or("default")
+    }
+
+    /// First value that breaks a constraint on its own, as `(key, message)`.
+    fn check(&self) -> Result<(), (&'static str, String)> {
+        if let Some(url) = self
+            .provider
+            .base_urls
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err((
+                "provider.base_urls",
+                format!("{:?} is not an http(s) URL", url),
+            ));
+        }
+        if self.provider.timeout_secs == 0 {
+            return Err(("provider.timeout_secs", "must be positive".to_string()));
+        }
+        if self.provider.max_in_flight == 0 {
+            return Err(("provider.max_in_flight", "must be positive".to_string()));
+        }
+        for (key, model) in [
+            ("models.chat", &self.models.chat),
+            ("models.agent", &self.models.agent),
+            ("models.mcp", &self.models.mcp),
+        ] {
+            if model.trim().is_empty() {
+                return Err((key, "must not be empty".to_string()));
+            }
+        }
+        if self.agent.max_iterations == 0 {
+            return Err(("agent.max_iterations", "must be positive".to_string()));
+        }
+        for (key, temperature) in [
+            ("chat.temperature", Some(self.chat.temperature)),
+            ("agent.temperature", self.agent.temperature),
+        ] {
+            if let Some(temperature) = temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+                return Err((key, format!("{} is outside 0.0 to 2.0", temperature)));
+            }
+        }
+        if self.tasks.max_concurrent == 0 {
+            return Err(("tasks.max_concurrent", "must be positive".to_string()));
+        }
+        let retry = &self.retry;
+        for (key, value) in [
+            ("retry.max_attempts", u64::from(retry.max_attempts)),
+            ("retry.deadline_secs", retry.deadline_secs),
+            (
+                "retry.breaker_threshold",
+                u64::from(retry.breaker_threshold),
+            ),
+        ] {
+            if value == 0 {
+                return Err((key, "must be positive".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// First pair of values that contradict each other, as `(key, other key,
+    /// message)`. Only meaningful once every layer is merged.
+    fn check_combined(&self) -> Result<(), (&'static str, &'static str, String)> {
+        let retry = &self.retry;
+        if retry.base_delay_ms > retry.max_delay_ms {
+            return Err((
+                "retry.base_delay_ms",
+                "retry.max_delay_ms",
+                format!("exceeds retry.max_delay_ms ({})", retry.max_delay_ms),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl NannaConfig {
+    /// Remove the tools excluded by `tools.allow` and `tools.deny` from
+    /// `registry`.
+    ///
+    /// Naming a tool the registry does not have is an error, since it is
+    /// most likely a typo that would otherwise leave a tool enabled.
+    pub fn filter_tools(&self, registry: &mut ToolRegistry) -> ConfigResult<()> {
+        let tools = &self.tools;
+        for (key, names) in [("tools.allow", &tools.allow), ("tools.deny", &tools.deny)] {
+            if let Some(unknown) = names.iter().find(|n| registry.get_tool(n).is_none()) {
+                let mut available = registry.list_tools();
+                available.sort_unstable();
+                return Err(ConfigError::Invalid {
+                    origin: self.origin_of(key).to_string(),
+                    key: key.to_string(),
+                    message: format!(
+                        "unknown tool {:?} (available: {})",
+                        unknown,
+                        available.join(", ")
+                    ),
+                });
+            }
+        }
+        tools.apply(registry);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    /// Comma-separated, blank items dropped
+    List,
+    Integer,
+    Float,
+    Bool,
+}
+
+/// Collects configuration layers and merges them into a [`NannaConfig`].
+///
+/// Later layers override earlier ones key by key; arrays are replaced, not
+/// appended to.
+#[derive(Debug, Default)]
+pub struct ConfigLoader {
+    /// `(origin, dotted key, value)` in precedence order
+    values: Vec<(String, String, toml::Value)>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the file at `path`, if it exists.
+    pub fn with_file(self, path: &Path) -> ConfigResult<Self> {
+        if !path.exists() {
+            return Ok(self);
+        }
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.with_toml_str(&path.display().to_string(), &content)
+    }
+
+    /// Add TOML `content`, naming it `origin` in errors.
+    pub fn with_toml_str(mut self, origin: &str, content: &str) -> ConfigResult<Self> {
+        let table: toml::Table =
+            content
+                .parse()
+                .map_err(|e: toml::de::Error| ConfigError::Parse {
+                    origin: origin.to_string(),
+                    message: e.to_string(),
+                })?;
+        let mut leaves = Vec::new();
+        flatten("", toml::Value::Table(table), &mut leaves);
+        for (key, value) in leaves {
+            self.push(origin, key, value)?;
+        }
+        Ok(self)
+    }
+
+    /// Add the `NANNA_*` variables found in `vars`; other variables are
+    /// ignored.
+    pub fn with_env(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> ConfigResult<Self> {
+        let vars: HashMap<String, String> = vars.into_iter().collect();
+        for (name, key, kind) in ENV_VARS {
+            let Some(raw) = vars.get(*name) else {
+                continue;
+            };
+            let invalid = |message: String| ConfigError::Invalid {
+                origin: name.to_string(),
+                key: key.to_string(),
+                message,
+            };
+            let value = match kind {
+                EnvKind::String => toml::Value::String(raw.clone()),
+                EnvKind::List => toml::Value::Array(
+                    raw.split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| toml::Value::String(item.to_string()))
+                        .collect(),
+                ),
+                EnvKind::Integer => raw
+                    .trim()
+                    .parse()
+                    .map(toml::Value::Integer)
+                    .map_err(|_| invalid(format!("{:?} is not an integer", raw)))?,
+                EnvKind::Float => raw
+                    .trim()
+                    .parse()
+                    .map(toml::Value::Float)
+                    .map_err(|_| invalid(format!("{:?} is not a number", raw)))?,
+                EnvKind::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+                    "1" | "true" | "yes" | "on" => toml::Value::Boolean(true),
+                    "0" | "false" | "no" | "off" => toml::Value::Boolean(false),
+                    _ => return Err(invalid(format!("{:?} is not a boolean", raw))),
+                },
+            };
+            self.push(name, key.to_string(), value)?;
+        }
+        Ok(self)
+    }
+
+    /// Merge every layer over the defaults, then check the values that
+    /// constrain each other.
+    pub fn build(self) -> ConfigResult<NannaConfig> {
+        let mut table = defaults_table();
+        let mut origins = HashMap::new();
+        for (origin, key, value) in self.values {
+            set_path(&mut table, &key, value);
+            origins.insert(key, origin);
+        }
+        let mut config: NannaConfig =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| ConfigError::Parse {
+                    origin: "merged configuration".to_string(),
+                    message: e.message().to_string(),
+                })?;
+        config.origins = origins;
+        if let Err((key, other, message)) = config.check_combined() {
+            // Blame the layer that set either value, preferring `key`'s
+            let origin = [key, other]
+                .into_iter()
+                .find_map(|k| config.origins.get(k))
+                .map_or("default", String::as_str);
+            return Err(ConfigError::Invalid {
+                origin: origin.to_string(),
+                key: key.to_string(),
+                message,
+            });
+        }
+        Ok(config)
+    }
+
+    /// Check `value` on its own over the defaults, then queue it.
+    fn push(&mut self, origin: &str, key: String, value: toml::Value) -> ConfigResult<()> {
+        let invalid = |message: String| ConfigError::Invalid {
+            origin: origin.to_string(),
+            key: key.clone(),
+            message,
+        };
+
+        let mut table = defaults_table();
+        if !has_path(&table, &key) && !OPTIONAL_KEYS.contains(&key.as_str()) {
+            return Err(invalid("unknown key".to_string()));
+        }
+        if matches!(&value, toml::Value::Table(inner) if inner.is_empty()) {
+            return Ok(());
+        }
+        set_path(&mut table, &key, value.clone());
+        let config: NannaConfig = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(e.message().trim().to_string()))?;
+        config.check().map_err(|(_, message)| invalid(message))?;
+
+        self.values.push((origin.to_string(), key, value));
+        Ok(())
+    }
+}
+
+/// Keys with no default, which are therefore missing from [`defaults_table`]
+const OPTIONAL_KEYS: &[&str] = &["agent.temperature"];
+
+fn defaults_table() -> toml::Table {
+    toml::Table::try_from(NannaConfig::default()).expect("default configuration serializes")
+}
+
+/// Split `value` into `(dotted key, value)` pairs, descending into tables
+/// but not arrays.
+fn flatten(prefix: &str, value: toml::Value, out: &mut Vec<(String, toml::Value)>) {
+    match value {
+        // Empty tables are kept so that `[unknown]` is still reported
+        toml::Value::Table(table) if prefix.is_empty() || !table.is_empty() => {
+            for (name, value) in table {
+                let key = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(&key, value, out);
+            }
+        }
+        value => out.push((prefix.to_string(), value)),
+    }
+}
+
+fn has_path(table: &toml::Table, key: &str) -> bool {
+    let mut current = table;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        match current.get(part) {
+            Some(toml::Value::Table(inner)) if parts.peek().is_some() => current = inner,
+            Some(_) => return parts.peek().is_none(),
+            None => return false,
+        }
+    }
+    false
+}
+
+fn set_path(table: &mut toml::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            if let Some(toml::Value::Table(inner)) = table.get_mut(head) {
+                set_path(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = ConfigLoader::new().build().unwrap();
+        assert_eq!(config, NannaConfig::default());
+        assert_eq!(config.provider.timeout(), Duration::from_secs(30));
+        assert_eq!(config.agent.system_prompt, DEFAULT_SYSTEM_PROMPT);
+        assert_eq!(config.agent.seed(), None);
+        assert_eq!(config.origin_of("models.agent"), "default");
+    }
+
+    #[test]
+    fn test_layers_merge_in_order() {
+        let user = r#"
+            [provider]
+            base_urls = ["http://a:11434", "http://b:11434"]
+            timeout_secs = 60
+
+            [models]
+            agent = "qwen3:8b"
+            chat = "llama3.1:8b-instruct"
+        "#;
+        let repo = r#"
+            provider.base_urls = ["http://c:11434"]
+            [models]
+            agent = "qwen3:14b"
+            [tools]
+            deny = ["write_file"]
+        "#;
+        let config = ConfigLoader::new()
+            .with_toml_str("user.toml", user)
+            .unwrap()
+            .with_toml_str("repo.toml", repo)
+            .unwrap()
+            .with_env(env(&[
+                ("NANNA_AGENT_MODEL", "qwen3:32b"),
+                ("NANNA_ROUTING", "round-robin"),
+                ("NANNA_STREAM", "off"),
+                ("UNRELATED", "x"),
+            ]))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(config.provider.base_urls, vec!["http://c:11434"]);
+        assert_eq!(config.provider.timeout_secs, 60);
+        assert_eq!(config.provider.routing, RoutingKind::RoundRobin);
+        assert_eq!(config.models.chat, "llama3.1:8b-instruct");
+        assert_eq!(config.models.agent, "qwen3:32b");
+        assert_eq!(config.models.mcp, "qwen3:0.6b");
+        assert!(!config.chat.stream);
+        assert_eq!(config.tools.deny, vec!["write_file"]);
+
+        assert_eq!(config.origin_of("models.agent"), "NANNA_AGENT_MODEL");
+        assert_eq!(config.origin_of("provider.base_urls"), "repo.toml");
+        assert_eq!(config.origin_of("provider.timeout_secs"), "user.toml");
+    }
+
+    #[test]
+    fn test_env_lists_and_numbers() {
+        let config = ConfigLoader::new()
+            .with_env(env(&[
+                ("NANNA_BASE_URLS", "http://a:8000, http://b:8000,"),
+                ("NANNA_PROVIDER", "openai"),
+                ("NANNA_TEMPERATURE", "0.2"),
+                ("NANNA_MAX_CONCURRENT_TASKS", "2"),
+                ("NANNA_TOOLS", "1"),
+                ("NANNA_MAX_ATTEMPTS", "5"),
+                ("NANNA_TOKENS_PER_MINUTE", "20000"),
+                ("NANNA_SEED", "7"),
+                ("NANNA_AGENT_TEMPERATURE", "0.4"),
+            ]))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.provider.base_urls,
+            vec!["http://a:8000", "http://b:8000"]
+        );
+        assert_eq!(config.provider.kind, ProviderKind::Openai);
+        assert!((config.chat.temperature - 0.2).abs() < 1e-6);
+        assert_eq!(config.tasks.max_concurrent, 2);
+        assert!(config.tools.enabled);
+        assert_eq!(config.retry.retry_config().max_attempts, 5);
+        assert_eq!(config.agent.seed(), Some(7));
+        assert_eq!(config.agent.temperature, Some(0.4));
+        let governor = config.provider.governor_config();
+        assert_eq!(governor.max_in_flight, 4);
+        assert_eq!(governor.tokens_per_minute, Some(20000));
+        assert_eq!(
+            config.retry.retry_config().deadline,
+            Some(Duration::from_secs(300))
+        );
+    }
+
+    #[test]
+    fn test_retry_delays_are_checked_together() {
+        let config = ConfigLoader::new()
+            .with_toml_str(
+                "nanna.toml",
+                "[retry]\nbase_delay_ms = 10\nmax_delay_ms = 50",
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.retry.base_delay_ms, 10);
+        assert_eq!(config.retry.max_delay_ms, 50);
+
+        let err = ConfigLoader::new()
+            .with_toml_str("nanna.toml", "[retry]\nbase_delay_ms = 900")
+            .unwrap()
+            .with_env(env(&[("NANNA_MAX_DELAY_MS", "500")]))
+            .unwrap()
+            .build()
+            .unwrap_err();
+        match err {
+            ConfigError::Invalid { origin, key, .. } => {
+                assert_eq!(origin, "nanna.toml");
+                assert_eq!(key, "retry.base_delay_ms");
+            }
+            other => panic!("expected invalid key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_name_origin_and_key() {
+        let cases = [
+            ("[provider]\nbase_url = \"http://x\"", "provider.base_url"),
+            ("[modles]\nchat = \"x\"", "modles.chat"),
+            ("[agent]\nmax_iterations = \"ten\"", "agent.max_iterations"),
+            ("[agent]\nmax_iterations = 0", "agent.max_iterations"),
+            ("provider.kind = \"bedrock\"", "provider.kind"),
+            (
+                "provider.base_urls = [\"localhost:11434\"]",
+                "provider.base_urls",
+            ),
+            ("chat.temperature = 3.5", "chat.temperature"),
+            ("agent.temperature = -1.0", "agent.temperature"),
+            ("models.agent = \"\"", "models.agent"),
+            ("retry.max_attempts = 0", "retry.max_attempts"),
+            ("provider.max_in_flight = 0", "provider.max_in_flight"),
+            ("provider = 3", "provider"),
+            ("[bogus]", "bogus"),
+        ];
+        for (content, expected_key) in cases {
+            match ConfigLoader::new().with_toml_str("nanna.toml", content) {
+                Err(ConfigError::Invalid { origin, key, .. }) => {
+                    assert_eq!(origin, "nanna.toml", "{}", content);
+                    assert_eq!(key, expected_key, "{}", content);
+                }
+                other => panic!("{}: expected invalid key, got {:?}", content, other),
+            }
+        }
+
+        let err = ConfigLoader::new()
+            .with_env(env(&[("NANNA_TIMEOUT_SECS", "soon")]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "NANNA_TIMEOUT_SECS: invalid `provider.timeout_secs`: \"soon\" is not an integer"
+        );
+
+        let err = ConfigLoader::new()
+            .with_toml_str("broken.toml", "[provider")
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+        assert!(err.to_string().starts_with("broken.toml: "));
+    }
+
+    #[test]
+    fn test_tool_filters() {
+        let config = ConfigLoader::new()
+            .with_toml_str(
+                "repo.toml",
+                "[tools]\nallow = [\"echo\", \"calculate\", \"read_file\"]\ndeny = [\"read_file\"]",
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut registry = crate::tools::create_tool_registry(Path::new("."));
+        config.filter_tools(&mut registry).unwrap();
+        let mut tools = registry.list_tools();
+        tools.sort_unstable();
+        assert_eq!(tools, vec!["calculate", "echo"]);
+
+        let config = ConfigLoader::new()
+            .with_env(env(&[("NANNA_TOOLS_DENY", "rm_rf")]))
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut registry = crate::tools::create_tool_registry(Path::new("."));
+        let err = config.filter_tools(&mut registry).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("NANNA_TOOLS_DENY: invalid `tools.deny`: unknown tool \"rm_rf\""),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_repo_config_path_stops_at_git_root() {
+        let root = std::env::temp_dir().join(format!("nanna-config-{}", std::process::id()));
+        let repo = root.join("repo");
+        let nested = repo.join("src").join("deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::write(root.join(CONFIG_FILE_NAME), "").unwrap();
+
+        assert_eq!(NannaConfig::repo_config_path(&nested), None);
+
+        std::fs::write(repo.join(CONFIG_FILE_NAME), "models.chat = \"m\"").unwrap();
+        let found = NannaConfig::repo_config_path(&nested).unwrap();
+        assert_eq!(found, repo.join(CONFIG_FILE_NAME));
+
+        let config = ConfigLoader::new()
+            .with_file(&found)
+            .unwrap()
+            .with_file(&root.join("missing.toml"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.models.chat, "m");
+        assert_eq!(config.origin_of("models.chat"), found.display().to_string());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,533 @@
+//! Text replacement behind the `edit_file` tool
+//!
+//! Small models mangle large files when asked to rewrite them, so edits are
+//! expressed as a snippet to find and the text to put in its place. The
+//! snippet has to identify one spot in the file; when it does not, the error
+//! points at the lines the model most likely meant so the next attempt can
+//! carry better context.
+
+use similar::TextDiff;
+use thiserror::Error;
+
+/// Near-miss lines reported when a snippet is not found
+const MAX_CANDIDATES: usize = 3;
+
+/// Similarity a line needs to be reported as a near miss
+const CANDIDATE_THRESHOLD: f32 = 0.6;
+
+/// Columns a tab is taken to span when the edit does not say
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EditError {
+    #[error("old_string must not be empty")]
+    EmptyPattern,
+
+    #[error("old_string and new_string are identical")]
+    NoChange,
+
+    #[error("old_string not found in file{}", describe_candidates(.candidates))]
+    NotFound { candidates: Vec<(usize, String)> },
+
+    #[error(
+        "old_string matches {} places (lines {}); include more surrounding lines to make it unique, or set replace_all",
+        .lines.len(),
+        join_lines(.lines)
+    )]
+    Ambiguous { lines: Vec<usize> },
+
+    #[error(
+        "old_string only matches at {} if whitespace is ignored; copy the exact indentation, or set ignore_whitespace",
+        describe_lines(.lines)
+    )]
+    WhitespaceMismatch { lines: Vec<usize> },
+}
+
+fn join_lines(lines: &[usize]) -> String {
+    lines
+        .iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// "line 3" or "lines 3, 7".
+fn describe_lines(lines: &[usize]) -> String {
+    match lines {
+        [line] => format!("line {}", line),
+        _ => format!("lines {}", join_lines(lines)),
+    }
+}
+
+pub(crate) fn describe_candidates(candidates: &[(usize, String)]) -> String {
+    if candidates.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = candidates
+        .iter()
+        .map(|(line, text)| format!("\n{:>6}  {}", line, text))
+        .collect();
+    format!("; closest lines:{}", lines.concat())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EditOptions {
+    /// Replace every occurrence instead of requiring exactly one
+    pub replace_all: bool,
+    /// Match lines whose leading or trailing whitespace differs
+    pub ignore_whitespace: bool,
+}
+
+/// A successful replacement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub content: String,
+    pub replacements: usize,
+    /// Whether whitespace had to be ignored to find `old_string`
+    pub ignored_whitespace: bool,
+}
+
+/// Replace `old` with `new` in `content`.
+///
+/// Exact matches are preferred. With
+/// [`ignore_whitespace`](EditOptions::ignore_whitespace), `old` may instead
+/// match whole lines that differ only in surrounding whitespace; `new` is
+/// then re-indented to the indentation found in the file. CRLF files are
+/// matched as if they used `\n` and keep their line endings; files mixing
+/// both are matched as they are.
+pub fn replace(
+    content: &str,
+    old: &str,
+    new: &str,
+    options: EditOptions,
+) -> Result<Edit, EditError> {
+    if old.is_empty() {
+        return Err(EditError::EmptyPattern);
+    }
+    if old == new {
+        return Err(EditError::NoChange);
+    }
+
+    let crlf = uses_crlf(content);
+    let text = if crlf {
+        content.replace("\r\n", "\n")
+    } else {
+        content.to_string()
+    };
+    // Files mixing line endings are matched as they are, so the edit
+    // touches no line it does not replace
+    let (old, new) = if content.contains("\r\n") && !crlf {
+        (old.to_string(), new.to_string())
+    } else {
+        (old.replace("\r\n", "\n"), new.replace("\r\n", "\n"))
+    };
+
+    let exact: Vec<usize> = text.match_indices(old.as_str()).map(|(i, _)| i).collect();
+    let edit = if !exact.is_empty() {
+        if exact.len() > 1 && !options.replace_all {
+            return Err(EditError::Ambiguous {
+                lines: exact.iter().map(|&i| line_number_at(&text, i)).collect(),
+            });
+        }
+        let replaced = if options.replace_all {
+            text.replace(old.as_str(), &new)
+        } else {
+            text.replacen(old.as_str(), &new, 1)
+        };
+        Edit {
+            content: replaced,
+            replacements: if options.replace_all { exact.len() } else { 1 },
+            ignored_whitespace: false,
+        }
+    } else {
+        let spans = whitespace_insensitive_matches(&text, &old);
+        if spans.is_empty() {
+            return Err(EditError::NotFound {
+                candidates: closest_lines(&text, &old),
+            });
+        }
+        let lines: Vec<usize> = spans
+            .iter()
+            .map(|s| line_number_at(&text, s.start))
+            .collect();
+        if !options.ignore_whitespace {
+            return Err(EditError::WhitespaceMismatch { lines });
+        }
+        if spans.len() > 1 && !options.replace_all {
+            return Err(EditError::Ambiguous { lines });
+        }
+        let mut replaced = text.clone();
+        // Back to front, so earlier offsets stay valid
+        for span in spans.iter().rev() {
+            let replacement = reindent(&new, &old, &text[span.start..span.end]);
+            let mut end = span.end;
+            // Deleting whole lines takes their line break with them
+            if replacement.is_empty() {
+                end += line_break_at(&text[end..]).len();
+            }
+            let replacement = if old.ends_with('\n') {
+                replacement
+                    .strip_suffix(line_break_at_end(&replacement))
+                    .unwrap_or(&replacement)
+            } else {
+                &replacement
+            };
+            replaced.replace_range(span.start..end, replacement);
+        }
+        Edit {
+            content: replaced,
+            replacements: spans.len(),
+            ignored_whitespace: true,
+        }
+    };
+
+    Ok(Edit {
+        content: if crlf {
+            edit.content.replace('\n', "\r\n")
+        } else {
+            edit.content
+        },
+        ..edit
+    })
+}
+
+/// Render the change from `before` to `after` as a unified diff.
+pub fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+pub(crate) fn uses_crlf(content: &str) -> bool {
+    content.contains("\r\n") && !content.replace("\r\n", "").contains('\n')
+}
+
+/// The line break `text` starts with, if any.
+fn line_break_at(text: &str) -> &str {
+    if text.starts_with("\r\n") {
+        "\r\n"
+    } else if text.starts_with('\n') {
+        "\n"
+    } else {
+        ""
+    }
+}
+
+/// The line break `text` ends with, if any.
+fn line_break_at_end(text: &str) -> &str {
+    if text.ends_with("\r\n") {
+        "\r\n"
+    } else if text.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    }
+}
+
+/// 1-based line number of byte offset `index`.
+fn line_number_at(text: &str, index: usize) -> usize {
+    text[..index].matches('\n').count() + 1
+}
+
+/// Indentation of the first non-blank line of `text`.
+fn first_indentation(text: &str) -> Option<&str> {
+    let line = text.lines().find(|l| !l.trim().is_empty())?;
+    Some(&line[..line.len() - line.trim_start().len()])
+}
+
+/// Byte ranges of runs of whole lines equal to the lines of `pattern` once
+/// surrounding whitespace is trimmed. Each range ends before the last line's
+/// line break, `\r` included.
+fn whitespace_insensitive_matches(text: &str, pattern: &str) -> Vec<std::ops::Range<usize>> {
+    let wanted: Vec<&str> = pattern.lines().map(str::trim).collect();
+    if wanted.iter().all(|l| l.is_empty()) {
+        return Vec::new();
+    }
+
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        offsets.push((offset, content.strip_suffix('\r').unwrap_or(content)));
+        offset += line.len();
+    }
+
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i + wanted.len() <= offsets.len() {
+        let window = &offsets[i..i + wanted.len()];
+        if window
+            .iter()
+            .zip(&wanted)
+            .all(|((_, line), want)| line.trim() == *want)
+        {
+            let (last_start, last_line) = window[window.len() - 1];
+            spans.push(window[0].0..last_start + last_line.len());
+            i += wanted.len();
+        } else {
+            i += 1;
+        }
+    }
+    spans
+}
+
+/// Shift `new` by the difference between the indentation `old` was written
+/// with and the indentation of the text it matched.
+fn reindent(new: &str, old: &str, matched: &str) -> String {
+    let (Some(written), Some(actual)) = (first_indentation(old), first_indentation(matched)) else {
+        return new.to_string();
+    };
+    if written == actual {
+        return new.to_string();
+    }
+    let tab = tab_width(written, actual);
+    let shift = columns(actual, tab) - columns(written, tab);
+    let reshape = |line: &str| -> String {
+        if line.trim().is_empty() {
+            return line.to_string();
+        }
+        if let Some(extra) = actual.strip_prefix(written) {
+            return format!("{}{}", extra, line);
+        }
+        if let Some(excess) = written.strip_prefix(actual) {
+            // Lines indented less than `old` lose as much as they have
+            let indent = line.len() - line.trim_start().len();
+            return line[indent.min(excess.len())..].to_string();
+        }
+        // Tabs against spaces: measure every line in columns, shift it by
+        // the difference and write it back in the file's style
+        let rest = line.trim_start();
+        let width = (columns(&line[..line.len() - rest.len()], tab) + shift).max(0) as usize;
+        let indentation = if actual.contains('\t') {
+            format!("{}{}", "\t".repeat(width / tab), " ".repeat(width % tab))
+        } else {
+            " ".repeat(width)
+        };
+        format!("{}{}", indentation, rest)
+    };
+    new.split_inclusive('\n').map(reshape).collect()
+}
+
+/// Width of a tab that makes `written` and `actual` the same depth, or
+/// [`DEFAULT_TAB_WIDTH`] when no width does.
+fn tab_width(written: &str, actual: &str) -> usize {
+    let count = |text: &str, c: char| text.chars().filter(|&x| x == c).count() as isize;
+    let spaces = count(actual, ' ') - count(written, ' ');
+    let tabs = count(written, '\t') - count(actual, '\t');
+    if tabs != 0 && spaces % tabs == 0 && spaces / tabs > 0 {
+        (spaces / tabs) as usize
+    } else {
+        DEFAULT_TAB_WIDTH
+    }
+}
+
+/// Width of `indentation` in columns.
+fn columns(indentation: &str, tab: usize) -> isize {
+    indentation
+        .chars()
+        .map(|c| if c == '\t' { tab } else { 1 })
+        .sum::<usize>() as isize
+}
+
+/// Lines most similar to the first non-blank line of `pattern`.
+pub(crate) fn closest_lines(text: &str, pattern: &str) -> Vec<(usize, String)> {
+    let Some(anchor) = pattern.lines().map(str::trim).find(|l| !l.is_empty()) else {
+        return Vec::new();
+    };
+    let mut scored: Vec<(f32, usize, &str)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let ratio = TextDiff::from_chars(anchor, line.trim()).ratio();
+            (ratio, i + 1, line)
+        })
+        .filter(|(ratio, _, _)| *ratio >= CANDIDATE_THRESHOLD)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(_, line, text)| (line, text.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+
+    fn exact() -> EditOptions {
+        EditOptions::default()
+    }
+
+    fn fuzzy() -> EditOptions {
+        EditOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_replace_unique_match() {
+        let edit = replace(SOURCE, "let x = 1;", "let x = 2;", exact()).unwrap();
+        assert_eq!(edit.replacements, 1);
+        assert!(!edit.ignored_whitespace);
+        assert!(edit.content.contains("let x = 2;"));
+        assert!(!edit.content.contains("let x = 1;"));
+    }
+
+    #[test]
+    fn test_replace_rejects_ambiguous_match() {
+        let err = replace(SOURCE, "x", "y", exact()).unwrap_err();
+        assert_eq!(err, EditError::Ambiguous { lines: vec![2, 3] });
+        assert!(err.to_string().contains("lines 2, 3"));
+
+        let edit = replace(
+            SOURCE,
+            "x",
+            "y",
+            EditOptions {
+                replace_all: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(edit.replacements, 2);
+        assert!(!edit.content.contains('x'));
+    }
+
+    #[test]
+    fn test_replace_reports_closest_lines() {
+        let err = replace(SOURCE, "let x = 10;", "let x = 2;", exact()).unwrap_err();
+        match &err {
+            EditError::NotFound { candidates } => {
+                assert_eq!(candidates[0], (2, "    let x = 1;".to_string()));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(err.to_string().contains("closest lines"));
+
+        let err = replace(SOURCE, "struct Unrelated;", "", exact()).unwrap_err();
+        assert_eq!(err, EditError::NotFound { candidates: vec![] });
+    }
+
+    #[test]
+    fn test_replace_rejects_empty_and_noop_edits() {
+        assert_eq!(
+            replace(SOURCE, "", "x", exact()).unwrap_err(),
+            EditError::EmptyPattern
+        );
+        assert_eq!(
+            replace(SOURCE, "main", "main", exact()).unwrap_err(),
+            EditError::NoChange
+        );
+    }
+
+    #[test]
+    fn test_replace_tolerates_whitespace_drift() {
+        let old = "let x = 1;\nprintln!(\"{}\", x);\n";
+        let new = "let x = 2;\nprintln!(\"{}\", x + 1);\n";
+
+        let err = replace(SOURCE, old, new, exact()).unwrap_err();
+        assert_eq!(err, EditError::WhitespaceMismatch { lines: vec![2] });
+
+        let edit = replace(SOURCE, old, new, fuzzy()).unwrap();
+        assert!(edit.ignored_whitespace);
+        assert_eq!(
+            edit.content,
+            "fn main() {\n    let x = 2;\n    println!(\"{}\", x + 1);\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_replace_shifts_every_line_by_the_difference() {
+        let source = "impl A {\n    fn f() {\n        g();\n    }\n}\n";
+        // Written two levels deep, with the closing brace one level out
+        let old = "        fn f() {\n            g();\n        }\n";
+        let new = "        fn f() {\n            h();\n        }\n    // moved\n";
+
+        let edit = replace(source, old, new, fuzzy()).unwrap();
+        assert_eq!(
+            edit.content,
+            "impl A {\n    fn f() {\n        h();\n    }\n// moved\n}\n"
+        );
+
+        let old = "fn f() {\n    g();\n}\n";
+        let new = "fn f() {\n    h();\n}\n";
+        let edit = replace(source, old, new, fuzzy()).unwrap();
+        assert_eq!(
+            edit.content,
+            "impl A {\n    fn f() {\n        h();\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_delete_removes_lines() {
+        let edit = replace(SOURCE, "\tlet x = 1;\n", "", fuzzy()).unwrap();
+        assert_eq!(edit.content, "fn main() {\n    println!(\"{}\", x);\n}\n");
+    }
+
+    #[test]
+    fn test_replace_preserves_crlf() {
+        let source = SOURCE.replace('\n', "\r\n");
+        let edit = replace(
+            &source,
+            "let x = 1;\n    println",
+            "let y = 1;\n    println",
+            exact(),
+        )
+        .unwrap();
+        assert_eq!(edit.content, source.replace("let x = 1;", "let y = 1;"));
+    }
+
+    #[test]
+    fn test_fuzzy_replace_converts_tabs_and_spaces() {
+        let source = "impl A {\n\tfn f() {\n\t\tg();\n\t}\n}\n";
+        let old = "    fn f() {\n        g();\n    }\n";
+        let new = "    fn f() {\n        if x {\n            h();\n        }\n    }\n";
+        let edit = replace(source, old, new, fuzzy()).unwrap();
+        assert_eq!(
+            edit.content,
+            "impl A {\n\tfn f() {\n\t\tif x {\n\t\t\th();\n\t\t}\n\t}\n}\n"
+        );
+
+        let source = "impl A {\n  fn f() {\n    g();\n  }\n}\n";
+        let old = "\tfn f() {\n\t\tg();\n\t}\n";
+        let new = "\tfn f() {\n\t\th();\n\t}\n";
+        let edit = replace(source, old, new, fuzzy()).unwrap();
+        assert_eq!(edit.content, "impl A {\n  fn f() {\n    h();\n  }\n}\n");
+    }
+
+    #[test]
+    fn test_replace_keeps_mixed_line_endings() {
+        let source = "a\r\nb\nc\r\nd\n";
+        let edit = replace(source, "b\nc\r\n", "B\nC\r\n", exact()).unwrap();
+        assert_eq!(edit.content, "a\r\nB\nC\r\nd\n");
+
+        let edit = replace(source, "  c\n", "  C\n", fuzzy()).unwrap();
+        assert_eq!(edit.content, "a\r\nb\nC\r\nd\n");
+
+        let edit = replace(source, "c\n", "", fuzzy()).unwrap();
+        assert_eq!(edit.content, "a\r\nb\nd\n");
+    }
+
+    #[test]
+    fn test_whitespace_mismatch_names_every_line() {
+        let one = EditError::WhitespaceMismatch { lines: vec![2] };
+        assert!(one.to_string().contains("only matches at line 2 if"));
+        let two = EditError::WhitespaceMismatch { lines: vec![2, 5] };
+        assert!(two.to_string().contains("only matches at lines 2, 5 if"));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let after = SOURCE.replace("let x = 1;", "let x = 2;");
+        let diff = unified_diff("src/main.rs", SOURCE, &after);
+        assert!(diff.starts_with("--- a/src/main.rs\n+++ b/src/main.rs\n"));
+        assert!(diff.contains("-    let x = 1;\n+    let x = 2;\n"));
+    }
+}
@@ -274,6 +274,7 @@ impl GitRepository {
                 _ => {}
             }
 
+            #[allow(clippy::collapsible_match)]
             match worktree_status {
                 'M' | 'D' => {
                     if !staged.contains(&file) {
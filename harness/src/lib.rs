@@ -1,11 +1,17 @@
+// Lets `#[derive(ToolArgs)]` refer to `::harness` from inside this crate
+extern crate self as harness;
+
 pub mod agent;
+pub mod config;
 pub mod container;
+pub mod edit;
 pub mod entities;
 pub mod eval;
 pub mod mcp;
 pub mod monitoring;
 pub mod observability;
 pub mod onboarding;
+pub mod patch;
 pub mod task;
 pub mod telemetry;
 pub mod tools;
@@ -30,15 +36,16 @@ pub use telemetry::{
     TelemetryError, TelemetryExporter, TelemetrySystem, TraceContext, TraceGuard,
 };
 pub use tools::{
-    create_tool_registry, CalculatorTool, EchoTool, GitDiffTool, GitHubPrStatusTool, GitHubStatus,
-    GitStatusTool, ListDirTool, PrStatusData, ReadFileTool, RunCommandTool, SearchTool, Tool,
-    ToolError, ToolRegistry, ToolResult, WriteFileTool,
+    create_tool_registry, ApplyPatchTool, CalculatorTool, EchoTool, EditFileTool, GitDiffTool,
+    GitHubPrStatusTool, GitHubStatus, GitStatusTool, ListDirTool, PrStatusData, ReadFileTool,
+    RunCommandTool, SearchTool, Tool, ToolArgs, ToolError, ToolRegistry, ToolResult, TypedTool,
+    WriteFileTool,
 };
 
 // Export agent types
 pub use agent::{
     AgentComponent, AgentConfig, AgentContext, AgentError, AgentLoop, AgentResult, AgentRunResult,
-    AgentState,
+    AgentState, TokenSink,
 };
 
 // Export eval report types
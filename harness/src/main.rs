@@ -1,16 +1,49 @@
 use clap::{Parser, Subcommand};
+use harness::agent::compaction::{compact_without_summary, ContextBudget};
+use harness::config::{NannaConfig, ProviderKind, RoutingKind};
 use harness::entities::ast::WorkspaceScanner;
 use harness::entities::git::GitRepository;
 use harness::entities::{EntityStore, InMemoryEntityStore};
 use harness::tools::ToolRegistry;
 use model::prelude::*;
 use std::io::{self, Write};
+use std::sync::Arc;
 use tracing::{error, info};
 
 #[derive(Parser)]
 #[command(name = "harness")]
 #[command(about = "A CLI tool for interacting with language models")]
 struct Cli {
+    /// Model provider backend [default: ollama]
+    #[arg(long, global = true, value_enum)]
+    provider: Option<ProviderKind>,
+    /// Base URL of the model server (defaults to the provider's default).
+    /// Repeat to spread requests over several servers.
+    #[arg(long, global = true)]
+    base_url: Vec<String>,
+    /// How to pick between servers when several base URLs are given
+    /// [default: fallback]
+    #[arg(long, global = true, value_enum)]
+    routing: Option<RoutingKind>,
+    /// Append every model request and response to this cassette file
+    #[arg(long, global = true, conflicts_with = "replay")]
+    record: Option<std::path::PathBuf>,
+    /// Serve model responses from this cassette instead of a model server
+    #[arg(long, global = true)]
+    replay: Option<std::path::PathBuf>,
+    /// Fail on requests the cassette has no recording for, instead of
+    /// serving the next recorded response
+    #[arg(long, global = true, requires = "replay")]
+    replay_strict: bool,
+    /// Cache identical model requests in this directory across runs.
+    /// Only requests sent at temperature 0 or with a fixed seed are cached;
+    /// the agent samples above 0, so set `[agent] seed` or pass --cache-all
+    /// to cache agent runs
+    #[arg(long, global = true)]
+    cache_dir: Option<std::path::PathBuf>,
+    /// Cache every request, not only those sent with temperature 0 or a seed
+    #[arg(long, global = true, requires = "cache_dir")]
+    cache_all: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,21 +52,27 @@ struct Cli {
 enum Commands {
     /// Have a conversation with the model
     Chat {
-        /// The model to use
-        #[arg(short, long, default_value = "llama3.1:8b")]
-        model: String,
+        /// The model to use [default: llama3.1:8b]
+        #[arg(short, long)]
+        model: Option<String>,
         /// Initial prompt (if not provided, starts interactive mode)
         #[arg(short, long)]
         prompt: Option<String>,
         /// Enable tool calling
         #[arg(short, long)]
         tools: bool,
-        /// Temperature setting (0.0 to 2.0)
-        #[arg(long, default_value = "0.7")]
-        temperature: f32,
+        /// Temperature setting (0.0 to 2.0) [default: 0.7]
+        #[arg(long)]
+        temperature: Option<f32>,
+        /// Wait for the full response instead of streaming tokens
+        #[arg(long)]
+        no_stream: bool,
+    },
+    /// List, download and manage models (lists them without a subcommand)
+    Models {
+        #[command(subcommand)]
+        action: Option<ModelsCommand>,
     },
-    /// List available models
-    Models,
     /// List available tools
     Tools,
     /// Health check
@@ -43,27 +82,59 @@ enum Commands {
         /// The prompt for the agent
         #[arg(short, long)]
         prompt: String,
-        /// The model to use
-        #[arg(short, long, default_value = "qwen3:0.6b")]
-        model: String,
-        /// Maximum agent iterations
-        #[arg(long, default_value = "100")]
-        max_iterations: usize,
+        /// The model to use [default: qwen3:0.6b]
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Maximum agent iterations [default: 100]
+        #[arg(long)]
+        max_iterations: Option<usize>,
         /// Enable verbose output
         #[arg(short, long)]
         verbose: bool,
         /// Enable tool calling
         #[arg(short, long)]
         tools: bool,
+        /// Print model output as it is generated
+        #[arg(long)]
+        stream: bool,
     },
     /// Run as an MCP server over stdio
     McpServe {
-        /// The model to use for agent tasks
-        #[arg(short, long, default_value = "qwen3:0.6b")]
+        /// The model to use for agent tasks [default: qwen3:0.6b]
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Maximum agent iterations per task [default: 100]
+        #[arg(long)]
+        max_iterations: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsCommand {
+    /// List installed models
+    List,
+    /// Download a model, showing progress
+    Pull {
+        /// Model to download, e.g. qwen3:0.6b
+        model: String,
+    },
+    /// Show a model's details and capabilities
+    Show {
+        /// Installed model to describe
+        model: String,
+    },
+    /// Delete an installed model
+    Rm {
+        /// Installed model to delete
         model: String,
-        /// Maximum agent iterations per task
-        #[arg(long, default_value = "100")]
-        max_iterations: usize,
+    },
+    /// Load a model into memory ahead of use
+    Warm {
+        /// Installed model to load
+        model: String,
+        /// How long to keep it loaded: 30s, 5m, 1h or forever
+        #[arg(long, default_value = "30m")]
+        keep_alive: KeepAlive,
     },
 }
 
@@ -75,11 +146,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    let config = OllamaConfig::default();
-    let provider = OllamaProvider::new(config)?;
-
     let workspace_root = std::env::current_dir()?;
-    let tool_registry = create_tool_registry(&workspace_root);
+    let mut config = NannaConfig::load(&workspace_root)?;
+    if let Some(kind) = cli.provider {
+        config.provider.kind = kind;
+    }
+    if !cli.base_url.is_empty() {
+        config.provider.base_urls = cli.base_url.clone();
+    }
+    if let Some(routing) = cli.routing {
+        config.provider.routing = routing;
+    }
+
+    let mut provider: Arc<dyn ModelProvider> = match &cli.replay {
+        Some(path) => {
+            let replay = ReplayProvider::from_file(path)?;
+            Arc::new(if cli.replay_strict {
+                replay.strict()
+            } else {
+                replay
+            })
+        }
+        None => build_provider(&config)?,
+    };
+    let mut cache = None;
+    if let Some(dir) = &cli.cache_dir {
+        let policy = if cli.cache_all {
+            CachePolicy::Always
+        } else {
+            CachePolicy::Deterministic
+        };
+        let config = CacheConfig::new().with_policy(policy).with_disk_dir(dir);
+        let caching = Arc::new(CachingProvider::new(provider, config)?);
+        cache = Some(Arc::clone(&caching));
+        provider = caching;
+    }
+    if let Some(path) = &cli.record {
+        provider = Arc::new(RecordingProvider::new(provider, path)?);
+    }
+
+    let tool_registry = create_tool_registry(&workspace_root, &config)?;
 
     match cli.command {
         Commands::Chat {
@@ -87,39 +193,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             prompt,
             tools,
             temperature,
+            no_stream,
         } => {
             let entity_store = initialize_workspace(&workspace_root).await;
+            let model = model.unwrap_or(config.models.chat);
+            let tools = tools || config.tools.enabled;
+            let temperature = temperature.unwrap_or(config.chat.temperature);
+            let stream = config.chat.stream && !no_stream;
 
             if let Some(initial_prompt) = prompt {
                 single_chat(
-                    &provider,
+                    provider.as_ref(),
                     &tool_registry,
                     &model,
                     &initial_prompt,
                     tools,
                     temperature,
+                    stream,
                 )
                 .await?;
             } else {
                 interactive_chat(
-                    &provider,
+                    provider.as_ref(),
                     &tool_registry,
                     &model,
                     tools,
                     temperature,
+                    stream,
                     entity_store,
                 )
                 .await?;
             }
         }
-        Commands::Models => {
-            list_models(&provider).await?;
+        Commands::Models { action } => {
+            manage_models(provider.as_ref(), action.unwrap_or(ModelsCommand::List)).await?;
         }
         Commands::Tools => {
             list_tools(&tool_registry);
         }
         Commands::Health => {
-            health_check(&provider).await?;
+            health_check(provider.as_ref()).await?;
         }
         Commands::Agent {
             prompt,
@@ -127,30 +240,111 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             max_iterations,
             verbose,
             tools,
+            stream,
         } => {
-            run_agent(
-                &prompt,
-                &model,
-                max_iterations,
+            let args = AgentArgs {
+                prompt: &prompt,
+                model: model.as_deref().unwrap_or(&config.models.agent),
+                max_iterations: max_iterations.unwrap_or(config.agent.max_iterations),
                 verbose,
-                tools,
-                &workspace_root,
-            )
-            .await?;
+                tools: tools || config.tools.enabled,
+                stream,
+            };
+            run_agent(provider, args, &config, &workspace_root).await?;
         }
         Commands::McpServe {
             model,
             max_iterations,
         } => {
-            run_mcp_server(&model, max_iterations).await?;
+            let model = model.unwrap_or_else(|| config.models.mcp.clone());
+            let max_iterations = max_iterations.unwrap_or(config.agent.max_iterations);
+            run_mcp_server(provider, &config, &model, max_iterations).await?;
         }
     }
 
+    if let Some(cache) = cache {
+        let stats = cache.stats();
+        info!(
+            "Response cache: {} hits, {} misses, {} bypassed ({:.0}% hit rate)",
+            stats.hits,
+            stats.misses,
+            stats.bypassed,
+            stats.hit_rate() * 100.0
+        );
+    }
+
     Ok(())
 }
 
-fn create_tool_registry(workspace_root: &std::path::Path) -> ToolRegistry {
-    harness::tools::create_tool_registry(workspace_root)
+fn build_provider(
+    config: &NannaConfig,
+) -> Result<Arc<dyn ModelProvider>, Box<dyn std::error::Error>> {
+    let base_urls = &config.provider.base_urls;
+    if base_urls.len() <= 1 {
+        return build_single_provider(config, base_urls.first().map(String::as_str));
+    }
+
+    let policy = match config.provider.routing {
+        RoutingKind::Fallback => RoutingPolicy::Fallback,
+        RoutingKind::RoundRobin => RoutingPolicy::RoundRobin,
+    };
+    let mut router = RouterProvider::new(policy);
+    for url in base_urls {
+        router = router.with_target(RouteTarget::new(
+            url.clone(),
+            build_single_provider(config, Some(url))?,
+        ));
+    }
+    Ok(Arc::new(router))
+}
+
+fn build_single_provider(
+    config: &NannaConfig,
+    base_url: Option<&str>,
+) -> Result<Arc<dyn ModelProvider>, Box<dyn std::error::Error>> {
+    let timeout = config.provider.timeout();
+    let provider: Arc<dyn ModelProvider> = match config.provider.kind {
+        ProviderKind::Ollama => {
+            let mut config = OllamaConfig::default().with_timeout(timeout);
+            if let Some(url) = base_url {
+                config = config.with_base_url(url);
+            }
+            Arc::new(OllamaProvider::new(config)?)
+        }
+        ProviderKind::Openai => {
+            let mut config = OpenAiConfig::default().with_timeout(timeout);
+            if let Some(url) = base_url {
+                config = config.with_base_url(url);
+            }
+            if let Ok(key) = std::env::var("OPENAI_API_KEY") {
+                config = config.with_api_key(key);
+            }
+            Arc::new(OpenAiProvider::new(config)?)
+        }
+        ProviderKind::Anthropic => {
+            let mut config = AnthropicConfig::default().with_timeout(timeout);
+            if let Some(url) = base_url {
+                config = config.with_base_url(url);
+            }
+            if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
+                config = config.with_api_key(key);
+            }
+            Arc::new(AnthropicProvider::new(config)?)
+        }
+    };
+    Ok(Arc::new(GovernedProvider::new(
+        provider,
+        config.provider.governor_config(),
+    )?))
+}
+
+fn create_tool_registry(
+    workspace_root: &std::path::Path,
+    config: &NannaConfig,
+) -> Result<ToolRegistry, Box<dyn std::error::Error>> {
+    let mut registry = harness::tools::create_tool_registry(workspace_root);
+    config.filter_tools(&mut registry)?;
+    Ok(registry)
 }
 
 async fn initialize_workspace(workspace_root: &std::path::Path) -> InMemoryEntityStore {
@@ -180,17 +374,74 @@ async fn initialize_workspace(workspace_root: &std::path::Path) -> InMemoryEntit
     store
 }
 
+/// Send one chat turn, streaming the reply to stdout when requested and
+/// supported. Returns the assembled response and whether its content was
+/// already printed.
+async fn chat_turn(
+    provider: &dyn ModelProvider,
+    request: ChatRequest,
+    stream: bool,
+) -> Result<(ChatResponse, bool), Box<dyn std::error::Error>> {
+    if let Some(streaming) = provider.as_streaming().filter(|_| stream) {
+        let chat_stream = streaming.chat_stream(request).await?;
+        let mut printed = false;
+        let mut print_answer = |text: &str| {
+            if text.is_empty() {
+                return;
+            }
+            if !printed {
+                print!("Assistant: ");
+                printed = true;
+            }
+            print!("{}", text);
+            let _ = io::stdout().flush();
+        };
+        // Inline <think> blocks are reasoning, not part of the reply
+        let mut think_filter = ThinkTagFilter::new();
+        let response = collect_stream(chat_stream, |chunk| {
+            if let Some(delta) = &chunk.delta {
+                print_answer(&think_filter.push(delta));
+            }
+        })
+        .await?;
+        print_answer(&think_filter.finish());
+        if printed {
+            println!();
+        }
+        return Ok((response, printed));
+    }
+
+    Ok((provider.chat(request).await?, false))
+}
+
+/// How much history a chat session with `model` may send, going by the
+/// user's capability overrides and what `provider` reports.
+async fn chat_context_budget(provider: &dyn ModelProvider, model: &str) -> ContextBudget {
+    let mut registry = CapabilityRegistry::configured();
+    registry.probe(provider, model).await;
+    let caps = registry.resolve(model);
+    match caps.context_length {
+        Some(context_length) => ContextBudget::default().with_max_tokens(context_length as usize),
+        None => ContextBudget::default(),
+    }
+}
+
 async fn single_chat(
-    provider: &OllamaProvider,
+    provider: &dyn ModelProvider,
     tool_registry: &ToolRegistry,
     model: &str,
     prompt: &str,
     enable_tools: bool,
     temperature: f32,
+    stream: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut messages = vec![ChatMessage::user(prompt)];
+    let budget = chat_context_budget(provider, model).await;
 
     loop {
+        if compact_without_summary(&mut messages, &budget, None) {
+            info!("Compacted chat history to fit the context window");
+        }
         let mut request = ChatRequest::new(model, messages.clone()).with_temperature(temperature);
 
         if enable_tools {
@@ -198,10 +449,10 @@ async fn single_chat(
             request = request.with_tools(tool_definitions);
         }
 
-        let response = provider.chat(request).await?;
+        let (response, printed) = chat_turn(provider, request, stream).await?;
         let choice = &response.choices[0];
 
-        if let Some(content) = &choice.message.content {
+        if let Some(content) = choice.message.content.as_ref().filter(|_| !printed) {
             println!("Assistant: {}", content);
         }
 
@@ -249,11 +500,12 @@ async fn single_chat(
 }
 
 async fn interactive_chat(
-    provider: &OllamaProvider,
+    provider: &dyn ModelProvider,
     tool_registry: &ToolRegistry,
     model: &str,
     enable_tools: bool,
     temperature: f32,
+    stream: bool,
     entity_store: InMemoryEntityStore,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let entity_count = entity_store
@@ -267,6 +519,7 @@ async fn interactive_chat(
     println!("Type 'quit' or 'exit' to end the conversation.\n");
 
     let mut messages = vec![];
+    let budget = chat_context_budget(provider, model).await;
 
     loop {
         print!("You: ");
@@ -288,6 +541,9 @@ async fn interactive_chat(
         messages.push(ChatMessage::user(input));
 
         loop {
+            if compact_without_summary(&mut messages, &budget, None) {
+                info!("Compacted chat history to fit the context window");
+            }
             let mut request =
                 ChatRequest::new(model, messages.clone()).with_temperature(temperature);
 
@@ -296,10 +552,10 @@ async fn interactive_chat(
                 request = request.with_tools(tool_definitions);
             }
 
-            let response = provider.chat(request).await?;
+            let (response, printed) = chat_turn(provider, request, stream).await?;
             let choice = &response.choices[0];
 
-            if let Some(content) = &choice.message.content {
+            if let Some(content) = choice.message.content.as_ref().filter(|_| !printed) {
                 println!("Assistant: {}", content);
             }
 
@@ -348,12 +604,15 @@ async fn interactive_chat(
     Ok(())
 }
 
-async fn list_models(provider: &OllamaProvider) -> Result<(), Box<dyn std::error::Error>> {
+async fn list_models(provider: &dyn ModelProvider) -> Result<(), Box<dyn std::error::Error>> {
     println!("Available models:");
     let models = provider.list_models().await?;
 
     if models.is_empty() {
-        println!("  No models found. Make sure Ollama is running and has models installed.");
+        println!(
+            "  No models found. Make sure {} is running and has models installed.",
+            provider.provider_name()
+        );
     } else {
         for model in models {
             println!(
@@ -370,6 +629,94 @@ async fn list_models(provider: &OllamaProvider) -> Result<(), Box<dyn std::error
     Ok(())
 }
 
+async fn manage_models(
+    provider: &dyn ModelProvider,
+    action: ModelsCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let admin = || {
+        provider.as_admin().ok_or_else(|| {
+            format!(
+                "The {} provider does not support managing models",
+                provider.provider_name()
+            )
+        })
+    };
+
+    match action {
+        ModelsCommand::List => list_models(provider).await?,
+        ModelsCommand::Pull { model } => {
+            println!("Pulling {}...", model);
+            let mut last_status = String::new();
+            pull_model(admin()?, &model, |progress| {
+                match progress.fraction() {
+                    Some(fraction) => {
+                        print!("\r  {} {:>3.0}%", progress.status, fraction * 100.0);
+                        let _ = io::stdout().flush();
+                    }
+                    None if progress.status != last_status => {
+                        if !last_status.is_empty() {
+                            println!();
+                        }
+                        print!("  {}", progress.status);
+                        let _ = io::stdout().flush();
+                    }
+                    None => {}
+                }
+                last_status = progress.status.clone();
+            })
+            .await?;
+            println!("\n✓ Pulled {}", model);
+        }
+        ModelsCommand::Show { model } => {
+            let details = admin()?.show(&model).await?;
+            println!("{}", details.name);
+            let fields = [
+                ("Family", details.family),
+                ("Parameters", details.parameter_size),
+                ("Quantization", details.quantization),
+                ("Format", details.format),
+                ("Modified", details.modified_at),
+                (
+                    "Context length",
+                    details.capabilities.context_length.map(|n| n.to_string()),
+                ),
+            ];
+            for (label, value) in fields {
+                if let Some(value) = value {
+                    println!("  {:<15} {}", format!("{}:", label), value);
+                }
+            }
+            let capabilities: Vec<&str> = [
+                ("tools", details.capabilities.tools),
+                ("thinking", details.capabilities.thinking),
+                ("vision", details.capabilities.vision),
+            ]
+            .into_iter()
+            .filter(|(_, supported)| *supported == Some(true))
+            .map(|(name, _)| name)
+            .collect();
+            if !capabilities.is_empty() {
+                println!("  {:<15} {}", "Capabilities:", capabilities.join(", "));
+            }
+        }
+        ModelsCommand::Rm { model } => {
+            admin()?.delete(&model).await?;
+            println!("✓ Deleted {}", model);
+        }
+        ModelsCommand::Warm { model, keep_alive } => {
+            admin()?.warm(&model, keep_alive).await?;
+            match keep_alive {
+                KeepAlive::Forever => println!("✓ Loaded {} until the server restarts", model),
+                KeepAlive::For(duration) => {
+                    println!("✓ Loaded {} for {}s", model, duration.as_secs())
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn list_tools(tool_registry: &ToolRegistry) {
     println!("Available tools:");
     let tools = tool_registry.list_tools();
@@ -386,12 +733,15 @@ fn list_tools(tool_registry: &ToolRegistry) {
     }
 }
 
-async fn health_check(provider: &OllamaProvider) -> Result<(), Box<dyn std::error::Error>> {
+async fn health_check(provider: &dyn ModelProvider) -> Result<(), Box<dyn std::error::Error>> {
     println!("Performing health check...");
 
     match provider.health_check().await {
         Ok(()) => {
-            println!("✓ Health check passed. Ollama is running and accessible.");
+            println!(
+                "✓ Health check passed. {} is running and accessible.",
+                provider.provider_name()
+            );
             info!("Health check successful");
         }
         Err(e) => {
@@ -404,26 +754,47 @@ async fn health_check(provider: &OllamaProvider) -> Result<(), Box<dyn std::erro
     Ok(())
 }
 
-async fn run_agent(
-    prompt: &str,
-    model: &str,
+/// Command-line options for the `agent` subcommand
+struct AgentArgs<'a> {
+    prompt: &'a str,
+    model: &'a str,
     max_iterations: usize,
     verbose: bool,
     tools: bool,
+    stream: bool,
+}
+
+async fn run_agent(
+    provider: Arc<dyn ModelProvider>,
+    args: AgentArgs<'_>,
+    config: &NannaConfig,
     workspace_root: &std::path::Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use harness::agent::{AgentConfig, AgentContext, AgentLoop};
-    use std::sync::Arc;
 
-    let config = OllamaConfig::default();
-    let provider = Arc::new(OllamaProvider::new(config)?);
+    let AgentArgs {
+        prompt,
+        model,
+        max_iterations,
+        verbose,
+        tools,
+        stream,
+    } = args;
+
     let entity_store = initialize_workspace(workspace_root).await;
 
     let agent_config = AgentConfig {
         max_iterations,
         verbose,
-        system_prompt: "You are a helpful coding assistant. Use the available tools to accomplish tasks. When you have completed the task, respond with a summary.".to_string(),
+        system_prompt: config.agent.system_prompt.clone(),
         model_name: model.to_string(),
+        retry: config.retry.retry_config(),
+        circuit_breaker: Some(Arc::new(CircuitBreaker::new(
+            config.retry.circuit_breaker_config(),
+        )?)),
+        seed: config.agent.seed(),
+        temperature: config.agent.temperature,
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -440,18 +811,41 @@ async fn run_agent(
     }
 
     let mut agent = if tools {
-        let tool_registry = create_tool_registry(workspace_root);
+        let tool_registry = create_tool_registry(workspace_root, config)?;
         AgentLoop::with_tools(agent_config, entity_store, provider, tool_registry)
     } else {
         AgentLoop::with_llm(agent_config, entity_store, provider)
     };
 
+    if stream {
+        agent.set_token_sink(Arc::new(|delta: &str| {
+            print!("{}", delta);
+            let _ = io::stdout().flush();
+        }));
+    }
+
     let result = agent.run(context).await?;
 
     println!("\n--- Agent Result ---");
     println!("Completed: {}", result.task_completed);
     println!("Iterations: {}", result.iterations);
     println!("Final state: {:?}", result.final_state);
+    if let Some(served_by) = &result.served_by {
+        println!("Served by: {}", served_by);
+    }
+    if let Some(usage) = &result.usage {
+        print!(
+            "Tokens: {} prompt + {} completion = {}",
+            usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+        );
+        match usage.tokens_per_second() {
+            Some(tps) => println!(" ({:.1} tokens/s)", tps),
+            None => println!(),
+        }
+        if let Some(queued) = usage.timings.as_ref().and_then(|t| t.queue_duration) {
+            println!("Queued: {:?}", queued);
+        }
+    }
 
     if verbose {
         println!("\n--- Conversation History ---");
@@ -476,20 +870,31 @@ async fn run_agent(
 }
 
 async fn run_mcp_server(
+    provider: Arc<dyn ModelProvider>,
+    config: &NannaConfig,
     model: &str,
     max_iterations: usize,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use harness::mcp::NannaMcpServer;
     use harness::task::TaskManager;
-    use std::sync::Arc;
 
-    let config = OllamaConfig::default();
-    let provider = Arc::new(OllamaProvider::new(config)?);
-    let task_manager = Arc::new(TaskManager::default());
+    let task_manager = Arc::new(
+        TaskManager::new(config.tasks.max_concurrent)
+            .with_system_prompt(config.agent.system_prompt.clone())
+            .with_retry(config.retry.retry_config())
+            .with_circuit_breaker(Arc::new(CircuitBreaker::new(
+                config.retry.circuit_breaker_config(),
+            )?))
+            .with_seed(config.agent.seed())
+            .with_temperature(config.agent.temperature)
+            .with_tools(config.tools.clone()),
+    );
 
     info!(
-        "Starting Nanna MCP server (model: {}, max_iterations: {})",
-        model, max_iterations
+        "Starting Nanna MCP server (provider: {}, model: {}, max_iterations: {})",
+        provider.provider_name(),
+        model,
+        max_iterations
     );
 
     let server = NannaMcpServer::new(task_manager, provider, model.to_string(), max_iterations);
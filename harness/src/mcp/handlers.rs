@@ -16,6 +16,7 @@ pub async fn handle_list_tasks(task_manager: &Arc<TaskManager>) -> Result<Value,
                 TaskStatus::Running { .. } => "Running",
                 TaskStatus::Completed { .. } => "Completed",
                 TaskStatus::Failed { .. } => "Failed",
+                TaskStatus::Cancelled { .. } => "Cancelled",
             };
             serde_json::json!({
                 "id": t.id.0,
@@ -124,6 +125,7 @@ pub async fn handle_poll_task(
         } => ("Running", Some(*iterations), Some(started_at.to_rfc3339())),
         TaskStatus::Completed { .. } => ("Completed", None, None),
         TaskStatus::Failed { .. } => ("Failed", None, None),
+        TaskStatus::Cancelled { .. } => ("Cancelled", None, None),
     };
 
     let mut response = serde_json::json!({
@@ -170,6 +172,8 @@ pub async fn handle_get_result(
             "files_modified": result.files_modified,
             "iterations": result.iterations,
             "model_used": result.model_used,
+            "served_by": result.served_by,
+            "usage": result.usage_json(),
         })),
         TaskStatus::Failed {
             error,
@@ -182,6 +186,15 @@ pub async fn handle_get_result(
             "error": error,
             "diagnostics": diagnostics.to_json(),
         })),
+        TaskStatus::Cancelled {
+            diagnostics,
+            finished_at,
+        } => Ok(serde_json::json!({
+            "task_id": task_id_str,
+            "status": "Cancelled",
+            "finished_at": finished_at.to_rfc3339(),
+            "diagnostics": diagnostics.to_json(),
+        })),
         TaskStatus::Pending => Err(format!("Task {} is still pending", task_id_str)),
         TaskStatus::Running { .. } => Err(format!("Task {} is still running", task_id_str)),
     }
@@ -225,48 +238,15 @@ pub async fn handle_onboard_repo(params: &Value) -> Result<Value, String> {
 mod tests {
     use super::*;
     use crate::task::TaskManager;
-    use async_trait::async_trait;
-    use model::provider::{ModelError, ModelResult};
     use model::types::{
-        ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, MessageRole, ModelInfo,
+        ChatMessage, ChatResponse, Choice, FinishReason, MessageRole, Usage, UsageTimings,
     };
-    use std::sync::Mutex;
+    use model::ReplayProvider;
 
-    struct MockProvider {
-        responses: Mutex<Vec<ChatResponse>>,
-    }
-
-    impl MockProvider {
-        fn new(responses: Vec<ChatResponse>) -> Arc<Self> {
-            Arc::new(Self {
-                responses: Mutex::new(responses),
-            })
-        }
-    }
-
-    #[async_trait]
-    impl ModelProvider for MockProvider {
-        async fn chat(&self, _request: ChatRequest) -> ModelResult<ChatResponse> {
-            let mut responses = self.responses.lock().unwrap();
-            if responses.is_empty() {
-                return Err(ModelError::Unknown {
-                    message: "No more responses".to_string(),
-                });
-            }
-            Ok(responses.remove(0))
-        }
-
-        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
-            Ok(vec![])
-        }
-
-        async fn health_check(&self) -> ModelResult<()> {
-            Ok(())
-        }
-
-        fn provider_name(&self) -> &'static str {
-            "mock"
-        }
+    /// Replays `responses` in order, like a cassette recorded from a run
+    /// whose prompts have since changed.
+    fn scripted(responses: Vec<ChatResponse>) -> Arc<ReplayProvider> {
+        Arc::new(ReplayProvider::scripted(responses))
     }
 
     fn stop_response(content: &str) -> ChatResponse {
@@ -277,17 +257,21 @@ mod tests {
                     content: Some(content.to_string()),
                     tool_calls: None,
                     tool_call_id: None,
+                    reasoning: None,
+                    reasoning_signature: None,
+                    images: Vec::new(),
                 },
                 finish_reason: Some(FinishReason::Stop),
             }],
             usage: None,
+            served_by: None,
         }
     }
 
     #[tokio::test]
     async fn test_handle_assign_task_missing_description() {
         let manager = Arc::new(TaskManager::default());
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let params = serde_json::json!({"repo_path": "/tmp"});
         let result = handle_assign_task(&params, &manager, &provider, "qwen3:0.6b", 100).await;
         assert!(result.is_err());
@@ -297,7 +281,7 @@ mod tests {
     #[tokio::test]
     async fn test_handle_assign_task_missing_repo_path() {
         let manager = Arc::new(TaskManager::default());
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let params = serde_json::json!({"description": "Do something"});
         let result = handle_assign_task(&params, &manager, &provider, "qwen3:0.6b", 100).await;
         assert!(result.is_err());
@@ -307,7 +291,7 @@ mod tests {
     #[tokio::test]
     async fn test_handle_assign_task_returns_task_id() {
         let manager = Arc::new(TaskManager::default());
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![stop_response("done")]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![stop_response("done")]);
         let params = serde_json::json!({
             "description": "Test task",
             "repo_path": "/tmp"
@@ -355,7 +339,7 @@ mod tests {
     #[tokio::test]
     async fn test_handle_list_tasks_after_submit() {
         let manager = Arc::new(TaskManager::default());
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let params = serde_json::json!({
             "description": "Test task",
             "repo_path": "/tmp"
@@ -387,6 +371,59 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_handle_get_result_reports_usage_last() {
+        let repo = tempfile::TempDir::new().unwrap();
+        crate::workspace::tests::init_git_repo(repo.path());
+        let with_usage = |content: &str, usage: Usage| ChatResponse {
+            usage: Some(usage),
+            ..stop_response(content)
+        };
+        let timed = |usage: Usage| {
+            usage.with_timings(UsageTimings {
+                eval_duration: Some(std::time::Duration::from_secs(1)),
+                ..Default::default()
+            })
+        };
+        let manager = Arc::new(TaskManager::default());
+        let provider: Arc<dyn ModelProvider> = scripted(vec![
+            with_usage(r#"{"plan": "say hello"}"#, timed(Usage::new(40, 10))),
+            with_usage("Hello", Usage::new(50, 30)),
+            with_usage(
+                r#"{"status": "COMPLETE", "reasoning": "done"}"#,
+                timed(Usage::new(60, 10)),
+            ),
+        ]);
+        let params = serde_json::json!({
+            "description": "say hello",
+            "repo_path": repo.path().to_str().unwrap()
+        });
+        let assigned = handle_assign_task(&params, &manager, &provider, "qwen3:0.6b", 10)
+            .await
+            .unwrap();
+
+        let params = serde_json::json!({ "task_id": assigned["task_id"] });
+        let mut result = Err(String::new());
+        for _ in 0..500 {
+            result = handle_get_result(&params, &manager).await;
+            if result.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let result = result.unwrap();
+
+        assert_eq!(result["status"], "Completed");
+        let keys: Vec<&String> = result.as_object().unwrap().keys().collect();
+        assert_eq!(keys.last().map(|k| k.as_str()), Some("usage"));
+        let usage = &result["usage"];
+        assert_eq!(usage["prompt_tokens"], 150);
+        assert_eq!(usage["completion_tokens"], 50);
+        // Only the two timed calls count: 20 tokens over 2 seconds
+        assert_eq!(usage["tokens_per_second"], 10.0);
+        assert_eq!(usage["eval_ms"], 2000);
+    }
+
     #[tokio::test]
     async fn test_handle_onboard_repo_rejects_relative_path() {
         let params = serde_json::json!({"repo_path": "relative/path"});
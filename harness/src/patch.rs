@@ -0,0 +1,971 @@
+//! Patch parsing and application behind the `apply_patch` tool
+//!
+//! Two formats are accepted: unified diffs as printed by `git diff` or
+//! `diff -u`, and the `*** Begin Patch` envelope some models are trained to
+//! emit, which adds, deletes, updates and moves files by name. Hunks are
+//! located by their content rather than by trusting line numbers, since
+//! models rarely count lines correctly.
+
+use crate::edit::{closest_lines, describe_candidates, uses_crlf};
+use std::fmt;
+use thiserror::Error;
+
+/// Context lines a hunk may lose at each end and still apply
+const MAX_FUZZ: usize = 2;
+
+const BEGIN_PATCH: &str = "*** Begin Patch";
+const END_PATCH: &str = "*** End Patch";
+const END_OF_FILE: &str = "*** End of File";
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PatchError {
+    #[error("Patch contains no file changes")]
+    Empty,
+
+    #[error("Malformed patch at line {line}: {message}")]
+    Malformed { line: usize, message: String },
+
+    #[error("Patch not applied, no files were changed:{}", describe_rejections(.0))]
+    Rejected(Vec<Rejection>),
+}
+
+fn malformed(index: usize, message: impl Into<String>) -> PatchError {
+    PatchError::Malformed {
+        line: index + 1,
+        message: message.into(),
+    }
+}
+
+fn describe_rejections(rejections: &[Rejection]) -> String {
+    rejections.iter().map(|r| format!("\n{}", r)).collect()
+}
+
+/// A file change, or one hunk of it, that could not be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub path: String,
+    /// 1-based hunk number, when a particular hunk failed
+    pub hunk: Option<usize>,
+    pub reason: String,
+}
+
+impl Rejection {
+    pub fn file(path: &str, reason: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            hunk: None,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.hunk {
+            Some(hunk) => write!(f, "{}: hunk {}: {}", self.path, hunk, self.reason),
+            None => write!(f, "{}: {}", self.path, self.reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+impl HunkLine {
+    fn old_text(&self) -> Option<&str> {
+        match self {
+            HunkLine::Context(text) | HunkLine::Remove(text) => Some(text),
+            HunkLine::Add(_) => None,
+        }
+    }
+
+    fn new_text(&self) -> Option<&str> {
+        match self {
+            HunkLine::Context(text) | HunkLine::Add(text) => Some(text),
+            HunkLine::Remove(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hunk {
+    /// 1-based line the hunk starts at in the original file, when known
+    pub old_start: Option<usize>,
+    /// Line the hunk follows, from an envelope's `@@ <anchor>` header
+    pub anchor: Option<String>,
+    /// Whether the hunk has to end at the end of the file
+    pub at_eof: bool,
+    pub lines: Vec<HunkLine>,
+    /// The original file has no final line break
+    pub old_missing_newline: bool,
+    /// The patched file has no final line break
+    pub new_missing_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileChange {
+    Add {
+        content: String,
+    },
+    Delete {
+        /// Text the file must have, when the patch shows the removed lines
+        content: Option<String>,
+    },
+    Update {
+        move_to: Option<String>,
+        hunks: Vec<Hunk>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub path: String,
+    pub change: FileChange,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Parse a unified diff or a `*** Begin Patch` envelope.
+    pub fn parse(text: &str) -> Result<Self, PatchError> {
+        let text = text.replace("\r\n", "\n");
+        let lines: Vec<&str> = text.lines().collect();
+        let files = if text.trim_start().starts_with(BEGIN_PATCH) {
+            parse_envelope(&lines)?
+        } else {
+            parse_unified(&lines)?
+        };
+        if files.is_empty() {
+            return Err(PatchError::Empty);
+        }
+        Ok(Self { files })
+    }
+}
+
+/// Paths and hunks collected for one file of a unified diff.
+#[derive(Default)]
+struct UnifiedFile {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    rename_from: Option<String>,
+    rename_to: Option<String>,
+    created: bool,
+    deleted: bool,
+    hunks: Vec<Hunk>,
+}
+
+const DEV_NULL: &str = "/dev/null";
+
+impl UnifiedFile {
+    fn from_git_header(paths: &str) -> Self {
+        let (old, new) = paths.split_once(" b/").unwrap_or((paths, paths));
+        Self {
+            old_path: Some(header_path(old)),
+            new_path: Some(header_path(new)),
+            ..Default::default()
+        }
+    }
+
+    fn finish(self, index: usize) -> Result<Option<FilePatch>, PatchError> {
+        let old = self.rename_from.or(self.old_path);
+        let new = self.rename_to.or(self.new_path);
+        let created = self.created || old.as_deref() == Some(DEV_NULL);
+        let deleted = self.deleted || new.as_deref() == Some(DEV_NULL);
+
+        if created {
+            let path = new.ok_or_else(|| malformed(index, "new file has no path"))?;
+            let content = whole_file(&self.hunks, true)
+                .ok_or_else(|| malformed(index, format!("new file {} has context", path)))?;
+            return Ok(Some(FilePatch {
+                path,
+                change: FileChange::Add { content },
+            }));
+        }
+
+        let path = old
+            .or_else(|| new.clone())
+            .ok_or_else(|| malformed(index, "file has no path"))?;
+        if deleted {
+            let content = whole_file(&self.hunks, false)
+                .ok_or_else(|| malformed(index, format!("deleted file {} has context", path)))?;
+            return Ok(Some(FilePatch {
+                path,
+                change: FileChange::Delete {
+                    content: Some(content),
+                },
+            }));
+        }
+        let move_to = new.filter(|new| *new != path);
+        if self.hunks.is_empty() && move_to.is_none() {
+            // Mode-only changes have nothing to apply
+            return Ok(None);
+        }
+        Ok(Some(FilePatch {
+            path,
+            change: FileChange::Update {
+                move_to,
+                hunks: self.hunks,
+            },
+        }))
+    }
+}
+
+/// Text of a file that hunks add (`added`) or remove in full, or `None` if
+/// they have any other kind of line.
+fn whole_file(hunks: &[Hunk], added: bool) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut missing_newline = false;
+    for hunk in hunks {
+        for line in &hunk.lines {
+            match (line, added) {
+                (HunkLine::Add(text), true) | (HunkLine::Remove(text), false) => {
+                    lines.push(text.as_str())
+                }
+                _ => return None,
+            }
+        }
+        missing_newline |= if added {
+            hunk.new_missing_newline
+        } else {
+            hunk.old_missing_newline
+        };
+    }
+    let mut content = lines.join("\n");
+    if !lines.is_empty() && !missing_newline {
+        content.push('\n');
+    }
+    Some(content)
+}
+
+/// Path from a `---`/`+++` header, without timestamp or `a/`/`b/` prefix.
+fn header_path(raw: &str) -> String {
+    let path = raw
+        .split('\t')
+        .next()
+        .unwrap_or(raw)
+        .trim()
+        .trim_matches('"');
+    if path == DEV_NULL {
+        return path.to_string();
+    }
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+fn is_file_header(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+}
+
+fn parse_unified(lines: &[&str]) -> Result<Vec<FilePatch>, PatchError> {
+    let mut files = Vec::new();
+    let mut current: Option<UnifiedFile> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            if let Some(file) = current.take() {
+                files.extend(file.finish(i)?);
+            }
+            current = Some(UnifiedFile::from_git_header(paths));
+        } else if is_file_header(lines, i) {
+            // Plain diffs have no `diff` line between files
+            if current.as_ref().is_some_and(|f| !f.hunks.is_empty()) {
+                files.extend(current.take().unwrap().finish(i)?);
+            }
+            let file = current.get_or_insert_with(UnifiedFile::default);
+            file.old_path = Some(header_path(&line[4..]));
+            file.new_path = Some(header_path(&lines[i + 1][4..]));
+            i += 1;
+        } else if line.starts_with("@@") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| malformed(i, "hunk before any file header"))?;
+            let (hunk, next) = parse_unified_hunk(lines, i)?;
+            file.hunks.push(hunk);
+            i = next;
+            continue;
+        } else if let Some(file) = current.as_mut() {
+            if let Some(path) = line.strip_prefix("rename from ") {
+                file.rename_from = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.rename_to = Some(path.to_string());
+            } else if line.starts_with("new file mode") {
+                file.created = true;
+            } else if line.starts_with("deleted file mode") {
+                file.deleted = true;
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                return Err(malformed(i, "binary patches are not supported"));
+            }
+        }
+        i += 1;
+    }
+    if let Some(file) = current {
+        files.extend(file.finish(lines.len())?);
+    }
+    Ok(files)
+}
+
+/// `start[,count]` from a hunk header.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+    Some((start.parse().ok()?, count.parse().ok()?))
+}
+
+/// Parse the hunk whose `@@` header is at `start`, returning it and the
+/// index of the first line after it. A header without line numbers is
+/// accepted; the hunk is then placed by its content alone.
+fn parse_unified_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize), PatchError> {
+    let ranges = lines[start]
+        .strip_prefix("@@ ")
+        .and_then(|rest| rest.split(" @@").next())
+        .and_then(|ranges| {
+            let (old, new) = ranges.split_once(' ')?;
+            Some((
+                parse_range(old.strip_prefix('-')?)?,
+                parse_range(new.strip_prefix('+')?)?,
+            ))
+        });
+
+    let mut hunk = Hunk {
+        // An empty old range names the line the hunk goes after
+        old_start: ranges.map(|((line, count), _)| if count == 0 { line + 1 } else { line }),
+        ..Default::default()
+    };
+    // Old and new lines the header says are still to come. Until they are
+    // read, a `--- `/`+++ ` pair is a removed and an added line, not the
+    // header of the next file
+    let mut remaining = ranges.map(|((_, old), (_, new))| (old, new));
+    let mut i = start + 1;
+    while i < lines.len() {
+        let line = lines[i];
+        let within = remaining.is_some_and(|(old, new)| old > 0 || new > 0);
+        if line.starts_with("@@")
+            || line.starts_with("diff --git ")
+            || (!within && is_file_header(lines, i))
+        {
+            break;
+        }
+        let pushed = hunk.lines.len();
+        match line.chars().next() {
+            Some(' ') => hunk.lines.push(HunkLine::Context(line[1..].to_string())),
+            Some('-') => hunk.lines.push(HunkLine::Remove(line[1..].to_string())),
+            Some('+') => hunk.lines.push(HunkLine::Add(line[1..].to_string())),
+            // Editors and models often strip the space from blank context
+            None => hunk.lines.push(HunkLine::Context(String::new())),
+            Some('\\') => match hunk.lines.last() {
+                Some(HunkLine::Remove(_)) => hunk.old_missing_newline = true,
+                Some(HunkLine::Add(_)) => hunk.new_missing_newline = true,
+                _ => {
+                    hunk.old_missing_newline = true;
+                    hunk.new_missing_newline = true;
+                }
+            },
+            Some(_) => break,
+        }
+        if let (Some((old, new)), Some(added)) = (&mut remaining, hunk.lines.get(pushed)) {
+            *old = old.saturating_sub(usize::from(added.old_text().is_some()));
+            *new = new.saturating_sub(usize::from(added.new_text().is_some()));
+        }
+        i += 1;
+    }
+
+    // Drop trailing lines the header does not account for, such as the
+    // blank line between files or the signature after a format-patch
+    loop {
+        let old = hunk.lines.iter().filter(|l| l.old_text().is_some()).count();
+        let new = hunk.lines.iter().filter(|l| l.new_text().is_some()).count();
+        let (old_excess, new_excess) = match ranges {
+            Some(((_, old_count), (_, new_count))) => (old > old_count, new > new_count),
+            None => (true, true),
+        };
+        let droppable = match hunk.lines.last() {
+            Some(HunkLine::Context(text)) => {
+                old_excess && new_excess && (ranges.is_some() || text.is_empty())
+            }
+            Some(HunkLine::Remove(_)) => old_excess && ranges.is_some(),
+            Some(HunkLine::Add(_)) => new_excess && ranges.is_some(),
+            None => false,
+        };
+        if !droppable {
+            break;
+        }
+        hunk.lines.pop();
+    }
+
+    if hunk.lines.is_empty() {
+        return Err(malformed(start, "hunk has no lines"));
+    }
+    Ok((hunk, i))
+}
+
+fn parse_envelope(lines: &[&str]) -> Result<Vec<FilePatch>, PatchError> {
+    let mut files = Vec::new();
+    let mut i = lines
+        .iter()
+        .position(|l| l.trim() == BEGIN_PATCH)
+        .map_or(0, |p| p + 1);
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        if line == END_PATCH {
+            break;
+        } else if let Some(path) = line.strip_prefix("*** Add File: ") {
+            i += 1;
+            let mut content = String::new();
+            while i < lines.len() && !lines[i].starts_with("*** ") {
+                let text = match lines[i].strip_prefix('+') {
+                    Some(text) => text,
+                    None if lines[i].is_empty() => "",
+                    None => return Err(malformed(i, "added file lines must start with '+'")),
+                };
+                content.push_str(text);
+                content.push('\n');
+                i += 1;
+            }
+            files.push(FilePatch {
+                path: path.trim().to_string(),
+                change: FileChange::Add { content },
+            });
+        } else if let Some(path) = line.strip_prefix("*** Delete File: ") {
+            i += 1;
+            files.push(FilePatch {
+                path: path.trim().to_string(),
+                change: FileChange::Delete { content: None },
+            });
+        } else if let Some(path) = line.strip_prefix("*** Update File: ") {
+            i += 1;
+            let move_to = lines
+                .get(i)
+                .and_then(|l| l.strip_prefix("*** Move to: "))
+                .map(|p| p.trim().to_string());
+            if move_to.is_some() {
+                i += 1;
+            }
+            let (hunks, next) = parse_envelope_hunks(lines, i)?;
+            if hunks.is_empty() && move_to.is_none() {
+                return Err(malformed(
+                    i,
+                    format!("update of {} has no hunks", path.trim()),
+                ));
+            }
+            files.push(FilePatch {
+                path: path.trim().to_string(),
+                change: FileChange::Update { move_to, hunks },
+            });
+            i = next;
+        } else if line.is_empty() {
+            i += 1;
+        } else {
+            return Err(malformed(
+                i,
+                format!("expected a file header, found '{}'", line),
+            ));
+        }
+    }
+    Ok(files)
+}
+
+fn parse_envelope_hunks(lines: &[&str], start: usize) -> Result<(Vec<Hunk>, usize), PatchError> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut i = start;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_end() == END_OF_FILE {
+            if let Some(hunk) = hunks.last_mut() {
+                hunk.at_eof = true;
+            }
+        } else if line.starts_with("*** ") {
+            break;
+        } else if let Some(anchor) = line.strip_prefix("@@") {
+            let anchor = anchor.trim();
+            hunks.push(Hunk {
+                anchor: (!anchor.is_empty()).then(|| anchor.to_string()),
+                ..Default::default()
+            });
+        } else {
+            let parsed = match line.chars().next() {
+                Some(' ') => HunkLine::Context(line[1..].to_string()),
+                Some('-') => HunkLine::Remove(line[1..].to_string()),
+                Some('+') => HunkLine::Add(line[1..].to_string()),
+                None => HunkLine::Context(String::new()),
+                Some(_) => return Err(malformed(i, "hunk lines must start with ' ', '-' or '+'")),
+            };
+            if hunks.is_empty() {
+                hunks.push(Hunk::default());
+            }
+            hunks.last_mut().unwrap().lines.push(parsed);
+        }
+        i += 1;
+    }
+    if let Some(hunk) = hunks.iter().find(|h| h.lines.is_empty()) {
+        let at = hunk.anchor.as_deref().unwrap_or("@@");
+        return Err(malformed(start, format!("hunk '{}' has no lines", at)));
+    }
+    Ok((hunks, i))
+}
+
+/// The result of applying every hunk of one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Applied {
+    pub content: String,
+    /// Hunks that only applied after dropping context or ignoring whitespace
+    pub fuzzy_hunks: usize,
+}
+
+/// Where a hunk applies in the original lines.
+struct Placement {
+    start: usize,
+    /// Leading context lines left unmatched
+    skip_front: usize,
+    /// Trailing context lines left unmatched
+    skip_back: usize,
+    ignored_whitespace: bool,
+}
+
+/// Apply `hunks` to `content` in order, or report every hunk that does not
+/// fit. Each hunk is searched for nearest its recorded position, first
+/// exactly, then ignoring surrounding whitespace, then with up to
+/// [`MAX_FUZZ`] context lines dropped from each end.
+pub fn apply_hunks(path: &str, content: &str, hunks: &[Hunk]) -> Result<Applied, Vec<Rejection>> {
+    let crlf = uses_crlf(content);
+    let text = if crlf {
+        content.replace("\r\n", "\n")
+    } else {
+        content.to_string()
+    };
+    let mut ends_with_newline = text.is_empty() || text.ends_with('\n');
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut out: Vec<&str> = Vec::new();
+    let mut cursor = 0;
+    let mut fuzzy_hunks = 0;
+    let mut rejections = Vec::new();
+    for (n, hunk) in hunks.iter().enumerate() {
+        let Some(place) = locate(&lines, cursor, hunk) else {
+            rejections.push(Rejection {
+                path: path.to_string(),
+                hunk: Some(n + 1),
+                reason: describe_miss(&text, hunk),
+            });
+            continue;
+        };
+        out.extend(&lines[cursor..place.start]);
+        let mut at = place.start;
+        for line in &hunk.lines[place.skip_front..hunk.lines.len() - place.skip_back] {
+            match line {
+                // Keep the file's own text where whitespace was ignored
+                HunkLine::Context(_) => {
+                    out.push(lines[at]);
+                    at += 1;
+                }
+                HunkLine::Remove(_) => at += 1,
+                HunkLine::Add(text) => out.push(text),
+            }
+        }
+        cursor = at;
+        if place.skip_front + place.skip_back > 0 || place.ignored_whitespace {
+            fuzzy_hunks += 1;
+        }
+        if hunk.old_missing_newline || hunk.new_missing_newline {
+            ends_with_newline = !hunk.new_missing_newline;
+        }
+    }
+    if !rejections.is_empty() {
+        return Err(rejections);
+    }
+    out.extend(&lines[cursor..]);
+
+    let mut result = out.join("\n");
+    if ends_with_newline && !out.is_empty() {
+        result.push('\n');
+    }
+    if crlf {
+        result = result.replace('\n', "\r\n");
+    }
+    Ok(Applied {
+        content: result,
+        fuzzy_hunks,
+    })
+}
+
+fn locate(lines: &[&str], cursor: usize, hunk: &Hunk) -> Option<Placement> {
+    let expected = match (&hunk.anchor, hunk.old_start) {
+        (Some(anchor), _) => lines[cursor..]
+            .iter()
+            .position(|l| l.trim() == anchor.trim())
+            .or_else(|| {
+                lines[cursor..]
+                    .iter()
+                    .position(|l| l.contains(anchor.trim()))
+            })
+            .map_or(cursor, |p| cursor + p + 1),
+        (None, Some(start)) => start.saturating_sub(1).max(cursor),
+        (None, None) => cursor,
+    };
+
+    let leading = hunk
+        .lines
+        .iter()
+        .take_while(|l| matches!(l, HunkLine::Context(_)))
+        .count();
+    let trailing = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|l| matches!(l, HunkLine::Context(_)))
+        .count();
+
+    for fuzz in 0..=MAX_FUZZ {
+        let skip_front = fuzz.min(leading);
+        let skip_back = fuzz.min(trailing);
+        if fuzz > 0 && skip_front + skip_back == 0 {
+            break;
+        }
+        if skip_front + skip_back >= hunk.lines.len() {
+            break;
+        }
+        let old: Vec<&str> = hunk.lines[skip_front..hunk.lines.len() - skip_back]
+            .iter()
+            .filter_map(HunkLine::old_text)
+            .collect();
+
+        if old.is_empty() {
+            // Pure insertion: nothing to match against
+            let start = if hunk.at_eof {
+                lines.len()
+            } else {
+                expected.min(lines.len())
+            };
+            return Some(Placement {
+                start,
+                skip_front,
+                skip_back,
+                ignored_whitespace: false,
+            });
+        }
+
+        for ignore_whitespace in [false, true] {
+            let matches_at = |pos: usize| {
+                old.iter().enumerate().all(|(k, want)| {
+                    let line = lines[pos + k];
+                    if ignore_whitespace {
+                        line.trim() == want.trim()
+                    } else {
+                        line == *want
+                    }
+                })
+            };
+            if cursor + old.len() > lines.len() {
+                continue;
+            }
+            let last = lines.len() - old.len();
+            let found = if hunk.at_eof && skip_back == 0 {
+                Some(last).filter(|&pos| matches_at(pos))
+            } else {
+                (cursor..=last)
+                    .filter(|&pos| matches_at(pos))
+                    .min_by_key(|&pos| pos.abs_diff(expected + skip_front))
+            };
+            if let Some(start) = found {
+                return Some(Placement {
+                    start,
+                    skip_front,
+                    skip_back,
+                    ignored_whitespace: ignore_whitespace,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn describe_miss(text: &str, hunk: &Hunk) -> String {
+    let first = hunk
+        .lines
+        .iter()
+        .filter_map(HunkLine::old_text)
+        .find(|l| !l.trim().is_empty());
+    let near = match (&hunk.anchor, hunk.old_start) {
+        (Some(anchor), _) => format!(" after '{}'", anchor),
+        (None, Some(line)) => format!(" near line {}", line),
+        (None, None) => String::new(),
+    };
+    match first {
+        Some(first) => format!(
+            "expected lines{} not found, starting with '{}'{}",
+            near,
+            first.trim(),
+            describe_candidates(&closest_lines(text, first))
+        ),
+        None => format!("nothing to anchor the hunk{}", near),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "fn main() {\n    let x = 1;\n    let y = 2;\n    println!(\"{}\", x + y);\n}\n";
+
+    fn only_update(patch: &Patch) -> (&str, &[Hunk]) {
+        match &patch.files[..] {
+            [FilePatch {
+                path,
+                change: FileChange::Update { hunks, .. },
+            }] => (path, hunks),
+            other => panic!("expected one update, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_git_diff() {
+        let diff = "\
+diff --git a/src/main.rs b/src/main.rs
+index 1234567..89abcde 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    let x = 1;
++    let x = 10;
+     let y = 2;
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..e69de29
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1,2 @@
++hello
++world
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/a.rs b/b.rs
+similarity index 100%
+rename from a.rs
+rename to b.rs
+";
+        let patch = Patch::parse(diff).unwrap();
+        assert_eq!(patch.files.len(), 4);
+        assert_eq!(patch.files[0].path, "src/main.rs");
+        assert_eq!(
+            patch.files[1].change,
+            FileChange::Add {
+                content: "hello\nworld\n".to_string()
+            }
+        );
+        assert_eq!(patch.files[2].path, "old.txt");
+        assert_eq!(
+            patch.files[2].change,
+            FileChange::Delete {
+                content: Some("bye\n".to_string())
+            }
+        );
+        assert_eq!(
+            patch.files[3].change,
+            FileChange::Update {
+                move_to: Some("b.rs".to_string()),
+                hunks: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_format_patch_ignores_mail_wrapping() {
+        let mail = "\
+From 1234 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] agent changes
+
+---
+ src/main.rs | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -2 +2 @@
+-    let x = 1;
++    let x = 10;
+--
+2.43.0
+
+";
+        let patch = Patch::parse(mail).unwrap();
+        let (path, hunks) = only_update(&patch);
+        assert_eq!(path, "src/main.rs");
+        assert_eq!(hunks[0].lines.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_hunk_lines_that_look_like_file_headers() {
+        let diff = "\
+--- a/notes.md
++++ b/notes.md
+@@ -1,3 +1,3 @@
+ # Notes
+--- old rule
++++ new rule
+ end
+--- a/other.md
++++ b/other.md
+@@ -1 +1 @@
+-x
++y
+";
+        let patch = Patch::parse(diff).unwrap();
+        assert_eq!(patch.files.len(), 2);
+        let FileChange::Update { hunks, .. } = &patch.files[0].change else {
+            panic!("expected an update, got {:?}", patch.files[0].change);
+        };
+        assert_eq!(
+            hunks[0].lines,
+            vec![
+                HunkLine::Context("# Notes".to_string()),
+                HunkLine::Remove("-- old rule".to_string()),
+                HunkLine::Add("++ new rule".to_string()),
+                HunkLine::Context("end".to_string()),
+            ]
+        );
+        assert_eq!(patch.files[1].path, "other.md");
+    }
+
+    #[test]
+    fn test_parse_envelope() {
+        let envelope = "\
+*** Begin Patch
+*** Add File: docs/notes.md
++# Notes
+*** Update File: src/main.rs
+*** Move to: src/bin/main.rs
+@@ fn main() {
+-    let x = 1;
++    let x = 10;
+*** Delete File: old.txt
+*** End Patch
+";
+        let patch = Patch::parse(envelope).unwrap();
+        assert_eq!(patch.files.len(), 3);
+        assert_eq!(
+            patch.files[0].change,
+            FileChange::Add {
+                content: "# Notes\n".to_string()
+            }
+        );
+        match &patch.files[1].change {
+            FileChange::Update { move_to, hunks } => {
+                assert_eq!(move_to.as_deref(), Some("src/bin/main.rs"));
+                assert_eq!(hunks[0].anchor.as_deref(), Some("fn main() {"));
+            }
+            other => panic!("unexpected change {:?}", other),
+        }
+        assert_eq!(patch.files[2].change, FileChange::Delete { content: None });
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert_eq!(Patch::parse("just some text"), Err(PatchError::Empty));
+        assert!(matches!(
+            Patch::parse("@@ -1 +1 @@\n-a\n+b\n"),
+            Err(PatchError::Malformed { line: 1, .. })
+        ));
+        assert!(matches!(
+            Patch::parse("*** Begin Patch\n*** Update File: a\nbad\n*** End Patch\n"),
+            Err(PatchError::Malformed { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_apply_with_wrong_line_numbers() {
+        let patch = Patch::parse(
+            "--- a/main.rs\n+++ b/main.rs\n@@ -40,3 +40,3 @@\n     let x = 1;\n-    let y = 2;\n+    let y = 20;\n     println!(\"{}\", x + y);\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let applied = apply_hunks(path, SOURCE, hunks).unwrap();
+        assert_eq!(applied.content, SOURCE.replace("y = 2;", "y = 20;"));
+        assert_eq!(applied.fuzzy_hunks, 0);
+    }
+
+    #[test]
+    fn test_apply_with_fuzz() {
+        let patch = Patch::parse(
+            "--- a/main.rs\n+++ b/main.rs\n@@ -1,5 +1,5 @@\n fn main() {\n   let x = 1;\n-  let y = 2;\n+  let y = 20;\n     println!(\"stale\");\n }\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let applied = apply_hunks(path, SOURCE, hunks).unwrap();
+        assert_eq!(
+            applied.content,
+            SOURCE.replace("    let y = 2;", "  let y = 20;")
+        );
+        assert_eq!(applied.fuzzy_hunks, 1);
+    }
+
+    #[test]
+    fn test_apply_reports_every_rejected_hunk() {
+        let patch = Patch::parse(
+            "--- a/main.rs\n+++ b/main.rs\n@@ -2 +2 @@\n-    let x = 100;\n+    let x = 1;\n@@ -3 +3 @@\n-    let y = 2;\n+    let y = 3;\n@@ -9 +9 @@\n-    missing();\n+    found();\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let rejections = apply_hunks(path, SOURCE, hunks).unwrap_err();
+        assert_eq!(rejections.len(), 2);
+        assert_eq!(rejections[0].hunk, Some(1));
+        assert!(rejections[0].reason.contains("near line 2"));
+        assert!(rejections[0].reason.contains("let x = 1;"));
+        assert_eq!(rejections[1].hunk, Some(3));
+
+        let err = PatchError::Rejected(rejections).to_string();
+        assert!(err.contains("no files were changed"));
+        assert!(err.contains("main.rs: hunk 3:"));
+    }
+
+    #[test]
+    fn test_apply_envelope_anchor_and_eof() {
+        let patch = Patch::parse(
+            "*** Begin Patch\n*** Update File: main.rs\n@@ fn main() {\n-    let y = 2;\n+    let y = 3;\n@@\n     println!(\"{}\", x + y);\n }\n+\n+fn helper() {}\n*** End of File\n*** End Patch\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let applied = apply_hunks(path, SOURCE, hunks).unwrap();
+        assert_eq!(
+            applied.content,
+            SOURCE.replace("y = 2;", "y = 3;") + "\nfn helper() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_missing_newline_marker() {
+        let patch = Patch::parse(
+            "--- a/main.rs\n+++ b/main.rs\n@@ -4,2 +4,2 @@\n     println!(\"{}\", x + y);\n-}\n+}\n\\ No newline at end of file\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let applied = apply_hunks(path, SOURCE, hunks).unwrap();
+        assert_eq!(applied.content, SOURCE.trim_end());
+    }
+
+    #[test]
+    fn test_apply_preserves_crlf() {
+        let source = SOURCE.replace('\n', "\r\n");
+        let patch = Patch::parse(
+            "--- a/main.rs\n+++ b/main.rs\n@@ -2 +2 @@\n-    let x = 1;\n+    let x = 5;\n",
+        )
+        .unwrap();
+        let (path, hunks) = only_update(&patch);
+        let applied = apply_hunks(path, &source, hunks).unwrap();
+        assert_eq!(applied.content, source.replace("x = 1;", "x = 5;"));
+    }
+}
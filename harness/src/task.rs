@@ -1,20 +1,27 @@
-use crate::agent::{AgentConfig, AgentContext, AgentError, AgentLoop};
+use crate::agent::{AgentConfig, AgentContext, AgentError, AgentLoop, DEFAULT_SYSTEM_PROMPT};
+use crate::config::ToolsSection;
 use crate::entities::context::types::ToolCallRecord;
 use crate::entities::InMemoryEntityStore;
 use crate::workspace::TaskWorkspace;
 use chrono::{DateTime, Utc};
-use model::provider::ModelProvider;
-use model::types::ChatMessage;
+use model::config::RetryConfig;
+use model::provider::{CancellationToken, ModelProvider};
+use model::retry::CircuitBreaker;
+use model::types::{ChatMessage, Usage};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 const MAX_DIFF_BYTES: usize = 1_000_000;
 pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 8;
+/// How long a cancelled task gets to stop on its own before it is aborted
+pub const DEFAULT_CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TaskId(pub String);
@@ -46,9 +53,32 @@ pub struct TaskResult {
     pub tool_calls_made: Vec<ToolCallRecord>,
     pub iterations: usize,
     pub model_used: String,
+    /// Backend that answered, when the provider routes across several
+    pub served_by: Option<String>,
+    pub usage: Option<Usage>,
 }
 
 impl TaskResult {
+    /// Token usage and throughput in a flat, client-friendly shape.
+    pub fn usage_json(&self) -> serde_json::Value {
+        let Some(usage) = &self.usage else {
+            return serde_json::Value::Null;
+        };
+        let millis = |d: Option<std::time::Duration>| d.map(|d| d.as_millis() as u64);
+        let timings = usage.timings.clone().unwrap_or_default();
+        serde_json::json!({
+            "prompt_tokens": usage.prompt_tokens,
+            "completion_tokens": usage.completion_tokens,
+            "total_tokens": usage.total_tokens,
+            "tokens_per_second": usage.tokens_per_second(),
+            "load_ms": millis(timings.load_duration),
+            "prompt_eval_ms": millis(timings.prompt_eval_duration),
+            "eval_ms": millis(timings.eval_duration),
+            "total_ms": millis(timings.total_duration),
+            "queue_ms": millis(timings.queue_duration),
+        })
+    }
+
     pub fn to_json(&self) -> serde_json::Value {
         serde_json::json!({
             "result_summary": self.result_summary,
@@ -58,6 +88,8 @@ impl TaskResult {
             "tool_calls_made": self.tool_calls_made,
             "iterations": self.iterations,
             "model_used": self.model_used,
+            "served_by": self.served_by,
+            "usage": self.usage_json(),
         })
     }
 }
@@ -71,6 +103,9 @@ pub struct FailureDiagnostics {
     pub tool_call_history: Vec<ToolCallRecord>,
     pub last_agent_state: Option<String>,
     pub conversation_snapshot: Option<Vec<ChatMessage>>,
+    /// Model output cut short by cancellation
+    #[serde(default)]
+    pub partial_output: Option<String>,
 }
 
 impl FailureDiagnostics {
@@ -83,6 +118,7 @@ impl FailureDiagnostics {
             "tool_call_history": self.tool_call_history,
             "last_agent_state": self.last_agent_state,
             "conversation_snapshot": self.conversation_snapshot,
+            "partial_output": self.partial_output,
         })
     }
 }
@@ -104,6 +140,12 @@ pub enum TaskStatus {
         error: String,
         diagnostics: FailureDiagnostics,
     },
+    /// Stopped by [`TaskManager::cancel`]; the diagnostics record how far
+    /// the task got
+    Cancelled {
+        finished_at: DateTime<Utc>,
+        diagnostics: FailureDiagnostics,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -117,11 +159,24 @@ pub struct Task {
     pub created_at: DateTime<Utc>,
 }
 
+/// Ways to stop a submitted task.
+struct TaskHandle {
+    join: JoinHandle<()>,
+    cancellation: CancellationToken,
+}
+
 pub struct TaskManager {
     tasks: Arc<RwLock<HashMap<TaskId, Task>>>,
-    handles: Arc<RwLock<HashMap<TaskId, tokio::task::AbortHandle>>>,
+    handles: Arc<RwLock<HashMap<TaskId, TaskHandle>>>,
     max_concurrent: Arc<Semaphore>,
     progress: Arc<RwLock<HashMap<TaskId, Arc<AtomicUsize>>>>,
+    system_prompt: String,
+    retry: RetryConfig,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    seed: Option<u64>,
+    temperature: Option<f32>,
+    tools: ToolsSection,
+    cancel_grace_period: Duration,
 }
 
 impl TaskManager {
@@ -131,9 +186,62 @@ impl TaskManager {
             handles: Arc::new(RwLock::new(HashMap::new())),
             max_concurrent: Arc::new(Semaphore::new(max_concurrent_tasks)),
             progress: Arc::new(RwLock::new(HashMap::new())),
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            retry: RetryConfig::default(),
+            circuit_breaker: None,
+            seed: None,
+            temperature: None,
+            tools: ToolsSection::default(),
+            cancel_grace_period: DEFAULT_CANCEL_GRACE_PERIOD,
         }
     }
 
+    /// System prompt given to the agent of every task.
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = system_prompt.into();
+        self
+    }
+
+    /// How the agent of every task retries failed model calls.
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Breaker shared by all tasks, so that a backend outage seen by one
+    /// task makes the others fail fast too.
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Sampling seed for the agent of every task.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sampling temperature for the agent of every task; `None` uses the
+    /// model's recommended temperature.
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Which tools the agent of every task may use, as `[tools] allow` and
+    /// `deny` pick them for the CLI agent.
+    pub fn with_tools(mut self, tools: ToolsSection) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// How long [`cancel`](Self::cancel) waits for a running task to stop
+    /// and record its partial output before aborting it.
+    pub fn with_cancel_grace_period(mut self, grace_period: Duration) -> Self {
+        self.cancel_grace_period = grace_period;
+        self
+    }
+
     pub async fn submit(
         &self,
         description: String,
@@ -169,6 +277,14 @@ impl TaskManager {
         let progress_ref = Arc::clone(&self.progress);
         let semaphore = Arc::clone(&self.max_concurrent);
         let task_id_clone = task_id.clone();
+        let system_prompt = self.system_prompt.clone();
+        let retry = self.retry.clone();
+        let circuit_breaker = self.circuit_breaker.clone();
+        let seed = self.seed;
+        let temperature = self.temperature;
+        let tools = self.tools.clone();
+        let cancellation = CancellationToken::new();
+        let agent_cancellation = cancellation.clone();
 
         let mut handles_guard = self.handles.write().await;
         let join_handle = tokio::spawn(async move {
@@ -187,14 +303,6 @@ impl TaskManager {
             let workspace_result = TaskWorkspace::create(&repo_path, &task_id_clone.0, &branch);
             match workspace_result {
                 Err(e) => {
-                    {
-                        let mut handles = handles_ref.write().await;
-                        handles.remove(&task_id_clone);
-                    }
-                    {
-                        let mut progress = progress_ref.write().await;
-                        progress.remove(&task_id_clone);
-                    }
                     let mut tasks = tasks_ref.write().await;
                     if let Some(task) = tasks.get_mut(&task_id_clone) {
                         task.status = TaskStatus::Failed {
@@ -208,18 +316,25 @@ impl TaskManager {
                                 tool_call_history: vec![],
                                 last_agent_state: None,
                                 conversation_snapshot: None,
+                                partial_output: None,
                             },
                         };
                     }
                 }
                 Ok(mut workspace) => {
-                    let tool_registry = workspace.create_tool_registry();
+                    let mut tool_registry = workspace.create_tool_registry();
+                    tools.apply(&mut tool_registry);
                     let entity_store = InMemoryEntityStore::new();
                     let agent_config = AgentConfig {
                         max_iterations,
                         verbose: false,
-                        system_prompt: "You are a helpful coding assistant. Use the available tools to accomplish tasks. When you have completed the task, respond with a summary.".to_string(),
+                        system_prompt,
                         model_name: model.clone(),
+                        retry,
+                        circuit_breaker,
+                        seed,
+                        temperature,
+                        ..Default::default()
                     };
                     let context = AgentContext {
                         user_prompt: description.clone(),
@@ -230,6 +345,8 @@ impl TaskManager {
                     let mut agent =
                         AgentLoop::with_tools(agent_config, entity_store, provider, tool_registry);
                     agent.set_progress_counter(Arc::clone(&progress_counter));
+                    agent.set_cancellation_token(agent_cancellation);
+                    agent.set_task_id(task_id_clone.0.clone());
                     let run_result = agent.run(context).await;
 
                     let changes_patch = workspace.extract_changes().ok().and_then(|patch| {
@@ -246,15 +363,6 @@ impl TaskManager {
 
                     let _ = workspace.cleanup();
 
-                    {
-                        let mut handles = handles_ref.write().await;
-                        handles.remove(&task_id_clone);
-                    }
-                    {
-                        let mut progress = progress_ref.write().await;
-                        progress.remove(&task_id_clone);
-                    }
-
                     match run_result {
                         Ok(result) => {
                             let files_modified = parse_modified_files(changes_patch.as_deref());
@@ -266,6 +374,8 @@ impl TaskManager {
                                 tool_calls_made: result.tool_calls_made,
                                 iterations: result.iterations,
                                 model_used: model,
+                                served_by: result.served_by,
+                                usage: result.usage,
                             };
                             let mut tasks = tasks_ref.write().await;
                             if let Some(task) = tasks.get_mut(&task_id_clone) {
@@ -294,6 +404,9 @@ impl TaskManager {
                                 AgentError::TaskCheckFailed { .. } => {
                                     ("TaskCheckFailed".to_string(), diag_iters)
                                 }
+                                AgentError::Cancelled { .. } => {
+                                    ("Cancelled".to_string(), diag_iters)
+                                }
                             };
                             let diagnostics = FailureDiagnostics {
                                 error_type,
@@ -303,22 +416,40 @@ impl TaskManager {
                                 tool_call_history,
                                 last_agent_state,
                                 conversation_snapshot: Some(conversation_snapshot),
+                                partial_output: e.partial_output().map(str::to_string),
                             };
                             let mut tasks = tasks_ref.write().await;
                             if let Some(task) = tasks.get_mut(&task_id_clone) {
-                                task.status = TaskStatus::Failed {
-                                    finished_at: Utc::now(),
-                                    error: e.to_string(),
-                                    diagnostics,
+                                task.status = match e {
+                                    AgentError::Cancelled { .. } => TaskStatus::Cancelled {
+                                        finished_at: Utc::now(),
+                                        diagnostics,
+                                    },
+                                    _ => TaskStatus::Failed {
+                                        finished_at: Utc::now(),
+                                        error: e.to_string(),
+                                        diagnostics,
+                                    },
                                 };
                             }
                         }
                     }
                 }
             }
+
+            // Only let go of the handle once the outcome is recorded, so a
+            // concurrent cancel either sees it or waits for it
+            handles_ref.write().await.remove(&task_id_clone);
+            progress_ref.write().await.remove(&task_id_clone);
         });
 
-        handles_guard.insert(task_id.clone(), join_handle.abort_handle());
+        handles_guard.insert(
+            task_id.clone(),
+            TaskHandle {
+                join: join_handle,
+                cancellation,
+            },
+        );
         drop(handles_guard);
 
         task_id
@@ -359,16 +490,31 @@ impl TaskManager {
         tasks.values().cloned().collect()
     }
 
+    /// Cancel a task. A running task is asked to stop first, which abandons
+    /// its model request and lets it record partial output and changes; it
+    /// is aborted if it has not stopped within the grace period.
     pub async fn cancel(&self, task_id: &TaskId) -> Result<Task, String> {
-        let had_handle = {
-            let mut handles = self.handles.write().await;
-            if let Some(handle) = handles.remove(task_id) {
-                handle.abort();
-                true
-            } else {
-                false
+        let handle = self.handles.write().await.remove(task_id);
+        let had_handle = handle.is_some();
+        if let Some(mut handle) = handle {
+            handle.cancellation.cancel();
+            let running = matches!(
+                self.tasks.read().await.get(task_id).map(|t| &t.status),
+                Some(TaskStatus::Running { .. })
+            );
+            if running
+                && tokio::time::timeout(self.cancel_grace_period, &mut handle.join)
+                    .await
+                    .is_err()
+            {
+                tracing::warn!(
+                    "Task {} did not stop within {:?}, aborting it",
+                    task_id,
+                    self.cancel_grace_period
+                );
             }
-        };
+            handle.join.abort();
+        }
 
         let iterations_completed = {
             let mut progress = self.progress.write().await;
@@ -386,8 +532,18 @@ impl TaskManager {
             .ok_or_else(|| format!("Task not found: {}", task_id))?;
 
         match &task.status {
-            TaskStatus::Completed { .. } | TaskStatus::Failed { .. } => {
-                let _ = had_handle;
+            // The task finished or stopped on its own during the grace
+            // period and recorded how far it got
+            TaskStatus::Completed { .. }
+            | TaskStatus::Failed { .. }
+            | TaskStatus::Cancelled { .. }
+                if had_handle =>
+            {
+                return Ok(task.clone())
+            }
+            TaskStatus::Completed { .. }
+            | TaskStatus::Failed { .. }
+            | TaskStatus::Cancelled { .. } => {
                 return Err(format!(
                     "Task {} cannot be cancelled: already finished",
                     task_id
@@ -396,9 +552,8 @@ impl TaskManager {
             _ => {}
         }
 
-        task.status = TaskStatus::Failed {
+        task.status = TaskStatus::Cancelled {
             finished_at: Utc::now(),
-            error: "Task cancelled".to_string(),
             diagnostics: FailureDiagnostics {
                 error_type: "Cancelled".to_string(),
                 iterations_completed,
@@ -407,6 +562,7 @@ impl TaskManager {
                 tool_call_history: vec![],
                 last_agent_state: None,
                 conversation_snapshot: None,
+                partial_output: None,
             },
         };
 
@@ -440,44 +596,15 @@ mod tests {
     use model::provider::{ModelError, ModelResult};
     use model::types::{
         ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, MessageRole, ModelInfo,
+        UsageTimings,
     };
-    use std::sync::Mutex;
-
-    struct MockProvider {
-        responses: Mutex<Vec<ChatResponse>>,
-    }
+    use model::ReplayProvider;
+    use std::time::Duration;
 
-    impl MockProvider {
-        fn new(responses: Vec<ChatResponse>) -> Arc<Self> {
-            Arc::new(Self {
-                responses: Mutex::new(responses),
-            })
-        }
-    }
-
-    #[async_trait]
-    impl ModelProvider for MockProvider {
-        async fn chat(&self, _request: ChatRequest) -> ModelResult<ChatResponse> {
-            let mut responses = self.responses.lock().unwrap();
-            if responses.is_empty() {
-                return Err(ModelError::Unknown {
-                    message: "No more responses".to_string(),
-                });
-            }
-            Ok(responses.remove(0))
-        }
-
-        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
-            Ok(vec![])
-        }
-
-        async fn health_check(&self) -> ModelResult<()> {
-            Ok(())
-        }
-
-        fn provider_name(&self) -> &'static str {
-            "mock"
-        }
+    /// Replays `responses` in order, like a cassette recorded from a run
+    /// whose prompts have since changed.
+    fn scripted(responses: Vec<ChatResponse>) -> Arc<ReplayProvider> {
+        Arc::new(ReplayProvider::scripted(responses))
     }
 
     fn stop_response(content: &str) -> ChatResponse {
@@ -488,10 +615,14 @@ mod tests {
                     content: Some(content.to_string()),
                     tool_calls: None,
                     tool_call_id: None,
+                    reasoning: None,
+                    reasoning_signature: None,
+                    images: Vec::new(),
                 },
                 finish_reason: Some(FinishReason::Stop),
             }],
             usage: None,
+            served_by: None,
         }
     }
 
@@ -499,11 +630,13 @@ mod tests {
     fn wrap_with_state_machine_responses(tool_responses: Vec<ChatResponse>) -> Vec<ChatResponse> {
         // EnrichingEntities: no LLM call
         let mut responses = vec![
-            stop_response("Plan: execute the task"), // PlanningEntityModification
+            stop_response(r#"{"plan": "execute the task"}"#), // PlanningEntityModification
         ];
         responses.extend(tool_responses); // PerformingEntityModification
                                           // UpdatingEntities: no LLM call
-        responses.push(stop_response("COMPLETE - task done")); // CheckingTaskCompletion
+        responses.push(stop_response(
+            r#"{"status": "COMPLETE", "reasoning": "task done"}"#,
+        )); // CheckingTaskCompletion
         responses
     }
 
@@ -524,12 +657,40 @@ mod tests {
             tool_calls_made: vec![],
             iterations: 3,
             model_used: "qwen3:0.6b".to_string(),
+            served_by: None,
+            usage: None,
         };
         let json = result.to_json();
         assert_eq!(json["result_summary"], "Done");
         assert_eq!(json["iterations"], 3);
         assert!(json["changes_patch"].is_string());
         assert!(json["format_patch"].is_string());
+        assert!(json["usage"].is_null());
+    }
+
+    #[test]
+    fn test_task_result_usage_json() {
+        let result = TaskResult {
+            result_summary: "Done".to_string(),
+            changes_patch: None,
+            format_patch: None,
+            files_modified: vec![],
+            tool_calls_made: vec![],
+            iterations: 1,
+            model_used: "qwen3:0.6b".to_string(),
+            served_by: None,
+            usage: Some(Usage::new(120, 40).with_timings(UsageTimings {
+                load_duration: Some(Duration::from_millis(300)),
+                eval_duration: Some(Duration::from_secs(2)),
+                ..Default::default()
+            })),
+        };
+        let usage = &result.to_json()["usage"];
+        assert_eq!(usage["prompt_tokens"], 120);
+        assert_eq!(usage["total_tokens"], 160);
+        assert_eq!(usage["tokens_per_second"], 20.0);
+        assert_eq!(usage["load_ms"], 300);
+        assert!(usage["prompt_eval_ms"].is_null());
     }
 
     #[test]
@@ -542,6 +703,7 @@ mod tests {
             tool_call_history: vec![],
             last_agent_state: None,
             conversation_snapshot: None,
+            partial_output: None,
         };
         let json = diag.to_json();
         assert_eq!(json["error_type"], "MaxIterationsExceeded");
@@ -566,6 +728,7 @@ mod tests {
             tool_call_history: vec![tool_call],
             last_agent_state: Some("Performing".to_string()),
             conversation_snapshot: Some(vec![ChatMessage::user("do something")]),
+            partial_output: None,
         };
         let json = diag.to_json();
         assert_eq!(json["error_type"], "StateError");
@@ -615,7 +778,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_cancel_running_task() {
-        let manager = TaskManager::default();
+        let manager = TaskManager::default().with_cancel_grace_period(Duration::from_millis(50));
         let task_id = TaskId::new();
         let task = Task {
             id: task_id.clone(),
@@ -634,18 +797,75 @@ mod tests {
             tasks.insert(task_id.clone(), task);
         }
         let dummy = tokio::spawn(std::future::pending::<()>());
-        let abort_handle = dummy.abort_handle();
         {
             let mut handles = manager.handles.write().await;
-            handles.insert(task_id.clone(), abort_handle);
+            handles.insert(
+                task_id.clone(),
+                TaskHandle {
+                    join: dummy,
+                    cancellation: CancellationToken::new(),
+                },
+            );
         }
         let result = manager.cancel(&task_id).await;
         assert!(result.is_ok());
         let task = result.unwrap();
+        assert!(matches!(&task.status, TaskStatus::Cancelled { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_returns_result_of_task_finishing_in_grace_period() {
+        let manager = TaskManager::default();
+        let task_id = TaskId::new();
+        let task = Task {
+            id: task_id.clone(),
+            description: "test".to_string(),
+            repo_path: PathBuf::from("/tmp"),
+            branch: "HEAD".to_string(),
+            model: "mock".to_string(),
+            status: TaskStatus::Running {
+                started_at: Utc::now(),
+                iterations: 0,
+            },
+            created_at: Utc::now(),
+        };
+        manager.tasks.write().await.insert(task_id.clone(), task);
+        // Ignores cancellation and completes shortly after
+        let tasks_ref = Arc::clone(&manager.tasks);
+        let finishing_id = task_id.clone();
+        let finishing = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let mut tasks = tasks_ref.write().await;
+            tasks.get_mut(&finishing_id).unwrap().status = TaskStatus::Completed {
+                finished_at: Utc::now(),
+                result: TaskResult {
+                    result_summary: "Done".to_string(),
+                    changes_patch: None,
+                    format_patch: None,
+                    files_modified: vec![],
+                    tool_calls_made: vec![],
+                    iterations: 2,
+                    model_used: "mock".to_string(),
+                    served_by: None,
+                    usage: None,
+                },
+            };
+        });
+        manager.handles.write().await.insert(
+            task_id.clone(),
+            TaskHandle {
+                join: finishing,
+                cancellation: CancellationToken::new(),
+            },
+        );
+
+        let task = manager.cancel(&task_id).await.unwrap();
+
         assert!(
-            matches!(&task.status, TaskStatus::Failed { diagnostics, .. } if diagnostics.error_type == "Cancelled")
+            matches!(&task.status, TaskStatus::Completed { result, .. } if result.result_summary == "Done"),
+            "{:?}",
+            task.status
         );
-        dummy.abort();
     }
 
     #[tokio::test]
@@ -665,7 +885,7 @@ mod tests {
     #[tokio::test]
     async fn test_cancel_pending_task() {
         let manager = TaskManager::new(0);
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let id = manager
             .submit(
                 "test".to_string(),
@@ -682,15 +902,13 @@ mod tests {
         let result = manager.cancel(&id).await;
         assert!(result.is_ok());
         let task = result.unwrap();
-        assert!(
-            matches!(&task.status, TaskStatus::Failed { diagnostics, .. } if diagnostics.error_type == "Cancelled")
-        );
+        assert!(matches!(&task.status, TaskStatus::Cancelled { .. }));
     }
 
     #[tokio::test]
     async fn test_concurrency_limit_keeps_tasks_pending() {
         let manager = TaskManager::new(0);
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(vec![]);
+        let provider: Arc<dyn ModelProvider> = scripted(vec![]);
         let id = manager
             .submit(
                 "test".to_string(),
@@ -710,9 +928,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_agent_completes_with_mock_provider() {
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(
-            wrap_with_state_machine_responses(vec![stop_response("Task complete!")]),
-        );
+        let provider: Arc<dyn ModelProvider> =
+            scripted(wrap_with_state_machine_responses(vec![stop_response(
+                "Task complete!",
+            )]));
         let config = AgentConfig {
             max_iterations: 20,
             ..Default::default()
@@ -733,7 +952,7 @@ mod tests {
     #[tokio::test]
     async fn test_agent_fails_with_max_iterations() {
         let responses: Vec<ChatResponse> = (0..5).map(|_| stop_response("not done yet")).collect();
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(responses);
+        let provider: Arc<dyn ModelProvider> = scripted(responses);
         let config = AgentConfig {
             max_iterations: 0,
             ..Default::default()
@@ -757,9 +976,10 @@ mod tests {
     #[tokio::test]
     async fn test_progress_counter_accessible_after_run() {
         let counter = Arc::new(AtomicUsize::new(0));
-        let provider: Arc<dyn ModelProvider> = MockProvider::new(
-            wrap_with_state_machine_responses(vec![stop_response("Task complete!")]),
-        );
+        let provider: Arc<dyn ModelProvider> =
+            scripted(wrap_with_state_machine_responses(vec![stop_response(
+                "Task complete!",
+            )]));
         let config = AgentConfig {
             max_iterations: 20,
             ..Default::default()
@@ -777,4 +997,165 @@ mod tests {
         let result = agent.run(context).await.unwrap();
         assert_eq!(counter.load(Ordering::Relaxed), result.iterations);
     }
+
+    /// Streams the start of a reply, then generates until cancelled.
+    struct StallingProvider;
+
+    #[async_trait]
+    impl ModelProvider for StallingProvider {
+        async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+            if let Some(token) = request.cancellation {
+                token.cancelled().await;
+            }
+            Err(ModelError::Cancelled)
+        }
+
+        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> ModelResult<()> {
+            Ok(())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "stalling"
+        }
+
+        fn as_streaming(&self) -> Option<&dyn model::provider::StreamingModelProvider> {
+            Some(self)
+        }
+    }
+
+    #[async_trait]
+    impl model::provider::StreamingModelProvider for StallingProvider {
+        async fn chat_stream(
+            &self,
+            request: ChatRequest,
+        ) -> ModelResult<model::stream::ChatStream> {
+            let token = request.cancellation.expect("task requests are cancellable");
+            let first = futures::stream::iter(vec![Ok(model::stream::ChatStreamChunk {
+                delta: Some("Plan: read the".to_string()),
+                ..Default::default()
+            })]);
+            let rest = futures::stream::once(async move {
+                token.cancelled().await;
+                Err(ModelError::Cancelled)
+            });
+            Ok(Box::pin(futures::StreamExt::chain(first, rest)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_generation_and_records_partial_output() {
+        let repo = tempfile::TempDir::new().unwrap();
+        crate::workspace::tests::init_git_repo(repo.path());
+        let manager = TaskManager::default();
+        let id = manager
+            .submit(
+                "describe the repo".to_string(),
+                repo.path().to_path_buf(),
+                "HEAD".to_string(),
+                "mock".to_string(),
+                10,
+                Arc::new(StallingProvider),
+            )
+            .await;
+        for _ in 0..200 {
+            if matches!(
+                manager.poll(&id).await.unwrap().status,
+                TaskStatus::Running { .. }
+            ) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let started = std::time::Instant::now();
+        let task = manager.cancel(&id).await.unwrap();
+
+        assert!(started.elapsed() < DEFAULT_CANCEL_GRACE_PERIOD);
+        let TaskStatus::Cancelled { diagnostics, .. } = task.status else {
+            panic!("task was not cancelled: {:?}", task.status);
+        };
+        assert_eq!(diagnostics.error_type, "Cancelled");
+        assert_eq!(
+            diagnostics.partial_output.as_deref(),
+            Some("Plan: read the")
+        );
+        assert!(diagnostics.conversation_snapshot.is_some());
+        assert!(manager.cancel(&id).await.is_err());
+    }
+
+    /// Answers from a script and records the tools offered with each request.
+    struct ToolRecordingProvider {
+        script: ReplayProvider,
+        offered: std::sync::Mutex<Vec<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ModelProvider for ToolRecordingProvider {
+        async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+            let names = request
+                .tools
+                .iter()
+                .flatten()
+                .map(|tool| tool.function.name.clone())
+                .collect();
+            self.offered.lock().unwrap().push(names);
+            self.script.chat(request).await
+        }
+
+        async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn health_check(&self) -> ModelResult<()> {
+            Ok(())
+        }
+
+        fn provider_name(&self) -> &'static str {
+            "tool-recording"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_agent_only_gets_allowed_tools() {
+        let repo = tempfile::TempDir::new().unwrap();
+        crate::workspace::tests::init_git_repo(repo.path());
+        let provider = Arc::new(ToolRecordingProvider {
+            script: ReplayProvider::scripted(wrap_with_state_machine_responses(vec![
+                stop_response("Done"),
+            ])),
+            offered: std::sync::Mutex::new(Vec::new()),
+        });
+        let manager = TaskManager::default().with_tools(ToolsSection {
+            allow: vec!["read_file".to_string(), "write_file".to_string()],
+            deny: vec!["write_file".to_string()],
+            ..Default::default()
+        });
+        let id = manager
+            .submit(
+                "read the readme".to_string(),
+                repo.path().to_path_buf(),
+                "HEAD".to_string(),
+                "mock".to_string(),
+                10,
+                provider.clone(),
+            )
+            .await;
+        for _ in 0..500 {
+            if matches!(
+                manager.poll(&id).await.unwrap().status,
+                TaskStatus::Completed { .. } | TaskStatus::Failed { .. }
+            ) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let offered = provider.offered.lock().unwrap().clone();
+        assert!(offered.contains(&vec!["read_file".to_string()]));
+        assert!(offered.iter().all(|names| names.len() <= 1));
+    }
 }
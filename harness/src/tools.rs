@@ -1,8 +1,17 @@
+use crate::edit::{self, EditOptions};
+use crate::patch::{self, FileChange, Patch, PatchError, Rejection};
 use async_trait::async_trait;
-use model::types::{FunctionDefinition, JsonSchema, PropertySchema, SchemaType, ToolDefinition};
+pub use harness_derive::ToolArgs;
+pub use model::types::PropertySchema;
+use model::types::{
+    ChatMessage, FunctionDefinition, ImageAttachment, JsonSchema, SchemaType, ToolDefinition,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -37,15 +46,27 @@ pub trait Tool: Send + Sync {
 
 pub struct ToolRegistry {
     tools: HashMap<String, Box<dyn Tool>>,
+    workspace_root: Option<PathBuf>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            workspace_root: None,
         }
     }
 
+    /// Directory the tools work in; image files they return must be inside it.
+    pub fn with_workspace_root(mut self, workspace_root: impl Into<PathBuf>) -> Self {
+        self.workspace_root = Some(workspace_root.into());
+        self
+    }
+
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.workspace_root.as_deref()
+    }
+
     pub fn register(&mut self, tool: Box<dyn Tool>) {
         let name = tool.name().to_string();
         self.tools.insert(name, tool);
@@ -59,13 +80,26 @@ impl ToolRegistry {
         self.tools.keys().map(|s| s.as_str()).collect()
     }
 
+    /// Keep only the tools whose name satisfies `keep`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.tools.retain(|name, _| keep(name));
+    }
+
     pub fn get_definitions(&self) -> Vec<ToolDefinition> {
         self.tools.values().map(|tool| tool.definition()).collect()
     }
 
+    /// Validate `args` against the tool's parameter schema, then run the tool.
     pub async fn execute(&self, name: &str, args: Value) -> ToolResult<Value> {
         match self.tools.get(name) {
-            Some(tool) => tool.execute(args).await,
+            Some(tool) => {
+                tool.definition()
+                    .function
+                    .parameters
+                    .validate_value(&args)
+                    .map_err(|message| ToolError::InvalidArguments { message })?;
+                tool.execute(args).await
+            }
             None => Err(ToolError::NotFound {
                 name: name.to_string(),
             }),
@@ -79,64 +113,281 @@ impl Default for ToolRegistry {
     }
 }
 
-pub struct EchoTool;
+/// Result key under which a tool can return [`ImageAttachment`]s, e.g. a
+/// rendered screenshot, to show to a vision-capable model.
+pub const TOOL_IMAGES_KEY: &str = "images";
+
+/// Result key listing the images that could not be attached, and why.
+pub const TOOL_IMAGE_ERRORS_KEY: &str = "image_errors";
+
+/// Tool message reporting the outcome of a call.
+///
+/// Images under [`TOOL_IMAGES_KEY`] are attached to the message rather than
+/// sent as text; the rest of the result becomes its content. Image files are
+/// read once, here, and only from inside `workspace_root`; those that cannot
+/// be attached are reported under [`TOOL_IMAGE_ERRORS_KEY`] instead.
+pub fn tool_response_message(
+    call_id: impl Into<String>,
+    result: ToolResult<Value>,
+    workspace_root: Option<&Path>,
+) -> ChatMessage {
+    let mut value = match result {
+        Ok(value) => value,
+        Err(e) => return ChatMessage::tool_response(call_id, format!("Error: {}", e)),
+    };
 
-impl EchoTool {
-    pub fn new() -> Self {
-        Self
+    let mut images = Vec::new();
+    if let Some(obj) = value.as_object_mut() {
+        let attached = obj
+            .get(TOOL_IMAGES_KEY)
+            .and_then(|v| serde_json::from_value::<Vec<ImageAttachment>>(v.clone()).ok());
+        if let Some(attached) = attached {
+            obj.remove(TOOL_IMAGES_KEY);
+            let mut errors = Vec::new();
+            for image in attached {
+                match inline_image(image, workspace_root) {
+                    Ok(image) => images.push(image),
+                    Err(e) => errors.push(e),
+                }
+            }
+            if !errors.is_empty() {
+                obj.insert(TOOL_IMAGE_ERRORS_KEY.to_string(), json!(errors));
+            }
+        }
     }
+
+    ChatMessage::tool_response(call_id, value.to_string()).with_images(images)
 }
 
-impl Default for EchoTool {
-    fn default() -> Self {
-        Self::new()
+/// Read a file attachment into Base64, so that it is neither re-read on
+/// every request nor able to reach outside the workspace.
+fn inline_image(
+    image: ImageAttachment,
+    workspace_root: Option<&Path>,
+) -> Result<ImageAttachment, String> {
+    let ImageAttachment::Path { path } = &image else {
+        return Ok(image);
+    };
+    let Some(workspace_root) = workspace_root else {
+        return Err(format!(
+            "{}: no workspace to read images from",
+            path.display()
+        ));
+    };
+    let path = validate_path_within_workspace(path, workspace_root)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let encoded = ImageAttachment::path(&path)
+        .encode()
+        .map_err(|e| e.to_string())?;
+    Ok(ImageAttachment::Base64 {
+        data: encoded.data,
+        media_type: Some(encoded.media_type),
+    })
+}
+
+/// Schema of a value that can appear in tool arguments.
+///
+/// Implemented for primitives, `PathBuf`, `Vec<T>` and `Option<T>`, and by
+/// `#[derive(ToolArgs)]` for nested argument structs.
+pub trait ArgSchema {
+    /// Whether a field of this type must be present
+    const REQUIRED: bool = true;
+
+    fn property_schema() -> PropertySchema;
+}
+
+macro_rules! arg_schema {
+    ($schema_type:expr, $minimum:expr => $($ty:ty),*) => {
+        $(impl ArgSchema for $ty {
+            fn property_schema() -> PropertySchema {
+                PropertySchema::new($schema_type).with_range($minimum, None)
+            }
+        })*
+    };
+}
+
+arg_schema!(SchemaType::String, None => String, PathBuf);
+arg_schema!(SchemaType::Boolean, None => bool);
+arg_schema!(SchemaType::Integer, None => i8, i16, i32, i64, isize);
+arg_schema!(SchemaType::Integer, Some(0.0) => u8, u16, u32, u64, usize);
+arg_schema!(SchemaType::Number, None => f32, f64);
+
+impl<T: ArgSchema> ArgSchema for Vec<T> {
+    fn property_schema() -> PropertySchema {
+        PropertySchema::new(SchemaType::Array).with_items(T::property_schema())
+    }
+}
+
+impl<T: ArgSchema> ArgSchema for Option<T> {
+    const REQUIRED: bool = false;
+
+    fn property_schema() -> PropertySchema {
+        T::property_schema()
+    }
+}
+
+/// One property of a [`ToolArgs`] struct.
+#[derive(Debug, Clone)]
+pub struct ArgField {
+    pub name: &'static str,
+    pub schema: PropertySchema,
+    pub required: bool,
+}
+
+impl ArgField {
+    pub fn new<T: ArgSchema>(name: &'static str, description: &str) -> Self {
+        let mut schema = T::property_schema();
+        if !description.is_empty() {
+            schema = schema.with_description(description);
+        }
+        Self {
+            name,
+            schema,
+            required: T::REQUIRED,
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    pub fn with_enum<V: Into<Value>>(mut self, values: impl IntoIterator<Item = V>) -> Self {
+        self.schema = self.schema.with_enum(values);
+        self
+    }
+
+    /// Tighten the bounds; a bound left `None` keeps the one implied by the
+    /// field's type, such as the minimum of 0 for unsigned integers.
+    pub fn with_range(mut self, minimum: Option<f64>, maximum: Option<f64>) -> Self {
+        self.schema.minimum = minimum.or(self.schema.minimum);
+        self.schema.maximum = maximum.or(self.schema.maximum);
+        self
+    }
+}
+
+/// Typed tool arguments, usually implemented with `#[derive(ToolArgs)]`.
+pub trait ToolArgs: DeserializeOwned {
+    /// Struct doc comment, used as the tool description
+    fn description() -> &'static str;
+
+    fn fields() -> Vec<ArgField>;
+
+    fn schema() -> JsonSchema {
+        let fields = Self::fields();
+        let required = fields
+            .iter()
+            .filter(|f| f.required)
+            .map(|f| f.name.to_string())
+            .collect();
+        JsonSchema {
+            properties: Some(
+                fields
+                    .into_iter()
+                    .map(|f| (f.name.to_string(), f.schema))
+                    .collect(),
+            ),
+            required: Some(required),
+            ..Default::default()
+        }
+    }
+
+    /// The arguments as a nested object property.
+    fn property_schema() -> PropertySchema {
+        let schema = Self::schema();
+        PropertySchema {
+            schema_type: Some(SchemaType::Object),
+            properties: schema.properties,
+            required: schema.required,
+            ..Default::default()
+        }
+    }
+
+    /// Check `args` against the schema and deserialize them.
+    fn parse(args: Value) -> ToolResult<Self> {
+        let args = if args.is_null() { json!({}) } else { args };
+        Self::schema()
+            .validate_value(&args)
+            .map_err(|message| ToolError::InvalidArguments { message })?;
+        serde_json::from_value(args).map_err(|e| ToolError::InvalidArguments {
+            message: format!("arguments: {}", e),
+        })
+    }
+}
+
+/// A tool whose arguments are a [`ToolArgs`] struct.
+///
+/// Every `TypedTool` is a [`Tool`]: the definition is generated from
+/// `Args`, and arguments are validated and deserialized before
+/// [`run`](TypedTool::run) is called.
+#[async_trait]
+pub trait TypedTool: Send + Sync {
+    type Args: ToolArgs + Send;
+
+    const NAME: &'static str;
+
+    fn description(&self) -> String {
+        Self::Args::description().to_string()
     }
+
+    async fn run(&self, args: Self::Args) -> ToolResult<Value>;
 }
 
 #[async_trait]
-impl Tool for EchoTool {
+impl<T: TypedTool> Tool for T {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             function: FunctionDefinition {
-                name: "echo".to_string(),
-                description: "Echo back the provided message".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "message".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some("The message to echo back".to_string()),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec!["message".to_string()]),
-                },
+                name: T::NAME.to_string(),
+                description: self.description(),
+                parameters: T::Args::schema(),
             },
         }
     }
 
     async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let message = args
-            .get("message")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidArguments {
-                message: "Missing or invalid 'message' parameter".to_string(),
-            })?;
+        let args = T::Args::parse(args)?;
+        self.run(args).await
+    }
+
+    fn name(&self) -> &str {
+        T::NAME
+    }
+}
+
+pub struct EchoTool;
+
+impl EchoTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for EchoTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Echo back the provided message
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct EchoArgs {
+    /// The message to echo back
+    pub message: String,
+}
+
+#[async_trait]
+impl TypedTool for EchoTool {
+    type Args = EchoArgs;
 
+    const NAME: &'static str = "echo";
+
+    async fn run(&self, args: EchoArgs) -> ToolResult<Value> {
         Ok(json!({
-            "echoed": message,
+            "echoed": args.message,
             "timestamp": chrono::Utc::now().to_rfc3339()
         }))
     }
-
-    fn name(&self) -> &str {
-        "echo"
-    }
 }
 
 pub struct CalculatorTool;
@@ -153,78 +404,26 @@ impl Default for CalculatorTool {
     }
 }
 
-#[async_trait]
-impl Tool for CalculatorTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "calculate".to_string(),
-                description: "Perform basic arithmetic calculations".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "operation".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "The operation: add, subtract, multiply, divide".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "a".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Number,
-                                description: Some("First number".to_string()),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "b".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Number,
-                                description: Some("Second number".to_string()),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec![
-                        "operation".to_string(),
-                        "a".to_string(),
-                        "b".to_string(),
-                    ]),
-                },
-            },
-        }
-    }
-
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let operation = args
-            .get("operation")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidArguments {
-                message: "Missing or invalid 'operation' parameter".to_string(),
-            })?;
+/// Perform basic arithmetic calculations
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct CalculateArgs {
+    /// The operation: add, subtract, multiply, divide
+    pub operation: String,
+    /// First number
+    pub a: f64,
+    /// Second number
+    pub b: f64,
+}
 
-        let a =
-            args.get("a")
-                .and_then(|v| v.as_f64())
-                .ok_or_else(|| ToolError::InvalidArguments {
-                    message: "Missing or invalid 'a' parameter".to_string(),
-                })?;
+#[async_trait]
+impl TypedTool for CalculatorTool {
+    type Args = CalculateArgs;
 
-        let b =
-            args.get("b")
-                .and_then(|v| v.as_f64())
-                .ok_or_else(|| ToolError::InvalidArguments {
-                    message: "Missing or invalid 'b' parameter".to_string(),
-                })?;
+    const NAME: &'static str = "calculate";
 
-        let result = match operation {
+    async fn run(&self, args: CalculateArgs) -> ToolResult<Value> {
+        let CalculateArgs { operation, a, b } = args;
+        let result = match operation.as_str() {
             "add" => a + b,
             "subtract" => a - b,
             "multiply" => a * b,
@@ -249,10 +448,6 @@ impl Tool for CalculatorTool {
             "result": result
         }))
     }
-
-    fn name(&self) -> &str {
-        "calculate"
-    }
 }
 
 fn validate_path_within_workspace(path: &Path, workspace_root: &Path) -> ToolResult<PathBuf> {
@@ -338,81 +533,32 @@ impl ReadFileTool {
     }
 }
 
+/// Read the contents of a file. Returns the file content with line numbers.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct ReadFileArgs {
+    /// Path to the file (relative to workspace root)
+    pub path: String,
+    /// Starting line number (1-indexed, optional)
+    pub start_line: Option<usize>,
+    /// Ending line number (inclusive, optional)
+    pub end_line: Option<usize>,
+}
+
 #[async_trait]
-impl Tool for ReadFileTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "read_file".to_string(),
-                description:
-                    "Read the contents of a file. Returns the file content with line numbers."
-                        .to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "path".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Path to the file (relative to workspace root)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "start_line".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Integer,
-                                description: Some(
-                                    "Starting line number (1-indexed, optional)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "end_line".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Integer,
-                                description: Some(
-                                    "Ending line number (inclusive, optional)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec!["path".to_string()]),
-                },
-            },
-        }
-    }
+impl TypedTool for ReadFileTool {
+    type Args = ReadFileArgs;
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let path_str = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| {
-            ToolError::InvalidArguments {
-                message: "Missing or invalid 'path' parameter".to_string(),
-            }
-        })?;
+    const NAME: &'static str = "read_file";
 
-        let path = Path::new(path_str);
+    async fn run(&self, args: ReadFileArgs) -> ToolResult<Value> {
+        let path = Path::new(&args.path);
         let safe_path = validate_path_within_workspace(path, &self.workspace_root)?;
 
         let content = std::fs::read_to_string(&safe_path)?;
         let lines: Vec<&str> = content.lines().collect();
 
-        let start = args
-            .get("start_line")
-            .and_then(|v| v.as_u64())
-            .map(|n| (n as usize).saturating_sub(1))
-            .unwrap_or(0);
-
-        let end = args
-            .get("end_line")
-            .and_then(|v| v.as_u64())
-            .map(|n| n as usize)
-            .unwrap_or(lines.len());
+        let start = args.start_line.map(|n| n.saturating_sub(1)).unwrap_or(0);
+        let end = args.end_line.unwrap_or(lines.len());
 
         let selected_lines: Vec<String> = lines
             .iter()
@@ -423,16 +569,12 @@ impl Tool for ReadFileTool {
             .collect();
 
         Ok(json!({
-            "path": path_str,
+            "path": args.path,
             "content": selected_lines.join("\n"),
             "total_lines": lines.len(),
             "lines_shown": selected_lines.len()
         }))
     }
-
-    fn name(&self) -> &str {
-        "read_file"
-    }
 }
 
 pub struct WriteFileTool {
@@ -445,74 +587,371 @@ impl WriteFileTool {
     }
 }
 
+/// Write content to a file. Creates the file if it doesn't exist, overwrites if it does.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct WriteFileArgs {
+    /// Path to the file (relative to workspace root)
+    pub path: String,
+    /// Content to write to the file
+    pub content: String,
+}
+
 #[async_trait]
-impl Tool for WriteFileTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "write_file".to_string(),
-                description: "Write content to a file. Creates the file if it doesn't exist, overwrites if it does.".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "path".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some("Path to the file (relative to workspace root)".to_string()),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "content".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some("Content to write to the file".to_string()),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec!["path".to_string(), "content".to_string()]),
-                },
-            },
+impl TypedTool for WriteFileTool {
+    type Args = WriteFileArgs;
+
+    const NAME: &'static str = "write_file";
+
+    async fn run(&self, args: WriteFileArgs) -> ToolResult<Value> {
+        let path = Path::new(&args.path);
+        let safe_path = validate_path_for_write(path, &self.workspace_root)?;
+
+        if let Some(parent) = safe_path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+
+        std::fs::write(&safe_path, &args.content)?;
+
+        Ok(json!({
+            "path": args.path,
+            "bytes_written": args.content.len(),
+            "success": true
+        }))
     }
+}
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let path_str = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| {
-            ToolError::InvalidArguments {
-                message: "Missing or invalid 'path' parameter".to_string(),
-            }
-        })?;
+pub struct EditFileTool {
+    workspace_root: PathBuf,
+}
 
-        let content = args
-            .get("content")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidArguments {
-                message: "Missing or invalid 'content' parameter".to_string(),
-            })?;
+impl EditFileTool {
+    pub fn new(workspace_root: PathBuf) -> Self {
+        Self { workspace_root }
+    }
+}
 
-        let path = Path::new(path_str);
+/// Replace text in an existing file without rewriting the whole file.
+///
+/// `old_string` must match exactly one place unless `replace_all` is set, so
+/// include a few surrounding lines. Returns a unified diff of the change.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct EditFileArgs {
+    /// Path to the file (relative to workspace root)
+    pub path: String,
+    /// Text to replace, including its indentation
+    pub old_string: String,
+    /// Text to put in its place
+    pub new_string: String,
+    /// Replace every occurrence instead of requiring a unique match
+    #[serde(default)]
+    pub replace_all: bool,
+    /// Match lines even if their indentation or trailing whitespace differs
+    #[serde(default)]
+    pub ignore_whitespace: bool,
+}
+
+#[async_trait]
+impl TypedTool for EditFileTool {
+    type Args = EditFileArgs;
+
+    const NAME: &'static str = "edit_file";
+
+    async fn run(&self, args: EditFileArgs) -> ToolResult<Value> {
+        let path = Path::new(&args.path);
         let safe_path = validate_path_for_write(path, &self.workspace_root)?;
+        if !safe_path.is_file() {
+            return Err(ToolError::ExecutionFailed {
+                message: format!(
+                    "File '{}' does not exist; use write_file to create it",
+                    args.path
+                ),
+            });
+        }
+
+        let before = std::fs::read_to_string(&safe_path)?;
+        let options = EditOptions {
+            replace_all: args.replace_all,
+            ignore_whitespace: args.ignore_whitespace,
+        };
+        let edit =
+            edit::replace(&before, &args.old_string, &args.new_string, options).map_err(|e| {
+                ToolError::ExecutionFailed {
+                    message: e.to_string(),
+                }
+            })?;
+        write_atomically(&safe_path, &edit.content)?;
+
+        Ok(json!({
+            "path": args.path,
+            "replacements": edit.replacements,
+            "ignored_whitespace": edit.ignored_whitespace,
+            "diff": edit::unified_diff(&args.path, &before, &edit.content),
+            "success": true
+        }))
+    }
+}
+
+/// Replace `path` through a temporary file in the same directory, so a
+/// failed write leaves the original untouched. The file keeps its
+/// permissions.
+fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let permissions = std::fs::metadata(path)?.permissions();
+    write_atomically_with(path, content, Some(&permissions))
+}
+
+/// [`write_atomically`], giving the file `permissions` if set and the
+/// default ones otherwise.
+fn write_atomically_with(
+    path: &Path,
+    content: &str,
+    permissions: Option<&std::fs::Permissions>,
+) -> std::io::Result<()> {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = std::fs::write(&temp, content)
+        .and_then(|_| match permissions {
+            Some(permissions) => std::fs::set_permissions(&temp, permissions.clone()),
+            None => Ok(()),
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+pub struct ApplyPatchTool {
+    workspace_root: PathBuf,
+}
+
+impl ApplyPatchTool {
+    pub fn new(workspace_root: PathBuf) -> Self {
+        Self { workspace_root }
+    }
+}
+
+/// Apply a patch to files in the workspace.
+///
+/// Accepts a unified diff as printed by `git diff`, or a `*** Begin Patch`
+/// envelope with `*** Add File:`, `*** Update File:` (optionally followed by
+/// `*** Move to:`) and `*** Delete File:` sections. Either every change
+/// applies or no file is touched.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct ApplyPatchArgs {
+    /// The patch text
+    pub patch: String,
+}
+
+/// File contents as a patch leaves them, written only once every change in
+/// the patch has applied. Files are keyed by their path without `.`
+/// components, so two spellings of one path share an entry.
+#[derive(Default)]
+struct StagedFiles {
+    order: Vec<PathBuf>,
+    contents: HashMap<PathBuf, Option<String>>,
+    /// What each staged file held on disk before the patch, for rollback
+    originals: HashMap<PathBuf, Option<String>>,
+    /// Permissions of the staged files that exist, and of move
+    /// destinations, which take those of their source
+    permissions: HashMap<PathBuf, std::fs::Permissions>,
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect()
+}
+
+fn read_if_file(path: &Path) -> ToolResult<Option<String>> {
+    if path.is_file() {
+        Ok(Some(std::fs::read_to_string(path)?))
+    } else {
+        Ok(None)
+    }
+}
+
+impl StagedFiles {
+    fn read(&self, path: &Path) -> ToolResult<Option<String>> {
+        let path = normalize_path(path);
+        match self.contents.get(&path) {
+            Some(content) => Ok(content.clone()),
+            None => read_if_file(&path),
+        }
+    }
+
+    fn stage(&mut self, path: &Path, content: Option<String>) -> ToolResult<()> {
+        let path = normalize_path(path);
+        if !self.contents.contains_key(&path) {
+            let original = read_if_file(&path)?;
+            if original.is_some() {
+                let permissions = std::fs::metadata(&path)?.permissions();
+                self.permissions.insert(path.clone(), permissions);
+            }
+            self.originals.insert(path.clone(), original);
+            self.order.push(path.clone());
+        }
+        self.contents.insert(path, content);
+        Ok(())
+    }
+
+    /// Stage `from` for removal and `to` with `content` and the permissions
+    /// of `from`.
+    fn stage_move(&mut self, from: &Path, to: &Path, content: String) -> ToolResult<()> {
+        self.stage(from, None)?;
+        self.stage(to, Some(content))?;
+        if let Some(permissions) = self.permissions.get(&normalize_path(from)).cloned() {
+            self.permissions.insert(normalize_path(to), permissions);
+        }
+        Ok(())
+    }
+
+    /// Write every staged file, restoring the ones already written if one
+    /// of them fails.
+    fn commit(self) -> ToolResult<()> {
+        for (i, path) in self.order.iter().enumerate() {
+            let permissions = self.permissions.get(path);
+            let Err(e) = write_or_remove(path, self.contents[path].as_deref(), permissions) else {
+                continue;
+            };
+            let unrestored: Vec<String> = self.order[..=i]
+                .iter()
+                .rev()
+                .filter_map(|path| {
+                    let permissions = self.permissions.get(path);
+                    write_or_remove(path, self.originals[path].as_deref(), permissions)
+                        .err()
+                        .map(|e| format!("{} ({})", path.display(), e))
+                })
+                .collect();
+            if unrestored.is_empty() {
+                return Err(e.into());
+            }
+            return Err(ToolError::ExecutionFailed {
+                message: format!(
+                    "writing {} failed: {}; could not restore {}",
+                    path.display(),
+                    e,
+                    unrestored.join(", ")
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn write_or_remove(
+    path: &Path,
+    content: Option<&str>,
+    permissions: Option<&std::fs::Permissions>,
+) -> std::io::Result<()> {
+    match content {
+        Some(content) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_atomically_with(path, content, permissions)
+        }
+        None if path.exists() => std::fs::remove_file(path),
+        None => Ok(()),
+    }
+}
+
+#[async_trait]
+impl TypedTool for ApplyPatchTool {
+    type Args = ApplyPatchArgs;
+
+    const NAME: &'static str = "apply_patch";
 
-        if let Some(parent) = safe_path.parent() {
-            std::fs::create_dir_all(parent)?;
+    async fn run(&self, args: ApplyPatchArgs) -> ToolResult<Value> {
+        let patch = Patch::parse(&args.patch).map_err(|e| ToolError::InvalidArguments {
+            message: e.to_string(),
+        })?;
+
+        let mut staged = StagedFiles::default();
+        let mut rejections = Vec::new();
+        let mut files = Vec::new();
+        for file in &patch.files {
+            let path = validate_path_for_write(Path::new(&file.path), &self.workspace_root)?;
+            match (&file.change, staged.read(&path)?) {
+                (FileChange::Add { content }, None) => {
+                    staged.stage(&path, Some(content.clone()))?;
+                    files.push(json!({ "path": file.path, "action": "add" }));
+                }
+                (FileChange::Add { .. }, Some(_)) => {
+                    rejections.push(Rejection::file(&file.path, "file already exists"));
+                }
+                (_, None) => {
+                    rejections.push(Rejection::file(&file.path, "file does not exist"));
+                }
+                (
+                    FileChange::Delete {
+                        content: Some(expected),
+                    },
+                    Some(content),
+                ) if content.replace("\r\n", "\n") != *expected => {
+                    rejections.push(Rejection::file(
+                        &file.path,
+                        "file does not match the lines the patch deletes",
+                    ));
+                }
+                (FileChange::Delete { .. }, Some(_)) => {
+                    staged.stage(&path, None)?;
+                    files.push(json!({ "path": file.path, "action": "delete" }));
+                }
+                (FileChange::Update { move_to, hunks }, Some(content)) => {
+                    let applied = match patch::apply_hunks(&file.path, &content, hunks) {
+                        Ok(applied) => applied,
+                        Err(hunk_rejections) => {
+                            rejections.extend(hunk_rejections);
+                            continue;
+                        }
+                    };
+                    let mut summary = json!({
+                        "path": file.path,
+                        "action": "update",
+                        "hunks": hunks.len(),
+                        "fuzzy_hunks": applied.fuzzy_hunks
+                    });
+                    match move_to {
+                        Some(dest) => {
+                            let dest_path =
+                                validate_path_for_write(Path::new(dest), &self.workspace_root)?;
+                            if staged.read(&dest_path)?.is_some() {
+                                rejections.push(Rejection::file(
+                                    &file.path,
+                                    format!("cannot move to {}: file already exists", dest),
+                                ));
+                                continue;
+                            }
+                            staged.stage_move(&path, &dest_path, applied.content)?;
+                            summary["action"] = json!("move");
+                            summary["moved_to"] = json!(dest);
+                        }
+                        None => staged.stage(&path, Some(applied.content))?,
+                    }
+                    files.push(summary);
+                }
+            }
         }
 
-        std::fs::write(&safe_path, content)?;
+        if !rejections.is_empty() {
+            return Err(ToolError::ExecutionFailed {
+                message: PatchError::Rejected(rejections).to_string(),
+            });
+        }
+        staged.commit()?;
 
         Ok(json!({
-            "path": path_str,
-            "bytes_written": content.len(),
+            "files": files,
             "success": true
         }))
     }
-
-    fn name(&self) -> &str {
-        "write_file"
-    }
 }
 
 pub struct ListDirTool {
@@ -569,72 +1008,35 @@ impl ListDirTool {
     }
 }
 
+/// List files and directories in a path.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct ListDirArgs {
+    /// Path to list (relative to workspace root, defaults to '.')
+    pub path: Option<String>,
+    /// Whether to list recursively (default: false)
+    #[serde(default)]
+    pub recursive: bool,
+    /// Glob pattern to filter files (e.g., '*.rs')
+    pub pattern: Option<String>,
+}
+
 #[async_trait]
-impl Tool for ListDirTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "list_directory".to_string(),
-                description: "List files and directories in a path.".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "path".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Path to list (relative to workspace root, defaults to '.')"
-                                        .to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "recursive".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Boolean,
-                                description: Some(
-                                    "Whether to list recursively (default: false)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "pattern".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Glob pattern to filter files (e.g., '*.rs')".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec![]),
-                },
-            },
-        }
-    }
+impl TypedTool for ListDirTool {
+    type Args = ListDirArgs;
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let path_str = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+    const NAME: &'static str = "list_directory";
+
+    async fn run(&self, args: ListDirArgs) -> ToolResult<Value> {
+        let path_str = args.path.as_deref().unwrap_or(".");
 
         let path = Path::new(path_str);
         let safe_path = validate_path_within_workspace(path, &self.workspace_root)?;
 
-        let recursive = args
-            .get("recursive")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-
-        let pattern = args.get("pattern").and_then(|v| v.as_str());
+        let pattern = args.pattern.as_deref();
 
         let mut entries = Vec::new();
 
-        if recursive {
+        if args.recursive {
             self.list_recursive(&safe_path, &self.workspace_root, pattern, &mut entries)?;
         } else {
             for entry in std::fs::read_dir(&safe_path)? {
@@ -667,10 +1069,6 @@ impl Tool for ListDirTool {
             "count": entries.len()
         }))
     }
-
-    fn name(&self) -> &str {
-        "list_directory"
-    }
 }
 
 pub struct SearchTool {
@@ -746,99 +1144,50 @@ impl SearchTool {
     }
 }
 
+/// Search for a pattern in files. Returns matching lines with context.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct SearchArgs {
+    /// Regex pattern to search for
+    pub pattern: String,
+    /// Path to search in (defaults to workspace root)
+    pub path: Option<String>,
+    /// Glob pattern to filter files (e.g., '*.rs')
+    pub file_pattern: Option<String>,
+    /// Maximum number of results to return (default: 50)
+    pub max_results: Option<usize>,
+}
+
 #[async_trait]
-impl Tool for SearchTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "search".to_string(),
-                description: "Search for a pattern in files. Returns matching lines with context."
-                    .to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "pattern".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some("Regex pattern to search for".to_string()),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "path".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Path to search in (defaults to workspace root)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "file_pattern".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Glob pattern to filter files (e.g., '*.rs')".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "max_results".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Integer,
-                                description: Some(
-                                    "Maximum number of results to return (default: 50)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec!["pattern".to_string()]),
-                },
-            },
-        }
-    }
+impl TypedTool for SearchTool {
+    type Args = SearchArgs;
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let pattern_str = args
-            .get("pattern")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidArguments {
-                message: "Missing or invalid 'pattern' parameter".to_string(),
-            })?;
+    const NAME: &'static str = "search";
 
-        let regex = regex::Regex::new(pattern_str).map_err(|e| ToolError::InvalidArguments {
+    async fn run(&self, args: SearchArgs) -> ToolResult<Value> {
+        let regex = regex::Regex::new(&args.pattern).map_err(|e| ToolError::InvalidArguments {
             message: format!("Invalid regex pattern: {}", e),
         })?;
 
-        let path_str = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-        let path = Path::new(path_str);
+        let path = Path::new(args.path.as_deref().unwrap_or("."));
         let safe_path = validate_path_within_workspace(path, &self.workspace_root)?;
 
-        let file_pattern = args.get("file_pattern").and_then(|v| v.as_str());
-        let max_results = args
-            .get("max_results")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(50) as usize;
+        let max_results = args.max_results.unwrap_or(50);
 
         let mut results = Vec::new();
-        self.search_recursive(&safe_path, &regex, file_pattern, max_results, &mut results)?;
+        self.search_recursive(
+            &safe_path,
+            &regex,
+            args.file_pattern.as_deref(),
+            max_results,
+            &mut results,
+        )?;
 
         Ok(json!({
-            "pattern": pattern_str,
+            "pattern": args.pattern,
             "results": results,
             "count": results.len()
         }))
     }
-
-    fn name(&self) -> &str {
-        "search"
-    }
 }
 
 pub struct GitStatusTool {
@@ -851,23 +1200,17 @@ impl GitStatusTool {
     }
 }
 
+/// Get the current git repository status including branch, staged files, and modified files.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct GitStatusArgs {}
+
 #[async_trait]
-impl Tool for GitStatusTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "git_status".to_string(),
-                description: "Get the current git repository status including branch, staged files, and modified files.".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some(HashMap::new()),
-                    required: Some(vec![]),
-                },
-            },
-        }
-    }
+impl TypedTool for GitStatusTool {
+    type Args = GitStatusArgs;
 
-    async fn execute(&self, _args: Value) -> ToolResult<Value> {
+    const NAME: &'static str = "git_status";
+
+    async fn run(&self, _args: GitStatusArgs) -> ToolResult<Value> {
         use crate::entities::git::GitRepository;
 
         let repo = GitRepository::detect(&self.workspace_root).ok_or_else(|| {
@@ -886,10 +1229,6 @@ impl Tool for GitStatusTool {
             "summary": repo.summary()
         }))
     }
-
-    fn name(&self) -> &str {
-        "git_status"
-    }
 }
 
 pub struct GitDiffTool {
@@ -902,58 +1241,29 @@ impl GitDiffTool {
     }
 }
 
+/// Show git diff for files. Can show staged or unstaged changes.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct GitDiffArgs {
+    /// Path to diff (optional, defaults to all files)
+    pub path: Option<String>,
+    /// Show staged changes instead of unstaged (default: false)
+    #[serde(default)]
+    pub staged: bool,
+}
+
 #[async_trait]
-impl Tool for GitDiffTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "git_diff".to_string(),
-                description: "Show git diff for files. Can show staged or unstaged changes."
-                    .to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "path".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Path to diff (optional, defaults to all files)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props.insert(
-                            "staged".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::Boolean,
-                                description: Some(
-                                    "Show staged changes instead of unstaged (default: false)"
-                                        .to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec![]),
-                },
-            },
-        }
-    }
+impl TypedTool for GitDiffTool {
+    type Args = GitDiffArgs;
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let path = args.get("path").and_then(|v| v.as_str());
-        let staged = args
-            .get("staged")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
+    const NAME: &'static str = "git_diff";
+
+    async fn run(&self, args: GitDiffArgs) -> ToolResult<Value> {
+        let path = args.path.as_deref();
 
         let mut cmd = std::process::Command::new("git");
         cmd.current_dir(&self.workspace_root);
 
-        if staged {
+        if args.staged {
             cmd.args(["diff", "--cached"]);
         } else {
             cmd.arg("diff");
@@ -977,15 +1287,11 @@ impl Tool for GitDiffTool {
 
         Ok(json!({
             "diff": diff,
-            "staged": staged,
+            "staged": args.staged,
             "path": path.unwrap_or("(all files)"),
             "has_changes": !diff.is_empty()
         }))
     }
-
-    fn name(&self) -> &str {
-        "git_diff"
-    }
 }
 
 pub struct RunCommandTool {
@@ -1005,46 +1311,23 @@ impl RunCommandTool {
     }
 }
 
+/// Run a shell command in the dev container workspace.
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct RunCommandArgs {
+    /// The shell command to run (passed to sh -c)
+    pub command: String,
+}
+
 #[async_trait]
-impl Tool for RunCommandTool {
-    fn definition(&self) -> ToolDefinition {
-        ToolDefinition {
-            function: FunctionDefinition {
-                name: "run_command".to_string(),
-                description: "Run a shell command in the dev container workspace.".to_string(),
-                parameters: JsonSchema {
-                    schema_type: SchemaType::Object,
-                    properties: Some({
-                        let mut props = HashMap::new();
-                        props.insert(
-                            "command".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "The shell command to run (passed to sh -c)".to_string(),
-                                ),
-                                items: None,
-                            },
-                        );
-                        props
-                    }),
-                    required: Some(vec!["command".to_string()]),
-                },
-            },
-        }
-    }
+impl TypedTool for RunCommandTool {
+    type Args = RunCommandArgs;
 
-    async fn execute(&self, args: Value) -> ToolResult<Value> {
-        let command = args
-            .get("command")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| ToolError::InvalidArguments {
-                message: "Missing or invalid 'command' parameter".to_string(),
-            })?;
+    const NAME: &'static str = "run_command";
 
+    async fn run(&self, args: RunCommandArgs) -> ToolResult<Value> {
         let result = crate::container::exec_in_container(
             &self.container_handle,
-            &["sh", "-c", command],
+            &["sh", "-c", &args.command],
             self.working_dir.as_deref(),
         )
         .map_err(|e| ToolError::ExecutionFailed {
@@ -1057,10 +1340,6 @@ impl Tool for RunCommandTool {
             "success": result.success,
         }))
     }
-
-    fn name(&self) -> &str {
-        "run_command"
-    }
 }
 
 /// GitHub API connection status for transparent degradation.
@@ -1743,6 +2022,18 @@ fn fetch_github_pr_data(
     Ok(gh_data)
 }
 
+/// Fields that `github_pr_status` can expand at level l1
+const PR_STATUS_FIELDS: [&str; 8] = [
+    "conflicts",
+    "ci",
+    "diff",
+    "sync",
+    "review",
+    "automerge",
+    "staleness",
+    "github",
+];
+
 pub struct GitHubPrStatusTool {
     workspace_root: PathBuf,
 }
@@ -1766,27 +2057,23 @@ impl Tool for GitHubPrStatusTool {
                         let mut props = HashMap::new();
                         props.insert(
                             "level".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Detail level: 'l0' for compact status line (default), 'l1' for expanded field detail".to_string(),
-                                ),
-                                items: None,
-                            },
+                            PropertySchema::new(SchemaType::String)
+                                .with_description(
+                                    "Detail level: 'l0' for compact status line, 'l1' for expanded field detail",
+                                )
+                                .with_enum(["l0", "l1"])
+                                .with_default("l0"),
                         );
                         props.insert(
                             "field".to_string(),
-                            PropertySchema {
-                                schema_type: SchemaType::String,
-                                description: Some(
-                                    "Field to expand (required for l1). Options: conflicts, ci, diff, sync, review, automerge, staleness, github".to_string(),
-                                ),
-                                items: None,
-                            },
+                            PropertySchema::new(SchemaType::String)
+                                .with_description("Field to expand (required for l1)")
+                                .with_enum(PR_STATUS_FIELDS),
                         );
                         props
                     }),
                     required: Some(vec![]),
+                    ..Default::default()
                 },
             },
         }
@@ -1837,11 +2124,13 @@ impl Tool for GitHubPrStatusTool {
 }
 
 pub fn create_tool_registry(workspace_root: &std::path::Path) -> ToolRegistry {
-    let mut registry = ToolRegistry::new();
+    let mut registry = ToolRegistry::new().with_workspace_root(workspace_root);
     registry.register(Box::new(EchoTool::new()));
     registry.register(Box::new(CalculatorTool::new()));
     registry.register(Box::new(ReadFileTool::new(workspace_root.to_path_buf())));
     registry.register(Box::new(WriteFileTool::new(workspace_root.to_path_buf())));
+    registry.register(Box::new(EditFileTool::new(workspace_root.to_path_buf())));
+    registry.register(Box::new(ApplyPatchTool::new(workspace_root.to_path_buf())));
     registry.register(Box::new(ListDirTool::new(workspace_root.to_path_buf())));
     registry.register(Box::new(SearchTool::new(workspace_root.to_path_buf())));
     registry.register(Box::new(GitStatusTool::new(workspace_root.to_path_buf())));
@@ -1908,6 +2197,206 @@ mod tests {
         assert_eq!(result["echoed"], "test");
     }
 
+    /// Look up files by name.
+    ///
+    /// Matches are case-sensitive.
+    #[derive(Debug, Deserialize, ToolArgs)]
+    struct FindArgs {
+        /// File name to look for
+        #[serde(rename = "name")]
+        file_name: String,
+        /// Directories to search
+        #[serde(default)]
+        roots: Vec<PathBuf>,
+        #[tool(enum_values = ["file", "dir"])]
+        kind: Option<String>,
+        #[tool(minimum = 1, maximum = 100)]
+        limit: Option<u32>,
+        #[tool(maximum = 8)]
+        depth: Option<u8>,
+        filter: Option<FindFilter>,
+    }
+
+    #[derive(Debug, Deserialize, ToolArgs)]
+    struct FindFilter {
+        /// Minimum size in bytes
+        min_size: u64,
+    }
+
+    struct FindTool;
+
+    #[async_trait]
+    impl TypedTool for FindTool {
+        type Args = FindArgs;
+
+        const NAME: &'static str = "find";
+
+        async fn run(&self, args: FindArgs) -> ToolResult<Value> {
+            Ok(json!({
+                "name": args.file_name,
+                "roots": args.roots.len(),
+                "kind": args.kind,
+                "limit": args.limit,
+                "depth": args.depth,
+                "min_size": args.filter.map(|f| f.min_size),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_derived_tool_definition() {
+        let def = FindTool.definition();
+        assert_eq!(def.function.name, "find");
+        assert_eq!(
+            def.function.description,
+            "Look up files by name. Matches are case-sensitive."
+        );
+
+        let params = &def.function.parameters;
+        assert_eq!(params.required, Some(vec!["name".to_string()]));
+        let props = params.properties.as_ref().unwrap();
+        assert_eq!(props.len(), 6);
+        assert_eq!(
+            props["name"].description.as_deref(),
+            Some("File name to look for")
+        );
+        assert_eq!(props["roots"].schema_type, Some(SchemaType::Array));
+        assert_eq!(
+            props["roots"].items.as_ref().unwrap().schema_type,
+            Some(SchemaType::String)
+        );
+        assert_eq!(
+            props["kind"].enum_values,
+            Some(vec![json!("file"), json!("dir")])
+        );
+        assert_eq!(props["limit"].minimum, Some(1.0));
+        assert_eq!(props["limit"].maximum, Some(100.0));
+        // Only the given bound replaces the one implied by the type
+        assert_eq!(props["depth"].minimum, Some(0.0));
+        assert_eq!(props["depth"].maximum, Some(8.0));
+        let filter = &props["filter"];
+        assert_eq!(filter.schema_type, Some(SchemaType::Object));
+        assert_eq!(filter.required, Some(vec!["min_size".to_string()]));
+        assert_eq!(
+            filter.properties.as_ref().unwrap()["min_size"].minimum,
+            Some(0.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_derived_tool_parses_arguments() {
+        let result = FindTool
+            .execute(json!({ "name": "lib.rs", "limit": 5, "filter": { "min_size": 10 } }))
+            .await
+            .unwrap();
+        assert_eq!(result["name"], "lib.rs");
+        assert_eq!(result["roots"], 0);
+        assert_eq!(result["min_size"], 10);
+
+        let cases = [
+            (json!({}), "arguments: missing required field 'name'"),
+            (
+                json!({ "name": 3 }),
+                "arguments.name: expected string, got number",
+            ),
+            (
+                json!({ "name": "a", "limit": 0 }),
+                "arguments.limit: 0 is less than 1",
+            ),
+            (
+                json!({ "name": "a", "filter": { "min_size": -1 } }),
+                "arguments.filter.min_size: -1 is less than 0",
+            ),
+        ];
+        for (args, expected) in cases {
+            match FindTool.execute(args).await {
+                Err(ToolError::InvalidArguments { message }) => assert_eq!(message, expected),
+                other => panic!("expected InvalidArguments, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_registry_validates_arguments() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool::new()));
+        registry.register(Box::new(GitHubPrStatusTool::new(PathBuf::from("/tmp"))));
+
+        let missing = registry.execute("echo", json!({})).await;
+        match missing {
+            Err(ToolError::InvalidArguments { message }) => {
+                assert_eq!(message, "arguments: missing required field 'message'")
+            }
+            other => panic!("expected InvalidArguments, got {:?}", other),
+        }
+
+        let wrong_type = registry.execute("echo", json!({ "message": 42 })).await;
+        assert!(matches!(
+            wrong_type,
+            Err(ToolError::InvalidArguments { .. })
+        ));
+
+        let bad_field = registry
+            .execute(
+                "github_pr_status",
+                json!({ "level": "l1", "field": "builds" }),
+            )
+            .await;
+        match bad_field {
+            Err(ToolError::InvalidArguments { message }) => {
+                assert!(message.starts_with("arguments.field: \"builds\" is not one of"))
+            }
+            other => panic!("expected InvalidArguments, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_response_message_moves_images() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0];
+        std::fs::write(workspace.path().join("shot.png"), png).unwrap();
+        let result = json!({
+            "rendered": true,
+            "images": [{"type": "path", "path": "shot.png"}]
+        });
+        let msg = tool_response_message("call_1", Ok(result), Some(workspace.path()));
+        assert_eq!(msg.content.as_deref(), Some(r#"{"rendered":true}"#));
+        assert_eq!(msg.images, vec![ImageAttachment::from_bytes(&png)]);
+
+        // Anything that is not a list of attachments stays in the text
+        let msg = tool_response_message("call_2", Ok(json!({"images": 3})), None);
+        assert_eq!(msg.content.as_deref(), Some(r#"{"images":3}"#));
+        assert!(msg.images.is_empty());
+
+        let err = ToolError::NotFound {
+            name: "x".to_string(),
+        };
+        let msg = tool_response_message("call_3", Err(err), None);
+        assert_eq!(msg.content.as_deref(), Some("Error: Tool not found: x"));
+    }
+
+    #[test]
+    fn test_tool_response_message_keeps_image_files_in_workspace() {
+        let outside = tempfile::TempDir::new().unwrap();
+        let secret = outside.path().join("secret.png");
+        std::fs::write(&secret, [0x89, b'P', b'N', b'G']).unwrap();
+        let workspace = tempfile::TempDir::new().unwrap();
+        let result = json!({"images": [{"type": "path", "path": secret}]});
+
+        let msg = tool_response_message("call_1", Ok(result.clone()), Some(workspace.path()));
+        assert!(msg.images.is_empty());
+        let content: Value = serde_json::from_str(msg.content.as_deref().unwrap()).unwrap();
+        let errors = content[TOOL_IMAGE_ERRORS_KEY].as_array().unwrap();
+        assert!(errors[0]
+            .as_str()
+            .unwrap()
+            .contains("outside workspace root"));
+
+        let msg = tool_response_message("call_2", Ok(result), None);
+        assert!(msg.images.is_empty());
+        assert!(msg.content.unwrap().contains(TOOL_IMAGE_ERRORS_KEY));
+    }
+
     #[tokio::test]
     async fn test_read_file_tool() {
         let temp_dir = std::env::temp_dir().join("nanna_test_read");
@@ -1975,6 +2464,203 @@ mod tests {
         std::fs::remove_dir_all(&temp_dir).unwrap();
     }
 
+    #[tokio::test]
+    async fn test_edit_file_tool() {
+        let temp_dir = std::env::temp_dir().join("nanna_test_edit");
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+
+        let tool = EditFileTool::new(temp_dir.clone());
+
+        let args = json!({
+            "path": "lib.rs",
+            "old_string": "fn b() {}",
+            "new_string": "fn b() -> u8 { 1 }"
+        });
+        let result = tool.execute(args).await.unwrap();
+        assert_eq!(result["replacements"], 1);
+        assert!(result["diff"]
+            .as_str()
+            .unwrap()
+            .contains("-fn b() {}\n+fn b() -> u8 { 1 }\n"));
+        let content = std::fs::read_to_string(temp_dir.join("lib.rs")).unwrap();
+        assert_eq!(content, "fn a() {}\nfn b() -> u8 { 1 }\n");
+
+        let args = json!({
+            "path": "lib.rs",
+            "old_string": "fn ",
+            "new_string": "pub fn "
+        });
+        let err = tool.execute(args).await.unwrap_err();
+        assert!(err.to_string().contains("matches 2 places"));
+
+        let args = json!({
+            "path": "missing.rs",
+            "old_string": "a",
+            "new_string": "b"
+        });
+        let err = tool.execute(args).await.unwrap_err();
+        assert!(err.to_string().contains("use write_file"));
+
+        let args = json!({
+            "path": "../outside.rs",
+            "old_string": "a",
+            "new_string": "b"
+        });
+        assert!(matches!(
+            tool.execute(args).await,
+            Err(ToolError::PathSecurityViolation { .. })
+        ));
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_edit_file_replaces_file_in_one_step() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("run.sh");
+        std::fs::write(&script, "echo one\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let tool = EditFileTool::new(temp_dir.path().to_path_buf());
+        let args = json!({
+            "path": "run.sh",
+            "old_string": "one",
+            "new_string": "two"
+        });
+        tool.execute(args).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&script).unwrap(), "echo two\n");
+        let mode = std::fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        let entries: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, ["run.sh"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_tool_is_all_or_nothing() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(temp_dir.path().join("b.txt"), "alpha\nbeta\n").unwrap();
+
+        let tool = ApplyPatchTool::new(temp_dir.path().to_path_buf());
+
+        let args = json!({
+            "patch": "*** Begin Patch\n*** Update File: a.txt\n-two\n+TWO\n*** Update File: b.txt\n-gamma\n+GAMMA\n*** Add File: c.txt\n+new\n*** End Patch\n"
+        });
+        let err = tool.execute(args).await.unwrap_err();
+        assert!(err.to_string().contains("b.txt: hunk 1:"));
+        let a = std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap();
+        assert_eq!(a, "one\ntwo\nthree\n");
+        assert!(!temp_dir.path().join("c.txt").exists());
+
+        let args = json!({
+            "patch": "*** Begin Patch\n*** Update File: a.txt\n*** Move to: moved/a.txt\n-two\n+TWO\n*** Delete File: b.txt\n*** Add File: c.txt\n+new\n*** End Patch\n"
+        });
+        let result = tool.execute(args).await.unwrap();
+        assert_eq!(result["files"].as_array().unwrap().len(), 3);
+        assert_eq!(result["files"][0]["action"], "move");
+        let moved = std::fs::read_to_string(temp_dir.path().join("moved/a.txt")).unwrap();
+        assert_eq!(moved, "one\nTWO\nthree\n");
+        assert!(!temp_dir.path().join("a.txt").exists());
+        assert!(!temp_dir.path().join("b.txt").exists());
+        let c = std::fs::read_to_string(temp_dir.path().join("c.txt")).unwrap();
+        assert_eq!(c, "new\n");
+
+        let args = json!({ "patch": "not a patch" });
+        assert!(matches!(
+            tool.execute(args).await,
+            Err(ToolError::InvalidArguments { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_apply_patch_tool_moves_keep_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("run.sh");
+        std::fs::write(&script, "echo one\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let tool = ApplyPatchTool::new(temp_dir.path().to_path_buf());
+        let args = json!({
+            "patch": "*** Begin Patch\n*** Update File: run.sh\n*** Move to: bin/run.sh\n-echo one\n+echo two\n*** End Patch\n"
+        });
+        tool.execute(args).await.unwrap();
+
+        let moved = temp_dir.path().join("bin/run.sh");
+        assert_eq!(std::fs::read_to_string(&moved).unwrap(), "echo two\n");
+        let mode = std::fs::metadata(&moved).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_tool_checks_deleted_lines() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(temp_dir.path().join("b.txt"), "alpha\nbeta\n").unwrap();
+
+        let tool = ApplyPatchTool::new(temp_dir.path().to_path_buf());
+
+        let args = json!({
+            "patch": "--- a/b.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-alpha\n-gamma\n"
+        });
+        let err = tool.execute(args).await.unwrap_err();
+        assert!(
+            err.to_string().contains("b.txt: file does not match"),
+            "{}",
+            err
+        );
+        assert!(temp_dir.path().join("b.txt").exists());
+
+        // Both spellings of a.txt edit the same staged file
+        let args = json!({
+            "patch": "*** Begin Patch\n*** Update File: a.txt\n-one\n+ONE\n*** Update File: ./a.txt\n-ONE\n+1\n*** End Patch\n"
+        });
+        tool.execute(args).await.unwrap();
+        let a = std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap();
+        assert_eq!(a, "1\ntwo\n");
+
+        let args = json!({
+            "patch": "--- a/b.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-alpha\n-beta\n"
+        });
+        tool.execute(args).await.unwrap();
+        assert!(!temp_dir.path().join("b.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_tool_replays_workspace_changes() {
+        let source = tempfile::TempDir::new().unwrap();
+        crate::workspace::tests::init_git_repo(source.path());
+
+        let mut ws = crate::workspace::TaskWorkspace::create(
+            source.path(),
+            &format!("ws-apply-{}", uuid::Uuid::new_v4()),
+            "HEAD",
+        )
+        .unwrap();
+        std::fs::write(ws.workspace_path.join("README.md"), "# Test\n\nMore docs\n").unwrap();
+        std::fs::write(ws.workspace_path.join("notes.txt"), "remember\n").unwrap();
+        let diff = ws.extract_changes().unwrap();
+        ws.cleanup().unwrap();
+
+        let tool = ApplyPatchTool::new(source.path().to_path_buf());
+        tool.execute(json!({ "patch": diff })).await.unwrap();
+
+        let readme = std::fs::read_to_string(source.path().join("README.md")).unwrap();
+        assert_eq!(readme, "# Test\n\nMore docs\n");
+        let notes = std::fs::read_to_string(source.path().join("notes.txt")).unwrap();
+        assert_eq!(notes, "remember\n");
+    }
+
     #[tokio::test]
     async fn test_list_directory_tool() {
         let temp_dir = std::env::temp_dir().join("nanna_test_list");
@@ -166,12 +166,12 @@ impl Drop for TaskWorkspace {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use tempfile::TempDir;
     use uuid::Uuid;
 
-    fn init_git_repo(dir: &Path) {
+    pub(crate) fn init_git_repo(dir: &Path) {
         for args in &[
             vec!["init"],
             vec!["config", "user.email", "test@test.com"],
@@ -1431,6 +1431,7 @@ fn make_stop_response(content: &str) -> ChatResponse {
             finish_reason: Some(FinishReason::Stop),
         }],
         usage: None,
+        served_by: None,
     }
 }
 
@@ -1440,11 +1441,13 @@ fn make_stop_response(content: &str) -> ChatResponse {
 fn wrap_with_state_machine_responses(tool_responses: Vec<ChatResponse>) -> Vec<ChatResponse> {
     // EnrichingEntities: no LLM call
     let mut responses = vec![
-        make_stop_response("Plan: execute the task"), // PlanningEntityModification
+        make_stop_response(r#"{"plan": "execute the task"}"#), // PlanningEntityModification
     ];
     responses.extend(tool_responses); // PerformingEntityModification
                                       // UpdatingEntities: no LLM call
-    responses.push(make_stop_response("COMPLETE - task done")); // CheckingTaskCompletion
+    responses.push(make_stop_response(
+        r#"{"status": "COMPLETE", "reasoning": "task done"}"#,
+    )); // CheckingTaskCompletion
     responses
 }
 
@@ -1463,6 +1466,7 @@ async fn test_agent_loop_tool_call_integration() {
             finish_reason: Some(FinishReason::ToolCalls),
         }],
         usage: None,
+        served_by: None,
     };
     let stop_response = make_stop_response("Task complete.");
 
@@ -1477,6 +1481,7 @@ async fn test_agent_loop_tool_call_integration() {
         verbose: false,
         system_prompt: "You are a helpful assistant.".to_string(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -1512,6 +1517,69 @@ async fn test_agent_loop_tool_call_integration() {
         .contains("hello world"));
 }
 
+#[tokio::test]
+async fn test_agent_run_replays_from_cassette() {
+    let tool_call_response = ChatResponse {
+        choices: vec![Choice {
+            message: ChatMessage::assistant_with_tools(
+                None,
+                vec![make_tool_call(
+                    "call_1",
+                    "calculate",
+                    json!({"operation": "add", "a": 2.0, "b": 3.0}),
+                )],
+            ),
+            finish_reason: Some(FinishReason::ToolCalls),
+        }],
+        usage: None,
+        served_by: None,
+    };
+    let scripted = Arc::new(SequenceMockProvider::new(
+        wrap_with_state_machine_responses(vec![
+            tool_call_response,
+            make_stop_response("The sum is 5."),
+        ]),
+    ));
+
+    let dir = tempfile::tempdir().unwrap();
+    let cassette = dir.path().join("echo_run.jsonl");
+
+    let run = |provider: Arc<dyn ModelProvider>| async move {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(CalculatorTool::new()));
+        let config = AgentConfig {
+            max_iterations: 20,
+            verbose: false,
+            system_prompt: "You are a helpful assistant.".to_string(),
+            model_name: "qwen3:0.6b".to_string(),
+            ..Default::default()
+        };
+        let context = AgentContext {
+            user_prompt: "Add 2 and 3".to_string(),
+            conversation_history: vec![ChatMessage::user("Add 2 and 3")],
+            app_state_id: "cassette_test".to_string(),
+        };
+        AgentLoop::with_tools(config, InMemoryEntityStore::new(), provider, registry)
+            .run(context)
+            .await
+            .unwrap()
+    };
+
+    let recorded = run(Arc::new(
+        RecordingProvider::new(scripted, &cassette).unwrap(),
+    ))
+    .await;
+
+    let replay = Arc::new(ReplayProvider::from_file(&cassette).unwrap().strict());
+    let replayed = run(replay.clone()).await;
+
+    assert!(replayed.task_completed);
+    assert_eq!(replay.remaining(), 0);
+    assert_eq!(replayed.result_summary, recorded.result_summary);
+    assert_eq!(replayed.tool_calls_made.len(), 1);
+    assert!(replayed.tool_calls_made[0].result.contains("5"));
+}
+
 #[tokio::test]
 async fn test_agent_loop_multi_tool_integration() {
     let multi_tool_response = ChatResponse {
@@ -1530,6 +1598,7 @@ async fn test_agent_loop_multi_tool_integration() {
             finish_reason: Some(FinishReason::ToolCalls),
         }],
         usage: None,
+        served_by: None,
     };
     let stop_response = make_stop_response("Both tools executed.");
 
@@ -1545,6 +1614,7 @@ async fn test_agent_loop_multi_tool_integration() {
         verbose: false,
         system_prompt: "You are a helpful assistant.".to_string(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -1589,6 +1659,7 @@ async fn test_agent_loop_error_recovery_integration() {
             finish_reason: Some(FinishReason::ToolCalls),
         }],
         usage: None,
+        served_by: None,
     };
     let stop_response = make_stop_response("Recovered from error.");
 
@@ -1603,6 +1674,7 @@ async fn test_agent_loop_error_recovery_integration() {
         verbose: false,
         system_prompt: "You are a helpful assistant.".to_string(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -1644,6 +1716,7 @@ async fn test_context_entity_stored_after_agent_run() {
             finish_reason: Some(FinishReason::ToolCalls),
         }],
         usage: None,
+        served_by: None,
     };
     let stop_response = make_stop_response("Context entity stored.");
 
@@ -1658,6 +1731,7 @@ async fn test_context_entity_stored_after_agent_run() {
         verbose: false,
         system_prompt: "You are a helpful assistant.".to_string(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -1803,6 +1877,7 @@ async fn test_e2e_agent_with_containerized_ollama() {
         verbose: true,
         system_prompt: "You are a helpful assistant. Use the echo tool when asked to echo something. After using the tool, respond with a brief summary.".to_string(),
         model_name: E2E_MODEL.to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -2150,6 +2225,7 @@ async fn test_state_machine_follows_architecture_with_tools() {
             finish_reason: Some(FinishReason::ToolCalls),
         }],
         usage: None,
+        served_by: None,
     };
 
     let provider = Arc::new(SequenceMockProvider::new(
@@ -2167,6 +2243,7 @@ async fn test_state_machine_follows_architecture_with_tools() {
         verbose: true,
         system_prompt: "You are a helpful assistant.".to_string(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -2221,17 +2298,17 @@ async fn test_state_machine_query_loop() {
     // Decision returns QUERY first, then PROCEED to re-plan
     let provider = Arc::new(SequenceMockProvider::new(vec![
         // Enrich: no LLM
-        make_stop_response("Plan: do the task"), // PlanningEntityModification
-        make_stop_response("First action done."), // PerformingEntityModification
+        make_stop_response(r#"{"plan": "do the task"}"#), // PlanningEntityModification
+        make_stop_response("First action done."),         // PerformingEntityModification
         // Update: no LLM
-        make_stop_response("INCOMPLETE"), // CheckingTaskCompletion
-        make_stop_response("QUERY - need more context"), // EntityModificationDecision: QUERY
+        make_stop_response(r#"{"status": "INCOMPLETE", "reasoning": "not yet"}"#), // CheckingTaskCompletion
+        make_stop_response(r#"{"decision": "QUERY", "reasoning": "need more context"}"#), // EntityModificationDecision: QUERY
         // QueryingEntities: no LLM, loops back to EntityModificationDecision
-        make_stop_response("PROCEED - ready to re-plan"), // EntityModificationDecision: PROCEED
-        make_stop_response("Revised plan after query"),   // PlanningEntityModification (2nd)
-        make_stop_response("Performed the action."),      // PerformingEntityModification (2nd)
+        make_stop_response(r#"{"decision": "PROCEED", "reasoning": "ready to re-plan"}"#), // EntityModificationDecision: PROCEED
+        make_stop_response(r#"{"plan": "revised after query"}"#), // PlanningEntityModification (2nd)
+        make_stop_response("Performed the action."), // PerformingEntityModification (2nd)
         // Update: no LLM
-        make_stop_response("COMPLETE - task done"), // CheckingTaskCompletion
+        make_stop_response(r#"{"status": "COMPLETE", "reasoning": "task done"}"#), // CheckingTaskCompletion
     ]));
 
     let mut registry = ToolRegistry::new();
@@ -2242,6 +2319,7 @@ async fn test_state_machine_query_loop() {
         verbose: true,
         system_prompt: String::new(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -2279,15 +2357,15 @@ async fn test_state_machine_multi_perform_iterations() {
     // Completion check returns INCOMPLETE after first perform, then COMPLETE after second
     let provider = Arc::new(SequenceMockProvider::new(vec![
         // Enrich: no LLM
-        make_stop_response("Plan: multi-step task"), // PlanningEntityModification
-        make_stop_response("First action done."),    // PerformingEntityModification
+        make_stop_response(r#"{"plan": "multi-step task"}"#), // PlanningEntityModification
+        make_stop_response("First action done."),             // PerformingEntityModification
         // Update: no LLM
-        make_stop_response("INCOMPLETE - more work"), // CheckingTaskCompletion
-        make_stop_response("PROCEED"),                // EntityModificationDecision
-        make_stop_response("Plan: second step"),      // PlanningEntityModification (2nd)
-        make_stop_response("Second action done."),    // PerformingEntityModification (2nd)
+        make_stop_response(r#"{"status": "INCOMPLETE", "reasoning": "more work"}"#), // CheckingTaskCompletion
+        make_stop_response(r#"{"decision": "PROCEED", "reasoning": "one step left"}"#), // EntityModificationDecision
+        make_stop_response(r#"{"plan": "second step"}"#), // PlanningEntityModification (2nd)
+        make_stop_response("Second action done."),        // PerformingEntityModification (2nd)
         // Update: no LLM
-        make_stop_response("COMPLETE"), // CheckingTaskCompletion
+        make_stop_response(r#"{"status": "COMPLETE", "reasoning": "done"}"#), // CheckingTaskCompletion
     ]));
 
     let mut registry = ToolRegistry::new();
@@ -2298,6 +2376,7 @@ async fn test_state_machine_multi_perform_iterations() {
         verbose: true,
         system_prompt: String::new(),
         model_name: "test-model".to_string(),
+        ..Default::default()
     };
 
     let context = AgentContext {
@@ -0,0 +1,187 @@
+//! Model management
+//!
+//! Backends that host their own models can download, describe, delete and
+//! preload them through [`ModelAdmin`]. Chat providers expose it through
+//! [`ModelProvider::as_admin`](crate::provider::ModelProvider::as_admin), so
+//! callers need neither the backend's own CLI nor a shell inside its
+//! container.
+
+use crate::capabilities::ModelCapabilities;
+use crate::provider::{ModelError, ModelResult};
+use crate::types::KeepAlive;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+/// One status update while a model downloads.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PullProgress {
+    /// What the server is doing, such as "pulling manifest" or "success"
+    pub status: String,
+    /// Layer being downloaded, if the update is about one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Size of that layer in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Bytes of that layer downloaded so far
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+impl PullProgress {
+    /// Share of the current layer downloaded, between 0.0 and 1.0.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => {
+                Some((completed as f64 / total as f64).min(1.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this is the final update of a successful pull.
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+/// Boxed stream of download progress.
+pub type PullStream = Pin<Box<dyn Stream<Item = ModelResult<PullProgress>> + Send>>;
+
+/// What the server knows about an installed model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelDetails {
+    pub name: String,
+    /// Architecture family, such as "qwen3" or "llama"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    /// Human-readable parameter count, such as "8.2B"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameter_size: Option<String>,
+    /// Weight quantization, such as "Q4_K_M"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<String>,
+    /// File format of the weights, such as "gguf"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<String>,
+    #[serde(default)]
+    pub capabilities: ModelCapabilities,
+    /// Prompt template the server applies to chat messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+#[async_trait]
+pub trait ModelAdmin: Send + Sync {
+    /// Start downloading `model`, yielding progress until it is installed.
+    async fn pull(&self, model: &str) -> ModelResult<PullStream>;
+
+    /// Describe an installed model, or fail with
+    /// [`ModelError::ModelNotFound`].
+    async fn show(&self, model: &str) -> ModelResult<ModelDetails>;
+
+    /// Remove an installed model, or fail with [`ModelError::ModelNotFound`].
+    async fn delete(&self, model: &str) -> ModelResult<()>;
+
+    /// Load `model` into memory now and keep it there for `keep_alive`, so
+    /// the first real request does not pay the load time.
+    async fn warm(&self, model: &str, keep_alive: KeepAlive) -> ModelResult<()>;
+}
+
+/// Pull `model` to completion, invoking `on_progress` for every update.
+pub async fn pull_model<F>(
+    admin: &dyn ModelAdmin,
+    model: &str,
+    mut on_progress: F,
+) -> ModelResult<()>
+where
+    F: FnMut(&PullProgress),
+{
+    let mut stream = admin.pull(model).await?;
+    let mut succeeded = false;
+    while let Some(progress) = stream.next().await {
+        let progress = progress?;
+        on_progress(&progress);
+        succeeded |= progress.is_success();
+    }
+    if succeeded {
+        Ok(())
+    } else {
+        Err(ModelError::Unknown {
+            message: format!("Pull of {} ended without success", model),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ScriptedAdmin(Vec<PullProgress>);
+
+    #[async_trait]
+    impl ModelAdmin for ScriptedAdmin {
+        async fn pull(&self, _model: &str) -> ModelResult<PullStream> {
+            let updates: Vec<ModelResult<PullProgress>> = self.0.iter().cloned().map(Ok).collect();
+            Ok(Box::pin(futures::stream::iter(updates)))
+        }
+
+        async fn show(&self, model: &str) -> ModelResult<ModelDetails> {
+            Err(ModelError::ModelNotFound {
+                model: model.to_string(),
+            })
+        }
+
+        async fn delete(&self, _model: &str) -> ModelResult<()> {
+            Ok(())
+        }
+
+        async fn warm(&self, _model: &str, _keep_alive: KeepAlive) -> ModelResult<()> {
+            Ok(())
+        }
+    }
+
+    fn status(status: &str) -> PullProgress {
+        PullProgress {
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pull_progress_fraction() {
+        let progress = PullProgress {
+            total: Some(200),
+            completed: Some(50),
+            ..status("pulling abc")
+        };
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(status("pulling manifest").fraction(), None);
+        let empty = PullProgress {
+            total: Some(0),
+            completed: Some(0),
+            ..status("pulling abc")
+        };
+        assert_eq!(empty.fraction(), None);
+    }
+
+    #[tokio::test]
+    async fn test_pull_model_requires_success() {
+        let admin = ScriptedAdmin(vec![status("pulling manifest"), status("success")]);
+        let mut seen = Vec::new();
+        pull_model(&admin, "qwen3:0.6b", |p| seen.push(p.status.clone()))
+            .await
+            .unwrap();
+        assert_eq!(seen, ["pulling manifest", "success"]);
+
+        let admin = ScriptedAdmin(vec![status("pulling manifest")]);
+        let err = pull_model(&admin, "qwen3:0.6b", |_| {}).await.unwrap_err();
+        assert!(err.to_string().contains("without success"));
+    }
+}
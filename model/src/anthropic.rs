@@ -0,0 +1,755 @@
+use crate::config::AnthropicConfig;
+use crate::provider::{cancellable, ModelError, ModelProvider, ModelResult};
+use crate::reasoning::merge_reasoning;
+use crate::types::{
+    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, FunctionCall, MessageRole,
+    ModelInfo, ToolCall, ToolChoice, ToolDefinition, Usage,
+};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::{debug, error, info};
+
+/// Provider for the Anthropic Messages API (`/v1/messages`), used for hosted
+/// secondary models.
+///
+/// System messages are sent as the top-level `system` prompt, tool calls as
+/// `tool_use` blocks and tool responses as `tool_result` blocks in a user
+/// turn. Consecutive messages that map to the same role are merged, since
+/// the API requires user and assistant turns to alternate.
+pub struct AnthropicProvider {
+    config: AnthropicConfig,
+    http_client: reqwest::Client,
+    base_url: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: AnthropicConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+
+        let trimmed = config.base_url.trim_end_matches('/');
+        let base_url = if trimmed.ends_with("/v1") {
+            trimmed.to_string()
+        } else {
+            format!("{}/v1", trimmed)
+        };
+
+        let http_client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| ModelError::Unknown {
+                message: format!("Failed to build HTTP client: {}", e),
+            })?;
+
+        Ok(Self {
+            config,
+            http_client,
+            base_url,
+        })
+    }
+
+    pub fn with_default_config() -> ModelResult<Self> {
+        Self::new(AnthropicConfig::default())
+    }
+
+    fn authorize(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let builder = builder.header("anthropic-version", &self.config.api_version);
+        match &self.config.api_key {
+            Some(key) => builder.header("x-api-key", key),
+            None => builder,
+        }
+    }
+
+    /// Split `messages` into the system prompt and alternating user and
+    /// assistant turns made of content blocks.
+    fn messages_to_json(messages: &[ChatMessage]) -> ModelResult<(Option<String>, Vec<Value>)> {
+        let mut system: Vec<&str> = Vec::new();
+        let mut turns: Vec<(&'static str, Vec<Value>)> = Vec::new();
+
+        for msg in messages {
+            let (role, blocks) = match msg.role {
+                MessageRole::System => {
+                    if let Some(content) = msg.content.as_deref().filter(|c| !c.is_empty()) {
+                        system.push(content);
+                    }
+                    continue;
+                }
+                MessageRole::User => {
+                    let mut blocks = Self::text_blocks(msg);
+                    blocks.extend(Self::image_blocks(msg)?);
+                    ("user", blocks)
+                }
+                MessageRole::Assistant => {
+                    let mut blocks = Self::thinking_blocks(msg);
+                    blocks.extend(Self::text_blocks(msg));
+                    for call in msg.tool_calls.iter().flatten() {
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.function.name,
+                            "input": Self::tool_input(&call.function.arguments),
+                        }));
+                    }
+                    ("assistant", blocks)
+                }
+                MessageRole::Tool => {
+                    let text = msg.content.as_deref().unwrap_or_default();
+                    let images = Self::image_blocks(msg)?;
+                    let content = if images.is_empty() {
+                        Value::String(text.to_string())
+                    } else {
+                        let mut blocks = Self::text_blocks(msg);
+                        blocks.extend(images);
+                        Value::Array(blocks)
+                    };
+                    (
+                        "user",
+                        vec![json!({
+                            "type": "tool_result",
+                            "tool_use_id": msg.tool_call_id.as_deref().unwrap_or_default(),
+                            "content": content,
+                        })],
+                    )
+                }
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            match turns.last_mut() {
+                Some((last_role, last_blocks)) if *last_role == role => {
+                    last_blocks.extend(blocks);
+                }
+                _ => turns.push((role, blocks)),
+            }
+        }
+
+        let system = (!system.is_empty()).then(|| system.join("\n\n"));
+        let turns = turns
+            .into_iter()
+            .map(|(role, content)| json!({"role": role, "content": content}))
+            .collect();
+        Ok((system, turns))
+    }
+
+    fn text_blocks(msg: &ChatMessage) -> Vec<Value> {
+        msg.content
+            .as_deref()
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| vec![json!({"type": "text", "text": text})])
+            .unwrap_or_default()
+    }
+
+    /// Signed thinking goes back as it came, which the API requires before
+    /// the tool calls of the turn that produced it. Unsigned reasoning,
+    /// such as another backend's, is not sent.
+    fn thinking_blocks(msg: &ChatMessage) -> Vec<Value> {
+        match (&msg.reasoning, &msg.reasoning_signature) {
+            (Some(thinking), Some(signature)) => vec![json!({
+                "type": "thinking",
+                "thinking": thinking,
+                "signature": signature,
+            })],
+            _ => Vec::new(),
+        }
+    }
+
+    fn image_blocks(msg: &ChatMessage) -> ModelResult<Vec<Value>> {
+        msg.images
+            .iter()
+            .map(|image| {
+                let encoded = image.encode()?;
+                Ok(json!({
+                    "type": "image",
+                    "source": {
+                        "type": "base64",
+                        "media_type": encoded.media_type,
+                        "data": encoded.data,
+                    },
+                }))
+            })
+            .collect()
+    }
+
+    /// `tool_use` input must be an object; arguments that are not one are
+    /// parsed if they are a JSON string and dropped otherwise.
+    fn tool_input(arguments: &Value) -> Value {
+        match arguments {
+            Value::Object(_) => arguments.clone(),
+            Value::String(s) => match serde_json::from_str(s) {
+                Ok(Value::Object(map)) => Value::Object(map),
+                _ => Value::Object(Map::new()),
+            },
+            _ => Value::Object(Map::new()),
+        }
+    }
+
+    fn tools_to_json(tools: &[ToolDefinition]) -> Vec<Value> {
+        tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.function.name,
+                    "description": tool.function.description,
+                    "input_schema": tool.function.parameters,
+                })
+            })
+            .collect()
+    }
+
+    fn tool_choice_to_json(choice: &ToolChoice) -> Value {
+        match choice {
+            ToolChoice::Auto => json!({"type": "auto"}),
+            ToolChoice::None => json!({"type": "none"}),
+            ToolChoice::Required => json!({"type": "any"}),
+            ToolChoice::Specific(name) => json!({"type": "tool", "name": name}),
+        }
+    }
+
+    fn build_payload(&self, request: &ChatRequest) -> ModelResult<Value> {
+        let (system, messages) = Self::messages_to_json(&request.messages)?;
+        let max_tokens = request.max_tokens.unwrap_or(self.config.default_max_tokens);
+
+        let mut payload = json!({
+            "model": request.model,
+            "messages": messages,
+            "max_tokens": max_tokens,
+        });
+
+        if let Some(system) = system {
+            payload["system"] = Value::String(system);
+        }
+
+        // Thinking counts against `max_tokens` and does not accept a
+        // temperature, so it gets its own budget on top of the reply.
+        if request.think == Some(true) {
+            let budget = self.config.thinking_budget;
+            payload["max_tokens"] = Value::from(max_tokens.saturating_add(budget));
+            payload["thinking"] = json!({"type": "enabled", "budget_tokens": budget});
+        } else {
+            let temperature = request
+                .temperature
+                .unwrap_or(self.config.default_temperature)
+                .clamp(0.0, 1.0);
+            payload["temperature"] = Value::from(temperature);
+            if let Some(top_p) = request.top_p {
+                payload["top_p"] = Value::from(top_p);
+            }
+            if let Some(top_k) = request.top_k {
+                payload["top_k"] = Value::from(top_k);
+            }
+        }
+
+        if !request.stop.is_empty() {
+            payload["stop_sequences"] = Value::from(request.stop.clone());
+        }
+
+        if let Some(tools) = &request.tools {
+            if !tools.is_empty() {
+                payload["tools"] = Value::Array(Self::tools_to_json(tools));
+                // Thinking only allows the model to choose whether to call a tool
+                let forced = matches!(
+                    request.tool_choice,
+                    Some(ToolChoice::Required | ToolChoice::Specific(_))
+                );
+                if let Some(choice) = &request.tool_choice {
+                    if request.think == Some(true) && forced {
+                        debug!(
+                            "Thinking cannot force a tool call; leaving the choice to the model"
+                        );
+                    } else {
+                        payload["tool_choice"] = Self::tool_choice_to_json(choice);
+                    }
+                }
+            }
+        }
+
+        if request.response_format.is_some() {
+            debug!("Messages API has no JSON mode; relying on the prompt for JSON output");
+        }
+
+        Ok(payload)
+    }
+
+    fn parse_stop_reason(reason: Option<&str>, has_tool_calls: bool) -> FinishReason {
+        match reason {
+            Some("tool_use") => FinishReason::ToolCalls,
+            Some("max_tokens") => FinishReason::Length,
+            Some("refusal") => FinishReason::ContentFilter,
+            _ if has_tool_calls => FinishReason::ToolCalls,
+            _ => FinishReason::Stop,
+        }
+    }
+
+    fn parse_raw_response(raw: AnthropicRawResponse) -> ModelResult<ChatResponse> {
+        let mut text = String::new();
+        let mut thinking = String::new();
+        let mut signatures = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        for block in raw.content {
+            match block {
+                AnthropicRawBlock::Text { text: part } => text.push_str(&part),
+                AnthropicRawBlock::Thinking {
+                    thinking: part,
+                    signature,
+                } => {
+                    if !thinking.is_empty() {
+                        thinking.push_str("\n\n");
+                    }
+                    thinking.push_str(&part);
+                    signatures.push(signature);
+                }
+                AnthropicRawBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    function: FunctionCall {
+                        name,
+                        arguments: input,
+                    },
+                }),
+                AnthropicRawBlock::Other => {}
+            }
+        }
+
+        let finish_reason =
+            Self::parse_stop_reason(raw.stop_reason.as_deref(), !tool_calls.is_empty());
+        // The signature only vouches for one block as sent, so it is kept
+        // when the reasoning is exactly that block
+        let signed = match signatures.as_slice() {
+            [Some(signature)] => Some((thinking.clone(), signature.clone())),
+            _ => None,
+        };
+        let (reasoning, content) = merge_reasoning(Some(thinking), &text);
+        let reasoning_signature = signed
+            .filter(|(signed, _)| reasoning.as_deref() == Some(signed.as_str()))
+            .map(|(_, signature)| signature);
+
+        let usage = raw.usage.map(|u| {
+            Usage::new(
+                u.input_tokens + u.cache_creation_input_tokens + u.cache_read_input_tokens,
+                u.output_tokens,
+            )
+        });
+
+        Ok(ChatResponse {
+            choices: vec![Choice {
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content,
+                    tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                    tool_call_id: None,
+                    reasoning,
+                    reasoning_signature,
+                    images: Vec::new(),
+                },
+                finish_reason: Some(finish_reason),
+            }],
+            usage,
+            served_by: None,
+        })
+    }
+
+    /// Errors arrive as `{"type": "error", "error": {"type", "message"}}`;
+    /// the message is used when present.
+    fn status_to_error(status: StatusCode, body: String, model: &str) -> ModelError {
+        let message = serde_json::from_str::<AnthropicRawError>(&body)
+            .map(|e| format!("{}: {}", e.error.error_type, e.error.message))
+            .unwrap_or(body);
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ModelError::Authentication,
+            StatusCode::TOO_MANY_REQUESTS => ModelError::RateLimit,
+            StatusCode::NOT_FOUND => ModelError::ModelNotFound {
+                model: model.to_string(),
+            },
+            // 529 is the API's "overloaded" status
+            s if s.is_server_error() || s.as_u16() == 529 => ModelError::ServiceUnavailable {
+                message: format!("Anthropic API error {}: {}", s, message),
+            },
+            s => ModelError::Unknown {
+                message: format!("Anthropic API error {}: {}", s, message),
+            },
+        }
+    }
+
+    fn handle_reqwest_error(&self, e: reqwest::Error) -> ModelError {
+        if e.is_timeout() {
+            ModelError::Timeout {
+                elapsed: self.config.timeout,
+            }
+        } else if e.is_connect() {
+            ModelError::ServiceUnavailable {
+                message: "Cannot connect to Anthropic API".to_string(),
+            }
+        } else {
+            ModelError::Unknown {
+                message: format!("Network error: {}", e),
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicRawResponse {
+    content: Vec<AnthropicRawBlock>,
+    stop_reason: Option<String>,
+    usage: Option<AnthropicRawUsage>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicRawBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: Option<String>,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    /// Redacted thinking and block types added after this was written
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicRawUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct AnthropicRawError {
+    error: AnthropicRawErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct AnthropicRawErrorDetail {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelList {
+    data: Vec<AnthropicModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelEntry {
+    id: String,
+    created_at: Option<String>,
+}
+
+#[async_trait]
+impl ModelProvider for AnthropicProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        debug!("Starting chat request with model: {}", request.model);
+
+        let payload = self.build_payload(&request)?;
+        let url = format!("{}/messages", self.base_url);
+
+        let raw: AnthropicRawResponse = cancellable(request.cancellation.as_ref(), async {
+            let response = self
+                .authorize(self.http_client.post(&url))
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| self.handle_reqwest_error(e))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(Self::status_to_error(status, body, &request.model));
+            }
+
+            response.json().await.map_err(|e| ModelError::Unknown {
+                message: format!("Failed to parse response: {}", e),
+            })
+        })
+        .await?;
+
+        info!("Chat request completed successfully");
+
+        Self::parse_raw_response(raw)
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        debug!("Listing available models");
+
+        let url = format!("{}/models", self.base_url);
+        let response = self
+            .authorize(self.http_client.get(&url))
+            .send()
+            .await
+            .map_err(|e| self.handle_reqwest_error(e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Self::status_to_error(status, body, ""));
+        }
+
+        let list: AnthropicModelList = response.json().await.map_err(|e| ModelError::Unknown {
+            message: format!("Failed to parse model list: {}", e),
+        })?;
+
+        let model_infos: Vec<ModelInfo> = list
+            .data
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model.id,
+                size: None,
+                digest: None,
+                modified_at: model.created_at,
+            })
+            .collect();
+
+        info!("Retrieved {} models", model_infos.len());
+        Ok(model_infos)
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        debug!("Performing health check");
+
+        match self.list_models().await {
+            Ok(_) => {
+                info!("Health check passed");
+                Ok(())
+            }
+            Err(e) => {
+                error!("Health check failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "anthropic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionDefinition, ImageAttachment, JsonSchema, SchemaType};
+
+    fn call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments,
+            },
+        }
+    }
+
+    #[test]
+    fn test_base_url_normalization() {
+        let provider = AnthropicProvider::with_default_config().unwrap();
+        assert_eq!(provider.base_url, "https://api.anthropic.com/v1");
+
+        let provider = AnthropicProvider::new(
+            AnthropicConfig::default().with_base_url("http://127.0.0.1:9000/v1/"),
+        )
+        .unwrap();
+        assert_eq!(provider.base_url, "http://127.0.0.1:9000/v1");
+    }
+
+    #[test]
+    fn test_messages_separate_system_and_merge_tool_results() {
+        let messages = vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::system("Use tools."),
+            ChatMessage::user("Read both files"),
+            ChatMessage::assistant_with_tools(
+                Some("Reading.".to_string()),
+                vec![
+                    call("toolu_1", "read_file", json!({"path": "a"})),
+                    call("toolu_2", "read_file", json!("{\"path\": \"b\"}")),
+                ],
+            ),
+            ChatMessage::tool_response("toolu_1", "A"),
+            ChatMessage::tool_response("toolu_2", "B"),
+            ChatMessage::user("Summarize"),
+        ];
+
+        let (system, turns) = AnthropicProvider::messages_to_json(&messages).unwrap();
+
+        assert_eq!(system.as_deref(), Some("Be brief.\n\nUse tools."));
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[0]["role"], "user");
+        assert_eq!(turns[1]["role"], "assistant");
+        assert_eq!(turns[1]["content"][0]["text"], "Reading.");
+        assert_eq!(turns[1]["content"][1]["type"], "tool_use");
+        assert_eq!(turns[1]["content"][2]["input"]["path"], "b");
+        let results = turns[2]["content"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["type"], "tool_result");
+        assert_eq!(results[0]["tool_use_id"], "toolu_1");
+        assert_eq!(results[1]["content"], "B");
+        assert_eq!(results[2]["text"], "Summarize");
+    }
+
+    #[test]
+    fn test_images_become_image_blocks() {
+        let messages = vec![
+            ChatMessage::user("Describe").with_image(ImageAttachment::base64("/9j/4AAQSkZJRg==")),
+            ChatMessage::tool_response("toolu_1", "rendered")
+                .with_image(ImageAttachment::base64("iVBORw0KGgo=")),
+        ];
+
+        let (_, turns) = AnthropicProvider::messages_to_json(&messages).unwrap();
+
+        let blocks = turns[0]["content"].as_array().unwrap();
+        assert_eq!(blocks[1]["type"], "image");
+        assert_eq!(blocks[1]["source"]["media_type"], "image/jpeg");
+        let result = &blocks[2]["content"];
+        assert_eq!(result[0]["text"], "rendered");
+        assert_eq!(result[1]["source"]["media_type"], "image/png");
+    }
+
+    #[test]
+    fn test_build_payload() {
+        let provider = AnthropicProvider::with_default_config().unwrap();
+        let tool = ToolDefinition {
+            function: FunctionDefinition {
+                name: "echo".to_string(),
+                description: "Echo".to_string(),
+                parameters: JsonSchema {
+                    schema_type: SchemaType::Object,
+                    ..Default::default()
+                },
+            },
+        };
+        let request = ChatRequest::new("claude-x", vec![ChatMessage::user("hi")])
+            .with_tools(vec![tool])
+            .with_temperature(1.6)
+            .with_top_k(40)
+            .with_stop(["</answer>"]);
+
+        let payload = provider.build_payload(&request).unwrap();
+
+        assert_eq!(payload["max_tokens"], 4096);
+        assert_eq!(payload["temperature"], 1.0);
+        assert!(payload.get("system").is_none());
+        assert_eq!(payload["tools"][0]["name"], "echo");
+        assert_eq!(payload["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(payload["tool_choice"]["type"], "auto");
+        assert_eq!(payload["top_k"], 40);
+        assert_eq!(payload["stop_sequences"][0], "</answer>");
+
+        let mut thinking = request;
+        thinking.think = Some(true);
+        let payload = provider.build_payload(&thinking).unwrap();
+        assert_eq!(payload["thinking"]["budget_tokens"], 2048);
+        assert_eq!(payload["max_tokens"], 4096 + 2048);
+        assert!(payload.get("temperature").is_none());
+        assert!(payload.get("top_k").is_none());
+        assert_eq!(payload["tool_choice"]["type"], "auto");
+
+        thinking.tool_choice = Some(ToolChoice::Specific("echo".to_string()));
+        let payload = provider.build_payload(&thinking).unwrap();
+        assert!(payload.get("tool_choice").is_none());
+    }
+
+    #[test]
+    fn test_tool_choice_to_json() {
+        assert_eq!(
+            AnthropicProvider::tool_choice_to_json(&ToolChoice::Required)["type"],
+            "any"
+        );
+        let specific =
+            AnthropicProvider::tool_choice_to_json(&ToolChoice::Specific("echo".to_string()));
+        assert_eq!(specific, json!({"type": "tool", "name": "echo"}));
+    }
+
+    #[test]
+    fn test_parse_stop_reasons() {
+        let cases = [
+            (Some("end_turn"), false, FinishReason::Stop),
+            (Some("stop_sequence"), false, FinishReason::Stop),
+            (Some("tool_use"), true, FinishReason::ToolCalls),
+            (Some("max_tokens"), false, FinishReason::Length),
+            (Some("refusal"), false, FinishReason::ContentFilter),
+            (None, true, FinishReason::ToolCalls),
+        ];
+        for (reason, has_tools, expected) in cases {
+            assert_eq!(
+                AnthropicProvider::parse_stop_reason(reason, has_tools),
+                expected,
+                "{:?}",
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_response_blocks() {
+        let raw: AnthropicRawResponse = serde_json::from_value(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                {"type": "thinking", "thinking": "Need the file.", "signature": "sig"},
+                {"type": "redacted_thinking", "data": "..."},
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "id": "toolu_9", "name": "read_file", "input": {"path": "x"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 7, "cache_read_input_tokens": 5}
+        }))
+        .unwrap();
+
+        let response = AnthropicProvider::parse_raw_response(raw).unwrap();
+
+        let message = &response.choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("Let me look."));
+        assert_eq!(message.reasoning.as_deref(), Some("Need the file."));
+        assert_eq!(message.reasoning_signature.as_deref(), Some("sig"));
+        let calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "toolu_9");
+        assert_eq!(calls[0].function.arguments["path"], "x");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 15);
+        assert_eq!(usage.total_tokens, 22);
+    }
+
+    #[test]
+    fn test_status_to_error_mapping() {
+        let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        match AnthropicProvider::status_to_error(
+            StatusCode::from_u16(529).unwrap(),
+            body.to_string(),
+            "m",
+        ) {
+            ModelError::ServiceUnavailable { message } => {
+                assert!(
+                    message.contains("overloaded_error: Overloaded"),
+                    "{}",
+                    message
+                )
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(matches!(
+            AnthropicProvider::status_to_error(StatusCode::UNAUTHORIZED, String::new(), "m"),
+            ModelError::Authentication
+        ));
+        assert!(matches!(
+            AnthropicProvider::status_to_error(StatusCode::NOT_FOUND, String::new(), "m"),
+            ModelError::ModelNotFound { .. }
+        ));
+    }
+}
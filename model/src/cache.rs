@@ -0,0 +1,538 @@
+//! Response caching
+//!
+//! [`CachingProvider`] answers repeated requests from memory (and optionally
+//! from disk) instead of calling the wrapped provider again. Requests are
+//! stored in the canonical form used for cassette keys, but with whitespace
+//! kept, and a hit must match the stored request exactly rather than just its
+//! hash. Which requests may be cached is decided by the configured
+//! [`CachePolicy`].
+//!
+//! A hit costs no tokens, so it reports zero usage; the stored entry keeps the
+//! usage of the call that produced it.
+
+use crate::admin::ModelAdmin;
+use crate::capabilities::ModelCapabilities;
+use crate::cassette::{canonical_request, fnv1a};
+use crate::config::{CacheConfig, CachePolicy};
+use crate::embedding::EmbeddingProvider;
+use crate::provider::{ModelError, ModelProvider, ModelResult, StreamingModelProvider};
+use crate::stream::{single_chunk_stream, ChatStream, StreamAccumulator};
+use crate::types::{ChatRequest, ChatResponse, ModelInfo, Usage};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// Hit/miss counters for a [`CachingProvider`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Requests the policy did not allow to be cached
+    pub bypassed: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Where a request is stored, and the request itself to check hits against.
+#[derive(Debug, Clone)]
+struct CacheKey {
+    hash: String,
+    request: String,
+}
+
+impl CacheKey {
+    fn new(request: &ChatRequest) -> Self {
+        let request = canonical_request(request);
+        Self {
+            hash: format!("{:016x}", fnv1a(request.as_bytes())),
+            request,
+        }
+    }
+}
+
+/// A response together with the canonical request that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    request: String,
+    response: ChatResponse,
+}
+
+/// Least-recently-used map from request hash to entry.
+#[derive(Debug)]
+struct LruStore {
+    capacity: usize,
+    entries: HashMap<String, CacheEntry>,
+    order: VecDeque<String>,
+}
+
+impl LruStore {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(pos).expect("position is in range");
+            self.order.push_back(key);
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.get(key).cloned()?;
+        self.touch(key);
+        Some(entry)
+    }
+
+    fn insert(&mut self, key: String, entry: CacheEntry) {
+        if self.entries.insert(key.clone(), entry).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// Memory and disk tiers shared with in-flight streams.
+#[derive(Debug)]
+struct CacheStore {
+    memory: Mutex<LruStore>,
+    disk_dir: Option<PathBuf>,
+}
+
+impl CacheStore {
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        self.disk_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", key)))
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<ChatResponse> {
+        let cached = self.memory.lock().unwrap().get(&key.hash);
+        let entry = match cached {
+            Some(entry) => entry,
+            None => self.read_disk(key)?,
+        };
+        if entry.request != key.request {
+            debug!("Cache entry {} belongs to another request", key.hash);
+            return None;
+        }
+        Some(entry.response)
+    }
+
+    fn read_disk(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let path = self.disk_path(&key.hash)?;
+        let bytes = std::fs::read(&path).ok()?;
+        match serde_json::from_slice::<CacheEntry>(&bytes) {
+            Ok(entry) => {
+                self.memory
+                    .lock()
+                    .unwrap()
+                    .insert(key.hash.clone(), entry.clone());
+                Some(entry)
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable cache entry {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn insert(&self, key: &CacheKey, response: &ChatResponse) {
+        let entry = CacheEntry {
+            request: key.request.clone(),
+            response: response.clone(),
+        };
+        if let Some(path) = self.disk_path(&key.hash) {
+            let written = serde_json::to_vec(&entry)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| write_atomically(&path, &bytes).map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                warn!("Failed to persist cache entry {}: {}", path.display(), e);
+            }
+        }
+        self.memory.lock().unwrap().insert(key.hash.clone(), entry);
+    }
+}
+
+/// Write through a temporary file in the same directory and rename it into
+/// place, so a concurrent reader or a crash never sees a partial entry.
+fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+    let temp = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = std::fs::write(&temp, bytes).and_then(|_| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Zero the usage of a response served from the cache.
+fn as_hit(mut response: ChatResponse) -> ChatResponse {
+    if response.usage.is_some() {
+        response.usage = Some(Usage::default());
+    }
+    response
+}
+
+/// Wraps a provider and serves identical requests from a cache.
+pub struct CachingProvider {
+    inner: Arc<dyn ModelProvider>,
+    policy: CachePolicy,
+    store: Arc<CacheStore>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+impl CachingProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>, config: CacheConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+
+        if let Some(dir) = &config.disk_dir {
+            std::fs::create_dir_all(dir).map_err(|e| ModelError::InvalidConfig {
+                message: format!("Cannot create cache directory {}: {}", dir.display(), e),
+            })?;
+        }
+
+        Ok(Self {
+            inner,
+            policy: config.policy,
+            store: Arc::new(CacheStore {
+                memory: Mutex::new(LruStore::new(config.capacity)),
+                disk_dir: config.disk_dir,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            bypassed: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            bypassed: self.bypassed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn is_cacheable(&self, request: &ChatRequest) -> bool {
+        match self.policy {
+            CachePolicy::Always => true,
+            CachePolicy::Deterministic => {
+                request.temperature == Some(0.0) || request.seed.is_some()
+            }
+        }
+    }
+
+    /// Cache key for `request`, or `None` if the policy excludes it.
+    fn lookup_key(&self, request: &ChatRequest) -> Option<CacheKey> {
+        if self.is_cacheable(request) {
+            Some(CacheKey::new(request))
+        } else {
+            self.bypassed.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Option<ChatResponse> {
+        match self.store.get(key) {
+            Some(response) => {
+                debug!("Cache hit for request {}", key.hash);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(as_hit(response))
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for CachingProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        let Some(key) = self.lookup_key(&request) else {
+            return self.inner.chat(request).await;
+        };
+        if let Some(response) = self.lookup(&key) {
+            return Ok(response);
+        }
+
+        let response = self.inner.chat(request).await?;
+        self.store.insert(&key, &response);
+        Ok(response)
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        self.inner.health_check().await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        self.inner.model_capabilities(model).await
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        self.inner
+            .as_streaming()
+            .map(|_| self as &dyn StreamingModelProvider)
+    }
+
+    /// Embeddings are not cached.
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        self.inner.as_embedding()
+    }
+
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        self.inner.as_admin()
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for CachingProvider {
+    /// Hits are replayed as a single chunk; misses stream through and are
+    /// stored once the final chunk arrives.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let streaming = self
+            .inner
+            .as_streaming()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support streaming", self.inner.provider_name()),
+            })?;
+
+        let Some(key) = self.lookup_key(&request) else {
+            return streaming.chat_stream(request).await;
+        };
+        if let Some(response) = self.lookup(&key) {
+            return Ok(single_chunk_stream(response));
+        }
+
+        let stream = streaming.chat_stream(request).await?;
+        let store = Arc::clone(&self.store);
+        let mut accumulator = StreamAccumulator::new();
+        Ok(Box::pin(stream.map(move |item| {
+            if let Ok(chunk) = &item {
+                accumulator.push(chunk);
+                if chunk.finish_reason.is_some() {
+                    store.insert(&key, &std::mem::take(&mut accumulator).finish());
+                }
+            }
+            item
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::collect_stream;
+    use crate::test_support::MockProvider;
+    use crate::types::{ChatMessage, ImageAttachment};
+
+    fn counting() -> Arc<MockProvider> {
+        Arc::new(MockProvider::counting().with_usage(Usage::new(10, 5)))
+    }
+
+    fn request(prompt: &str, temperature: f32) -> ChatRequest {
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user(prompt)])
+            .with_temperature(temperature)
+    }
+
+    fn content(response: &ChatResponse) -> &str {
+        response.choices[0].message.content.as_deref().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_policy_caches_reproducible_requests() {
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), CacheConfig::default()).unwrap();
+
+        let first = cache.chat(request("plan", 0.0)).await.unwrap();
+        let second = cache.chat(request("plan", 0.0)).await.unwrap();
+        assert_eq!(content(&first), "reply 1");
+        assert_eq!(content(&second), "reply 1");
+
+        cache.chat(request("plan", 0.7)).await.unwrap();
+        cache.chat(request("plan", 0.7)).await.unwrap();
+
+        // A fixed seed makes sampling reproducible at any temperature
+        cache.chat(request("plan", 0.7).with_seed(7)).await.unwrap();
+        cache.chat(request("plan", 0.7).with_seed(7)).await.unwrap();
+
+        assert_eq!(inner.requests().len(), 4);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                bypassed: 2
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_lru_evicts_least_recently_used() {
+        let inner = counting();
+        let config = CacheConfig::new()
+            .with_capacity(2)
+            .with_policy(CachePolicy::Always);
+        let cache = CachingProvider::new(inner.clone(), config).unwrap();
+
+        cache.chat(request("a", 0.7)).await.unwrap();
+        cache.chat(request("b", 0.7)).await.unwrap();
+        cache.chat(request("a", 0.7)).await.unwrap(); // a is now most recent
+        cache.chat(request("c", 0.7)).await.unwrap(); // evicts b
+        assert_eq!(inner.requests().len(), 3);
+
+        cache.chat(request("a", 0.7)).await.unwrap();
+        assert_eq!(inner.requests().len(), 3);
+        cache.chat(request("b", 0.7)).await.unwrap();
+        assert_eq!(inner.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_disk_store_survives_new_provider() {
+        let dir = std::env::temp_dir().join(format!("response-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = CacheConfig::new()
+            .with_policy(CachePolicy::Always)
+            .with_disk_dir(&dir);
+
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), config.clone()).unwrap();
+        let first = cache.chat(request("decide", 0.3)).await.unwrap();
+        assert_eq!(first.usage.unwrap().total_tokens, 15);
+
+        // Entries are renamed into place, leaving no temporary files behind
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with(".json"));
+
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), config).unwrap();
+        let response = cache.chat(request("decide", 0.3)).await.unwrap();
+
+        assert_eq!(content(&response), "reply 1");
+        assert_eq!(inner.requests().len(), 0);
+        assert_eq!(cache.stats().hits, 1);
+        // A hit costs nothing
+        assert_eq!(response.usage.unwrap().total_tokens, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hit_requires_the_same_request() {
+        let dir = std::env::temp_dir().join(format!("response-cache-keys-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let image = dir.join("chart.png");
+        let config = CacheConfig::new()
+            .with_policy(CachePolicy::Always)
+            .with_disk_dir(&dir);
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), config.clone()).unwrap();
+
+        // Whitespace is part of the prompt
+        cache.chat(request("plan", 0.7)).await.unwrap();
+        cache.chat(request("plan  ", 0.7)).await.unwrap();
+        assert_eq!(inner.requests().len(), 2);
+
+        // An image file is cached by what it holds
+        let with_image = || {
+            ChatRequest::new(
+                "qwen3-vl",
+                vec![ChatMessage::user("describe").with_image(ImageAttachment::path(&image))],
+            )
+        };
+        std::fs::write(&image, b"\x89PNG first").unwrap();
+        cache.chat(with_image()).await.unwrap();
+        cache.chat(with_image()).await.unwrap();
+        assert_eq!(inner.requests().len(), 3);
+        std::fs::write(&image, b"\x89PNG second").unwrap();
+        cache.chat(with_image()).await.unwrap();
+        assert_eq!(inner.requests().len(), 4);
+
+        // An entry stored under the key for another request is not served
+        let key = CacheKey::new(&request("plan", 0.7));
+        let path = dir.join(format!("{}.json", key.hash));
+        let mut entry: CacheEntry = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        entry.request = CacheKey::new(&request("other", 0.7)).request;
+        std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), config).unwrap();
+        cache.chat(request("plan", 0.7)).await.unwrap();
+        assert_eq!(inner.requests().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streamed_miss_is_cached() {
+        let inner = counting();
+        let cache = CachingProvider::new(inner.clone(), CacheConfig::default()).unwrap();
+        let streaming = cache.as_streaming().unwrap();
+
+        let stream = streaming.chat_stream(request("hi", 0.0)).await.unwrap();
+        let mut deltas = 0;
+        let streamed = collect_stream(stream, |_| deltas += 1).await.unwrap();
+        assert_eq!(deltas, 2);
+
+        let stream = streaming.chat_stream(request("hi", 0.0)).await.unwrap();
+        let replayed = collect_stream(stream, |_| {}).await.unwrap();
+
+        assert_eq!(content(&replayed), content(&streamed));
+        assert_eq!(inner.requests().len(), 1);
+        assert_eq!(
+            cache.chat(request("hi", 0.0)).await.unwrap().choices.len(),
+            1
+        );
+        assert_eq!(inner.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_zero_capacity() {
+        let config = CacheConfig::new().with_capacity(0);
+        assert!(CachingProvider::new(counting(), config).is_err());
+    }
+}
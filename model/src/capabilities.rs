@@ -0,0 +1,367 @@
+//! Per-model capabilities
+//!
+//! Models differ in context window, whether they emit structured tool calls,
+//! whether they think before answering, and which sampling settings their
+//! authors recommend. [`CapabilityRegistry`] answers those questions for a
+//! model name by layering, from lowest to highest precedence:
+//!
+//! 1. built-in defaults per model family (`qwen3`, `llama3.1`, ...),
+//! 2. what the backend reports about the model, via
+//!    [`ModelProvider::model_capabilities`](crate::provider::ModelProvider::model_capabilities),
+//! 3. user overrides from a TOML file, per family and then per model:
+//!
+//! ```toml
+//! [families.qwen3]
+//! context_length = 32768
+//!
+//! [models."qwen3:0.6b"]
+//! tools = false
+//! ```
+
+use crate::provider::{ModelError, ModelProvider, ModelResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// What a model can do. Unset fields are unknown.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelCapabilities {
+    /// Context window in tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    /// Emits structured tool calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<bool>,
+    /// Can produce separate reasoning before the answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<bool>,
+    /// Accepts image inputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vision: Option<bool>,
+    /// Recommended sampling temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Recommended sampling temperature while thinking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_temperature: Option<f32>,
+}
+
+impl ModelCapabilities {
+    /// Overwrite every field that `other` sets.
+    pub fn merge(&mut self, other: &ModelCapabilities) {
+        fn take<T: Clone>(field: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                *field = other.clone();
+            }
+        }
+        take(&mut self.context_length, &other.context_length);
+        take(&mut self.tools, &other.tools);
+        take(&mut self.thinking, &other.thinking);
+        take(&mut self.vision, &other.vision);
+        take(&mut self.temperature, &other.temperature);
+        take(&mut self.thinking_temperature, &other.thinking_temperature);
+    }
+
+    /// Recommended temperature, preferring the thinking one when `thinking`.
+    pub fn recommended_temperature(&self, thinking: bool) -> Option<f32> {
+        if thinking {
+            self.thinking_temperature.or(self.temperature)
+        } else {
+            self.temperature
+        }
+    }
+}
+
+/// User overrides, as read from a capabilities TOML file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CapabilityOverrides {
+    /// Keyed by family, e.g. `qwen3` or `llama3.1`
+    pub families: HashMap<String, ModelCapabilities>,
+    /// Keyed by full model name, e.g. `qwen3:0.6b`
+    pub models: HashMap<String, ModelCapabilities>,
+}
+
+/// Resolves [`ModelCapabilities`] for model names.
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityRegistry {
+    families: HashMap<String, ModelCapabilities>,
+    reported: HashMap<String, ModelCapabilities>,
+    overrides: CapabilityOverrides,
+}
+
+impl CapabilityRegistry {
+    /// An empty registry, without built-in family defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry preloaded with defaults for common local model families.
+    pub fn builtin() -> Self {
+        let family = |context_length, tools, thinking, vision, temperature| ModelCapabilities {
+            context_length: Some(context_length),
+            tools: Some(tools),
+            thinking: Some(thinking),
+            vision: Some(vision),
+            temperature: Some(temperature),
+            thinking_temperature: None,
+        };
+        Self::new()
+            .with_family(
+                "qwen3",
+                ModelCapabilities {
+                    thinking_temperature: Some(0.6),
+                    ..family(40_960, true, true, false, 0.7)
+                },
+            )
+            .with_family("qwen2.5", family(32_768, true, false, false, 0.7))
+            .with_family("qwen2.5-coder", family(32_768, true, false, false, 0.7))
+            .with_family("llama3.1", family(131_072, true, false, false, 0.6))
+            .with_family("llama3.2", family(131_072, true, false, false, 0.6))
+            .with_family("llama3.2-vision", family(131_072, false, false, true, 0.6))
+            .with_family("mistral", family(32_768, true, false, false, 0.7))
+            .with_family("gemma3", family(131_072, false, false, true, 1.0))
+            .with_family(
+                "deepseek-r1",
+                ModelCapabilities {
+                    thinking_temperature: Some(0.6),
+                    ..family(131_072, false, true, false, 0.6)
+                },
+            )
+            .with_family("llava", family(4_096, false, false, true, 0.7))
+    }
+
+    /// Set the defaults for a model family.
+    pub fn with_family(mut self, family: impl Into<String>, caps: ModelCapabilities) -> Self {
+        self.families.insert(family.into().to_lowercase(), caps);
+        self
+    }
+
+    pub fn with_overrides(mut self, overrides: CapabilityOverrides) -> Self {
+        self.overrides.families = overrides
+            .families
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .collect();
+        self.overrides.models = overrides.models;
+        self
+    }
+
+    /// Parse overrides from TOML text; `origin` names the source in errors.
+    pub fn parse_overrides(content: &str, origin: &str) -> ModelResult<CapabilityOverrides> {
+        toml::from_str(content).map_err(|e| ModelError::InvalidConfig {
+            message: format!("{}: {}", origin, e),
+        })
+    }
+
+    /// Load overrides from `path`, if it exists.
+    pub fn load_overrides(mut self, path: &Path) -> ModelResult<Self> {
+        if !path.exists() {
+            return Ok(self);
+        }
+        let content = std::fs::read_to_string(path).map_err(|e| ModelError::InvalidConfig {
+            message: format!("{}: {}", path.display(), e),
+        })?;
+        let overrides = Self::parse_overrides(&content, &path.display().to_string())?;
+        self.overrides.families.extend(
+            overrides
+                .families
+                .into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v)),
+        );
+        self.overrides.models.extend(overrides.models);
+        Ok(self)
+    }
+
+    /// The built-in defaults with the user's overrides from
+    /// [`Self::default_overrides_path`] applied. Overrides that cannot be
+    /// read are logged and skipped.
+    pub fn configured() -> Self {
+        let registry = Self::builtin();
+        let Some(path) = Self::default_overrides_path() else {
+            return registry;
+        };
+        match registry.clone().load_overrides(&path) {
+            Ok(registry) => registry,
+            Err(e) => {
+                warn!("Ignoring model capability overrides: {}", e);
+                registry
+            }
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/nanna/models.toml`, falling back to `~/.config`.
+    pub fn default_overrides_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("nanna").join("models.toml"))
+    }
+
+    /// Record what a backend reported about `model`.
+    pub fn record(&mut self, model: impl Into<String>, caps: ModelCapabilities) {
+        self.reported.insert(model.into(), caps);
+    }
+
+    /// Ask `provider` about `model` and record the answer.
+    ///
+    /// Failures are logged and leave the registry unchanged, since the
+    /// built-in and user layers still apply.
+    pub async fn probe(&mut self, provider: &dyn ModelProvider, model: &str) {
+        match provider.model_capabilities(model).await {
+            Ok(Some(caps)) => self.record(model, caps),
+            Ok(None) => {}
+            Err(e) => debug!("Could not fetch capabilities of {}: {}", model, e),
+        }
+    }
+
+    /// Capabilities of `model`, merged across all layers.
+    pub fn resolve(&self, model: &str) -> ModelCapabilities {
+        let family = family_of(model);
+        let mut caps = ModelCapabilities::default();
+        if let Some(builtin) = best_family_match(&self.families, &family) {
+            caps.merge(builtin);
+        }
+        if let Some(reported) = self.reported.get(model) {
+            caps.merge(reported);
+        }
+        if let Some(user) = best_family_match(&self.overrides.families, &family) {
+            caps.merge(user);
+        }
+        if let Some(user) = self.overrides.models.get(model) {
+            caps.merge(user);
+        }
+        caps
+    }
+}
+
+/// Family part of a model name: the tag and any registry or namespace prefix
+/// removed, lowercased. `hf.co/unsloth/Qwen3-8B-GGUF:Q4_K_M` is `qwen3-8b-gguf`.
+pub fn family_of(model: &str) -> String {
+    let name = model.split(':').next().unwrap_or(model);
+    let name = name.rsplit('/').next().unwrap_or(name);
+    name.to_lowercase()
+}
+
+/// The longest family key that `family` equals or extends at a `-`, `.` or
+/// `_` boundary.
+fn best_family_match<'a>(
+    families: &'a HashMap<String, ModelCapabilities>,
+    family: &str,
+) -> Option<&'a ModelCapabilities> {
+    families
+        .iter()
+        .filter(|(key, _)| {
+            family
+                .strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', '.', '_']))
+        })
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, caps)| caps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_of() {
+        assert_eq!(family_of("qwen3:0.6b"), "qwen3");
+        assert_eq!(family_of("llama3.1"), "llama3.1");
+        assert_eq!(
+            family_of("hf.co/unsloth/Qwen3-8B-GGUF:Q4_K_M"),
+            "qwen3-8b-gguf"
+        );
+    }
+
+    #[test]
+    fn test_builtin_family_matching() {
+        let registry = CapabilityRegistry::builtin();
+
+        let qwen = registry.resolve("qwen3:0.6b");
+        assert_eq!(qwen.context_length, Some(40_960));
+        assert_eq!(qwen.thinking, Some(true));
+        assert_eq!(qwen.recommended_temperature(true), Some(0.6));
+        assert_eq!(qwen.recommended_temperature(false), Some(0.7));
+
+        assert_eq!(registry.resolve("qwen2.5-coder:7b").tools, Some(true));
+        assert_eq!(registry.resolve("llama3.2-vision").vision, Some(true));
+        assert_eq!(registry.resolve("llama3.2:3b").vision, Some(false));
+        assert_eq!(
+            registry
+                .resolve("hf.co/unsloth/Qwen3-8B-GGUF:Q4_K_M")
+                .thinking,
+            Some(true)
+        );
+        assert_eq!(registry.resolve("qwen30:1b"), ModelCapabilities::default());
+        assert_eq!(registry.resolve("unknown"), ModelCapabilities::default());
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut registry = CapabilityRegistry::builtin();
+        registry.record(
+            "qwen3:0.6b",
+            ModelCapabilities {
+                context_length: Some(8_192),
+                vision: Some(true),
+                ..Default::default()
+            },
+        );
+        let overrides = CapabilityRegistry::parse_overrides(
+            r#"
+            [families.qwen3]
+            vision = false
+            temperature = 0.5
+
+            [models."qwen3:0.6b"]
+            tools = false
+            "#,
+            "test",
+        )
+        .unwrap();
+        let registry = registry.with_overrides(overrides);
+
+        let caps = registry.resolve("qwen3:0.6b");
+        assert_eq!(caps.context_length, Some(8_192));
+        assert_eq!(caps.vision, Some(false));
+        assert_eq!(caps.temperature, Some(0.5));
+        assert_eq!(caps.tools, Some(false));
+        assert_eq!(caps.thinking, Some(true));
+
+        // Reported and per-model layers apply to that model only
+        assert_eq!(registry.resolve("qwen3:8b").context_length, Some(40_960));
+        assert_eq!(registry.resolve("qwen3:8b").tools, Some(true));
+    }
+
+    #[test]
+    fn test_overrides_reject_unknown_keys() {
+        let err = CapabilityRegistry::parse_overrides(
+            "[models.\"qwen3\"]\ncontext_lenght = 1",
+            "models.toml",
+        )
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("models.toml"), "{}", message);
+        assert!(message.contains("context_lenght"), "{}", message);
+    }
+
+    #[test]
+    fn test_load_overrides_from_file() {
+        let dir = std::env::temp_dir().join(format!("nanna-caps-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("models.toml");
+        std::fs::write(&path, "[families.Mistral]\ncontext_length = 1024\n").unwrap();
+
+        let registry = CapabilityRegistry::builtin().load_overrides(&path).unwrap();
+        assert_eq!(registry.resolve("mistral:7b").context_length, Some(1024));
+
+        let missing = CapabilityRegistry::new()
+            .load_overrides(&dir.join("absent.toml"))
+            .unwrap();
+        assert_eq!(missing.resolve("mistral"), ModelCapabilities::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,711 @@
+//! Record and replay model traffic
+//!
+//! [`RecordingProvider`] wraps a real provider and appends every
+//! request/response pair to a cassette file, one JSON object per line.
+//! [`ReplayProvider`] serves those responses back without a model server,
+//! matching requests by [`request_key`], a hash of the request with map keys
+//! sorted, empty fields dropped and image files hashed by content.
+//!
+//! In strict mode an unmatched request is an error. Otherwise the replayer
+//! falls back to handing out recorded responses in their original order, which
+//! keeps replays working when prompts embed run-specific details such as
+//! temporary paths.
+//!
+//! Streamed chats are recorded once complete and replayed as a single chunk.
+//! Embedding batches are recorded too and always matched by key. Model
+//! management passes through while recording; a replay has no server to
+//! manage, so it offers none.
+
+use crate::admin::ModelAdmin;
+use crate::capabilities::ModelCapabilities;
+use crate::embedding::{EmbeddingProvider, EmbeddingResponse};
+use crate::provider::{ModelError, ModelProvider, ModelResult, StreamingModelProvider};
+use crate::stream::{single_chunk_stream, ChatStream, StreamAccumulator};
+use crate::types::{ChatRequest, ChatResponse, ModelInfo};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// One recorded exchange, stored as a line of a cassette file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    pub key: String,
+    pub request: ChatRequest,
+    pub response: ChatResponse,
+}
+
+/// One recorded embedding batch, stored as a line of a cassette file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingEntry {
+    pub key: String,
+    pub model: String,
+    pub inputs: Vec<String>,
+    pub embeddings: EmbeddingResponse,
+}
+
+/// Any line of a cassette file.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CassetteLine {
+    Chat(Box<CassetteEntry>),
+    Embedding(Box<EmbeddingEntry>),
+}
+
+/// Stable hash identifying a request independently of map ordering.
+pub fn request_key(request: &ChatRequest) -> String {
+    hash_canonical(request_value(request))
+}
+
+/// Stable hash identifying an embedding batch.
+pub fn embedding_key(model: &str, inputs: &[String]) -> String {
+    hash_canonical(serde_json::json!({ "model": model, "input": inputs }))
+}
+
+/// The request as JSON, with each image file replaced by a hash of its
+/// bytes so a changed image no longer matches.
+fn request_value(request: &ChatRequest) -> Value {
+    let mut value = serde_json::to_value(request).unwrap_or(Value::Null);
+    let messages = value.get_mut("messages").and_then(Value::as_array_mut);
+    for message in messages.into_iter().flatten() {
+        let images = message.get_mut("images").and_then(Value::as_array_mut);
+        for image in images.into_iter().flatten() {
+            let path = image.get("path").and_then(Value::as_str).map(PathBuf::from);
+            // An unreadable file fails the request later; keep its path
+            if let Some(bytes) = path.and_then(|path| std::fs::read(path).ok()) {
+                *image = serde_json::json!({
+                    "type": "path",
+                    "content": format!("{:016x}", fnv1a(&bytes)),
+                });
+            }
+        }
+    }
+    value
+}
+
+/// Canonical JSON text of `request`, keeping whitespace, for callers that
+/// must tell apart requests [`request_key`] treats as equal.
+pub(crate) fn canonical_request(request: &ChatRequest) -> String {
+    canonicalize(request_value(request), false).to_string()
+}
+
+/// FNV-1a: stable across Rust releases, unlike `DefaultHasher`.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn hash_canonical(value: Value) -> String {
+    let canonical = canonicalize(value, true).to_string();
+    format!("{:016x}", fnv1a(canonical.as_bytes()))
+}
+
+/// Sort map keys and drop nulls, trimming strings if `trim` is set.
+fn canonicalize(value: Value, trim: bool) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, canonicalize(v, trim)))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| canonicalize(item, trim))
+                .collect(),
+        ),
+        Value::String(s) if trim => Value::String(s.trim().to_string()),
+        other => other,
+    }
+}
+
+/// Records every exchange with the wrapped provider to a cassette file.
+pub struct RecordingProvider {
+    inner: Arc<dyn ModelProvider>,
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl RecordingProvider {
+    /// Wrap `inner`, appending to the cassette at `path` (created if missing).
+    pub fn new(inner: Arc<dyn ModelProvider>, path: impl AsRef<Path>) -> ModelResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| ModelError::InvalidConfig {
+                message: format!("Cannot open cassette {}: {}", path.display(), e),
+            })?;
+        Ok(Self {
+            inner,
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&self, line: &CassetteLine) -> ModelResult<()> {
+        append_line(&self.file, &self.path, line)
+    }
+}
+
+fn append_line(file: &Mutex<File>, path: &Path, line: &CassetteLine) -> ModelResult<()> {
+    let mut text = serde_json::to_string(line)?;
+    text.push('\n');
+    let mut file = file.lock().unwrap();
+    file.write_all(text.as_bytes())
+        .and_then(|_| file.flush())
+        .map_err(|e| ModelError::Unknown {
+            message: format!("Failed to write cassette {}: {}", path.display(), e),
+        })
+}
+
+#[async_trait]
+impl ModelProvider for RecordingProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        let key = request_key(&request);
+        let response = self.inner.chat(request.clone()).await?;
+        self.append(&CassetteLine::Chat(Box::new(CassetteEntry {
+            key,
+            request,
+            response: response.clone(),
+        })))?;
+        Ok(response)
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        self.inner.health_check().await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        self.inner.model_capabilities(model).await
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        self.inner
+            .as_streaming()
+            .map(|_| self as &dyn StreamingModelProvider)
+    }
+
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        self.inner
+            .as_embedding()
+            .map(|_| self as &dyn EmbeddingProvider)
+    }
+
+    /// Model management is passed through unrecorded.
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        self.inner.as_admin()
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for RecordingProvider {
+    /// Streams through and records the assembled response once the final
+    /// chunk arrives; streams that end early are not recorded.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let streaming = self
+            .inner
+            .as_streaming()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support streaming", self.inner.provider_name()),
+            })?;
+
+        let key = request_key(&request);
+        let stream = streaming.chat_stream(request.clone()).await?;
+        let file = Arc::clone(&self.file);
+        let path = self.path.clone();
+        let mut request = Some(request);
+        let mut accumulator = StreamAccumulator::new();
+        Ok(Box::pin(stream.map(move |item| {
+            let chunk = item?;
+            accumulator.push(&chunk);
+            if let Some(request) = request.take_if(|_| chunk.finish_reason.is_some()) {
+                let entry = CassetteEntry {
+                    key: key.clone(),
+                    request,
+                    response: std::mem::take(&mut accumulator).finish(),
+                };
+                append_line(&file, &path, &CassetteLine::Chat(Box::new(entry)))?;
+            }
+            Ok(chunk)
+        })))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RecordingProvider {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        let embedding = self
+            .inner
+            .as_embedding()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support embeddings", self.inner.provider_name()),
+            })?;
+
+        let response = embedding.embed(model, inputs).await?;
+        self.append(&CassetteLine::Embedding(Box::new(EmbeddingEntry {
+            key: embedding_key(model, inputs),
+            model: model.to_string(),
+            inputs: inputs.to_vec(),
+            embeddings: response.clone(),
+        })))?;
+        Ok(response)
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    /// Unserved responses per request key, in recording order
+    by_key: HashMap<String, VecDeque<usize>>,
+    /// Indices of entries not yet served, in recording order
+    remaining: VecDeque<usize>,
+}
+
+/// Serves responses from a cassette instead of calling a model.
+pub struct ReplayProvider {
+    entries: Vec<CassetteEntry>,
+    embeddings: HashMap<String, EmbeddingResponse>,
+    strict: bool,
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayProvider {
+    pub fn new(entries: Vec<CassetteEntry>) -> Self {
+        let mut state = ReplayState::default();
+        for (idx, entry) in entries.iter().enumerate() {
+            state
+                .by_key
+                .entry(entry.key.clone())
+                .or_default()
+                .push_back(idx);
+            state.remaining.push_back(idx);
+        }
+        Self {
+            entries,
+            embeddings: HashMap::new(),
+            strict: false,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Serve `responses` in order whatever the request, as a lenient replay
+    /// of a cassette whose every prompt has since changed.
+    pub fn scripted(responses: Vec<ChatResponse>) -> Self {
+        let blank = ChatRequest::new("", Vec::new());
+        Self::new(
+            responses
+                .into_iter()
+                .map(|response| CassetteEntry {
+                    key: String::new(),
+                    request: blank.clone(),
+                    response,
+                })
+                .collect(),
+        )
+    }
+
+    /// Also serve the given recorded embedding batches.
+    pub fn with_embeddings(mut self, entries: Vec<EmbeddingEntry>) -> Self {
+        self.embeddings
+            .extend(entries.into_iter().map(|e| (e.key, e.embeddings)));
+        self
+    }
+
+    /// Load a cassette written by [`RecordingProvider`].
+    pub fn from_file(path: impl AsRef<Path>) -> ModelResult<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| ModelError::InvalidConfig {
+            message: format!("Cannot open cassette {}: {}", path.display(), e),
+        })?;
+
+        let mut entries = Vec::new();
+        let mut embeddings = Vec::new();
+        for (line_no, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| ModelError::InvalidConfig {
+                message: format!("Cannot read cassette {}: {}", path.display(), e),
+            })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| ModelError::InvalidConfig {
+                message: format!(
+                    "{}:{}: invalid cassette entry: {}",
+                    path.display(),
+                    line_no + 1,
+                    e
+                ),
+            })?;
+            match entry {
+                CassetteLine::Chat(entry) => entries.push(*entry),
+                CassetteLine::Embedding(entry) => embeddings.push(*entry),
+            }
+        }
+        Ok(Self::new(entries).with_embeddings(embeddings))
+    }
+
+    /// Error on requests that do not match a recorded one.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Number of recorded responses not yet served.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().remaining.len()
+    }
+
+    fn next_response(&self, key: &str) -> ModelResult<ChatResponse> {
+        let mut state = self.state.lock().unwrap();
+        let matched = state
+            .by_key
+            .get_mut(key)
+            .and_then(|queue| queue.pop_front());
+
+        let idx = match matched {
+            Some(idx) => idx,
+            None if self.strict => {
+                return Err(ModelError::Unknown {
+                    message: format!("No cassette entry matches request {}", key),
+                });
+            }
+            None => match state.remaining.front() {
+                Some(&idx) => {
+                    let entry_key = &self.entries[idx].key;
+                    if let Some(queue) = state.by_key.get_mut(entry_key) {
+                        queue.retain(|i| *i != idx);
+                    }
+                    idx
+                }
+                None => {
+                    return Err(ModelError::Unknown {
+                        message: "Cassette exhausted".to_string(),
+                    });
+                }
+            },
+        };
+
+        state.remaining.retain(|i| *i != idx);
+        Ok(self.entries[idx].response.clone())
+    }
+}
+
+#[async_trait]
+impl ModelProvider for ReplayProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        self.next_response(&request_key(&request))
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        let mut names: Vec<&str> = self
+            .entries
+            .iter()
+            .map(|e| e.request.model.as_str())
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        Ok(names
+            .into_iter()
+            .map(|name| ModelInfo {
+                name: name.to_string(),
+                size: None,
+                digest: None,
+                modified_at: None,
+            })
+            .collect())
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        Ok(())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        Some(self)
+    }
+
+    /// Only offered when the cassette holds embeddings, so callers can fall
+    /// back to a local embedder otherwise.
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        (!self.embeddings.is_empty()).then_some(self as &dyn EmbeddingProvider)
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for ReplayProvider {
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        self.next_response(&request_key(&request))
+            .map(single_chunk_stream)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for ReplayProvider {
+    /// Embeddings are deterministic, so a recorded batch can be served any
+    /// number of times.
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        let key = embedding_key(model, inputs);
+        self.embeddings
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| ModelError::Unknown {
+                message: format!("No cassette entry matches embedding request {}", key),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::collect_stream;
+    use crate::test_support::MockProvider;
+    use crate::types::{
+        ChatMessage, Choice, FinishReason, FunctionDefinition, ImageAttachment, JsonSchema,
+        PropertySchema, SchemaType, ToolDefinition,
+    };
+
+    fn counting() -> Arc<MockProvider> {
+        Arc::new(MockProvider::counting())
+    }
+
+    fn cassette_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cassette-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn request(prompt: &str) -> ChatRequest {
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user(prompt)])
+    }
+
+    fn content(response: &ChatResponse) -> &str {
+        response.choices[0].message.content.as_deref().unwrap()
+    }
+
+    fn tool_with_properties(names: &[&str]) -> ToolDefinition {
+        let properties = names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    PropertySchema {
+                        schema_type: Some(SchemaType::String),
+                        description: None,
+                        items: None,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        ToolDefinition {
+            function: FunctionDefinition {
+                name: "write_file".to_string(),
+                description: "Write a file".to_string(),
+                parameters: JsonSchema {
+                    schema_type: SchemaType::Object,
+                    properties: Some(properties),
+                    required: None,
+                    ..Default::default()
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_request_key_is_normalized() {
+        assert_eq!(
+            request_key(&request("hello")),
+            request_key(&request("hello  "))
+        );
+        assert_ne!(request_key(&request("hello")), request_key(&request("bye")));
+
+        let a = request("x").with_tools(vec![tool_with_properties(&["path", "content", "mode"])]);
+        let b = request("x").with_tools(vec![tool_with_properties(&["mode", "content", "path"])]);
+        assert_eq!(request_key(&a), request_key(&b));
+    }
+
+    #[test]
+    fn test_request_key_follows_image_content() {
+        let path = cassette_path("image.png");
+        let with_image = || {
+            ChatRequest::new(
+                "qwen3-vl",
+                vec![ChatMessage::user("describe").with_image(ImageAttachment::path(&path))],
+            )
+        };
+
+        std::fs::write(&path, b"\x89PNG first").unwrap();
+        let before = request_key(&with_image());
+        assert_eq!(request_key(&with_image()), before);
+        std::fs::write(&path, b"\x89PNG second").unwrap();
+        assert_ne!(request_key(&with_image()), before);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_strict() {
+        let dir = std::env::temp_dir().join(format!("cassette-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("strict.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let recorder = RecordingProvider::new(counting(), &path).unwrap();
+        recorder.chat(request("first")).await.unwrap();
+        recorder.chat(request("second")).await.unwrap();
+        recorder.chat(request("first")).await.unwrap();
+
+        let replay = ReplayProvider::from_file(&path).unwrap().strict();
+        assert_eq!(replay.remaining(), 3);
+
+        // Matching is by request, not by position
+        let second = replay.chat(request("second")).await.unwrap();
+        assert_eq!(content(&second), "reply 2");
+        let first = replay.chat(request("first")).await.unwrap();
+        assert_eq!(content(&first), "reply 1");
+        let again = replay.chat(request("first")).await.unwrap();
+        assert_eq!(content(&again), "reply 3");
+
+        let err = replay.chat(request("unknown")).await.unwrap_err();
+        assert!(err.to_string().contains("No cassette entry"));
+        assert_eq!(replay.remaining(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lenient_replay_falls_back_to_recording_order() {
+        let entries = ["a", "b"]
+            .iter()
+            .map(|prompt| CassetteEntry {
+                key: request_key(&request(prompt)),
+                request: request(prompt),
+                response: ChatResponse {
+                    choices: vec![Choice {
+                        message: ChatMessage::assistant(format!("answer {}", prompt)),
+                        finish_reason: Some(FinishReason::Stop),
+                    }],
+                    usage: None,
+                    served_by: None,
+                },
+            })
+            .collect();
+        let replay = ReplayProvider::new(entries);
+
+        let first = replay.chat(request("changed prompt")).await.unwrap();
+        assert_eq!(content(&first), "answer a");
+        let second = replay.chat(request("b")).await.unwrap();
+        assert_eq!(content(&second), "answer b");
+        assert!(replay.chat(request("a")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_and_embedding_traffic_round_trips() {
+        let path = cassette_path("stream.jsonl");
+        let recorder = RecordingProvider::new(counting(), &path).unwrap();
+
+        let stream = recorder
+            .as_streaming()
+            .expect("inner provider streams")
+            .chat_stream(request("first"))
+            .await
+            .unwrap();
+        let streamed = collect_stream(stream, |_| {}).await.unwrap();
+        assert_eq!(content(&streamed), "reply 1");
+
+        let inputs = vec!["ab".to_string(), "abcd".to_string()];
+        let embedder = recorder.as_embedding().expect("inner provider embeds");
+        embedder.embed("nomic-embed-text", &inputs).await.unwrap();
+        assert!(recorder.as_admin().is_some());
+
+        let replay = ReplayProvider::from_file(&path).unwrap().strict();
+        let stream = replay
+            .as_streaming()
+            .unwrap()
+            .chat_stream(request("first"))
+            .await
+            .unwrap();
+        assert_eq!(
+            content(&collect_stream(stream, |_| {}).await.unwrap()),
+            "reply 1"
+        );
+
+        let embedded = replay
+            .as_embedding()
+            .expect("cassette holds embeddings")
+            .embed("nomic-embed-text", &inputs)
+            .await
+            .unwrap();
+        assert_eq!(embedded.embeddings, vec![vec![2.0], vec![4.0]]);
+        assert!(replay
+            .as_embedding()
+            .unwrap()
+            .embed("nomic-embed-text", &inputs[..1])
+            .await
+            .is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scripted_replay_serves_in_order() {
+        let replay = ReplayProvider::scripted(vec![
+            ChatResponse {
+                choices: vec![Choice {
+                    message: ChatMessage::assistant("one"),
+                    finish_reason: Some(FinishReason::Stop),
+                }],
+                usage: None,
+                served_by: None,
+            };
+            2
+        ]);
+
+        assert!(replay.as_embedding().is_none());
+        assert!(replay.list_models().await.unwrap().is_empty());
+        replay.chat(request("anything")).await.unwrap();
+        replay.chat(request("else")).await.unwrap();
+        assert!(replay.chat(request("more")).await.is_err());
+    }
+
+    #[test]
+    fn test_from_file_reports_bad_lines() {
+        let path = std::env::temp_dir().join(format!("cassette-bad-{}.jsonl", std::process::id()));
+        std::fs::write(&path, "\n{not json}\n").unwrap();
+
+        let err = ReplayProvider::from_file(&path).err().unwrap();
+        assert!(matches!(err, ModelError::InvalidConfig { .. }));
+        assert!(err.to_string().contains(":2:"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,11 +1,15 @@
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaConfig {
     pub base_url: String,
     pub timeout: Duration,
-    pub default_context_length: u32,
+    /// `num_ctx` for requests that do not set one; `None` leaves the
+    /// window to the server's own setting
+    pub default_context_length: Option<u32>,
     pub default_temperature: f32,
     pub default_max_tokens: Option<u32>,
 }
@@ -15,7 +19,7 @@ impl Default for OllamaConfig {
         Self {
             base_url: "http://localhost:11434".to_string(),
             timeout: Duration::from_secs(30),
-            default_context_length: 110_000,
+            default_context_length: None,
             default_temperature: 0.7,
             default_max_tokens: None,
         }
@@ -38,7 +42,7 @@ impl OllamaConfig {
     }
 
     pub fn with_context_length(mut self, context_length: u32) -> Self {
-        self.default_context_length = context_length;
+        self.default_context_length = Some(context_length);
         self
     }
 
@@ -61,7 +65,7 @@ impl OllamaConfig {
             return Err("Base URL must start with http:// or https://".to_string());
         }
 
-        if self.default_context_length == 0 {
+        if self.default_context_length == Some(0) {
             return Err("Context length must be greater than 0".to_string());
         }
 
@@ -83,6 +87,184 @@ impl OllamaConfig {
     }
 }
 
+/// Configuration for servers that speak the OpenAI `/v1/chat/completions`
+/// wire format (vLLM, llama.cpp `server`, LM Studio, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAiConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+    pub default_temperature: f32,
+    pub default_max_tokens: Option<u32>,
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8000/v1".to_string(),
+            api_key: None,
+            timeout: Duration::from_secs(30),
+            default_temperature: 0.7,
+            default_max_tokens: None,
+        }
+    }
+}
+
+impl OpenAiConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.default_temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.default_max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.base_url.is_empty() {
+            return Err("Base URL cannot be empty".to_string());
+        }
+
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            return Err("Base URL must start with http:// or https://".to_string());
+        }
+
+        if !(0.0..=2.0).contains(&self.default_temperature) {
+            return Err("Temperature must be between 0.0 and 2.0".to_string());
+        }
+
+        if let Some(max_tokens) = self.default_max_tokens {
+            if max_tokens == 0 {
+                return Err("Max tokens must be greater than 0".to_string());
+            }
+        }
+
+        if self.timeout.is_zero() {
+            return Err("Timeout must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration for the Anthropic Messages API (`/v1/messages`) and
+/// servers that imitate it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    /// Sent as the `anthropic-version` header
+    pub api_version: String,
+    pub timeout: Duration,
+    pub default_temperature: f32,
+    /// The API requires a completion limit on every request
+    pub default_max_tokens: u32,
+    /// Tokens the model may spend thinking when a request asks for it
+    pub thinking_budget: u32,
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.anthropic.com".to_string(),
+            api_key: None,
+            api_version: "2023-06-01".to_string(),
+            timeout: Duration::from_secs(120),
+            default_temperature: 0.7,
+            default_max_tokens: 4096,
+            thinking_budget: 2048,
+        }
+    }
+}
+
+impl AnthropicConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.default_temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.default_max_tokens = max_tokens;
+        self
+    }
+
+    pub fn with_thinking_budget(mut self, thinking_budget: u32) -> Self {
+        self.thinking_budget = thinking_budget;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.base_url.is_empty() {
+            return Err("Base URL cannot be empty".to_string());
+        }
+
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            return Err("Base URL must start with http:// or https://".to_string());
+        }
+
+        if self.api_version.is_empty() {
+            return Err("API version cannot be empty".to_string());
+        }
+
+        if !(0.0..=1.0).contains(&self.default_temperature) {
+            return Err("Temperature must be between 0.0 and 1.0".to_string());
+        }
+
+        if self.default_max_tokens == 0 {
+            return Err("Max tokens must be greater than 0".to_string());
+        }
+
+        if self.thinking_budget < 1024 {
+            return Err("Thinking budget must be at least 1024 tokens".to_string());
+        }
+
+        if self.timeout.is_zero() {
+            return Err("Timeout must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelDefaults {
     pub temperature: f32,
@@ -100,6 +282,259 @@ impl Default for ModelDefaults {
     }
 }
 
+/// Which requests a response cache may answer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachePolicy {
+    /// Only requests whose output is reproducible: temperature 0 or a fixed
+    /// seed. Agent prompts sample at 0.2-0.7, so agent traffic is only
+    /// cached when the agent is given a seed.
+    #[default]
+    Deterministic,
+    /// Every request, regardless of sampling settings
+    Always,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Maximum number of responses kept in memory
+    pub capacity: usize,
+    pub policy: CachePolicy,
+    /// Directory for persisting responses across runs
+    pub disk_dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            policy: CachePolicy::Deterministic,
+            disk_dir: None,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_disk_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk_dir = Some(dir.into());
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.capacity == 0 {
+            return Err("Cache capacity must be greater than 0".to_string());
+        }
+
+        if let Some(dir) = &self.disk_dir {
+            if dir.as_os_str().is_empty() {
+                return Err("Cache directory cannot be empty".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How a [`RetryingProvider`](crate::retry::RetryingProvider) retries
+/// failed requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Attempts per request, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each further retry
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Random extra delay as a fraction of the backoff (0.0 to 1.0)
+    pub jitter_factor: f64,
+    /// Time allowed for a request across all attempts and delays
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter_factor: 0.1,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send every request exactly once.
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter_factor: f64) -> Self {
+        self.jitter_factor = jitter_factor;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Delay before retrying after failed attempt number `attempt` (from 0).
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        if self.jitter_factor > 0.0 {
+            let jitter = rand::thread_rng().gen_range(0.0..=self.jitter_factor);
+            backoff + backoff.mul_f64(jitter)
+        } else {
+            backoff
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("Retry attempts must be greater than 0".to_string());
+        }
+
+        if self.base_delay > self.max_delay {
+            return Err("Base retry delay cannot exceed the maximum delay".to_string());
+        }
+
+        if !(0.0..=1.0).contains(&self.jitter_factor) {
+            return Err("Jitter factor must be between 0.0 and 1.0".to_string());
+        }
+
+        if self.deadline.is_some_and(|d| d.is_zero()) {
+            return Err("Deadline must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// When a [`CircuitBreaker`](crate::retry::CircuitBreaker) stops sending
+/// requests to a failing backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive backend failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial request is let through
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.failure_threshold == 0 {
+            return Err("Failure threshold must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// How much load a [`GovernedProvider`](crate::governor::GovernedProvider)
+/// lets through to its backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GovernorConfig {
+    /// Requests sent to the backend at the same time; others wait in line
+    pub max_in_flight: usize,
+    /// Prompt and completion tokens allowed per rolling minute
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl Default for GovernorConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 4,
+            tokens_per_minute: None,
+        }
+    }
+}
+
+impl GovernorConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    pub fn with_tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(tokens_per_minute);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_in_flight == 0 {
+            return Err("Maximum in-flight requests must be greater than 0".to_string());
+        }
+
+        if self.tokens_per_minute == Some(0) {
+            return Err("Tokens per minute must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,7 +543,7 @@ mod tests {
     fn test_default_config() {
         let config = OllamaConfig::default();
         assert_eq!(config.base_url, "http://localhost:11434");
-        assert_eq!(config.default_context_length, 110_000);
+        assert_eq!(config.default_context_length, None);
         assert_eq!(config.default_temperature, 0.7);
         assert!(config.validate().is_ok());
     }
@@ -122,7 +557,7 @@ mod tests {
             .with_timeout(Duration::from_secs(60));
 
         assert_eq!(config.base_url, "https://api.example.com");
-        assert_eq!(config.default_context_length, 50_000);
+        assert_eq!(config.default_context_length, Some(50_000));
         assert_eq!(config.default_temperature, 0.5);
         assert_eq!(config.timeout, Duration::from_secs(60));
         assert!(config.validate().is_ok());
@@ -140,10 +575,10 @@ mod tests {
         assert!(config.validate().is_err());
 
         config.base_url = "http://localhost:11434".to_string();
-        config.default_context_length = 0;
+        config.default_context_length = Some(0);
         assert!(config.validate().is_err());
 
-        config.default_context_length = 110_000;
+        config.default_context_length = None;
         config.default_temperature = -1.0;
         assert!(config.validate().is_err());
 
@@ -155,6 +590,48 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_openai_config_validation() {
+        assert!(OpenAiConfig::default().validate().is_ok());
+
+        let config = OpenAiConfig::new().with_base_url("localhost:8000");
+        assert!(config.validate().is_err());
+
+        let config = OpenAiConfig {
+            default_max_tokens: Some(0),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = OpenAiConfig::new()
+            .with_base_url("http://127.0.0.1:8080")
+            .with_api_key("sk-local")
+            .with_max_tokens(256);
+        assert_eq!(config.api_key.as_deref(), Some("sk-local"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_anthropic_config_validation() {
+        assert!(AnthropicConfig::default().validate().is_ok());
+        assert!(AnthropicConfig::new()
+            .with_base_url("api.anthropic.com")
+            .validate()
+            .is_err());
+        assert!(AnthropicConfig::new()
+            .with_temperature(1.5)
+            .validate()
+            .is_err());
+        assert!(AnthropicConfig::new()
+            .with_max_tokens(0)
+            .validate()
+            .is_err());
+        assert!(AnthropicConfig::new()
+            .with_thinking_budget(100)
+            .validate()
+            .is_err());
+    }
+
     #[test]
     fn test_serialization() {
         let config = OllamaConfig::default();
@@ -166,4 +643,35 @@ mod tests {
             deserialized.default_context_length
         );
     }
+
+    #[test]
+    fn test_retry_config() {
+        let config = RetryConfig::new()
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300))
+            .with_jitter(0.0);
+        assert_eq!(config.delay_for(0), Duration::from_millis(100));
+        assert_eq!(config.delay_for(1), Duration::from_millis(200));
+        assert_eq!(config.delay_for(2), Duration::from_millis(300));
+        assert_eq!(config.delay_for(40), Duration::from_millis(300));
+        assert!(config.validate().is_ok());
+
+        let jittered = config.with_jitter(0.5).delay_for(0);
+        assert!(jittered >= Duration::from_millis(100));
+        assert!(jittered <= Duration::from_millis(150));
+
+        assert!(RetryConfig::new().with_max_attempts(0).validate().is_err());
+        assert!(RetryConfig::new().with_jitter(1.5).validate().is_err());
+        assert!(RetryConfig::new()
+            .with_deadline(Duration::ZERO)
+            .validate()
+            .is_err());
+        assert!(RetryConfig::new()
+            .with_backoff(Duration::from_secs(2), Duration::from_secs(1))
+            .validate()
+            .is_err());
+        assert!(CircuitBreakerConfig::new()
+            .with_failure_threshold(0)
+            .validate()
+            .is_err());
+    }
 }
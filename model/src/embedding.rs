@@ -0,0 +1,196 @@
+//! Text embeddings
+//!
+//! [`EmbeddingProvider`] turns a batch of texts into vectors for similarity
+//! search. Chat providers that can also embed expose it through
+//! [`ModelProvider::as_embedding`](crate::provider::ModelProvider::as_embedding).
+//! [`HashingEmbedder`] needs no model server: it hashes words into a fixed
+//! number of buckets, which is crude but deterministic, so retrieval code can
+//! be tested offline.
+
+use crate::provider::{ModelError, ModelResult};
+use crate::types::Usage;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Vectors for a batch of inputs, in input order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub model: String,
+    pub embeddings: Vec<Vec<f32>>,
+    /// Length of every vector in `embeddings`
+    pub dimensions: usize,
+    pub usage: Option<Usage>,
+}
+
+impl EmbeddingResponse {
+    /// Build a response, checking that every vector has the same length.
+    pub fn new(model: impl Into<String>, embeddings: Vec<Vec<f32>>) -> ModelResult<Self> {
+        let dimensions = embeddings.first().map(Vec::len).unwrap_or(0);
+        if let Some(bad) = embeddings.iter().position(|e| e.len() != dimensions) {
+            return Err(ModelError::Unknown {
+                message: format!(
+                    "Embedding {} has {} dimensions, expected {}",
+                    bad,
+                    embeddings[bad].len(),
+                    dimensions
+                ),
+            });
+        }
+        Ok(Self {
+            model: model.into(),
+            embeddings,
+            dimensions,
+            usage: None,
+        })
+    }
+
+    pub fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+}
+
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embed every input with `model`, returning one vector per input.
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse>;
+}
+
+/// Cosine similarity of two vectors, or 0.0 if either is all zeros or their
+/// lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Deterministic embedder based on feature hashing.
+///
+/// Each lowercased word and each character trigram of it is hashed into one of
+/// `dimensions` buckets with a hash-derived sign, and the result is
+/// L2-normalized. Texts sharing words or word fragments score as similar;
+/// there is no notion of meaning beyond that. The `model` argument is ignored.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Embed a single text.
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            let word = word.to_lowercase();
+            self.add_feature(&mut vector, &word);
+
+            let chars: Vec<char> = format!("#{}#", word).chars().collect();
+            for trigram in chars.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add_feature(&mut vector, &trigram);
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str) {
+        // FNV-1a, so vectors are stable across Rust releases
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in feature.as_bytes() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        let bucket = (hash % self.dimensions as u64) as usize;
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign;
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbedder {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        let embeddings = inputs.iter().map(|text| self.embed_text(text)).collect();
+        let mut response = EmbeddingResponse::new(model, embeddings)?;
+        response.dimensions = self.dimensions;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_hashing_embedder_is_deterministic_and_normalized() {
+        let embedder = HashingEmbedder::new(64);
+        let inputs = vec![
+            "Parse the config file".to_string(),
+            "parse config".to_string(),
+            "Render a chart of sales".to_string(),
+            String::new(),
+        ];
+
+        let first = embedder.embed("any", &inputs).await.unwrap();
+        let second = embedder.embed("any", &inputs).await.unwrap();
+        assert_eq!(first.dimensions, 64);
+        assert_eq!(first.embeddings.len(), 4);
+        assert_eq!(first.embeddings, second.embeddings);
+
+        let norm: f32 = first.embeddings[0]
+            .iter()
+            .map(|x| x * x)
+            .sum::<f32>()
+            .sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert!(first.embeddings[3].iter().all(|x| *x == 0.0));
+
+        let related = cosine_similarity(&first.embeddings[0], &first.embeddings[1]);
+        let unrelated = cosine_similarity(&first.embeddings[0], &first.embeddings[2]);
+        assert!(related > unrelated, "{} <= {}", related, unrelated);
+    }
+
+    #[test]
+    fn test_response_rejects_ragged_vectors() {
+        assert!(EmbeddingResponse::new("m", vec![vec![1.0, 2.0], vec![1.0]]).is_err());
+        let empty = EmbeddingResponse::new("m", vec![]).unwrap();
+        assert_eq!(empty.dimensions, 0);
+    }
+}
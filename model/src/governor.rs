@@ -0,0 +1,757 @@
+//! Load governing for shared backends
+//!
+//! [`GovernedProvider`] wraps the provider of one backend and limits how many
+//! requests are in flight at once and, optionally, how many tokens it is sent
+//! per rolling minute. Requests that cannot go yet wait in one line per
+//! [`ChatRequest::task`], and the lines take turns, so an agent task that
+//! sends one request at a time is not held up behind every request of a
+//! busier one. Embeddings and model management wait in the same lines. The
+//! time each request spent waiting is reported in its
+//! [`UsageTimings::queue_duration`] and summed up in [`GovernorStats`].
+//!
+//! Tokens are counted from an estimate when a request is admitted, then
+//! corrected to the usage the backend reports; a failed request gives its
+//! estimate back.
+
+use crate::admin::{ModelAdmin, ModelDetails, PullStream};
+use crate::capabilities::ModelCapabilities;
+use crate::config::GovernorConfig;
+use crate::embedding::{EmbeddingProvider, EmbeddingResponse};
+use crate::provider::{
+    cancellable, ModelError, ModelProvider, ModelResult, StreamingModelProvider,
+};
+use crate::stream::ChatStream;
+use crate::tokens::{estimate_conversation_tokens, estimate_tokens};
+use crate::types::{ChatRequest, ChatResponse, KeepAlive, ModelInfo, Usage, UsageTimings};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+use tracing::debug;
+
+const TOKEN_WINDOW: Duration = Duration::from_secs(60);
+
+/// Queue and throughput counters for a [`GovernedProvider`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GovernorStats {
+    /// Requests admitted to the backend so far
+    pub requests: u64,
+    /// Requests currently sent to the backend
+    pub in_flight: usize,
+    /// Requests currently waiting in line
+    pub queued: usize,
+    /// Tokens counted against the last minute
+    pub tokens_last_minute: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl GovernorStats {
+    pub fn average_wait(&self) -> Duration {
+        if self.requests == 0 {
+            Duration::ZERO
+        } else {
+            self.total_wait / self.requests as u32
+        }
+    }
+}
+
+/// Tokens sent in the last minute, by when they were counted.
+#[derive(Debug, Default)]
+struct TokenWindow {
+    entries: VecDeque<(Instant, u64)>,
+}
+
+impl TokenWindow {
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.entries.front() {
+            if now.duration_since(*at) < TOKEN_WINDOW {
+                break;
+            }
+            self.entries.pop_front();
+        }
+    }
+
+    fn used(&mut self, now: Instant) -> u64 {
+        self.prune(now);
+        self.entries.iter().map(|(_, tokens)| tokens).sum()
+    }
+
+    /// How long until `tokens` more fit under `limit`, or `None` if they fit
+    /// now. A request larger than the whole limit goes once the window is
+    /// empty.
+    fn wait_for(&mut self, now: Instant, tokens: u64, limit: u64) -> Option<Duration> {
+        let mut used = self.used(now);
+        if used + tokens <= limit || self.entries.is_empty() {
+            return None;
+        }
+        for (at, counted) in &self.entries {
+            used -= counted;
+            if used + tokens <= limit || used == 0 {
+                return Some(*at + TOKEN_WINDOW - now);
+            }
+        }
+        None
+    }
+
+    fn record(&mut self, now: Instant, tokens: u64) {
+        if tokens > 0 {
+            self.entries.push_back((now, tokens));
+        }
+    }
+
+    /// Take back up to `tokens` of those counted at `at`, unless they have
+    /// already left the window.
+    fn refund(&mut self, at: Instant, tokens: u64) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|(counted_at, _)| *counted_at == at)
+        {
+            entry.1 = entry.1.saturating_sub(tokens);
+        }
+    }
+}
+
+/// Requests waiting for their turn to be admitted, one line per task. The
+/// lines are served round-robin, one request at a time.
+#[derive(Debug, Default)]
+struct FairLine {
+    state: Mutex<LineState>,
+}
+
+#[derive(Debug, Default)]
+struct LineState {
+    /// Someone is being admitted right now
+    taken: bool,
+    /// Tasks with waiting requests, in the order they get their next turn
+    lines: VecDeque<(Option<String>, VecDeque<oneshot::Sender<()>>)>,
+}
+
+impl FairLine {
+    /// Wait for a turn on behalf of `task`. The turn passes on when the
+    /// returned guard is dropped.
+    async fn enter<'a>(&'a self, task: Option<&'a str>) -> Turn<'a> {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if !state.taken {
+                state.taken = true;
+                return Turn::new(self, task);
+            }
+            let (sender, receiver) = oneshot::channel();
+            match state.lines.iter_mut().find(|(t, _)| t.as_deref() == task) {
+                Some((_, line)) => line.push_back(sender),
+                None => state
+                    .lines
+                    .push_back((task.map(str::to_string), VecDeque::from([sender]))),
+            }
+            receiver
+        };
+        let mut waiting = Waiting {
+            line: self,
+            task,
+            receiver: Some(receiver),
+        };
+        let receiver = waiting.receiver.as_mut().expect("still waiting");
+        let _ = receiver.await;
+        waiting.receiver = None;
+        Turn::new(self, task)
+    }
+
+    /// Hand the turn on from `from` to the first request of the next task
+    /// in line. `from`'s own line goes last if others are waiting.
+    fn pass(&self, from: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        if state.lines.len() > 1 && state.lines[0].0.as_deref() == from {
+            state.lines.rotate_left(1);
+        }
+        while let Some((task, mut line)) = state.lines.pop_front() {
+            let next = line.pop_front();
+            if !line.is_empty() {
+                state.lines.push_back((task, line));
+            }
+            // A caller that gave up has dropped its receiver
+            if next.is_some_and(|sender| sender.send(()).is_ok()) {
+                return;
+            }
+        }
+        state.taken = false;
+    }
+}
+
+/// The right to be admitted next.
+struct Turn<'a> {
+    line: &'a FairLine,
+    task: Option<String>,
+}
+
+impl<'a> Turn<'a> {
+    fn new(line: &'a FairLine, task: Option<&str>) -> Self {
+        Self {
+            line,
+            task: task.map(str::to_string),
+        }
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.line.pass(self.task.as_deref());
+    }
+}
+
+/// A caller waiting in line. If it gives up just after being handed the
+/// turn, the turn passes on instead of being lost.
+struct Waiting<'a> {
+    line: &'a FairLine,
+    task: Option<&'a str>,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if receiver.try_recv().is_ok() {
+                self.line.pass(self.task);
+            }
+        }
+    }
+}
+
+/// Counts a request as queued until dropped, including when the waiting
+/// caller gives up.
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedGuard<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A request let through to the backend.
+struct Admission {
+    permit: OwnedSemaphorePermit,
+    /// When its estimate was counted
+    at: Instant,
+    estimate: u64,
+    waited: Duration,
+}
+
+/// State shared with streams that outlive the call that opened them.
+#[derive(Debug)]
+struct Shared {
+    window: Mutex<TokenWindow>,
+    stats: Mutex<GovernorStats>,
+}
+
+impl Shared {
+    /// Correct the estimate counted at admission to the `actual` tokens the
+    /// request used, if known. A failed request used none.
+    fn settle(&self, at: Instant, estimate: u64, actual: Option<u64>) {
+        let Some(actual) = actual else {
+            return;
+        };
+        let mut window = self.window.lock().unwrap();
+        if actual >= estimate {
+            window.record(Instant::now(), actual - estimate);
+        } else {
+            window.refund(at, estimate - actual);
+        }
+    }
+}
+
+/// Tokens a chat reply reports, none if the request failed and `None` if
+/// the backend did not say.
+fn chat_tokens(result: &ModelResult<ChatResponse>) -> Option<u64> {
+    match result {
+        Ok(response) => response
+            .usage
+            .as_ref()
+            .map(|usage| u64::from(usage.total_tokens)),
+        Err(_) => Some(0),
+    }
+}
+
+/// Wraps the provider of one backend and limits the load sent to it.
+pub struct GovernedProvider {
+    inner: Arc<dyn ModelProvider>,
+    config: GovernorConfig,
+    slots: Arc<Semaphore>,
+    /// Taken in turn while waiting for a slot and token budget, so requests
+    /// are admitted fairly across tasks
+    line: FairLine,
+    queued: AtomicUsize,
+    shared: Arc<Shared>,
+}
+
+impl GovernedProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>, config: GovernorConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+        Ok(Self {
+            inner,
+            slots: Arc::new(Semaphore::new(config.max_in_flight)),
+            config,
+            line: FairLine::default(),
+            queued: AtomicUsize::new(0),
+            shared: Arc::new(Shared {
+                window: Mutex::new(TokenWindow::default()),
+                stats: Mutex::new(GovernorStats::default()),
+            }),
+        })
+    }
+
+    pub fn stats(&self) -> GovernorStats {
+        let mut stats = *self.shared.stats.lock().unwrap();
+        stats.in_flight = self.config.max_in_flight - self.slots.available_permits();
+        stats.queued = self.queued.load(Ordering::Relaxed);
+        stats.tokens_last_minute = self.shared.window.lock().unwrap().used(Instant::now());
+        stats
+    }
+
+    /// Wait for a turn in `task`'s line, then for a slot and, with a token
+    /// limit, for `estimate` tokens of budget.
+    async fn admit(&self, task: Option<&str>, estimate: u64) -> Admission {
+        let started = Instant::now();
+        let queued = QueuedGuard::new(&self.queued);
+        let turn = self.line.enter(task).await;
+
+        let permit = Arc::clone(&self.slots)
+            .acquire_owned()
+            .await
+            .expect("governor semaphore is never closed");
+        let at = loop {
+            let limit = self.config.tokens_per_minute.map(u64::from);
+            let wait = {
+                let mut window = self.shared.window.lock().unwrap();
+                let now = Instant::now();
+                match limit.and_then(|limit| window.wait_for(now, estimate, limit)) {
+                    Some(wait) => wait,
+                    None => {
+                        window.record(now, estimate);
+                        break now;
+                    }
+                }
+            };
+            debug!("Token budget exhausted, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        };
+        drop(turn);
+        drop(queued);
+
+        let waited = started.elapsed();
+        let mut stats = self.shared.stats.lock().unwrap();
+        stats.requests += 1;
+        stats.total_wait += waited;
+        stats.max_wait = stats.max_wait.max(waited);
+        Admission {
+            permit,
+            at,
+            estimate,
+            waited,
+        }
+    }
+
+    /// [`Self::admit`] for `request`, giving up when it is cancelled.
+    async fn admit_request(&self, request: &ChatRequest) -> ModelResult<Admission> {
+        let estimate = estimate_conversation_tokens(&request.messages) as u64;
+        cancellable(request.cancellation.as_ref(), async {
+            Ok(self.admit(request.task.as_deref(), estimate).await)
+        })
+        .await
+    }
+
+    fn admin(&self) -> ModelResult<&dyn ModelAdmin> {
+        self.inner
+            .as_admin()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not manage models", self.inner.provider_name()),
+            })
+    }
+}
+
+/// Report the time spent in line, even when the backend reports no usage.
+fn with_queue_duration(usage: &mut Option<Usage>, waited: Duration) {
+    usage
+        .get_or_insert_with(Usage::default)
+        .timings
+        .get_or_insert_with(UsageTimings::default)
+        .queue_duration = Some(waited);
+}
+
+#[async_trait]
+impl ModelProvider for GovernedProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        let admission = self.admit_request(&request).await?;
+        let result = self.inner.chat(request).await;
+        self.shared
+            .settle(admission.at, admission.estimate, chat_tokens(&result));
+
+        let mut response = result?;
+        with_queue_duration(&mut response.usage, admission.waited);
+        Ok(response)
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        self.inner.health_check().await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        self.inner.model_capabilities(model).await
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        self.inner
+            .as_streaming()
+            .map(|_| self as &dyn StreamingModelProvider)
+    }
+
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        self.inner
+            .as_embedding()
+            .map(|_| self as &dyn EmbeddingProvider)
+    }
+
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        self.inner.as_admin().map(|_| self as &dyn ModelAdmin)
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for GovernedProvider {
+    /// Holds the slot until the stream is finished or dropped.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let streaming = self
+            .inner
+            .as_streaming()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support streaming", self.inner.provider_name()),
+            })?;
+
+        let admission = self.admit_request(&request).await?;
+        let stream = match streaming.chat_stream(request).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.shared
+                    .settle(admission.at, admission.estimate, Some(0));
+                return Err(e);
+            }
+        };
+
+        let shared = Arc::clone(&self.shared);
+        let (at, waited) = (admission.at, admission.waited);
+        // Tokens counted so far; a later usage chunk corrects an earlier one
+        let mut counted = admission.estimate;
+        let mut permit = Some(admission.permit);
+        Ok(Box::pin(stream.map(move |mut item| {
+            if let Ok(chunk) = &mut item {
+                if let Some(usage) = &chunk.usage {
+                    let actual = u64::from(usage.total_tokens);
+                    shared.settle(at, counted, Some(actual));
+                    counted = actual;
+                }
+                // A usage chunk after the final one replaces this
+                if chunk.usage.is_some() || chunk.finish_reason.is_some() {
+                    with_queue_duration(&mut chunk.usage, waited);
+                }
+                if chunk.finish_reason.is_some() {
+                    permit.take();
+                }
+            }
+            item
+        })))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for GovernedProvider {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        let embedder = self
+            .inner
+            .as_embedding()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support embeddings", self.inner.provider_name()),
+            })?;
+
+        let estimate = inputs
+            .iter()
+            .map(|input| estimate_tokens(input) as u64)
+            .sum();
+        let admission = self.admit(None, estimate).await;
+        let result = embedder.embed(model, inputs).await;
+        let actual = match &result {
+            Ok(response) => response.usage.as_ref().map(|u| u64::from(u.total_tokens)),
+            Err(_) => Some(0),
+        };
+        self.shared.settle(admission.at, admission.estimate, actual);
+        result
+    }
+}
+
+/// Model management takes a slot like any request but costs no tokens.
+#[async_trait]
+impl ModelAdmin for GovernedProvider {
+    /// Holds the slot until the download is finished or dropped.
+    async fn pull(&self, model: &str) -> ModelResult<PullStream> {
+        let admin = self.admin()?;
+        let admission = self.admit(None, 0).await;
+        let stream = admin.pull(model).await?;
+        let mut permit = Some(admission.permit);
+        Ok(Box::pin(stream.map(move |item| {
+            if item.as_ref().map_or(true, |progress| progress.is_success()) {
+                permit.take();
+            }
+            item
+        })))
+    }
+
+    async fn show(&self, model: &str) -> ModelResult<ModelDetails> {
+        let admin = self.admin()?;
+        let _admission = self.admit(None, 0).await;
+        admin.show(model).await
+    }
+
+    async fn delete(&self, model: &str) -> ModelResult<()> {
+        let admin = self.admin()?;
+        let _admission = self.admit(None, 0).await;
+        admin.delete(model).await
+    }
+
+    async fn warm(&self, model: &str, keep_alive: KeepAlive) -> ModelResult<()> {
+        let admin = self.admin()?;
+        let _admission = self.admit(None, 0).await;
+        admin.warm(model, keep_alive).await
+    }
+}
+
+impl std::fmt::Debug for GovernedProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GovernedProvider")
+            .field("inner", &self.inner.provider_name())
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::collect_stream;
+    use crate::test_support::MockProvider;
+    use crate::types::ChatMessage;
+
+    /// Answers after `delay`, reporting `tokens` of usage.
+    fn slow(delay: Duration, tokens: u32) -> Arc<MockProvider> {
+        Arc::new(
+            MockProvider::replying("ok")
+                .with_delay(delay)
+                .with_usage(Usage::new(tokens, 0)),
+        )
+    }
+
+    fn request(model: &str) -> ChatRequest {
+        ChatRequest::new(model, vec![ChatMessage::user("hi")])
+    }
+
+    #[tokio::test]
+    async fn test_limits_in_flight_requests() {
+        let inner = slow(Duration::from_millis(20), 10);
+        let governed = Arc::new(
+            GovernedProvider::new(inner.clone(), GovernorConfig::new().with_max_in_flight(2))
+                .unwrap(),
+        );
+
+        let handles: Vec<_> = (0..6)
+            .map(|i| {
+                let governed = Arc::clone(&governed);
+                tokio::spawn(async move { governed.chat(request(&format!("m{}", i))).await })
+            })
+            .collect();
+        let mut waits = Vec::new();
+        for handle in handles {
+            let response = handle.await.unwrap().unwrap();
+            let timings = response.usage.unwrap().timings.unwrap();
+            waits.push(timings.queue_duration.unwrap());
+        }
+
+        assert_eq!(inner.peak(), 2);
+        assert!(waits.iter().any(|w| *w >= Duration::from_millis(15)));
+        let stats = governed.stats();
+        assert_eq!(stats.requests, 6);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.queued, 0);
+        assert!(stats.max_wait >= Duration::from_millis(15));
+        assert!(stats.average_wait() <= stats.max_wait);
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_served_in_arrival_order() {
+        let inner = slow(Duration::from_millis(5), 1);
+        let governed = Arc::new(
+            GovernedProvider::new(inner.clone(), GovernorConfig::new().with_max_in_flight(1))
+                .unwrap(),
+        );
+
+        let mut handles = Vec::new();
+        for i in 0..5 {
+            let governed = Arc::clone(&governed);
+            handles.push(tokio::spawn(async move {
+                governed.chat(request(&i.to_string())).await.unwrap();
+            }));
+            // Let each request join the line before the next one arrives
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(inner.calls(), ["0", "1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_token_window() {
+        let mut window = TokenWindow::default();
+        let start = Instant::now();
+        assert_eq!(window.wait_for(start, 500, 100), None);
+
+        window.record(start, 60);
+        window.record(start + Duration::from_secs(10), 30);
+        assert_eq!(window.wait_for(start, 10, 100), None);
+        assert_eq!(
+            window.wait_for(start + Duration::from_secs(20), 50, 100),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(
+            window.wait_for(start + Duration::from_secs(20), 90, 100),
+            Some(Duration::from_secs(50))
+        );
+        assert_eq!(window.used(start + Duration::from_secs(65)), 30);
+        assert_eq!(window.used(start + Duration::from_secs(75)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_token_budget_counts_reported_usage() {
+        let inner = slow(Duration::ZERO, 400);
+        let governed =
+            GovernedProvider::new(inner, GovernorConfig::new().with_tokens_per_minute(1000))
+                .unwrap();
+
+        governed.chat(request("m")).await.unwrap();
+        let stream = governed
+            .as_streaming()
+            .unwrap()
+            .chat_stream(request("m"))
+            .await
+            .unwrap();
+        let response = collect_stream(stream, |_| {}).await.unwrap();
+
+        let stats = governed.stats();
+        assert_eq!(stats.tokens_last_minute, 800);
+        assert_eq!(stats.in_flight, 0);
+        let timings = response.usage.unwrap().timings.unwrap();
+        assert!(timings.queue_duration.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tasks_take_turns() {
+        let inner = slow(Duration::from_millis(50), 1);
+        let governed = Arc::new(
+            GovernedProvider::new(inner.clone(), GovernorConfig::new().with_max_in_flight(1))
+                .unwrap(),
+        );
+
+        let mut handles = Vec::new();
+        for (task, model) in [("a", "a0"), ("a", "a1"), ("a", "a2"), ("b", "b0")] {
+            let governed = Arc::clone(&governed);
+            handles.push(tokio::spawn(async move {
+                governed.chat(request(model).with_task(task)).await.unwrap();
+            }));
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // b0 arrived last but goes before a's third request
+        assert_eq!(inner.calls(), ["a0", "a1", "b0", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_estimates_are_settled_against_outcome() {
+        let config = GovernorConfig::new().with_tokens_per_minute(1000);
+
+        let failing = Arc::new(MockProvider::failing(|| ModelError::RateLimit));
+        let governed = GovernedProvider::new(failing, config.clone()).unwrap();
+        assert!(governed.chat(request("m")).await.is_err());
+        assert!(governed
+            .as_streaming()
+            .unwrap()
+            .chat_stream(request("m"))
+            .await
+            .is_err());
+        assert_eq!(governed.stats().tokens_last_minute, 0);
+
+        let small = slow(Duration::ZERO, 1);
+        let governed = GovernedProvider::new(small, config.clone()).unwrap();
+        let long = ChatRequest::new("m", vec![ChatMessage::user("word ".repeat(200))]);
+        governed.chat(long).await.unwrap();
+        assert_eq!(governed.stats().tokens_last_minute, 1);
+
+        let silent = Arc::new(MockProvider::replying("ok"));
+        let governed = GovernedProvider::new(silent, config).unwrap();
+        let response = governed.chat(request("m")).await.unwrap();
+        let estimate = estimate_conversation_tokens(&request("m").messages) as u64;
+        assert_eq!(governed.stats().tokens_last_minute, estimate);
+        let timings = response.usage.unwrap().timings.unwrap();
+        assert!(timings.queue_duration.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_and_model_management_share_the_limit() {
+        let inner = slow(Duration::from_millis(10), 1);
+        let governed = Arc::new(
+            GovernedProvider::new(inner.clone(), GovernorConfig::new().with_max_in_flight(1))
+                .unwrap(),
+        );
+
+        let chat = {
+            let governed = Arc::clone(&governed);
+            tokio::spawn(async move { governed.chat(request("m")).await.map(|_| ()) })
+        };
+        let inputs = vec!["some text".to_string()];
+        let embedder = governed.as_embedding().unwrap();
+        let admin = governed.as_admin().unwrap();
+        let (embedded, warmed) = tokio::join!(
+            embedder.embed("m", &inputs),
+            admin.warm("m", KeepAlive::Forever)
+        );
+        embedded.unwrap();
+        warmed.unwrap();
+        chat.await.unwrap().unwrap();
+
+        assert_eq!(inner.peak(), 1);
+        assert_eq!(governed.stats().requests, 3);
+    }
+}
@@ -1,27 +1,98 @@
+pub mod admin;
+#[cfg(feature = "anthropic")]
+pub mod anthropic;
+pub mod cache;
+pub mod capabilities;
+pub mod cassette;
 pub mod config;
+pub mod embedding;
+pub mod governor;
 pub mod judge;
+#[cfg(feature = "ollama")]
 pub mod ollama;
+#[cfg(feature = "openai")]
+pub mod openai;
 pub mod provider;
+pub mod reasoning;
+pub mod retry;
+pub mod router;
+pub mod schema;
+pub mod stream;
+pub mod structured;
+#[cfg(test)]
+mod test_support;
+pub mod tokens;
+pub mod tool_parser;
 pub mod types;
 
-pub use config::{ModelDefaults, OllamaConfig};
-pub use judge::{JudgeConfig, ModelJudge, ValidationCriteria, ValidationMetrics, ValidationResult};
-pub use provider::{ModelError, ModelProvider, ModelResult, StreamingModelProvider};
+pub use admin::{pull_model, ModelAdmin, ModelDetails, PullProgress, PullStream};
+pub use cache::{CacheStats, CachingProvider};
+pub use capabilities::{CapabilityOverrides, CapabilityRegistry, ModelCapabilities};
+pub use cassette::{
+    embedding_key, request_key, CassetteEntry, EmbeddingEntry, RecordingProvider, ReplayProvider,
+};
+pub use config::{
+    AnthropicConfig, CacheConfig, CachePolicy, CircuitBreakerConfig, GovernorConfig, ModelDefaults,
+    OllamaConfig, OpenAiConfig, RetryConfig,
+};
+pub use embedding::{cosine_similarity, EmbeddingProvider, EmbeddingResponse, HashingEmbedder};
+pub use governor::{GovernedProvider, GovernorStats};
+pub use judge::{
+    Comparison, Grade, JudgeConfig, JudgeSample, LlmJudge, LlmJudgeConfig, ModelJudge,
+    PairwiseSample, Preference, Rubric, RubricCriterion, ValidationCriteria, ValidationMetrics,
+    ValidationResult,
+};
+pub use provider::{
+    cancellable, CancellationToken, ModelError, ModelProvider, ModelResult, StreamingModelProvider,
+};
+pub use reasoning::{split_reasoning, ThinkTagFilter};
+pub use retry::{is_retryable, CircuitBreaker, CircuitState, RetryingProvider};
+pub use router::{RouteTarget, RouterProvider, RoutingPolicy};
+pub use stream::{collect_stream, ChatStream, ChatStreamChunk, StreamAccumulator};
+pub use structured::{chat_json, chat_json_with, extract_json, parse_json, UnparsedReply};
+pub use tokens::{estimate_conversation_tokens, estimate_message_tokens, estimate_tokens};
+pub use tool_parser::{ParsedToolCalls, TextToolFormat, ToolCallParser};
 pub use types::{
-    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, FunctionCall, FunctionDefinition,
-    JsonSchema, MessageRole, ModelInfo, PropertySchema, SchemaType, ToolCall, ToolChoice,
-    ToolDefinition, Usage,
+    ChatMessage, ChatRequest, ChatResponse, Choice, EncodedImage, FinishReason, FunctionCall,
+    FunctionDefinition, ImageAttachment, JsonSchema, KeepAlive, MessageRole, ModelInfo,
+    PropertySchema, ResponseFormat, SchemaType, ToolCall, ToolChoice, ToolDefinition, Usage,
+    UsageTimings,
 };
 
 #[cfg(feature = "ollama")]
 pub use ollama::OllamaProvider;
 
+#[cfg(feature = "openai")]
+pub use openai::OpenAiProvider;
+
+#[cfg(feature = "anthropic")]
+pub use anthropic::AnthropicProvider;
+
 pub mod prelude {
+    pub use crate::admin::*;
+    pub use crate::cache::*;
+    pub use crate::capabilities::*;
+    pub use crate::cassette::*;
     pub use crate::config::*;
+    pub use crate::embedding::*;
+    pub use crate::governor::*;
     pub use crate::judge::*;
     pub use crate::provider::*;
+    pub use crate::reasoning::*;
+    pub use crate::retry::*;
+    pub use crate::router::*;
+    pub use crate::stream::*;
+    pub use crate::structured::*;
+    pub use crate::tokens::*;
+    pub use crate::tool_parser::*;
     pub use crate::types::*;
 
     #[cfg(feature = "ollama")]
     pub use crate::ollama::*;
+
+    #[cfg(feature = "openai")]
+    pub use crate::openai::*;
+
+    #[cfg(feature = "anthropic")]
+    pub use crate::anthropic::*;
 }
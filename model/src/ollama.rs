@@ -1,25 +1,43 @@
+use crate::admin::{ModelAdmin, ModelDetails, PullProgress, PullStream};
+use crate::capabilities::ModelCapabilities;
 use crate::config::OllamaConfig;
+use crate::embedding::{EmbeddingProvider, EmbeddingResponse};
 use crate::judge::{
     JudgeConfig, ModelJudge, ValidationCriteria, ValidationMetrics, ValidationResult,
 };
-use crate::provider::{ModelError, ModelProvider, ModelResult};
+use crate::provider::{
+    cancellable, ModelError, ModelProvider, ModelResult, StreamingModelProvider,
+};
+use crate::reasoning::merge_reasoning;
+use crate::stream::{cancellable_stream, ChatStream, ChatStreamChunk, LineDecoder};
 use crate::types::{
-    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, FunctionCall, MessageRole,
-    ModelInfo, ToolCall, ToolDefinition, Usage,
+    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, FunctionCall, KeepAlive,
+    MessageRole, ModelInfo, ResponseFormat, ToolCall, ToolDefinition, Usage, UsageTimings,
 };
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use ollama_rs::Ollama;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use serde_json::Value;
 use std::time::Duration;
 use std::time::Instant;
 use tracing::{debug, error, info, warn};
 
+/// Downloads run far longer than chat requests, so they get their own limit
+const PULL_TIMEOUT: Duration = Duration::from_secs(6 * 60 * 60);
+/// Loading a large model from disk can take minutes
+const WARM_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 pub struct OllamaProvider {
     client: Ollama,
     config: OllamaConfig,
     judge_config: JudgeConfig,
     http_client: reqwest::Client,
+    /// Streams can run far past `config.timeout`, so this client only
+    /// limits connecting; reads are bounded per chunk instead
+    stream_client: reqwest::Client,
     base_url: String,
 }
 
@@ -49,12 +67,19 @@ impl OllamaProvider {
             .map_err(|e| ModelError::Unknown {
                 message: format!("Failed to build HTTP client: {}", e),
             })?;
+        let stream_client = reqwest::Client::builder()
+            .connect_timeout(config.timeout)
+            .build()
+            .map_err(|e| ModelError::Unknown {
+                message: format!("Failed to build HTTP client: {}", e),
+            })?;
 
         Ok(Self {
             client,
             config,
             judge_config: JudgeConfig::default(),
             http_client,
+            stream_client,
             base_url,
         })
     }
@@ -68,7 +93,11 @@ impl OllamaProvider {
         self
     }
 
-    fn messages_to_json(messages: &[ChatMessage]) -> Vec<Value> {
+    /// Reasoning of earlier turns is only sent when `include_reasoning` is set.
+    fn messages_to_json(
+        messages: &[ChatMessage],
+        include_reasoning: bool,
+    ) -> ModelResult<Vec<Value>> {
         messages
             .iter()
             .map(|msg| {
@@ -105,7 +134,20 @@ impl OllamaProvider {
                     obj["tool_call_id"] = Value::String(tool_call_id.clone());
                 }
 
-                obj
+                if let (true, Some(reasoning)) = (include_reasoning, &msg.reasoning) {
+                    obj["thinking"] = Value::String(reasoning.clone());
+                }
+
+                if !msg.images.is_empty() {
+                    let images = msg
+                        .images
+                        .iter()
+                        .map(|image| image.encode().map(|encoded| Value::String(encoded.data)))
+                        .collect::<ModelResult<Vec<_>>>()?;
+                    obj["images"] = Value::Array(images);
+                }
+
+                Ok(obj)
             })
             .collect()
     }
@@ -122,7 +164,205 @@ impl OllamaProvider {
             .collect()
     }
 
+    fn build_payload(&self, request: &ChatRequest, stream: bool) -> ModelResult<Value> {
+        let messages = Self::messages_to_json(&request.messages, request.include_reasoning)?;
+
+        let temperature = request
+            .temperature
+            .unwrap_or(self.config.default_temperature);
+
+        let mut options = serde_json::json!({
+            "temperature": temperature,
+        });
+        // Unset, the server keeps its configured window and avoids reloading
+        // the model to resize it
+        if let Some(num_ctx) = request.num_ctx.or(self.config.default_context_length) {
+            options["num_ctx"] = Value::from(num_ctx);
+        }
+        if let Some(num_predict) = request.max_tokens.or(self.config.default_max_tokens) {
+            options["num_predict"] = Value::from(num_predict);
+        }
+        if let Some(top_p) = request.top_p {
+            options["top_p"] = Value::from(top_p);
+        }
+        if let Some(top_k) = request.top_k {
+            options["top_k"] = Value::from(top_k);
+        }
+        if let Some(seed) = request.seed {
+            options["seed"] = Value::from(seed);
+        }
+        if !request.stop.is_empty() {
+            options["stop"] = Value::from(request.stop.clone());
+        }
+        if let Some(repeat_penalty) = request.repeat_penalty {
+            options["repeat_penalty"] = Value::from(repeat_penalty);
+        }
+
+        let mut payload = serde_json::json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": stream,
+            "options": options,
+        });
+
+        if let Some(keep_alive) = request.keep_alive {
+            payload["keep_alive"] = Value::from(keep_alive.as_seconds());
+        }
+
+        if let Some(tools) = &request.tools {
+            if !tools.is_empty() {
+                payload["tools"] = Value::Array(Self::tools_to_json(tools));
+            }
+        }
+
+        // Ollama takes either the literal "json" or a JSON schema object
+        match &request.response_format {
+            Some(ResponseFormat::Json) => payload["format"] = Value::from("json"),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => payload["format"] = schema.clone(),
+            None => {}
+        }
+
+        if let Some(think) = request.think {
+            payload["think"] = Value::Bool(think);
+        }
+
+        Ok(payload)
+    }
+
+    async fn post_chat(
+        &self,
+        client: &reqwest::Client,
+        payload: &Value,
+    ) -> ModelResult<reqwest::Response> {
+        let url = format!("{}/api/chat", self.base_url);
+
+        let response = client
+            .post(&url)
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, self.config.timeout))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let model = payload["model"].as_str().unwrap_or_default();
+            return Err(Self::status_to_error(status, body, model));
+        }
+
+        Ok(response)
+    }
+
+    /// Classify a non-2xx answer so retry and failover can tell a busy or
+    /// crashed server from a request that will never succeed.
+    fn status_to_error(status: StatusCode, body: String, model: &str) -> ModelError {
+        match status {
+            StatusCode::NOT_FOUND => ModelError::ModelNotFound {
+                model: model.to_string(),
+            },
+            StatusCode::BAD_REQUEST => ModelError::InvalidConfig {
+                message: format!("Ollama rejected the request: {}", body),
+            },
+            StatusCode::TOO_MANY_REQUESTS => ModelError::RateLimit,
+            s if s.is_server_error() => ModelError::ServiceUnavailable {
+                message: format!("Ollama API error {}: {}", s, body),
+            },
+            s => ModelError::Unknown {
+                message: format!("Ollama API error {}: {}", s, body),
+            },
+        }
+    }
+
+    /// Token counts and timings from the final record of a chat response.
+    ///
+    /// Ollama omits `prompt_eval_count` when the whole prompt was served from
+    /// its KV cache, so that case is reported as zero prompt tokens.
+    fn usage_from_raw(raw: &OllamaChatRawResponse) -> Option<Usage> {
+        let eval_count = raw.eval_count?;
+        let prompt_eval_count = raw.prompt_eval_count.unwrap_or(0);
+        let usage = Usage::new(prompt_eval_count.max(0) as u32, eval_count.max(0) as u32);
+
+        let timings = UsageTimings {
+            total_duration: raw.total_duration.map(Duration::from_nanos),
+            load_duration: raw.load_duration.map(Duration::from_nanos),
+            prompt_eval_duration: raw.prompt_eval_duration.map(Duration::from_nanos),
+            eval_duration: raw.eval_duration.map(Duration::from_nanos),
+            ..Default::default()
+        };
+        if timings == UsageTimings::default() {
+            Some(usage)
+        } else {
+            Some(usage.with_timings(timings))
+        }
+    }
+
+    fn parse_done_reason(done_reason: Option<&str>) -> FinishReason {
+        match done_reason {
+            Some("length") => FinishReason::Length,
+            _ => FinishReason::Stop,
+        }
+    }
+
+    fn convert_tool_calls(calls: Vec<OllamaRawToolCall>, first_index: usize) -> Vec<ToolCall> {
+        calls
+            .into_iter()
+            .enumerate()
+            .map(|(idx, tc)| ToolCall {
+                id: format!("call_{}", first_index + idx),
+                function: FunctionCall {
+                    name: tc.function.name,
+                    arguments: tc.function.arguments,
+                },
+            })
+            .collect()
+    }
+
+    /// Convert one NDJSON record of a streamed `/api/chat` response.
+    ///
+    /// Ollama emits each tool call whole rather than as argument fragments, so
+    /// `next_call_index` only keeps generated ids unique across chunks.
+    fn parse_stream_chunk(
+        raw: OllamaChatRawResponse,
+        next_call_index: &mut usize,
+    ) -> ChatStreamChunk {
+        let (finish_reason, usage) = if raw.done {
+            (
+                Some(Self::parse_done_reason(raw.done_reason.as_deref())),
+                Self::usage_from_raw(&raw),
+            )
+        } else {
+            (None, None)
+        };
+
+        let tool_calls = raw
+            .message
+            .tool_calls
+            .filter(|tc| !tc.is_empty())
+            .map(|calls| {
+                let converted = Self::convert_tool_calls(calls, *next_call_index);
+                *next_call_index += converted.len();
+                converted
+            });
+
+        let delta = if raw.message.content.is_empty() {
+            None
+        } else {
+            Some(raw.message.content)
+        };
+        let reasoning = raw.message.thinking.filter(|t| !t.is_empty());
+
+        ChatStreamChunk {
+            delta,
+            reasoning,
+            tool_calls,
+            finish_reason,
+            usage,
+            served_by: None,
+        }
+    }
+
     fn parse_raw_response(raw: OllamaChatRawResponse) -> ModelResult<ChatResponse> {
+        let usage = Self::usage_from_raw(&raw);
         let has_tool_calls = raw
             .message
             .tool_calls
@@ -131,45 +371,22 @@ impl OllamaProvider {
             .unwrap_or(false);
 
         let (finish_reason, tool_calls) = if has_tool_calls {
-            let calls: Vec<ToolCall> = raw
-                .message
-                .tool_calls
-                .unwrap_or_default()
-                .into_iter()
-                .enumerate()
-                .map(|(idx, tc)| ToolCall {
-                    id: format!("call_{}", idx),
-                    function: FunctionCall {
-                        name: tc.function.name,
-                        arguments: tc.function.arguments,
-                    },
-                })
-                .collect();
+            let calls = Self::convert_tool_calls(raw.message.tool_calls.unwrap_or_default(), 0);
             (FinishReason::ToolCalls, Some(calls))
         } else {
-            (FinishReason::Stop, None)
+            (Self::parse_done_reason(raw.done_reason.as_deref()), None)
         };
 
-        let content = if raw.message.content.is_empty() {
-            None
-        } else {
-            Some(raw.message.content)
-        };
+        let (reasoning, content) = merge_reasoning(raw.message.thinking, &raw.message.content);
 
         let message = ChatMessage {
             role: MessageRole::Assistant,
             content,
             tool_calls,
             tool_call_id: None,
-        };
-
-        let usage = match (raw.prompt_eval_count, raw.eval_count) {
-            (Some(p), Some(e)) => Some(Usage {
-                prompt_tokens: p as u32,
-                completion_tokens: e as u32,
-                total_tokens: (p + e) as u32,
-            }),
-            _ => None,
+            reasoning,
+            reasoning_signature: None,
+            images: Vec::new(),
         };
 
         Ok(ChatResponse {
@@ -178,15 +395,57 @@ impl OllamaProvider {
                 finish_reason: Some(finish_reason),
             }],
             usage,
+            served_by: None,
         })
     }
 
-    fn handle_ollama_error(err: ollama_rs::error::OllamaError) -> ModelError {
+    /// Capabilities from an `/api/show` response.
+    ///
+    /// A smaller `num_ctx` parameter wins over the trained context length in
+    /// `model_info`, since it is the window Ollama actually serves; a larger
+    /// one is capped, since the model degrades past what it was trained on.
+    fn parse_show_response(raw: &OllamaShowRawResponse) -> ModelCapabilities {
+        let mut caps = ModelCapabilities::default();
+
+        if let Some(capabilities) = &raw.capabilities {
+            let has = |name: &str| capabilities.iter().any(|c| c == name);
+            caps.tools = Some(has("tools"));
+            caps.thinking = Some(has("thinking"));
+            caps.vision = Some(has("vision"));
+        }
+
+        caps.context_length = raw
+            .model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|n| n.min(u32::MAX as u64) as u32);
+
+        for line in raw.parameters.as_deref().unwrap_or("").lines() {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => {
+                    if let Ok(n) = value.parse::<u32>() {
+                        caps.context_length =
+                            Some(caps.context_length.map_or(n, |trained| trained.min(n)));
+                    }
+                }
+                (Some("temperature"), Some(value)) => {
+                    caps.temperature = value.parse().ok();
+                }
+                _ => {}
+            }
+        }
+
+        caps
+    }
+
+    fn handle_ollama_error(&self, err: ollama_rs::error::OllamaError) -> ModelError {
         match err {
             ollama_rs::error::OllamaError::ReqwestError(e) => {
                 if e.is_timeout() {
-                    ModelError::ServiceUnavailable {
-                        message: "Request timeout".to_string(),
+                    ModelError::Timeout {
+                        elapsed: self.config.timeout,
                     }
                 } else if e.is_connect() {
                     ModelError::ServiceUnavailable {
@@ -205,11 +464,10 @@ impl OllamaProvider {
         }
     }
 
-    fn handle_reqwest_error(e: reqwest::Error) -> ModelError {
+    /// Map a failed request; `timeout` is the limit it ran under.
+    fn handle_reqwest_error(e: reqwest::Error, timeout: Duration) -> ModelError {
         if e.is_timeout() {
-            ModelError::ServiceUnavailable {
-                message: "Request timeout".to_string(),
-            }
+            ModelError::Timeout { elapsed: timeout }
         } else if e.is_connect() {
             ModelError::ServiceUnavailable {
                 message: "Cannot connect to Ollama service".to_string(),
@@ -225,10 +483,16 @@ impl OllamaProvider {
 #[derive(Deserialize)]
 struct OllamaChatRawResponse {
     message: OllamaRawMessage,
-    #[allow(dead_code)]
     done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
     prompt_eval_count: Option<i64>,
     eval_count: Option<i64>,
+    /// Durations are reported in nanoseconds
+    total_duration: Option<u64>,
+    load_duration: Option<u64>,
+    prompt_eval_duration: Option<u64>,
+    eval_duration: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -236,6 +500,9 @@ struct OllamaRawMessage {
     #[allow(dead_code)]
     role: String,
     content: String,
+    /// Present when thinking was enabled on the request
+    #[serde(default)]
+    thinking: Option<String>,
     tool_calls: Option<Vec<OllamaRawToolCall>>,
 }
 
@@ -255,49 +522,14 @@ impl ModelProvider for OllamaProvider {
     async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
         debug!("Starting chat request with model: {}", request.model);
 
-        let messages = Self::messages_to_json(&request.messages);
-
-        let temperature = request
-            .temperature
-            .unwrap_or(self.config.default_temperature);
-
-        let mut payload = serde_json::json!({
-            "model": request.model,
-            "messages": messages,
-            "stream": false,
-            "options": {
-                "temperature": temperature
-            }
-        });
-
-        if let Some(tools) = &request.tools {
-            if !tools.is_empty() {
-                payload["tools"] = Value::Array(Self::tools_to_json(tools));
-            }
-        }
-
-        let url = format!("{}/api/chat", self.base_url);
-
-        let response = self
-            .http_client
-            .post(&url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(Self::handle_reqwest_error)?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(ModelError::Unknown {
-                message: format!("Ollama API error {}: {}", status, body),
-            });
-        }
-
-        let raw: OllamaChatRawResponse =
+        let payload = self.build_payload(&request, false)?;
+        let raw: OllamaChatRawResponse = cancellable(request.cancellation.as_ref(), async {
+            let response = self.post_chat(&self.http_client, &payload).await?;
             response.json().await.map_err(|e| ModelError::Unknown {
                 message: format!("Failed to parse response: {}", e),
-            })?;
+            })
+        })
+        .await?;
 
         info!("Chat request completed successfully");
 
@@ -311,7 +543,7 @@ impl ModelProvider for OllamaProvider {
             .client
             .list_local_models()
             .await
-            .map_err(Self::handle_ollama_error)?;
+            .map_err(|e| self.handle_ollama_error(e))?;
 
         let model_infos: Vec<ModelInfo> = models
             .into_iter()
@@ -345,6 +577,359 @@ impl ModelProvider for OllamaProvider {
     fn provider_name(&self) -> &'static str {
         "ollama"
     }
+
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        debug!("Fetching capabilities of model: {}", model);
+
+        let raw = self.fetch_show(model).await?;
+        Ok(Some(Self::parse_show_response(&raw)))
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        Some(self)
+    }
+
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        Some(self)
+    }
+
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        Some(self)
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaShowRawResponse {
+    /// Missing on Ollama releases before 0.6.4
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+    #[serde(default)]
+    model_info: serde_json::Map<String, Value>,
+    /// Modelfile `PARAMETER` lines, one `name value` pair per line
+    #[serde(default)]
+    parameters: Option<String>,
+    #[serde(default)]
+    details: OllamaModelDetailsRaw,
+    modified_at: Option<String>,
+    template: Option<String>,
+    license: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct OllamaModelDetailsRaw {
+    format: Option<String>,
+    family: Option<String>,
+    parameter_size: Option<String>,
+    quantization_level: Option<String>,
+}
+
+/// A pull record is either progress or an `{"error": ...}` object emitted
+/// when the download fails.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OllamaPullRecord {
+    Error { error: String },
+    Progress(PullProgress),
+}
+
+impl OllamaProvider {
+    /// Pass through a successful response, mapping failures the same way as
+    /// chat requests.
+    async fn check_model_response(
+        response: reqwest::Response,
+        model: &str,
+    ) -> ModelResult<reqwest::Response> {
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Self::status_to_error(status, body, model));
+        }
+        Ok(response)
+    }
+
+    async fn fetch_show(&self, model: &str) -> ModelResult<OllamaShowRawResponse> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, self.config.timeout))?;
+
+        let response = Self::check_model_response(response, model).await?;
+        response.json().await.map_err(|e| ModelError::Unknown {
+            message: format!("Failed to parse show response: {}", e),
+        })
+    }
+}
+
+#[async_trait]
+impl ModelAdmin for OllamaProvider {
+    async fn pull(&self, model: &str) -> ModelResult<PullStream> {
+        debug!("Pulling model: {}", model);
+
+        let url = format!("{}/api/pull", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .timeout(PULL_TIMEOUT)
+            .json(&serde_json::json!({ "model": model, "stream": true }))
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, PULL_TIMEOUT))?;
+        let response = Self::check_model_response(response, model).await?;
+
+        let state = OllamaStreamState::new(response, PULL_TIMEOUT);
+
+        let stream = futures::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.next_line().await {
+                Ok(Some(line)) => {
+                    let item = serde_json::from_str::<OllamaPullRecord>(&line)
+                        .map_err(ModelError::from)
+                        .and_then(|record| match record {
+                            OllamaPullRecord::Error { error } => Err(ModelError::Unknown {
+                                message: format!("Ollama pull error: {}", error),
+                            }),
+                            OllamaPullRecord::Progress(progress) => Ok(progress),
+                        });
+                    let next = if item.is_ok() { Some(state) } else { None };
+                    Some((item, next))
+                }
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn show(&self, model: &str) -> ModelResult<ModelDetails> {
+        debug!("Showing model: {}", model);
+
+        let raw = self.fetch_show(model).await?;
+        let capabilities = Self::parse_show_response(&raw);
+        Ok(ModelDetails {
+            name: model.to_string(),
+            family: raw.details.family,
+            parameter_size: raw.details.parameter_size,
+            quantization: raw.details.quantization_level,
+            format: raw.details.format,
+            modified_at: raw.modified_at,
+            capabilities,
+            template: raw.template,
+            license: raw.license,
+        })
+    }
+
+    async fn delete(&self, model: &str) -> ModelResult<()> {
+        debug!("Deleting model: {}", model);
+
+        let url = format!("{}/api/delete", self.base_url);
+        let response = self
+            .http_client
+            .delete(&url)
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, self.config.timeout))?;
+        Self::check_model_response(response, model).await?;
+
+        info!("Deleted model {}", model);
+        Ok(())
+    }
+
+    async fn warm(&self, model: &str, keep_alive: KeepAlive) -> ModelResult<()> {
+        debug!("Loading model: {}", model);
+
+        // A generate request without a prompt only loads the model
+        let url = format!("{}/api/generate", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .timeout(WARM_TIMEOUT)
+            .json(&serde_json::json!({
+                "model": model,
+                "keep_alive": keep_alive.as_seconds(),
+            }))
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, WARM_TIMEOUT))?;
+        Self::check_model_response(response, model).await?;
+
+        info!("Loaded model {}", model);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedRawResponse {
+    embeddings: Vec<Vec<f32>>,
+    prompt_eval_count: Option<i64>,
+    total_duration: Option<u64>,
+    load_duration: Option<u64>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaProvider {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        debug!("Embedding {} input(s) with model: {}", inputs.len(), model);
+
+        let url = format!("{}/api/embed", self.base_url);
+        let payload = serde_json::json!({
+            "model": model,
+            "input": inputs,
+        });
+
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| Self::handle_reqwest_error(e, self.config.timeout))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Self::status_to_error(status, body, model));
+        }
+
+        let raw: OllamaEmbedRawResponse =
+            response.json().await.map_err(|e| ModelError::Unknown {
+                message: format!("Failed to parse embed response: {}", e),
+            })?;
+
+        if raw.embeddings.len() != inputs.len() {
+            return Err(ModelError::Unknown {
+                message: format!(
+                    "Ollama returned {} embeddings for {} inputs",
+                    raw.embeddings.len(),
+                    inputs.len()
+                ),
+            });
+        }
+
+        let mut result = EmbeddingResponse::new(model, raw.embeddings)?;
+        if let Some(prompt_tokens) = raw.prompt_eval_count {
+            let timings = UsageTimings {
+                total_duration: raw.total_duration.map(Duration::from_nanos),
+                load_duration: raw.load_duration.map(Duration::from_nanos),
+                ..Default::default()
+            };
+            result =
+                result.with_usage(Usage::new(prompt_tokens.max(0) as u32, 0).with_timings(timings));
+        }
+        Ok(result)
+    }
+}
+
+struct OllamaStreamState {
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    decoder: LineDecoder,
+    next_call_index: usize,
+    exhausted: bool,
+    /// Longest silence tolerated between chunks
+    idle_timeout: Duration,
+}
+
+impl OllamaStreamState {
+    fn new(response: reqwest::Response, idle_timeout: Duration) -> Self {
+        Self {
+            bytes: response
+                .bytes_stream()
+                .map(|r| r.map(|b| b.to_vec()))
+                .boxed(),
+            decoder: LineDecoder::default(),
+            next_call_index: 0,
+            exhausted: false,
+            idle_timeout,
+        }
+    }
+
+    async fn next_line(&mut self) -> ModelResult<Option<String>> {
+        loop {
+            if let Some(line) = self.decoder.next_line() {
+                return Ok(Some(line));
+            }
+            if self.exhausted {
+                return Ok(self.decoder.finish());
+            }
+            let next = tokio::time::timeout(self.idle_timeout, self.bytes.next())
+                .await
+                .map_err(|_| ModelError::Timeout {
+                    elapsed: self.idle_timeout,
+                })?;
+            match next {
+                Some(Ok(bytes)) => self.decoder.push(&bytes),
+                Some(Err(e)) => {
+                    return Err(OllamaProvider::handle_reqwest_error(e, self.idle_timeout))
+                }
+                None => self.exhausted = true,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for OllamaProvider {
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        debug!(
+            "Starting streaming chat request with model: {}",
+            request.model
+        );
+
+        let payload = self.build_payload(&request, true)?;
+        // Waiting for the response to start is bounded like a plain request
+        let idle_timeout = self.config.timeout;
+        let connect = async {
+            tokio::time::timeout(idle_timeout, self.post_chat(&self.stream_client, &payload))
+                .await
+                .unwrap_or(Err(ModelError::Timeout {
+                    elapsed: idle_timeout,
+                }))
+        };
+        let response = cancellable(request.cancellation.as_ref(), connect).await?;
+
+        let state = OllamaStreamState::new(response, idle_timeout);
+
+        let stream = futures::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.next_line().await {
+                Ok(Some(line)) => {
+                    let item = serde_json::from_str::<OllamaStreamRecord>(&line)
+                        .map_err(ModelError::from)
+                        .and_then(|record| match record {
+                            OllamaStreamRecord::Error { error } => Err(ModelError::Unknown {
+                                message: format!("Ollama stream error: {}", error),
+                            }),
+                            OllamaStreamRecord::Chunk(raw) => {
+                                Ok(Self::parse_stream_chunk(raw, &mut state.next_call_index))
+                            }
+                        });
+                    let next = if item.is_ok() { Some(state) } else { None };
+                    Some((item, next))
+                }
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        Ok(cancellable_stream(
+            Box::pin(stream),
+            request.cancellation.clone(),
+        ))
+    }
+}
+
+/// A streamed record is either a chunk or an `{"error": ...}` object emitted
+/// when generation fails mid-stream.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OllamaStreamRecord {
+    Error { error: String },
+    Chunk(OllamaChatRawResponse),
 }
 
 #[async_trait]
@@ -959,18 +1544,41 @@ fn calculate_variance(values: &[f64]) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{FunctionDefinition, JsonSchema, PropertySchema, SchemaType};
+    use crate::types::{
+        FunctionDefinition, ImageAttachment, JsonSchema, KeepAlive, PropertySchema, SchemaType,
+    };
     use std::collections::HashMap;
 
+    #[test]
+    fn test_status_to_error_mapping() {
+        assert!(matches!(
+            OllamaProvider::status_to_error(StatusCode::NOT_FOUND, String::new(), "m"),
+            ModelError::ModelNotFound { .. }
+        ));
+        assert!(matches!(
+            OllamaProvider::status_to_error(StatusCode::BAD_REQUEST, String::new(), "m"),
+            ModelError::InvalidConfig { .. }
+        ));
+        assert!(matches!(
+            OllamaProvider::status_to_error(StatusCode::TOO_MANY_REQUESTS, String::new(), "m"),
+            ModelError::RateLimit
+        ));
+        assert!(matches!(
+            OllamaProvider::status_to_error(StatusCode::SERVICE_UNAVAILABLE, String::new(), "m"),
+            ModelError::ServiceUnavailable { .. }
+        ));
+    }
+
     #[test]
     fn test_tool_definition_to_json() {
         let mut props = HashMap::new();
         props.insert(
             "location".to_string(),
             PropertySchema {
-                schema_type: SchemaType::String,
+                schema_type: Some(SchemaType::String),
                 description: Some("The city name".to_string()),
                 items: None,
+                ..Default::default()
             },
         );
         let tool = ToolDefinition {
@@ -981,6 +1589,7 @@ mod tests {
                     schema_type: SchemaType::Object,
                     properties: Some(props),
                     required: Some(vec!["location".to_string()]),
+                    ..Default::default()
                 },
             },
         };
@@ -1005,7 +1614,7 @@ mod tests {
             ChatMessage::assistant("Hi there"),
         ];
 
-        let json = OllamaProvider::messages_to_json(&messages);
+        let json = OllamaProvider::messages_to_json(&messages, false).unwrap();
 
         assert_eq!(json.len(), 3);
         assert_eq!(json[0]["role"], "system");
@@ -1019,7 +1628,7 @@ mod tests {
     #[test]
     fn test_tool_response_message_to_json() {
         let msg = ChatMessage::tool_response("call_123", "The weather is sunny");
-        let json = OllamaProvider::messages_to_json(&[msg]);
+        let json = OllamaProvider::messages_to_json(&[msg], false).unwrap();
 
         assert_eq!(json.len(), 1);
         assert_eq!(json[0]["role"], "tool");
@@ -1027,12 +1636,26 @@ mod tests {
         assert_eq!(json[0]["tool_call_id"], "call_123");
     }
 
+    #[test]
+    fn test_images_to_json() {
+        let msg = ChatMessage::user("What is this?").with_image(ImageAttachment::base64("aGk="));
+        let json = OllamaProvider::messages_to_json(&[msg], false).unwrap();
+        assert_eq!(json[0]["images"], serde_json::json!(["aGk="]));
+
+        let missing = ChatMessage::user("?").with_image(ImageAttachment::path("/nonexistent.png"));
+        assert!(matches!(
+            OllamaProvider::messages_to_json(&[missing], false),
+            Err(ModelError::InvalidConfig { .. })
+        ));
+    }
+
     #[test]
     fn test_parse_tool_call_response() {
         let raw = OllamaChatRawResponse {
             message: OllamaRawMessage {
                 role: "assistant".to_string(),
                 content: String::new(),
+                thinking: None,
                 tool_calls: Some(vec![OllamaRawToolCall {
                     function: OllamaRawFunction {
                         name: "get_weather".to_string(),
@@ -1041,8 +1664,13 @@ mod tests {
                 }]),
             },
             done: true,
+            done_reason: None,
             prompt_eval_count: Some(10),
             eval_count: Some(20),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_duration: None,
+            eval_duration: None,
         };
 
         let response = OllamaProvider::parse_raw_response(raw).unwrap();
@@ -1071,11 +1699,17 @@ mod tests {
             message: OllamaRawMessage {
                 role: "assistant".to_string(),
                 content: "Hello, how can I help you?".to_string(),
+                thinking: None,
                 tool_calls: None,
             },
             done: true,
+            done_reason: None,
             prompt_eval_count: Some(5),
             eval_count: Some(15),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_duration: None,
+            eval_duration: None,
         };
 
         let response = OllamaProvider::parse_raw_response(raw).unwrap();
@@ -1095,11 +1729,17 @@ mod tests {
             message: OllamaRawMessage {
                 role: "assistant".to_string(),
                 content: "No tools needed".to_string(),
+                thinking: None,
                 tool_calls: Some(vec![]),
             },
             done: true,
+            done_reason: None,
             prompt_eval_count: None,
             eval_count: None,
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_duration: None,
+            eval_duration: None,
         };
 
         let response = OllamaProvider::parse_raw_response(raw).unwrap();
@@ -1108,6 +1748,30 @@ mod tests {
         assert!(response.usage.is_none());
     }
 
+    #[test]
+    fn test_parse_response_with_cached_prompt() {
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "message": {"role": "assistant", "content": "Cached"},
+            "done": true,
+            "eval_count": 4,
+            "total_duration": 900_000_000u64,
+            "eval_duration": 800_000_000u64
+        }))
+        .unwrap();
+
+        let usage = OllamaProvider::parse_raw_response(raw)
+            .unwrap()
+            .usage
+            .unwrap();
+
+        assert_eq!(usage.prompt_tokens, 0);
+        assert_eq!(usage.completion_tokens, 4);
+        assert_eq!(usage.tokens_per_second(), Some(5.0));
+        let timings = usage.timings.unwrap();
+        assert_eq!(timings.total_duration, Some(Duration::from_millis(900)));
+        assert!(timings.prompt_eval_duration.is_none());
+    }
+
     #[test]
     fn test_assistant_message_with_tool_calls_to_json() {
         let msg = ChatMessage::assistant_with_tools(
@@ -1121,7 +1785,7 @@ mod tests {
             }],
         );
 
-        let json = OllamaProvider::messages_to_json(&[msg]);
+        let json = OllamaProvider::messages_to_json(&[msg], false).unwrap();
 
         assert_eq!(json[0]["role"], "assistant");
         assert_eq!(json[0]["content"], "I will check the weather");
@@ -1132,6 +1796,204 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_stream_chunks() {
+        let mut next_call_index = 0;
+
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "model": "qwen3:0.6b",
+            "message": {"role": "assistant", "content": "Hel"},
+            "done": false
+        }))
+        .unwrap();
+        let chunk = OllamaProvider::parse_stream_chunk(raw, &mut next_call_index);
+        assert_eq!(chunk.delta.as_deref(), Some("Hel"));
+        assert!(chunk.finish_reason.is_none());
+        assert!(chunk.usage.is_none());
+
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "echo", "arguments": {"message": "hi"}}}]
+            },
+            "done": false
+        }))
+        .unwrap();
+        let chunk = OllamaProvider::parse_stream_chunk(raw, &mut next_call_index);
+        assert!(chunk.delta.is_none());
+        assert_eq!(chunk.tool_calls.as_ref().unwrap()[0].id, "call_0");
+        assert_eq!(next_call_index, 1);
+
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "message": {"role": "assistant", "content": ""},
+            "done": true,
+            "done_reason": "length",
+            "prompt_eval_count": 7,
+            "eval_count": 3,
+            "load_duration": 250_000_000u64,
+            "eval_duration": 1_500_000_000u64
+        }))
+        .unwrap();
+        let chunk = OllamaProvider::parse_stream_chunk(raw, &mut next_call_index);
+        assert_eq!(chunk.finish_reason, Some(FinishReason::Length));
+        let usage = chunk.usage.unwrap();
+        assert_eq!(usage.total_tokens, 10);
+        let timings = usage.timings.as_ref().unwrap();
+        assert_eq!(timings.load_duration, Some(Duration::from_millis(250)));
+        assert_eq!(usage.tokens_per_second(), Some(2.0));
+    }
+
+    #[test]
+    fn test_build_payload_maps_response_format() {
+        let provider = OllamaProvider::with_default_config().unwrap();
+        let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]);
+        assert!(provider
+            .build_payload(&request, false)
+            .unwrap()
+            .get("format")
+            .is_none());
+
+        let json = request.clone().with_response_format(ResponseFormat::Json);
+        assert_eq!(
+            provider.build_payload(&json, false).unwrap()["format"],
+            "json"
+        );
+
+        let schema = serde_json::json!({"type": "object", "required": ["done"]});
+        let constrained =
+            request.with_response_format(ResponseFormat::json_schema("status", schema.clone()));
+        assert_eq!(
+            provider.build_payload(&constrained, true).unwrap()["format"],
+            schema
+        );
+    }
+
+    #[test]
+    fn test_parse_response_separates_reasoning() {
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "message": {
+                "role": "assistant",
+                "content": "<think>\nThe task is done.\n</think>\n\nDONE: finished"
+            },
+            "done": true
+        }))
+        .unwrap();
+        let message = &OllamaProvider::parse_raw_response(raw).unwrap().choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("DONE: finished"));
+        assert_eq!(message.reasoning.as_deref(), Some("The task is done."));
+
+        let raw: OllamaChatRawResponse = serde_json::from_value(serde_json::json!({
+            "message": {"role": "assistant", "content": "PLAN", "thinking": "Need a plan."},
+            "done": true
+        }))
+        .unwrap();
+        let message = &OllamaProvider::parse_raw_response(raw).unwrap().choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("PLAN"));
+        assert_eq!(message.reasoning.as_deref(), Some("Need a plan."));
+    }
+
+    #[test]
+    fn test_build_payload_maps_sampling_options() {
+        let provider = OllamaProvider::new(
+            OllamaConfig::default()
+                .with_context_length(8_192)
+                .with_max_tokens(256),
+        )
+        .unwrap();
+        let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]);
+
+        let unset = OllamaProvider::with_default_config().unwrap();
+        let options = &unset.build_payload(&request, false).unwrap()["options"];
+        assert!(options.get("num_ctx").is_none());
+
+        let options = &provider.build_payload(&request, false).unwrap()["options"];
+        assert_eq!(options["num_ctx"], 8_192);
+        assert_eq!(options["num_predict"], 256);
+        assert!(options.get("seed").is_none());
+
+        let tuned = request
+            .with_max_tokens(64)
+            .with_top_p(0.5)
+            .with_top_k(40)
+            .with_seed(42)
+            .with_stop(["</answer>"])
+            .with_repeat_penalty(1.25)
+            .with_num_ctx(4_096)
+            .with_keep_alive(KeepAlive::Forever);
+        let payload = provider.build_payload(&tuned, false).unwrap();
+        let options = &payload["options"];
+        assert_eq!(options["num_ctx"], 4_096);
+        assert_eq!(options["num_predict"], 64);
+        assert_eq!(options["top_p"], 0.5);
+        assert_eq!(options["top_k"], 40);
+        assert_eq!(options["seed"], 42);
+        assert_eq!(options["stop"], serde_json::json!(["</answer>"]));
+        assert_eq!(options["repeat_penalty"], 1.25);
+        assert_eq!(payload["keep_alive"], -1);
+    }
+
+    #[test]
+    fn test_build_payload_thinking_and_history() {
+        let provider = OllamaProvider::with_default_config().unwrap();
+        let messages = vec![
+            ChatMessage::user("hi"),
+            ChatMessage::assistant("hello").with_reasoning("greet back"),
+        ];
+        let request = ChatRequest::new("qwen3", messages);
+
+        let payload = provider.build_payload(&request, false).unwrap();
+        assert!(payload.get("think").is_none());
+        assert!(payload["messages"][1].get("thinking").is_none());
+
+        let request = request.with_think(false).with_reasoning_in_history();
+        let payload = provider.build_payload(&request, false).unwrap();
+        assert_eq!(payload["think"], false);
+        assert_eq!(payload["messages"][1]["thinking"], "greet back");
+    }
+
+    #[test]
+    fn test_parse_show_response() {
+        let raw: OllamaShowRawResponse = serde_json::from_value(serde_json::json!({
+            "capabilities": ["completion", "tools", "thinking"],
+            "model_info": {
+                "general.architecture": "qwen3",
+                "qwen3.context_length": 40960
+            },
+            "parameters": "temperature 0.6\ntop_k 20\nstop \"<|im_end|>\""
+        }))
+        .unwrap();
+        let caps = OllamaProvider::parse_show_response(&raw);
+        assert_eq!(caps.tools, Some(true));
+        assert_eq!(caps.thinking, Some(true));
+        assert_eq!(caps.vision, Some(false));
+        assert_eq!(caps.context_length, Some(40_960));
+        assert_eq!(caps.temperature, Some(0.6));
+
+        let raw: OllamaShowRawResponse = serde_json::from_value(serde_json::json!({
+            "model_info": {"llama.context_length": 131072},
+            "parameters": "num_ctx 8192"
+        }))
+        .unwrap();
+        let caps = OllamaProvider::parse_show_response(&raw);
+        assert_eq!(caps.tools, None);
+        assert_eq!(caps.context_length, Some(8_192));
+
+        let raw: OllamaShowRawResponse = serde_json::from_value(serde_json::json!({
+            "model_info": {"llama.context_length": 131072},
+            "parameters": "num_ctx 262144"
+        }))
+        .unwrap();
+        let caps = OllamaProvider::parse_show_response(&raw);
+        assert_eq!(caps.context_length, Some(131_072));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_supports_streaming() {
+        let provider = OllamaProvider::with_default_config().unwrap();
+        assert!(provider.as_streaming().is_some());
+    }
+
     #[tokio::test]
     async fn test_provider_creation() {
         let config = OllamaConfig::default();
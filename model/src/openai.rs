@@ -0,0 +1,812 @@
+use crate::config::OpenAiConfig;
+use crate::provider::{cancellable, ModelError, ModelProvider, ModelResult};
+use crate::reasoning::merge_reasoning;
+use crate::types::{
+    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, FunctionCall, MessageRole,
+    ModelInfo, ResponseFormat, ToolCall, ToolChoice, ToolDefinition, Usage,
+};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, error, info};
+
+/// Provider for servers implementing the OpenAI `/v1/chat/completions` API,
+/// such as vLLM and the llama.cpp server.
+pub struct OpenAiProvider {
+    config: OpenAiConfig,
+    http_client: reqwest::Client,
+    base_url: String,
+}
+
+impl OpenAiProvider {
+    pub fn new(config: OpenAiConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+
+        let trimmed = config.base_url.trim_end_matches('/');
+        let base_url = if trimmed.ends_with("/v1") {
+            trimmed.to_string()
+        } else {
+            format!("{}/v1", trimmed)
+        };
+
+        let http_client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| ModelError::Unknown {
+                message: format!("Failed to build HTTP client: {}", e),
+            })?;
+
+        Ok(Self {
+            config,
+            http_client,
+            base_url,
+        })
+    }
+
+    pub fn with_default_config() -> ModelResult<Self> {
+        Self::new(OpenAiConfig::default())
+    }
+
+    fn authorize(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.config.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    /// Images become content parts. Tool messages only take text, so images
+    /// returned by a run of tool calls follow it in a single user message.
+    fn messages_to_json(messages: &[ChatMessage]) -> ModelResult<Vec<Value>> {
+        let mut out = Vec::with_capacity(messages.len());
+        let mut tool_images = Vec::new();
+
+        for msg in messages {
+            if msg.role != MessageRole::Tool && !tool_images.is_empty() {
+                out.push(serde_json::json!({
+                    "role": "user",
+                    "content": std::mem::take(&mut tool_images),
+                }));
+            }
+
+            let role = match &msg.role {
+                MessageRole::System => "system",
+                MessageRole::User => "user",
+                MessageRole::Assistant => "assistant",
+                MessageRole::Tool => "tool",
+            };
+
+            let images = msg
+                .images
+                .iter()
+                .map(|image| {
+                    image.encode().map(|encoded| {
+                        serde_json::json!({
+                            "type": "image_url",
+                            "image_url": {"url": encoded.data_url()}
+                        })
+                    })
+                })
+                .collect::<ModelResult<Vec<_>>>()?;
+
+            let content = if images.is_empty() || msg.role == MessageRole::Tool {
+                tool_images.extend(images);
+                serde_json::json!(msg.content)
+            } else {
+                let mut parts = Vec::with_capacity(images.len() + 1);
+                if let Some(text) = msg.content.as_deref().filter(|t| !t.is_empty()) {
+                    parts.push(serde_json::json!({"type": "text", "text": text}));
+                }
+                parts.extend(images);
+                Value::Array(parts)
+            };
+
+            let mut obj = serde_json::json!({
+                "role": role,
+                "content": content,
+            });
+
+            if let Some(tool_calls) = &msg.tool_calls {
+                if !tool_calls.is_empty() {
+                    let tc_json: Vec<Value> = tool_calls
+                        .iter()
+                        .map(|tc| {
+                            serde_json::json!({
+                                "id": tc.id,
+                                "type": "function",
+                                "function": {
+                                    "name": tc.function.name,
+                                    "arguments": tc.function.arguments.to_string()
+                                }
+                            })
+                        })
+                        .collect();
+                    obj["tool_calls"] = Value::Array(tc_json);
+                }
+            }
+
+            if let Some(tool_call_id) = &msg.tool_call_id {
+                obj["tool_call_id"] = Value::String(tool_call_id.clone());
+            }
+
+            out.push(obj);
+        }
+
+        if !tool_images.is_empty() {
+            out.push(serde_json::json!({"role": "user", "content": tool_images}));
+        }
+
+        Ok(out)
+    }
+
+    fn tools_to_json(tools: &[ToolDefinition]) -> Vec<Value> {
+        tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": tool.function
+                })
+            })
+            .collect()
+    }
+
+    /// Strict mode rejects schemas unless every object closes its properties
+    /// and requires all of them, so only ask for it when the schema complies.
+    fn is_strict_compatible(schema: &Value) -> bool {
+        let Some(object) = schema.as_object() else {
+            return schema
+                .as_array()
+                .is_none_or(|items| items.iter().all(Self::is_strict_compatible));
+        };
+
+        if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+            let required: Vec<&str> = object
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|names| names.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            if !properties
+                .keys()
+                .all(|key| required.contains(&key.as_str()))
+            {
+                return false;
+            }
+            if !properties.values().all(Self::is_strict_compatible) {
+                return false;
+            }
+        }
+        let is_object = object.get("type").and_then(Value::as_str) == Some("object")
+            || object.contains_key("properties");
+        if is_object && object.get("additionalProperties") != Some(&Value::Bool(false)) {
+            return false;
+        }
+
+        ["items", "anyOf", "oneOf", "allOf"]
+            .iter()
+            .filter_map(|key| object.get(*key))
+            .all(Self::is_strict_compatible)
+            && ["$defs", "definitions"]
+                .iter()
+                .filter_map(|key| object.get(*key).and_then(Value::as_object))
+                .all(|defs| defs.values().all(Self::is_strict_compatible))
+    }
+
+    fn tool_choice_to_json(choice: &ToolChoice) -> Value {
+        match choice {
+            ToolChoice::Auto => Value::String("auto".to_string()),
+            ToolChoice::None => Value::String("none".to_string()),
+            ToolChoice::Required => Value::String("required".to_string()),
+            ToolChoice::Specific(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name }
+            }),
+        }
+    }
+
+    fn build_payload(&self, request: &ChatRequest) -> ModelResult<Value> {
+        let temperature = request
+            .temperature
+            .unwrap_or(self.config.default_temperature);
+
+        let mut payload = serde_json::json!({
+            "model": request.model,
+            "messages": Self::messages_to_json(&request.messages)?,
+            "stream": false,
+            "temperature": temperature,
+        });
+
+        if let Some(max_tokens) = request.max_tokens.or(self.config.default_max_tokens) {
+            payload["max_tokens"] = Value::from(max_tokens);
+        }
+        if let Some(top_p) = request.top_p {
+            payload["top_p"] = Value::from(top_p);
+        }
+        if let Some(seed) = request.seed {
+            payload["seed"] = Value::from(seed);
+        }
+        if !request.stop.is_empty() {
+            payload["stop"] = Value::from(request.stop.clone());
+        }
+        // Not part of the OpenAI API, but vLLM and the llama.cpp server take
+        // them as extra sampling parameters. vLLM spells the penalty
+        // `repetition_penalty`; each server ignores the other's name
+        if let Some(top_k) = request.top_k {
+            payload["top_k"] = Value::from(top_k);
+        }
+        if let Some(penalty) = request.repeat_penalty {
+            payload["repeat_penalty"] = Value::from(penalty);
+            payload["repetition_penalty"] = Value::from(penalty);
+        }
+        // These servers fix the context size and keep the model loaded for
+        // as long as they run, so neither can be set per request
+        if request.num_ctx.is_some() || request.keep_alive.is_some() {
+            debug!(
+                "Ignoring num_ctx and keep_alive for {}: set them when starting the server",
+                request.model
+            );
+        }
+
+        if let Some(tools) = &request.tools {
+            if !tools.is_empty() {
+                payload["tools"] = Value::Array(Self::tools_to_json(tools));
+                if let Some(choice) = &request.tool_choice {
+                    payload["tool_choice"] = Self::tool_choice_to_json(choice);
+                }
+            }
+        }
+
+        match &request.response_format {
+            Some(ResponseFormat::Json) => {
+                payload["response_format"] = serde_json::json!({"type": "json_object"});
+            }
+            Some(ResponseFormat::JsonSchema { name, schema }) => {
+                let mut json_schema = serde_json::json!({"name": name, "schema": schema});
+                if Self::is_strict_compatible(schema) {
+                    json_schema["strict"] = Value::Bool(true);
+                }
+                payload["response_format"] = serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": json_schema
+                });
+            }
+            None => {}
+        }
+
+        Ok(payload)
+    }
+
+    /// The wire format encodes tool arguments as a JSON string, but some
+    /// servers send an object instead; accept both.
+    fn parse_arguments(arguments: Value) -> Value {
+        match arguments {
+            Value::String(s) if s.trim().is_empty() => Value::Object(Default::default()),
+            Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+            other => other,
+        }
+    }
+
+    fn parse_finish_reason(reason: Option<&str>, has_tool_calls: bool) -> FinishReason {
+        match reason {
+            Some("length") => FinishReason::Length,
+            Some("content_filter") => FinishReason::ContentFilter,
+            Some("tool_calls") | Some("function_call") => FinishReason::ToolCalls,
+            _ if has_tool_calls => FinishReason::ToolCalls,
+            _ => FinishReason::Stop,
+        }
+    }
+
+    fn parse_raw_response(raw: OpenAiChatRawResponse) -> ModelResult<ChatResponse> {
+        let choices = raw
+            .choices
+            .into_iter()
+            .map(|choice| {
+                let tool_calls: Option<Vec<ToolCall>> = choice
+                    .message
+                    .tool_calls
+                    .filter(|tc| !tc.is_empty())
+                    .map(|calls| {
+                        calls
+                            .into_iter()
+                            .enumerate()
+                            .map(|(idx, tc)| ToolCall {
+                                id: tc.id.unwrap_or_else(|| format!("call_{}", idx)),
+                                function: FunctionCall {
+                                    name: tc.function.name,
+                                    arguments: Self::parse_arguments(tc.function.arguments),
+                                },
+                            })
+                            .collect()
+                    });
+
+                let finish_reason = Self::parse_finish_reason(
+                    choice.finish_reason.as_deref(),
+                    tool_calls.is_some(),
+                );
+
+                let (reasoning, content) = merge_reasoning(
+                    choice.message.reasoning_content,
+                    choice.message.content.as_deref().unwrap_or(""),
+                );
+
+                Choice {
+                    message: ChatMessage {
+                        role: MessageRole::Assistant,
+                        content,
+                        tool_calls,
+                        tool_call_id: None,
+                        reasoning,
+                        reasoning_signature: None,
+                        images: Vec::new(),
+                    },
+                    finish_reason: Some(finish_reason),
+                }
+            })
+            .collect();
+
+        let usage = raw.usage.map(|u| Usage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+            timings: None,
+        });
+
+        Ok(ChatResponse {
+            choices,
+            usage,
+            served_by: None,
+        })
+    }
+
+    fn status_to_error(status: StatusCode, body: String, model: &str) -> ModelError {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ModelError::Authentication,
+            StatusCode::TOO_MANY_REQUESTS => ModelError::RateLimit,
+            StatusCode::NOT_FOUND => ModelError::ModelNotFound {
+                model: model.to_string(),
+            },
+            s if s.is_server_error() => ModelError::ServiceUnavailable {
+                message: format!("OpenAI-compatible API error {}: {}", s, body),
+            },
+            s => ModelError::Unknown {
+                message: format!("OpenAI-compatible API error {}: {}", s, body),
+            },
+        }
+    }
+
+    fn handle_reqwest_error(&self, e: reqwest::Error) -> ModelError {
+        if e.is_timeout() {
+            ModelError::Timeout {
+                elapsed: self.config.timeout,
+            }
+        } else if e.is_connect() {
+            ModelError::ServiceUnavailable {
+                message: "Cannot connect to OpenAI-compatible service".to_string(),
+            }
+        } else {
+            ModelError::Unknown {
+                message: format!("Network error: {}", e),
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAiChatRawResponse {
+    choices: Vec<OpenAiRawChoice>,
+    usage: Option<OpenAiRawUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiRawChoice {
+    message: OpenAiRawMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiRawMessage {
+    content: Option<String>,
+    /// Reasoning reported by servers such as vLLM and llama.cpp
+    #[serde(default)]
+    reasoning_content: Option<String>,
+    tool_calls: Option<Vec<OpenAiRawToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiRawToolCall {
+    id: Option<String>,
+    function: OpenAiRawFunction,
+}
+
+#[derive(Deserialize)]
+struct OpenAiRawFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct OpenAiRawUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct OpenAiModelList {
+    data: Vec<OpenAiModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiModelEntry {
+    id: String,
+    created: Option<i64>,
+}
+
+#[async_trait]
+impl ModelProvider for OpenAiProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        debug!("Starting chat request with model: {}", request.model);
+
+        let payload = self.build_payload(&request)?;
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let raw: OpenAiChatRawResponse = cancellable(request.cancellation.as_ref(), async {
+            let response = self
+                .authorize(self.http_client.post(&url))
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| self.handle_reqwest_error(e))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(Self::status_to_error(status, body, &request.model));
+            }
+
+            response.json().await.map_err(|e| ModelError::Unknown {
+                message: format!("Failed to parse response: {}", e),
+            })
+        })
+        .await?;
+
+        info!("Chat request completed successfully");
+
+        Self::parse_raw_response(raw)
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        debug!("Listing available models");
+
+        let url = format!("{}/models", self.base_url);
+        let response = self
+            .authorize(self.http_client.get(&url))
+            .send()
+            .await
+            .map_err(|e| self.handle_reqwest_error(e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Self::status_to_error(status, body, ""));
+        }
+
+        let list: OpenAiModelList = response.json().await.map_err(|e| ModelError::Unknown {
+            message: format!("Failed to parse model list: {}", e),
+        })?;
+
+        let model_infos: Vec<ModelInfo> = list
+            .data
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model.id,
+                size: None,
+                digest: None,
+                modified_at: model.created.map(|c| c.to_string()),
+            })
+            .collect();
+
+        info!("Retrieved {} models", model_infos.len());
+        Ok(model_infos)
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        debug!("Performing health check");
+
+        match self.list_models().await {
+            Ok(_) => {
+                info!("Health check passed");
+                Ok(())
+            }
+            Err(e) => {
+                error!("Health check failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "openai"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionDefinition, ImageAttachment, JsonSchema, KeepAlive, SchemaType};
+
+    #[test]
+    fn test_base_url_normalization() {
+        let provider =
+            OpenAiProvider::new(OpenAiConfig::default().with_base_url("http://localhost:8080"))
+                .unwrap();
+        assert_eq!(provider.base_url, "http://localhost:8080/v1");
+
+        let provider =
+            OpenAiProvider::new(OpenAiConfig::default().with_base_url("http://localhost:8080/v1/"))
+                .unwrap();
+        assert_eq!(provider.base_url, "http://localhost:8080/v1");
+    }
+
+    #[test]
+    fn test_tool_call_arguments_are_string_encoded() {
+        let msg = ChatMessage::assistant_with_tools(
+            None,
+            vec![ToolCall {
+                id: "call_abc".to_string(),
+                function: FunctionCall {
+                    name: "get_weather".to_string(),
+                    arguments: serde_json::json!({"location": "Paris"}),
+                },
+            }],
+        );
+
+        let json = OpenAiProvider::messages_to_json(&[msg]).unwrap();
+
+        assert_eq!(json[0]["role"], "assistant");
+        assert!(json[0]["content"].is_null());
+        assert_eq!(json[0]["tool_calls"][0]["id"], "call_abc");
+        assert_eq!(json[0]["tool_calls"][0]["type"], "function");
+        assert_eq!(
+            json[0]["tool_calls"][0]["function"]["arguments"],
+            r#"{"location":"Paris"}"#
+        );
+    }
+
+    #[test]
+    fn test_images_become_content_parts() {
+        let image = ImageAttachment::Base64 {
+            data: "aGk=".to_string(),
+            media_type: Some("image/png".to_string()),
+        };
+        let messages = vec![
+            ChatMessage::user("Describe").with_image(image.clone()),
+            ChatMessage::tool_response("call_1", "rendered").with_image(image.clone()),
+            ChatMessage::tool_response("call_2", "rendered").with_image(image),
+            ChatMessage::user("Compare them"),
+        ];
+
+        let json = OpenAiProvider::messages_to_json(&messages).unwrap();
+
+        assert_eq!(json.len(), 5);
+        assert_eq!(json[0]["content"][0]["text"], "Describe");
+        assert_eq!(
+            json[0]["content"][1]["image_url"]["url"],
+            "data:image/png;base64,aGk="
+        );
+        assert_eq!(json[1]["content"], "rendered");
+        assert_eq!(json[2]["role"], "tool");
+        assert_eq!(json[3]["role"], "user");
+        assert_eq!(json[3]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(json[4]["content"], "Compare them");
+    }
+
+    #[test]
+    fn test_tool_choice_to_json() {
+        assert_eq!(
+            OpenAiProvider::tool_choice_to_json(&ToolChoice::Required),
+            "required"
+        );
+        let specific =
+            OpenAiProvider::tool_choice_to_json(&ToolChoice::Specific("echo".to_string()));
+        assert_eq!(specific["function"]["name"], "echo");
+    }
+
+    #[test]
+    fn test_build_payload_includes_tools_and_limits() {
+        let provider = OpenAiProvider::with_default_config().unwrap();
+        let tool = ToolDefinition {
+            function: FunctionDefinition {
+                name: "echo".to_string(),
+                description: "Echo".to_string(),
+                parameters: JsonSchema {
+                    schema_type: SchemaType::Object,
+                    properties: None,
+                    required: None,
+                    ..Default::default()
+                },
+            },
+        };
+        let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")])
+            .with_tools(vec![tool])
+            .with_max_tokens(64)
+            .with_seed(42)
+            .with_stop(["\n\n"]);
+
+        let payload = provider.build_payload(&request).unwrap();
+
+        assert_eq!(payload["model"], "qwen3");
+        assert_eq!(payload["stream"], false);
+        assert_eq!(payload["max_tokens"], 64);
+        assert_eq!(payload["seed"], 42);
+        assert_eq!(payload["stop"][0], "\n\n");
+        assert!(payload.get("top_p").is_none());
+        assert!(payload.get("top_k").is_none());
+        assert_eq!(payload["tool_choice"], "auto");
+        assert_eq!(payload["tools"][0]["function"]["name"], "echo");
+    }
+
+    #[test]
+    fn test_build_payload_passes_extra_sampling_parameters() {
+        let provider = OpenAiProvider::with_default_config().unwrap();
+        let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")])
+            .with_top_k(40)
+            .with_repeat_penalty(1.1)
+            .with_num_ctx(8192)
+            .with_keep_alive(KeepAlive::Forever);
+
+        let payload = provider.build_payload(&request).unwrap();
+
+        assert_eq!(payload["top_k"], 40);
+        assert_eq!(payload["repeat_penalty"], payload["repetition_penalty"]);
+        assert!((payload["repeat_penalty"].as_f64().unwrap() - 1.1).abs() < 1e-6);
+        assert!(payload.get("num_ctx").is_none());
+        assert!(payload.get("keep_alive").is_none());
+    }
+
+    #[test]
+    fn test_build_payload_maps_response_format() {
+        let provider = OpenAiProvider::with_default_config().unwrap();
+        let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]);
+
+        let json = request.clone().with_response_format(ResponseFormat::Json);
+        assert_eq!(
+            provider.build_payload(&json).unwrap()["response_format"]["type"],
+            "json_object"
+        );
+
+        let schema = serde_json::json!({"type": "object"});
+        let constrained = request
+            .clone()
+            .with_response_format(ResponseFormat::json_schema("status", schema.clone()));
+        let format = &provider.build_payload(&constrained).unwrap()["response_format"];
+        assert_eq!(format["type"], "json_schema");
+        assert_eq!(format["json_schema"]["name"], "status");
+        assert_eq!(format["json_schema"]["schema"], schema);
+        assert!(format["json_schema"].get("strict").is_none());
+
+        let closed = serde_json::json!({
+            "type": "object",
+            "properties": {"status": {"type": "string"}},
+            "required": ["status"],
+            "additionalProperties": false
+        });
+        let strict = request.with_response_format(ResponseFormat::json_schema("status", closed));
+        let format = &provider.build_payload(&strict).unwrap()["response_format"];
+        assert_eq!(format["json_schema"]["strict"], true);
+    }
+
+    #[test]
+    fn test_strict_compatibility_checks_nested_objects() {
+        let item = |extra: Value| {
+            let mut item = serde_json::json!({
+                "type": "object",
+                "properties": {"path": {"type": "string"}, "line": {"type": "integer"}},
+                "required": ["path", "line"],
+                "additionalProperties": false
+            });
+            item.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            item
+        };
+        let wrap = |items: Value| {
+            serde_json::json!({
+                "type": "object",
+                "properties": {"hits": {"type": "array", "items": items}},
+                "required": ["hits"],
+                "additionalProperties": false
+            })
+        };
+
+        assert!(OpenAiProvider::is_strict_compatible(&wrap(item(
+            serde_json::json!({})
+        ))));
+        // Optional property
+        assert!(!OpenAiProvider::is_strict_compatible(&wrap(item(
+            serde_json::json!({"required": ["path"]})
+        ))));
+        // Open object
+        assert!(!OpenAiProvider::is_strict_compatible(&wrap(item(
+            serde_json::json!({"additionalProperties": true})
+        ))));
+        assert!(!OpenAiProvider::is_strict_compatible(&serde_json::json!({
+            "anyOf": [{"type": "object", "properties": {}}]
+        })));
+    }
+
+    #[test]
+    fn test_parse_tool_call_response() {
+        let raw: OpenAiChatRawResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_xyz",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"location\":\"Paris\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20}
+        }))
+        .unwrap();
+
+        let response = OpenAiProvider::parse_raw_response(raw).unwrap();
+
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert!(choice.message.content.is_none());
+        let calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "call_xyz");
+        assert_eq!(calls[0].function.arguments["location"], "Paris");
+        assert_eq!(response.usage.unwrap().total_tokens, 20);
+    }
+
+    #[test]
+    fn test_parse_arguments_accepts_objects_and_empty_strings() {
+        assert_eq!(
+            OpenAiProvider::parse_arguments(serde_json::json!({"a": 1}))["a"],
+            1
+        );
+        assert!(OpenAiProvider::parse_arguments(Value::String(String::new())).is_object());
+        assert_eq!(
+            OpenAiProvider::parse_arguments(Value::String("not json".to_string())),
+            "not json"
+        );
+    }
+
+    #[test]
+    fn test_status_to_error_mapping() {
+        assert!(matches!(
+            OpenAiProvider::status_to_error(StatusCode::TOO_MANY_REQUESTS, String::new(), "m"),
+            ModelError::RateLimit
+        ));
+        assert!(matches!(
+            OpenAiProvider::status_to_error(StatusCode::UNAUTHORIZED, String::new(), "m"),
+            ModelError::Authentication
+        ));
+        assert!(matches!(
+            OpenAiProvider::status_to_error(StatusCode::NOT_FOUND, String::new(), "m"),
+            ModelError::ModelNotFound { .. }
+        ));
+        assert!(matches!(
+            OpenAiProvider::status_to_error(StatusCode::BAD_GATEWAY, String::new(), "m"),
+            ModelError::ServiceUnavailable { .. }
+        ));
+    }
+
+    #[test]
+    fn test_provider_name() {
+        let provider = OpenAiProvider::with_default_config().unwrap();
+        assert_eq!(provider.provider_name(), "openai");
+    }
+}
@@ -1,7 +1,15 @@
+use crate::admin::ModelAdmin;
+use crate::capabilities::ModelCapabilities;
+use crate::embedding::EmbeddingProvider;
+use crate::stream::ChatStream;
 use crate::types::{ChatRequest, ChatResponse, ModelInfo};
 use async_trait::async_trait;
+use std::future::Future;
+use std::time::Duration;
 use thiserror::Error;
 
+pub use tokio_util::sync::CancellationToken;
+
 #[derive(Error, Debug)]
 pub enum ModelError {
     #[error("Network error: {0}")]
@@ -25,12 +33,41 @@ pub enum ModelError {
     #[error("Authentication failed")]
     Authentication,
 
+    #[error("Request timed out after {elapsed:?}")]
+    Timeout { elapsed: Duration },
+
+    #[error("Circuit breaker open after repeated backend failures; retry in {retry_in:?}")]
+    CircuitOpen { retry_in: Duration },
+
+    #[error("Request cancelled")]
+    Cancelled,
+
     #[error("Unknown error: {message}")]
     Unknown { message: String },
 }
 
 pub type ModelResult<T> = Result<T, ModelError>;
 
+/// Run `future` until it finishes or `token` is cancelled, whichever comes
+/// first.
+///
+/// On cancellation the future is dropped, which closes any HTTP connection
+/// it holds; Ollama and hosted APIs stop generating once the client goes
+/// away.
+pub async fn cancellable<T>(
+    token: Option<&CancellationToken>,
+    future: impl Future<Output = ModelResult<T>>,
+) -> ModelResult<T> {
+    match token {
+        Some(token) => tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(ModelError::Cancelled),
+            result = future => result,
+        },
+        None => future.await,
+    }
+}
+
 #[async_trait]
 pub trait ModelProvider: Send + Sync {
     async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse>;
@@ -40,17 +77,33 @@ pub trait ModelProvider: Send + Sync {
     async fn health_check(&self) -> ModelResult<()>;
 
     fn provider_name(&self) -> &'static str;
+
+    /// What the backend knows about `model`, or `None` if it cannot say.
+    async fn model_capabilities(&self, _model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        Ok(None)
+    }
+
+    /// Access the streaming interface, if this provider supports it.
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        None
+    }
+
+    /// Access the embedding interface, if this provider supports it.
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        None
+    }
+
+    /// Access model management, if this provider hosts its own models.
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        None
+    }
 }
 
 #[async_trait]
 pub trait StreamingModelProvider: ModelProvider {
-    type StreamItem;
-    type StreamError;
-
-    async fn chat_stream(
-        &self,
-        request: ChatRequest,
-    ) -> ModelResult<impl futures::Stream<Item = Result<Self::StreamItem, Self::StreamError>>>;
+    /// Start a chat request and yield content deltas, tool calls and final
+    /// usage as the model produces them.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream>;
 }
 
 #[cfg(test)]
@@ -70,10 +123,14 @@ mod tests {
                         content: Some("Mock response".to_string()),
                         tool_calls: None,
                         tool_call_id: None,
+                        reasoning: None,
+                        reasoning_signature: None,
+                        images: Vec::new(),
                     },
                     finish_reason: Some(crate::types::FinishReason::Stop),
                 }],
                 usage: None,
+                served_by: None,
             })
         }
 
@@ -111,5 +168,28 @@ mod tests {
 
         provider.health_check().await.unwrap();
         assert_eq!(provider.provider_name(), "mock");
+        assert!(provider.as_streaming().is_none());
+        assert!(provider.as_admin().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancellable_stops_pending_future() {
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel.cancel();
+        });
+
+        let result: ModelResult<()> = cancellable(Some(&token), async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(ModelError::Cancelled)));
+
+        let result = cancellable(Some(&CancellationToken::new()), async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(cancellable(None, async { Ok(2) }).await.unwrap(), 2);
     }
 }
@@ -0,0 +1,215 @@
+//! Reasoning ("thinking") content
+//!
+//! Reasoning models such as qwen3 emit their chain of thought before the
+//! answer. Ollama reports it in a separate `thinking` field when thinking is
+//! enabled on the request, but otherwise, and with many chat templates, it
+//! arrives inline as `<think>...</think>` blocks in the content.
+//! [`split_reasoning`] moves those blocks out of the content so keyword and
+//! JSON parsing only ever see the answer.
+
+const OPEN_TAG: &str = "<think>";
+const CLOSE_TAG: &str = "</think>";
+
+/// Separate inline `<think>` blocks from the answer.
+///
+/// Returns `(reasoning, content)`. Multiple blocks are joined with blank
+/// lines. Text without tags is returned unchanged. An unclosed `<think>` is
+/// treated as reasoning up to the end of the text (generation stopped
+/// mid-thought), and a `</think>` with no opening tag marks everything before
+/// it as reasoning, since some chat templates put the opening tag in the
+/// prompt.
+pub fn split_reasoning(text: &str) -> (Option<String>, String) {
+    if !text.contains(OPEN_TAG) && !text.contains(CLOSE_TAG) {
+        return (None, text.to_string());
+    }
+
+    let mut blocks = Vec::new();
+    let mut content = String::new();
+    let mut rest = text;
+
+    if let Some(close) = rest.find(CLOSE_TAG) {
+        if !rest[..close].contains(OPEN_TAG) {
+            blocks.push(rest[..close].trim());
+            rest = &rest[close + CLOSE_TAG.len()..];
+        }
+    }
+
+    while let Some(open) = rest.find(OPEN_TAG) {
+        content.push_str(&rest[..open]);
+        let inner = &rest[open + OPEN_TAG.len()..];
+        match inner.find(CLOSE_TAG) {
+            Some(close) => {
+                blocks.push(inner[..close].trim());
+                rest = &inner[close + CLOSE_TAG.len()..];
+            }
+            None => {
+                blocks.push(inner.trim());
+                rest = "";
+            }
+        }
+    }
+    content.push_str(rest);
+
+    let blocks: Vec<&str> = blocks.into_iter().filter(|b| !b.is_empty()).collect();
+    let reasoning = (!blocks.is_empty()).then(|| blocks.join("\n\n"));
+    (reasoning, content.trim().to_string())
+}
+
+/// Merge separately reported reasoning with any found inline in `content`.
+///
+/// Returns `(reasoning, content)` with empty values mapped to `None`.
+pub(crate) fn merge_reasoning(
+    reported: Option<String>,
+    content: &str,
+) -> (Option<String>, Option<String>) {
+    let (inline, content) = split_reasoning(content);
+    let reported = reported.filter(|r| !r.trim().is_empty());
+    let reasoning = match (reported, inline) {
+        (Some(reported), Some(inline)) => Some(format!("{}\n\n{}", reported.trim(), inline)),
+        (reported, inline) => reported.map(|r| r.trim().to_string()).or(inline),
+    };
+    let content = (!content.is_empty()).then_some(content);
+    (reasoning, content)
+}
+
+/// Streaming counterpart of [`split_reasoning`]: drops inline `<think>`
+/// blocks from content deltas as they arrive, so they can be shown live.
+///
+/// A tag may be split across deltas, so text that could start one is held
+/// back until the next delta decides it. Unlike [`split_reasoning`], a
+/// `</think>` with no opening tag cannot be recognised after the fact, and
+/// the text before it has already been passed through.
+#[derive(Debug, Default)]
+pub struct ThinkTagFilter {
+    in_think: bool,
+    after_close: bool,
+    pending: String,
+}
+
+impl ThinkTagFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a content delta, returning the part of it that is answer text.
+    pub fn push(&mut self, delta: &str) -> String {
+        self.pending.push_str(delta);
+        let mut answer = String::new();
+        loop {
+            let tag = if self.in_think { CLOSE_TAG } else { OPEN_TAG };
+            let (end, found) = match self.pending.find(tag) {
+                Some(pos) => (pos, true),
+                None => (
+                    self.pending.len() - partial_tag_len(&self.pending, tag),
+                    false,
+                ),
+            };
+            let text: String = self.pending.drain(..end).collect();
+            if !self.in_think {
+                self.emit(&text, &mut answer);
+            }
+            if !found {
+                return answer;
+            }
+            self.pending.drain(..tag.len());
+            self.after_close = self.in_think;
+            self.in_think = !self.in_think;
+        }
+    }
+
+    /// Answer text still held back when the stream ends.
+    pub fn finish(&mut self) -> String {
+        let pending = std::mem::take(&mut self.pending);
+        let mut answer = String::new();
+        if !self.in_think {
+            self.emit(&pending, &mut answer);
+        }
+        answer
+    }
+
+    /// Append answer text, dropping the blank lines that follow a block.
+    fn emit(&mut self, text: &str, answer: &mut String) {
+        let text = if self.after_close {
+            text.trim_start()
+        } else {
+            text
+        };
+        if !text.is_empty() {
+            self.after_close = false;
+            answer.push_str(text);
+        }
+    }
+}
+
+/// Length of the longest suffix of `text` that is a proper prefix of `tag`.
+fn partial_tag_len(text: &str, tag: &str) -> usize {
+    (1..tag.len())
+        .rev()
+        .find(|&len| text.ends_with(&tag[..len]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_reasoning() {
+        let cases = [
+            ("Just an answer", None, "Just an answer"),
+            (
+                "<think>\nThe user wants X.\n</think>\n\nPLAN",
+                Some("The user wants X."),
+                "PLAN",
+            ),
+            (
+                "<think>one</think>A<think>two</think>B",
+                Some("one\n\ntwo"),
+                "AB",
+            ),
+            ("<think>\n\n</think>\n\nDONE", None, "DONE"),
+            ("<think>still going", Some("still going"), ""),
+            (
+                "implicit open</think>ANSWER",
+                Some("implicit open"),
+                "ANSWER",
+            ),
+        ];
+        for (text, reasoning, content) in cases {
+            let (r, c) = split_reasoning(text);
+            assert_eq!(r.as_deref(), reasoning, "reasoning of {:?}", text);
+            assert_eq!(c, content, "content of {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_merge_reasoning() {
+        let (reasoning, content) =
+            merge_reasoning(Some("field".to_string()), "<think>tag</think>ok");
+        assert_eq!(reasoning.as_deref(), Some("field\n\ntag"));
+        assert_eq!(content.as_deref(), Some("ok"));
+
+        let (reasoning, content) = merge_reasoning(Some(String::new()), "");
+        assert!(reasoning.is_none());
+        assert!(content.is_none());
+    }
+
+    #[test]
+    fn test_think_tag_filter_drops_blocks_split_across_deltas() {
+        let cases: [(&[&str], &str); 4] = [
+            (&["Just ", "an answer"], "Just an answer"),
+            (
+                &["<think>", "weigh options", "</think>", "\n\nDONE"],
+                "DONE",
+            ),
+            (&["<th", "ink>plan</th", "ink>\nA <", "b>"], "A <b>"),
+            (&["A<think>one</think>B<think>still going"], "AB"),
+        ];
+        for (deltas, expected) in cases {
+            let mut filter = ThinkTagFilter::new();
+            let mut answer: String = deltas.iter().map(|delta| filter.push(delta)).collect();
+            answer.push_str(&filter.finish());
+            assert_eq!(answer, expected, "answer of {:?}", deltas);
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Retries, deadlines and circuit breaking
+//!
+//! [`RetryingProvider`] wraps another [`ModelProvider`] and retries requests
+//! that failed for reasons worth retrying (see [`is_retryable`]) with jittered
+//! exponential backoff, within an optional deadline covering every attempt.
+//! Errors that retrying cannot fix, such as an unknown model or a bad
+//! configuration, are returned at once.
+//!
+//! A [`CircuitBreaker`] can be shared between wrappers of the same backend.
+//! After enough consecutive backend failures it opens and requests fail fast
+//! with [`ModelError::CircuitOpen`] until a cool-down has passed; then a
+//! single trial request decides whether it closes again.
+
+use crate::admin::ModelAdmin;
+use crate::capabilities::ModelCapabilities;
+use crate::config::{CircuitBreakerConfig, RetryConfig};
+use crate::embedding::EmbeddingProvider;
+use crate::provider::{
+    cancellable, CancellationToken, ModelError, ModelProvider, ModelResult, StreamingModelProvider,
+};
+use crate::stream::ChatStream;
+use crate::types::{ChatRequest, ChatResponse, ModelInfo};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Whether a request that failed with `error` may succeed if sent again.
+///
+/// Transport failures, timeouts, rate limiting and overloaded servers are
+/// retryable; configuration, authentication, unknown models, malformed
+/// payloads, an open circuit and unclassified backend errors are not.
+pub fn is_retryable(error: &ModelError) -> bool {
+    match error {
+        ModelError::Network(e) => e
+            .status()
+            .map(|s| s.is_server_error() || s == reqwest::StatusCode::TOO_MANY_REQUESTS)
+            .unwrap_or(true),
+        ModelError::ServiceUnavailable { .. }
+        | ModelError::RateLimit
+        | ModelError::Timeout { .. } => true,
+        ModelError::Serialization(_)
+        | ModelError::ModelNotFound { .. }
+        | ModelError::InvalidConfig { .. }
+        | ModelError::Authentication
+        | ModelError::CircuitOpen { .. }
+        | ModelError::Cancelled
+        | ModelError::Unknown { .. } => false,
+    }
+}
+
+/// Observable state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests fail fast
+    Open,
+    /// One trial request is deciding whether to close again
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+/// Counts consecutive backend failures and stops requests while a backend
+/// appears to be down.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+        Ok(Self {
+            config,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        })
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.state.lock().unwrap() {
+            BreakerState::Closed { .. } => CircuitState::Closed,
+            BreakerState::Open { until } if Instant::now() >= until => CircuitState::HalfOpen,
+            BreakerState::Open { .. } => CircuitState::Open,
+            BreakerState::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Permission to send a request, or [`ModelError::CircuitOpen`].
+    ///
+    /// Once the cool-down has passed only one caller is let through as the
+    /// trial; others keep failing fast until it reports back. A trial that
+    /// never reports back (because it was cancelled) is replaced after
+    /// another cool-down.
+    pub fn acquire(&self) -> ModelResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } if now < until => Err(ModelError::CircuitOpen {
+                retry_in: until - now,
+            }),
+            BreakerState::HalfOpen { since } if now < since + self.config.cool_down => {
+                Err(ModelError::CircuitOpen {
+                    retry_in: since + self.config.cool_down - now,
+                })
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                debug!("Circuit half-open, letting a trial request through");
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// The backend answered, even if with an error of its own.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, BreakerState::Closed { .. }) {
+            debug!("Circuit closed");
+        }
+        *state = BreakerState::Closed { failures: 0 };
+    }
+
+    /// The backend failed in a way that suggests it is unavailable.
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                self.config.failure_threshold
+            }
+        };
+        *state = if failures >= self.config.failure_threshold {
+            warn!(
+                "Circuit opened after {} consecutive failures, pausing requests for {:?}",
+                failures, self.config.cool_down
+            );
+            BreakerState::Open {
+                until: Instant::now() + self.config.cool_down,
+            }
+        } else {
+            BreakerState::Closed { failures }
+        };
+    }
+}
+
+/// Wraps a provider and retries its failed chat requests.
+pub struct RetryingProvider {
+    inner: Arc<dyn ModelProvider>,
+    config: RetryConfig,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl RetryingProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>, config: RetryConfig) -> ModelResult<Self> {
+        config
+            .validate()
+            .map_err(|msg| ModelError::InvalidConfig { message: msg })?;
+        Ok(Self {
+            inner,
+            config,
+            breaker: None,
+        })
+    }
+
+    /// Guard requests with `breaker`, which may be shared with other
+    /// wrappers of the same backend.
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&Arc<CircuitBreaker>> {
+        self.breaker.as_ref()
+    }
+
+    /// Run `call` until it succeeds, fails for good, runs out of attempts,
+    /// passes the deadline or `cancellation` fires.
+    async fn run<T, F, Fut>(
+        &self,
+        cancellation: Option<&CancellationToken>,
+        mut call: F,
+    ) -> ModelResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ModelResult<T>>,
+    {
+        let started = Instant::now();
+        let deadline = self.config.deadline.map(|d| started + d);
+        let mut attempt = 0;
+        loop {
+            if let Some(breaker) = &self.breaker {
+                breaker.acquire()?;
+            }
+
+            let attempt_call = async {
+                match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, call())
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(ModelError::Timeout {
+                                elapsed: started.elapsed(),
+                            })
+                        }),
+                    None => call().await,
+                }
+            };
+            let result = cancellable(cancellation, attempt_call).await;
+
+            let error = match result {
+                Ok(value) => {
+                    if let Some(breaker) = &self.breaker {
+                        breaker.record_success();
+                    }
+                    return Ok(value);
+                }
+                Err(ModelError::Cancelled) => return Err(ModelError::Cancelled),
+                Err(e) => e,
+            };
+
+            let retryable = is_retryable(&error);
+            if let Some(breaker) = &self.breaker {
+                if retryable {
+                    breaker.record_failure();
+                } else {
+                    breaker.record_success();
+                }
+            }
+
+            attempt += 1;
+            if !retryable || attempt >= self.config.max_attempts {
+                return Err(error);
+            }
+            let delay = self.config.delay_for(attempt - 1);
+            if deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+                return Err(error);
+            }
+            warn!(
+                "{} request failed (attempt {}/{}), retrying in {:?}: {}",
+                self.inner.provider_name(),
+                attempt,
+                self.config.max_attempts,
+                delay,
+                error
+            );
+            cancellable(cancellation, async {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            })
+            .await?;
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for RetryingProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        self.run(request.cancellation.as_ref(), || {
+            self.inner.chat(request.clone())
+        })
+        .await
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        self.inner.health_check().await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        self.inner.model_capabilities(model).await
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        self.inner
+            .as_streaming()
+            .map(|_| self as &dyn StreamingModelProvider)
+    }
+
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        self.inner.as_embedding()
+    }
+
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        self.inner.as_admin()
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for RetryingProvider {
+    /// Retries opening the stream; once tokens are flowing an error is passed
+    /// through to the caller.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let streaming = self
+            .inner
+            .as_streaming()
+            .ok_or_else(|| ModelError::InvalidConfig {
+                message: format!("{} does not support streaming", self.inner.provider_name()),
+            })?;
+        self.run(request.cancellation.as_ref(), || {
+            streaming.chat_stream(request.clone())
+        })
+        .await
+    }
+}
+
+impl std::fmt::Debug for RetryingProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryingProvider")
+            .field("inner", &self.inner.provider_name())
+            .field("config", &self.config)
+            .field("breaker", &self.breaker)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::collect_stream;
+    use crate::test_support::MockProvider;
+    use crate::types::ChatMessage;
+    use std::time::Duration;
+
+    /// Fails with the queued errors in order, then answers "ok".
+    fn flaky(failures: Vec<fn() -> ModelError>) -> Arc<MockProvider> {
+        Arc::new(MockProvider::replying("ok").with_failures(failures))
+    }
+
+    fn unavailable() -> ModelError {
+        ModelError::ServiceUnavailable {
+            message: "connection refused".to_string(),
+        }
+    }
+
+    fn not_found() -> ModelError {
+        ModelError::ModelNotFound {
+            model: "missing".to_string(),
+        }
+    }
+
+    /// No waiting between attempts
+    fn immediate(max_attempts: u32) -> RetryConfig {
+        RetryConfig::new()
+            .with_max_attempts(max_attempts)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .with_jitter(0.0)
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest::new("m", vec![ChatMessage::user("hi")])
+    }
+
+    #[test]
+    fn test_error_classification() {
+        assert!(is_retryable(&unavailable()));
+        assert!(is_retryable(&ModelError::RateLimit));
+        assert!(is_retryable(&ModelError::Timeout {
+            elapsed: Duration::from_secs(1)
+        }));
+        assert!(!is_retryable(&not_found()));
+        assert!(!is_retryable(&ModelError::InvalidConfig {
+            message: "bad".to_string()
+        }));
+        assert!(!is_retryable(&ModelError::Authentication));
+        assert!(!is_retryable(&ModelError::CircuitOpen {
+            retry_in: Duration::ZERO
+        }));
+        assert!(!is_retryable(&ModelError::Unknown {
+            message: "HTTP 418".to_string()
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let inner = flaky(vec![unavailable, || ModelError::RateLimit]);
+        let provider = RetryingProvider::new(inner.clone(), immediate(3)).unwrap();
+
+        let response = provider.chat(request()).await.unwrap();
+
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("ok"));
+        assert_eq!(inner.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let inner = flaky(vec![unavailable; 5]);
+        let provider = RetryingProvider::new(inner.clone(), immediate(2)).unwrap();
+
+        let err = provider.chat(request()).await.unwrap_err();
+
+        assert!(matches!(err, ModelError::ServiceUnavailable { .. }));
+        assert_eq!(inner.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fatal_errors_are_not_retried() {
+        let inner = flaky(vec![not_found]);
+        let provider = RetryingProvider::new(inner.clone(), immediate(3)).unwrap();
+
+        let err = provider.chat(request()).await.unwrap_err();
+
+        assert!(matches!(err, ModelError::ModelNotFound { .. }));
+        assert_eq!(inner.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_bounds_slow_requests() {
+        let inner = Arc::new(MockProvider::replying("ok").with_delay(Duration::from_secs(5)));
+        let config = immediate(3).with_deadline(Duration::from_millis(50));
+        let provider = RetryingProvider::new(inner.clone(), config).unwrap();
+
+        let started = Instant::now();
+        let err = provider.chat(request()).await.unwrap_err();
+
+        assert!(matches!(err, ModelError::Timeout { .. }));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(inner.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_recovers() {
+        let breaker = Arc::new(
+            CircuitBreaker::new(
+                CircuitBreakerConfig::new()
+                    .with_failure_threshold(2)
+                    .with_cool_down(Duration::from_millis(50)),
+            )
+            .unwrap(),
+        );
+        let inner = flaky(vec![unavailable, unavailable]);
+        let provider = RetryingProvider::new(inner.clone(), immediate(5))
+            .unwrap()
+            .with_circuit_breaker(Arc::clone(&breaker));
+
+        let err = provider.chat(request()).await.unwrap_err();
+        assert!(matches!(err, ModelError::CircuitOpen { .. }), "{}", err);
+        assert_eq!(inner.requests().len(), 2);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // Fails fast without reaching the backend
+        assert!(provider.chat(request()).await.is_err());
+        assert_eq!(inner.requests().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        provider.chat(request()).await.unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(inner.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_trial_reopens_circuit() {
+        let breaker = CircuitBreaker::new(
+            CircuitBreakerConfig::new()
+                .with_failure_threshold(3)
+                .with_cool_down(Duration::from_millis(20)),
+        )
+        .unwrap();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        assert!(breaker.acquire().is_err());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        breaker.acquire().unwrap();
+        // Only one trial at a time
+        assert!(breaker.acquire().is_err());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_stream_opening_is_retried() {
+        let inner = flaky(vec![unavailable]);
+        let provider = RetryingProvider::new(inner.clone(), immediate(2)).unwrap();
+
+        let stream = provider
+            .as_streaming()
+            .unwrap()
+            .chat_stream(request())
+            .await
+            .unwrap();
+        let response = collect_stream(stream, |_| {}).await.unwrap();
+
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("ok"));
+        assert_eq!(inner.requests().len(), 2);
+    }
+}
@@ -0,0 +1,684 @@
+//! Routing across several model backends
+//!
+//! [`RouterProvider`] is itself a [`ModelProvider`] that holds an ordered list of
+//! [`RouteTarget`]s. Each request is sent to the targets whose model prefix
+//! matches, in an order decided by the [`RoutingPolicy`]; transient failures
+//! (unreachable host, rate limiting) move on to the next target. The label of
+//! the target that answered is recorded in [`ChatResponse::served_by`].
+//! Embedding requests fail over the same way across the matching targets
+//! that can embed. Model management goes to every matching target that
+//! manages models for pulls and deletes, and to the first healthy one for
+//! the rest.
+
+use crate::admin::{ModelAdmin, ModelDetails, PullProgress, PullStream};
+use crate::capabilities::ModelCapabilities;
+use crate::embedding::{EmbeddingProvider, EmbeddingResponse};
+use crate::provider::{ModelError, ModelProvider, ModelResult, StreamingModelProvider};
+use crate::stream::{single_chunk_stream, ChatStream};
+use crate::types::{ChatRequest, ChatResponse, KeepAlive, ModelInfo};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// How the router orders matching targets for each request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutingPolicy {
+    /// Always try targets in the order they were added
+    #[default]
+    Fallback,
+    /// Rotate the starting target on every request, falling back to the rest
+    RoundRobin,
+}
+
+/// One backend the router can send requests to.
+#[derive(Clone)]
+pub struct RouteTarget {
+    /// Label reported in `ChatResponse::served_by`
+    pub name: String,
+    pub provider: Arc<dyn ModelProvider>,
+    /// Only route models whose name starts with this prefix
+    pub model_prefix: Option<String>,
+    /// Replace the requested model name when sending to this target
+    pub model: Option<String>,
+}
+
+impl RouteTarget {
+    pub fn new(name: impl Into<String>, provider: Arc<dyn ModelProvider>) -> Self {
+        Self {
+            name: name.into(),
+            provider,
+            model_prefix: None,
+            model: None,
+        }
+    }
+
+    pub fn with_model_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.model_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    fn matches(&self, model: &str) -> bool {
+        self.model_prefix
+            .as_deref()
+            .map(|prefix| model.starts_with(prefix))
+            .unwrap_or(true)
+    }
+
+    fn prepare(&self, request: &ChatRequest) -> ChatRequest {
+        let mut request = request.clone();
+        if let Some(model) = &self.model {
+            request.model = model.clone();
+        }
+        request
+    }
+}
+
+impl std::fmt::Debug for RouteTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouteTarget")
+            .field("name", &self.name)
+            .field("provider", &self.provider.provider_name())
+            .field("model_prefix", &self.model_prefix)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+/// Errors worth retrying on a different backend.
+pub fn is_failover_error(error: &ModelError) -> bool {
+    matches!(
+        error,
+        ModelError::ServiceUnavailable { .. }
+            | ModelError::RateLimit
+            | ModelError::Network(_)
+            | ModelError::Timeout { .. }
+            | ModelError::CircuitOpen { .. }
+    )
+}
+
+/// A [`ModelProvider`] that distributes requests over other providers.
+#[derive(Debug)]
+pub struct RouterProvider {
+    targets: Vec<RouteTarget>,
+    policy: RoutingPolicy,
+    next: AtomicUsize,
+}
+
+impl RouterProvider {
+    pub fn new(policy: RoutingPolicy) -> Self {
+        Self {
+            targets: Vec::new(),
+            policy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_target(mut self, target: RouteTarget) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    pub fn targets(&self) -> &[RouteTarget] {
+        &self.targets
+    }
+
+    pub fn policy(&self) -> RoutingPolicy {
+        self.policy
+    }
+
+    /// Targets eligible for `model`, in the order they should be tried.
+    fn candidates(&self, model: &str) -> ModelResult<Vec<&RouteTarget>> {
+        let mut candidates: Vec<&RouteTarget> =
+            self.targets.iter().filter(|t| t.matches(model)).collect();
+        if candidates.is_empty() {
+            return Err(ModelError::ModelNotFound {
+                model: model.to_string(),
+            });
+        }
+        if self.policy == RoutingPolicy::RoundRobin {
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+            candidates.rotate_left(start);
+        }
+        Ok(candidates)
+    }
+
+    /// Targets eligible for `model` that manage models, in the order they
+    /// were added, with the model name each one knows it by.
+    fn admin_targets<'a>(
+        &'a self,
+        model: &'a str,
+    ) -> ModelResult<Vec<(&'a RouteTarget, &'a dyn ModelAdmin, &'a str)>> {
+        let matching: Vec<&RouteTarget> =
+            self.targets.iter().filter(|t| t.matches(model)).collect();
+        if matching.is_empty() {
+            return Err(ModelError::ModelNotFound {
+                model: model.to_string(),
+            });
+        }
+        let admins: Vec<_> = matching
+            .into_iter()
+            .filter_map(|t| {
+                let admin = t.provider.as_admin()?;
+                Some((t, admin, t.model.as_deref().unwrap_or(model)))
+            })
+            .collect();
+        if admins.is_empty() {
+            return Err(ModelError::InvalidConfig {
+                message: format!("No backend for {} manages models", model),
+            });
+        }
+        Ok(admins)
+    }
+
+    /// The first target for `model` that manages models and is healthy.
+    async fn healthy_admin<'a>(
+        &'a self,
+        model: &'a str,
+    ) -> ModelResult<(&'a dyn ModelAdmin, &'a str)> {
+        let mut last_error = None;
+        for (target, admin, name) in self.admin_targets(model)? {
+            match target.provider.health_check().await {
+                Ok(()) => return Ok((admin, name)),
+                Err(e) => {
+                    warn!("Backend {} is unhealthy: {}", target.name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("at least one candidate was tried"))
+    }
+
+    fn served_by(target: &RouteTarget, inner: Option<String>) -> String {
+        match inner {
+            Some(inner) => format!("{}/{}", target.name, inner),
+            None => target.name.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for RouterProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        let mut last_error = None;
+        for target in self.candidates(&request.model)? {
+            match target.provider.chat(target.prepare(&request)).await {
+                Ok(mut response) => {
+                    debug!("Request for {} served by {}", request.model, target.name);
+                    response.served_by = Some(Self::served_by(target, response.served_by));
+                    return Ok(response);
+                }
+                Err(e) if is_failover_error(&e) => {
+                    warn!("Backend {} failed, trying next: {}", target.name, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.expect("at least one candidate was tried"))
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        let mut models: Vec<ModelInfo> = Vec::new();
+        let mut last_error = None;
+        let mut any_ok = false;
+        for target in &self.targets {
+            match target.provider.list_models().await {
+                Ok(listed) => {
+                    any_ok = true;
+                    for model in listed {
+                        if !models.iter().any(|m| m.name == model.name) {
+                            models.push(model);
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("Backend {} could not list models: {}", target.name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match (any_ok, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(models),
+        }
+    }
+
+    /// Healthy as long as at least one backend is.
+    async fn health_check(&self) -> ModelResult<()> {
+        let mut last_error = ModelError::InvalidConfig {
+            message: "Router has no targets".to_string(),
+        };
+        for target in &self.targets {
+            match target.provider.health_check().await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!("Backend {} is unhealthy: {}", target.name, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "router"
+    }
+
+    /// Asks each backend that serves `model` in turn until one can answer.
+    async fn model_capabilities(&self, model: &str) -> ModelResult<Option<ModelCapabilities>> {
+        let mut last_error = None;
+        for target in self.candidates(model)? {
+            let name = target.model.as_deref().unwrap_or(model);
+            match target.provider.model_capabilities(name).await {
+                Ok(Some(caps)) => return Ok(Some(caps)),
+                Ok(None) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        Some(self)
+    }
+
+    /// Offered when any backend can embed.
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        self.targets
+            .iter()
+            .any(|t| t.provider.as_embedding().is_some())
+            .then_some(self as &dyn EmbeddingProvider)
+    }
+
+    /// Offered when any backend manages models.
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        self.targets
+            .iter()
+            .any(|t| t.provider.as_admin().is_some())
+            .then_some(self as &dyn ModelAdmin)
+    }
+}
+
+#[async_trait]
+impl ModelAdmin for RouterProvider {
+    /// Pulls on every backend for `model`. Updates are labelled with the
+    /// backend they come from, and a single final "success" follows once
+    /// every backend has reported its own.
+    async fn pull(&self, model: &str) -> ModelResult<PullStream> {
+        let failed = Arc::new(AtomicBool::new(false));
+        let mut pulls: Vec<PullStream> = Vec::new();
+        for (target, admin, name) in self.admin_targets(model)? {
+            let stream = admin.pull(name).await?;
+            let label = target.name.clone();
+            let succeeded = Arc::new(AtomicBool::new(false));
+
+            let (seen, errored) = (Arc::clone(&succeeded), Arc::clone(&failed));
+            let updates = stream.map(move |progress| {
+                let mut progress =
+                    progress.inspect_err(|_| errored.store(true, Ordering::Relaxed))?;
+                if progress.is_success() {
+                    seen.store(true, Ordering::Relaxed);
+                }
+                progress.status = format!("{}: {}", label, progress.status);
+                Ok(progress)
+            });
+
+            let (failed, label, name) =
+                (Arc::clone(&failed), target.name.clone(), name.to_string());
+            let unfinished = futures::stream::once(async move {
+                if succeeded.load(Ordering::Relaxed) || failed.swap(true, Ordering::Relaxed) {
+                    return None;
+                }
+                Some(Err(ModelError::Unknown {
+                    message: format!("Pull of {} on {} ended without success", name, label),
+                }))
+            })
+            .filter_map(futures::future::ready);
+
+            pulls.push(Box::pin(updates.chain(unfinished)));
+        }
+
+        let done = futures::stream::once(async move {
+            (!failed.load(Ordering::Relaxed)).then(|| {
+                Ok(PullProgress {
+                    status: "success".to_string(),
+                    ..Default::default()
+                })
+            })
+        })
+        .filter_map(futures::future::ready);
+        Ok(Box::pin(futures::stream::iter(pulls).flatten().chain(done)))
+    }
+
+    async fn show(&self, model: &str) -> ModelResult<ModelDetails> {
+        let (admin, name) = self.healthy_admin(model).await?;
+        admin.show(name).await
+    }
+
+    /// Deletes from every backend for `model`, succeeding if any had it.
+    async fn delete(&self, model: &str) -> ModelResult<()> {
+        let mut deleted = false;
+        for (target, admin, name) in self.admin_targets(model)? {
+            match admin.delete(name).await {
+                Ok(()) => {
+                    debug!("Deleted {} from {}", name, target.name);
+                    deleted = true;
+                }
+                Err(ModelError::ModelNotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if deleted {
+            Ok(())
+        } else {
+            Err(ModelError::ModelNotFound {
+                model: model.to_string(),
+            })
+        }
+    }
+
+    async fn warm(&self, model: &str, keep_alive: KeepAlive) -> ModelResult<()> {
+        let (admin, name) = self.healthy_admin(model).await?;
+        admin.warm(name, keep_alive).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RouterProvider {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        let mut last_error = None;
+        for target in self.candidates(model)? {
+            let Some(embedder) = target.provider.as_embedding() else {
+                continue;
+            };
+            let name = target.model.as_deref().unwrap_or(model);
+            match embedder.embed(name, inputs).await {
+                Ok(response) => {
+                    debug!("Embeddings for {} served by {}", model, target.name);
+                    return Ok(response);
+                }
+                Err(e) if is_failover_error(&e) => {
+                    warn!("Backend {} failed, trying next: {}", target.name, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ModelError::InvalidConfig {
+            message: format!("No backend for {} supports embeddings", model),
+        }))
+    }
+}
+
+#[async_trait]
+impl StreamingModelProvider for RouterProvider {
+    /// Fails over only while opening the stream; once tokens are flowing an
+    /// error is passed through to the caller.
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let mut last_error = None;
+        for target in self.candidates(&request.model)? {
+            let prepared = target.prepare(&request);
+            let result = match target.provider.as_streaming() {
+                Some(streaming) => streaming.chat_stream(prepared).await,
+                None => target
+                    .provider
+                    .chat(prepared)
+                    .await
+                    .map(single_chunk_stream),
+            };
+            match result {
+                Ok(stream) => {
+                    debug!("Stream for {} served by {}", request.model, target.name);
+                    let target = target.clone();
+                    return Ok(Box::pin(stream.map(move |chunk| {
+                        chunk.map(|mut chunk| {
+                            chunk.served_by = Some(Self::served_by(&target, chunk.served_by));
+                            chunk
+                        })
+                    })));
+                }
+                Err(e) if is_failover_error(&e) => {
+                    warn!("Backend {} failed, trying next: {}", target.name, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.expect("at least one candidate was tried"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::collect_stream;
+    use crate::test_support::MockProvider;
+    use crate::types::{ChatMessage, FinishReason};
+
+    /// Answers with its own name and manages only the model it is named
+    /// after.
+    fn backend(name: &str) -> Arc<MockProvider> {
+        Arc::new(MockProvider::replying(name).with_models(&[name]))
+    }
+
+    fn failing(failure: fn() -> ModelError) -> Arc<MockProvider> {
+        Arc::new(MockProvider::failing(failure))
+    }
+
+    fn unavailable() -> ModelError {
+        ModelError::ServiceUnavailable {
+            message: "connection refused".to_string(),
+        }
+    }
+
+    fn auth_failure() -> ModelError {
+        ModelError::Authentication
+    }
+
+    fn request(model: &str) -> ChatRequest {
+        ChatRequest::new(model, vec![ChatMessage::user("hi")])
+    }
+
+    fn content(response: &ChatResponse) -> &str {
+        response.choices[0].message.content.as_deref().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fallback_skips_unavailable_backend() {
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", failing(unavailable)))
+            .with_target(RouteTarget::new("host-b", backend("b")));
+
+        let response = router.chat(request("qwen3:0.6b")).await.unwrap();
+
+        assert_eq!(content(&response), "b");
+        assert_eq!(response.served_by.as_deref(), Some("host-b"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_stops_on_non_transient_error() {
+        let second = backend("b");
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", failing(auth_failure)))
+            .with_target(RouteTarget::new("host-b", second.clone()));
+
+        let err = router.chat(request("qwen3:0.6b")).await.unwrap_err();
+
+        assert!(matches!(err, ModelError::Authentication));
+        assert!(second.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_all_backends_down_returns_last_error() {
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", failing(unavailable)))
+            .with_target(RouteTarget::new(
+                "host-b",
+                failing(|| ModelError::RateLimit),
+            ));
+
+        let err = router.chat(request("qwen3:0.6b")).await.unwrap_err();
+        assert!(matches!(err, ModelError::RateLimit));
+        assert!(router.health_check().await.is_err());
+        assert!(router.list_models().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_model_prefix_and_rewrites_model() {
+        let remote = backend("remote");
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(
+                RouteTarget::new("vllm", remote.clone())
+                    .with_model_prefix("gpt-")
+                    .with_model("Qwen/Qwen3-8B"),
+            )
+            .with_target(RouteTarget::new("ollama", backend("local")).with_model_prefix("qwen"));
+
+        let response = router.chat(request("gpt-4o")).await.unwrap();
+        assert_eq!(response.served_by.as_deref(), Some("vllm"));
+        assert_eq!(remote.calls()[0], "Qwen/Qwen3-8B");
+
+        let response = router.chat(request("qwen3:0.6b")).await.unwrap();
+        assert_eq!(response.served_by.as_deref(), Some("ollama"));
+
+        let err = router.chat(request("llama3.1:8b")).await.unwrap_err();
+        assert!(matches!(err, ModelError::ModelNotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_rotates_replicas() {
+        let router = RouterProvider::new(RoutingPolicy::RoundRobin)
+            .with_target(RouteTarget::new("host-a", backend("a")))
+            .with_target(RouteTarget::new("host-b", backend("b")));
+
+        let mut served = Vec::new();
+        for _ in 0..4 {
+            let response = router.chat(request("qwen3:0.6b")).await.unwrap();
+            served.push(response.served_by.unwrap());
+        }
+
+        assert_eq!(served, ["host-a", "host-b", "host-a", "host-b"]);
+    }
+
+    #[tokio::test]
+    async fn test_nested_router_reports_full_path() {
+        let inner = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("gpu-1", backend("x")));
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("cluster", Arc::new(inner)));
+
+        let response = router.chat(request("qwen3:0.6b")).await.unwrap();
+        assert_eq!(response.served_by.as_deref(), Some("cluster/gpu-1"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_falls_back_to_plain_chat() {
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", failing(unavailable)))
+            .with_target(RouteTarget::new("host-b", backend("streamed")));
+
+        let stream = router
+            .as_streaming()
+            .unwrap()
+            .chat_stream(request("qwen3:0.6b"))
+            .await
+            .unwrap();
+        let response = collect_stream(stream, |_| {}).await.unwrap();
+
+        assert_eq!(content(&response), "streamed");
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.served_by.as_deref(), Some("host-b"));
+    }
+
+    #[tokio::test]
+    async fn test_list_models_merges_healthy_backends() {
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", backend("qwen3:0.6b")))
+            .with_target(RouteTarget::new("host-b", failing(unavailable)))
+            .with_target(RouteTarget::new("host-c", backend("qwen3:0.6b")));
+
+        let models = router.list_models().await.unwrap();
+        assert_eq!(models.len(), 1);
+        router.health_check().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_fail_over_and_rewrite_model() {
+        let second = backend("b");
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", failing(unavailable)))
+            .with_target(RouteTarget::new("host-b", second.clone()).with_model("nomic-embed-text"));
+
+        let response = router
+            .as_embedding()
+            .unwrap()
+            .embed("embed", &["hello".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(response.model, "nomic-embed-text");
+        assert_eq!(response.embeddings.len(), 1);
+        assert_eq!(second.calls()[0], "nomic-embed-text");
+    }
+
+    #[tokio::test]
+    async fn test_admin_goes_to_first_healthy_backend() {
+        let down = failing(unavailable);
+        let up = backend("b");
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", down.clone()))
+            .with_target(RouteTarget::new("host-b", up.clone()).with_model("qwen3:8b"));
+        let admin = router.as_admin().unwrap();
+
+        let details = admin.show("qwen3").await.unwrap();
+        admin.warm("qwen3", KeepAlive::Forever).await.unwrap();
+
+        assert_eq!(details.name, "qwen3:8b");
+        assert!(down.calls().is_empty());
+        assert_eq!(*up.calls(), ["show qwen3:8b", "warm qwen3:8b"]);
+    }
+
+    #[tokio::test]
+    async fn test_pull_and_delete_go_to_every_backend() {
+        let first = backend("qwen3:0.6b");
+        let second = backend("other");
+        let router = RouterProvider::new(RoutingPolicy::Fallback)
+            .with_target(RouteTarget::new("host-a", first.clone()))
+            .with_target(RouteTarget::new("host-b", second.clone()));
+        let admin = router.as_admin().unwrap();
+
+        let mut statuses = Vec::new();
+        crate::admin::pull_model(admin, "qwen3:0.6b", |p| statuses.push(p.status.clone()))
+            .await
+            .unwrap();
+        admin.delete("qwen3:0.6b").await.unwrap();
+
+        assert_eq!(
+            statuses,
+            [
+                "host-a: pulling manifest",
+                "host-a: success",
+                "host-b: pulling manifest",
+                "host-b: success",
+                "success"
+            ]
+        );
+        for backend in [first, second] {
+            assert_eq!(*backend.calls(), ["pull qwen3:0.6b", "delete qwen3:0.6b"]);
+        }
+        assert!(matches!(
+            admin.delete("llama3.1:8b").await,
+            Err(ModelError::ModelNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+//! Argument validation against tool schemas
+//!
+//! Models regularly produce tool calls with missing fields, wrong types or
+//! values outside the documented set. Checking the arguments against the
+//! tool's [`JsonSchema`] before dispatch turns those into a precise error the
+//! model can correct, instead of a failure somewhere inside the tool.
+
+use crate::types::{JsonSchema, PropertySchema, SchemaType};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+impl JsonSchema {
+    /// Check tool arguments against this schema.
+    ///
+    /// `null` is treated as an empty object, since some backends send it for
+    /// calls without arguments. The error names the offending field.
+    pub fn validate_value(&self, args: &Value) -> Result<(), String> {
+        let empty = Map::new();
+        let object = match args {
+            Value::Null => &empty,
+            Value::Object(object) => object,
+            other => {
+                return Err(format!(
+                    "arguments: expected object, got {}",
+                    type_name(other)
+                ))
+            }
+        };
+        validate_object(
+            object,
+            self.properties.as_ref(),
+            self.required.as_deref(),
+            self.additional_properties,
+            "arguments",
+        )
+    }
+}
+
+impl PropertySchema {
+    /// Check a single value against this schema. `path` prefixes error messages.
+    pub fn validate_value(&self, value: &Value, path: &str) -> Result<(), String> {
+        if let Some(alternatives) = &self.one_of {
+            let matches = alternatives
+                .iter()
+                .filter(|alt| alt.validate_value(value, path).is_ok())
+                .count();
+            if matches != 1 {
+                return Err(format!(
+                    "{}: expected exactly one of {} alternatives to match, {} did",
+                    path,
+                    alternatives.len(),
+                    matches
+                ));
+            }
+        } else if let Some(schema_type) = &self.schema_type {
+            if !type_matches(schema_type, value) {
+                return Err(format!(
+                    "{}: expected {}, got {}",
+                    path,
+                    schema_type_name(schema_type),
+                    type_name(value)
+                ));
+            }
+        }
+
+        if let Some(allowed) = &self.enum_values {
+            if !allowed.contains(value) {
+                let options: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                return Err(format!(
+                    "{}: {} is not one of {}",
+                    path,
+                    value,
+                    options.join(", ")
+                ));
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = self.minimum.filter(|min| number < *min) {
+                return Err(format!("{}: {} is less than {}", path, number, minimum));
+            }
+            if let Some(maximum) = self.maximum.filter(|max| number > *max) {
+                return Err(format!("{}: {} is greater than {}", path, number, maximum));
+            }
+        }
+
+        match value {
+            Value::Object(object) => validate_object(
+                object,
+                self.properties.as_ref(),
+                self.required.as_deref(),
+                self.additional_properties,
+                path,
+            ),
+            Value::Array(elements) => match &self.items {
+                Some(items) => elements.iter().enumerate().try_for_each(|(i, element)| {
+                    items.validate_value(element, &format!("{}[{}]", path, i))
+                }),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+fn validate_object(
+    object: &Map<String, Value>,
+    properties: Option<&HashMap<String, PropertySchema>>,
+    required: Option<&[String]>,
+    additional_properties: Option<bool>,
+    path: &str,
+) -> Result<(), String> {
+    for name in required.unwrap_or_default() {
+        if !object.contains_key(name) {
+            return Err(format!("{}: missing required field '{}'", path, name));
+        }
+    }
+
+    // Sorted so the first reported error does not depend on map order
+    let mut keys: Vec<&String> = object.keys().collect();
+    keys.sort();
+    for key in keys {
+        match properties.and_then(|props| props.get(key)) {
+            Some(schema) => schema.validate_value(&object[key], &format!("{}.{}", path, key))?,
+            None if additional_properties == Some(false) => {
+                return Err(format!("{}: unexpected field '{}'", path, key));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn type_matches(schema_type: &SchemaType, value: &Value) -> bool {
+    match schema_type {
+        SchemaType::Object => value.is_object(),
+        SchemaType::String => value.is_string(),
+        SchemaType::Number => value.is_number(),
+        SchemaType::Integer => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        SchemaType::Boolean => value.is_boolean(),
+        SchemaType::Array => value.is_array(),
+    }
+}
+
+fn schema_type_name(schema_type: &SchemaType) -> &'static str {
+    match schema_type {
+        SchemaType::Object => "object",
+        SchemaType::String => "string",
+        SchemaType::Number => "number",
+        SchemaType::Integer => "integer",
+        SchemaType::Boolean => "boolean",
+        SchemaType::Array => "array",
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pr_status_schema() -> JsonSchema {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "level".to_string(),
+            PropertySchema::new(SchemaType::String)
+                .with_enum(["l0", "l1"])
+                .with_default("l0"),
+        );
+        properties.insert(
+            "limit".to_string(),
+            PropertySchema::new(SchemaType::Integer).with_range(Some(1.0), Some(100.0)),
+        );
+        properties.insert(
+            "filter".to_string(),
+            PropertySchema::new(SchemaType::Object)
+                .with_property(
+                    "labels",
+                    PropertySchema::new(SchemaType::Array)
+                        .with_items(PropertySchema::new(SchemaType::String)),
+                )
+                .with_required(&["labels"])
+                .with_additional_properties(false),
+        );
+        properties.insert(
+            "target".to_string(),
+            PropertySchema::default().with_one_of(vec![
+                PropertySchema::new(SchemaType::Integer),
+                PropertySchema::new(SchemaType::String),
+            ]),
+        );
+        JsonSchema {
+            properties: Some(properties),
+            required: Some(vec!["level".to_string()]),
+            additional_properties: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_arguments_pass() {
+        let schema = pr_status_schema();
+        let args = json!({
+            "level": "l1",
+            "limit": 10,
+            "filter": {"labels": ["bug"]},
+            "target": 42
+        });
+        assert_eq!(schema.validate_value(&args), Ok(()));
+        assert_eq!(JsonSchema::default().validate_value(&Value::Null), Ok(()));
+    }
+
+    #[test]
+    fn test_reports_offending_field() {
+        let schema = pr_status_schema();
+        let cases = [
+            (json!({}), "arguments: missing required field 'level'"),
+            (
+                json!({"level": "l2"}),
+                "arguments.level: \"l2\" is not one of",
+            ),
+            (
+                json!({"level": "l0", "limit": 0}),
+                "arguments.limit: 0 is less than 1",
+            ),
+            (
+                json!({"level": "l0", "limit": "ten"}),
+                "arguments.limit: expected integer, got string",
+            ),
+            (
+                json!({"level": "l0", "filter": {"labels": [1]}}),
+                "arguments.filter.labels[0]: expected string, got number",
+            ),
+            (
+                json!({"level": "l0", "filter": {"labels": [], "x": 1}}),
+                "arguments.filter: unexpected field 'x'",
+            ),
+            (
+                json!({"level": "l0", "target": true}),
+                "arguments.target: expected exactly one of 2 alternatives",
+            ),
+            (
+                json!({"level": "l0", "verbose": true}),
+                "unexpected field 'verbose'",
+            ),
+            (json!(["l0"]), "arguments: expected object, got array"),
+        ];
+        for (args, expected) in cases {
+            let error = schema.validate_value(&args).unwrap_err();
+            assert!(
+                error.starts_with(expected) || error.contains(expected),
+                "{} should report '{}', got '{}'",
+                args,
+                expected,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_serializes_json_schema_keywords() {
+        let json = serde_json::to_value(pr_status_schema()).unwrap();
+        assert_eq!(json["additionalProperties"], false);
+        assert_eq!(json["properties"]["level"]["enum"], json!(["l0", "l1"]));
+        assert_eq!(json["properties"]["level"]["default"], "l0");
+        assert!(json["properties"]["level"].get("description").is_none());
+        assert_eq!(json["properties"]["limit"]["maximum"], 100.0);
+        assert_eq!(
+            json["properties"]["filter"]["properties"]["labels"]["items"]["type"],
+            "string"
+        );
+        assert_eq!(json["properties"]["target"]["oneOf"][1]["type"], "string");
+    }
+
+    #[test]
+    fn test_one_of_object_or_integer_has_no_own_type() {
+        let schema = PropertySchema::default().with_one_of(vec![
+            PropertySchema::new(SchemaType::Object)
+                .with_property("line", PropertySchema::new(SchemaType::Integer))
+                .with_required(&["line"]),
+            PropertySchema::new(SchemaType::Integer),
+        ]);
+
+        let json = serde_json::to_value(&schema).unwrap();
+        assert!(json.get("type").is_none(), "{}", json);
+        assert_eq!(json["oneOf"][0]["type"], "object");
+        assert_eq!(json["oneOf"][1]["type"], "integer");
+
+        assert_eq!(schema.validate_value(&json!({"line": 3}), "target"), Ok(()));
+        assert_eq!(schema.validate_value(&json!(3), "target"), Ok(()));
+        assert!(schema.validate_value(&json!("3"), "target").is_err());
+
+        let parsed: PropertySchema = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.schema_type, None);
+        assert_eq!(parsed.validate_value(&json!(3), "target"), Ok(()));
+    }
+}
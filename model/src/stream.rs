@@ -0,0 +1,352 @@
+//! Streaming chat support
+//!
+//! Providers that implement [`StreamingModelProvider`](crate::provider::StreamingModelProvider)
+//! yield [`ChatStreamChunk`]s as the model generates them. [`StreamAccumulator`]
+//! folds those chunks back into a regular [`ChatResponse`], so callers can show
+//! tokens as they arrive and still hand a complete message to the rest of the
+//! pipeline.
+
+use crate::provider::{CancellationToken, ModelError, ModelResult};
+use crate::reasoning::merge_reasoning;
+use crate::types::{ChatMessage, ChatResponse, Choice, FinishReason, MessageRole, ToolCall, Usage};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+/// Boxed stream of incremental chat chunks.
+pub type ChatStream = Pin<Box<dyn Stream<Item = ModelResult<ChatStreamChunk>> + Send>>;
+
+/// One incremental piece of a streamed chat response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatStreamChunk {
+    /// Newly generated content, if any
+    pub delta: Option<String>,
+    /// Newly generated reasoning, for backends that report it separately
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Tool calls completed in this chunk
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Set on the final chunk
+    pub finish_reason: Option<FinishReason>,
+    /// Token accounting, usually only present on the final chunk
+    pub usage: Option<Usage>,
+    /// Backend that produced the stream, when routed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<String>,
+}
+
+/// Assembles streamed chunks into a complete [`ChatResponse`].
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    content: String,
+    reasoning: String,
+    tool_calls: Vec<ToolCall>,
+    finish_reason: Option<FinishReason>,
+    usage: Option<Usage>,
+    served_by: Option<String>,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &ChatStreamChunk) {
+        if let Some(delta) = &chunk.delta {
+            self.content.push_str(delta);
+        }
+        if let Some(reasoning) = &chunk.reasoning {
+            self.reasoning.push_str(reasoning);
+        }
+        if let Some(calls) = &chunk.tool_calls {
+            self.tool_calls.extend(calls.iter().cloned());
+        }
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason.clone();
+        }
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage.clone();
+        }
+        if chunk.served_by.is_some() {
+            self.served_by = chunk.served_by.clone();
+        }
+    }
+
+    pub fn finish(self) -> ChatResponse {
+        let has_tool_calls = !self.tool_calls.is_empty();
+        let finish_reason = if has_tool_calls {
+            FinishReason::ToolCalls
+        } else {
+            self.finish_reason.unwrap_or(FinishReason::Stop)
+        };
+
+        // Inline <think> blocks can only be separated once the whole text is in
+        let (reasoning, content) = merge_reasoning(Some(self.reasoning), &self.content);
+        let message = ChatMessage {
+            role: MessageRole::Assistant,
+            content,
+            tool_calls: if has_tool_calls {
+                Some(self.tool_calls)
+            } else {
+                None
+            },
+            tool_call_id: None,
+            reasoning,
+            reasoning_signature: None,
+            images: Vec::new(),
+        };
+
+        ChatResponse {
+            choices: vec![Choice {
+                message,
+                finish_reason: Some(finish_reason),
+            }],
+            usage: self.usage,
+            served_by: self.served_by,
+        }
+    }
+}
+
+/// Drain a stream into a [`ChatResponse`], invoking `on_chunk` for each chunk.
+pub async fn collect_stream<F>(mut stream: ChatStream, mut on_chunk: F) -> ModelResult<ChatResponse>
+where
+    F: FnMut(&ChatStreamChunk),
+{
+    let mut accumulator = StreamAccumulator::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        on_chunk(&chunk);
+        accumulator.push(&chunk);
+    }
+    Ok(accumulator.finish())
+}
+
+/// Present a complete response as a stream of one chunk, for callers that
+/// expect a stream from a backend that cannot produce one.
+pub(crate) fn single_chunk_stream(response: ChatResponse) -> ChatStream {
+    let choice = response.choices.into_iter().next();
+    let chunk = ChatStreamChunk {
+        delta: choice.as_ref().and_then(|c| c.message.content.clone()),
+        reasoning: choice.as_ref().and_then(|c| c.message.reasoning.clone()),
+        tool_calls: choice.as_ref().and_then(|c| c.message.tool_calls.clone()),
+        finish_reason: choice.and_then(|c| c.finish_reason),
+        usage: response.usage,
+        served_by: response.served_by,
+    };
+    Box::pin(futures::stream::iter(vec![Ok(chunk)]))
+}
+
+/// End `stream` with [`ModelError::Cancelled`] as soon as `token` fires.
+///
+/// The inner stream is dropped at that point, closing the response body so
+/// the backend stops generating.
+#[cfg_attr(not(feature = "ollama"), allow(dead_code))]
+pub(crate) fn cancellable_stream(
+    stream: ChatStream,
+    token: Option<CancellationToken>,
+) -> ChatStream {
+    let Some(token) = token else {
+        return stream;
+    };
+    Box::pin(futures::stream::unfold(Some(stream), move |stream| {
+        let token = token.clone();
+        async move {
+            let mut stream = stream?;
+            tokio::select! {
+                biased;
+                _ = token.cancelled() => Some((Err(ModelError::Cancelled), None)),
+                item = stream.next() => item.map(|item| (item, Some(stream))),
+            }
+        }
+    }))
+}
+
+/// Splits a byte stream into newline-delimited records.
+#[cfg_attr(not(feature = "ollama"), allow(dead_code))]
+#[derive(Debug, Default)]
+pub(crate) struct LineDecoder {
+    buffer: Vec<u8>,
+}
+
+#[cfg_attr(not(feature = "ollama"), allow(dead_code))]
+impl LineDecoder {
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Next complete, non-blank line, if one is buffered.
+    pub(crate) fn next_line(&mut self) -> Option<String> {
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// Whatever is left once the underlying stream has ended.
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer))
+            .trim()
+            .to_string();
+        if rest.is_empty() {
+            None
+        } else {
+            Some(rest)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FunctionCall;
+
+    #[test]
+    fn test_line_decoder_handles_split_records() {
+        let mut decoder = LineDecoder::default();
+        decoder.push(b"{\"a\":1}\n{\"b\"");
+        assert_eq!(decoder.next_line().as_deref(), Some("{\"a\":1}"));
+        assert_eq!(decoder.next_line(), None);
+
+        decoder.push(b":2}\n\n{\"c\":3}");
+        assert_eq!(decoder.next_line().as_deref(), Some("{\"b\":2}"));
+        assert_eq!(decoder.next_line(), None);
+        assert_eq!(decoder.finish().as_deref(), Some("{\"c\":3}"));
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[tokio::test]
+    async fn test_cancellable_stream_ends_mid_generation() {
+        let first = futures::stream::iter(vec![Ok(ChatStreamChunk {
+            delta: Some("partial".to_string()),
+            ..Default::default()
+        })]);
+        let inner: ChatStream = Box::pin(first.chain(futures::stream::pending()));
+        let token = CancellationToken::new();
+        let mut stream = cancellable_stream(inner, Some(token.clone()));
+
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.delta.as_deref(), Some("partial"));
+
+        token.cancel();
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(ModelError::Cancelled))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn test_accumulator_assembles_content_and_usage() {
+        let mut acc = StreamAccumulator::new();
+        acc.push(&ChatStreamChunk {
+            delta: Some("Hel".to_string()),
+            ..Default::default()
+        });
+        acc.push(&ChatStreamChunk {
+            delta: Some("lo".to_string()),
+            ..Default::default()
+        });
+        acc.push(&ChatStreamChunk {
+            finish_reason: Some(FinishReason::Stop),
+            usage: Some(Usage::new(3, 2)),
+            ..Default::default()
+        });
+
+        let response = acc.finish();
+        assert_eq!(
+            response.choices[0].message.content.as_deref(),
+            Some("Hello")
+        );
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.usage.unwrap().total_tokens, 5);
+    }
+
+    #[test]
+    fn test_accumulator_tool_calls_override_finish_reason() {
+        let mut acc = StreamAccumulator::new();
+        acc.push(&ChatStreamChunk {
+            tool_calls: Some(vec![ToolCall {
+                id: "call_0".to_string(),
+                function: FunctionCall {
+                    name: "echo".to_string(),
+                    arguments: serde_json::json!({"message": "hi"}),
+                },
+            }]),
+            ..Default::default()
+        });
+        acc.push(&ChatStreamChunk {
+            finish_reason: Some(FinishReason::Stop),
+            ..Default::default()
+        });
+
+        let response = acc.finish();
+        assert_eq!(
+            response.choices[0].finish_reason,
+            Some(FinishReason::ToolCalls)
+        );
+        assert!(response.choices[0].message.content.is_none());
+        assert_eq!(
+            response.choices[0]
+                .message
+                .tool_calls
+                .as_ref()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_accumulator_separates_reasoning() {
+        let mut acc = StreamAccumulator::new();
+        for delta in ["<think>", "weigh options", "</think>", "\n\nDONE"] {
+            acc.push(&ChatStreamChunk {
+                delta: Some(delta.to_string()),
+                ..Default::default()
+            });
+        }
+        let message = &acc.finish().choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("DONE"));
+        assert_eq!(message.reasoning.as_deref(), Some("weigh options"));
+
+        let mut acc = StreamAccumulator::new();
+        acc.push(&ChatStreamChunk {
+            reasoning: Some("thinking".to_string()),
+            ..Default::default()
+        });
+        acc.push(&ChatStreamChunk {
+            delta: Some("answer".to_string()),
+            ..Default::default()
+        });
+        let message = &acc.finish().choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("answer"));
+        assert_eq!(message.reasoning.as_deref(), Some("thinking"));
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_reports_each_chunk() {
+        let chunks = vec![
+            Ok(ChatStreamChunk {
+                delta: Some("a".to_string()),
+                ..Default::default()
+            }),
+            Ok(ChatStreamChunk {
+                delta: Some("b".to_string()),
+                ..Default::default()
+            }),
+        ];
+        let stream: ChatStream = Box::pin(futures::stream::iter(chunks));
+
+        let mut seen = Vec::new();
+        let response = collect_stream(stream, |c| seen.push(c.delta.clone().unwrap()))
+            .await
+            .unwrap();
+
+        assert_eq!(seen, vec!["a", "b"]);
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("ab"));
+    }
+}
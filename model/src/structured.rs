@@ -0,0 +1,261 @@
+//! Structured (JSON) replies
+//!
+//! Requests carrying a [`ResponseFormat`](crate::types::ResponseFormat) ask the backend to constrain its
+//! output, but small models still wrap JSON in prose or code fences, and
+//! backends without constrained decoding ignore the format entirely.
+//! [`parse_json`] tolerates that wrapping, and [`chat_json`] deserializes the
+//! reply into a caller-supplied type, feeding parse errors and schema
+//! violations back to the model until it produces a valid reply or runs out
+//! of repair attempts.
+
+use crate::provider::{ModelProvider, ModelResult};
+use crate::types::{ChatMessage, ChatRequest, ChatResponse, PropertySchema, ResponseFormat};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Deserialize;
+use serde_json::Value;
+use std::future::Future;
+use tracing::debug;
+
+/// Locate the JSON payload in a model reply.
+///
+/// Prefers the contents of a ```` ```json ```` fence, then the outermost
+/// `{...}` or `[...]` span. Returns `None` if neither is present.
+pub fn extract_json(text: &str) -> Option<&str> {
+    if let Some(start) = text.find("```") {
+        let body = &text[start + 3..];
+        let body = body.strip_prefix("json").unwrap_or(body);
+        if let Some(end) = body.find("```") {
+            let fenced = body[..end].trim();
+            if !fenced.is_empty() {
+                return Some(fenced);
+            }
+        }
+    }
+
+    let start = text.find(['{', '['])?;
+    let close = if text[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = text.rfind(close)?;
+    (end > start).then(|| &text[start..=end])
+}
+
+/// Deserialize a model reply, ignoring prose or fences around the JSON.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let text = text.trim();
+    match serde_json::from_str(text) {
+        Ok(value) => Ok(value),
+        Err(e) => match extract_json(text) {
+            Some(json) if json != text => serde_json::from_str(json),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Check a reply against the schema `request` asks for, if any.
+///
+/// Schemas using keywords outside the subset [`PropertySchema`] models are
+/// not checked, leaving the reply to deserialization alone.
+fn check_schema(request: &ChatRequest, content: &str) -> Result<(), serde_json::Error> {
+    let Some(ResponseFormat::JsonSchema { schema, .. }) = &request.response_format else {
+        return Ok(());
+    };
+    let Ok(schema) = PropertySchema::deserialize(schema) else {
+        return Ok(());
+    };
+    let value: Value = parse_json(content)?;
+    schema
+        .validate_value(&value, "reply")
+        .map_err(serde_json::Error::custom)
+}
+
+/// Send `request` and deserialize the reply as `T`.
+///
+/// When the reply does not parse or does not match the request's schema, the
+/// error is sent back to the model and the
+/// request retried, up to `max_repairs` times. The last parse error is
+/// returned as [`ModelError::Serialization`](crate::provider::ModelError::Serialization).
+pub async fn chat_json<T: DeserializeOwned>(
+    provider: &dyn ModelProvider,
+    request: ChatRequest,
+    max_repairs: usize,
+) -> ModelResult<T> {
+    chat_json_with(|request| provider.chat(request), request, max_repairs)
+        .await?
+        .map_err(|unparsed| unparsed.error.into())
+}
+
+/// A reply that still did not match the requested format after every repair.
+#[derive(Debug)]
+pub struct UnparsedReply {
+    /// The last reply the model gave
+    pub content: String,
+    pub error: serde_json::Error,
+}
+
+/// [`chat_json`] with each attempt sent through `send`, so callers can add
+/// their own retries, streaming or accounting.
+///
+/// Errors from `send` end the exchange at once; a reply that never parses is
+/// returned as the inner [`UnparsedReply`].
+pub async fn chat_json_with<T, E, F, Fut>(
+    mut send: F,
+    mut request: ChatRequest,
+    max_repairs: usize,
+) -> Result<Result<T, UnparsedReply>, E>
+where
+    T: DeserializeOwned,
+    F: FnMut(ChatRequest) -> Fut,
+    Fut: Future<Output = Result<ChatResponse, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        let response = send(request.clone()).await?;
+        let content = response
+            .choices
+            .first()
+            .and_then(|choice| choice.message.content.clone())
+            .unwrap_or_default();
+
+        let error = match check_schema(&request, &content).and_then(|()| parse_json(&content)) {
+            Ok(value) => return Ok(Ok(value)),
+            Err(e) => e,
+        };
+        if attempt >= max_repairs {
+            return Ok(Err(UnparsedReply { content, error }));
+        }
+        attempt += 1;
+        debug!(
+            "Structured reply did not parse ({}), repair attempt {}/{}",
+            error, attempt, max_repairs
+        );
+
+        request.messages.push(ChatMessage::assistant(content));
+        request.messages.push(ChatMessage::user(format!(
+            "Your reply was not valid JSON for the requested format: {}. \
+             Reply again with only the corrected JSON object.",
+            error
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ModelError;
+    use crate::test_support::MockProvider;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Verdict {
+        complete: bool,
+        reason: String,
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("Is the task done?")])
+    }
+
+    #[test]
+    fn test_extract_json_from_fences_and_prose() {
+        assert_eq!(extract_json("```json\n{\"a\": 1}\n```"), Some("{\"a\": 1}"));
+        assert_eq!(
+            extract_json("Sure! {\"a\": {\"b\": 2}} Hope that helps."),
+            Some("{\"a\": {\"b\": 2}}")
+        );
+        assert_eq!(extract_json("list: [1, 2]"), Some("[1, 2]"));
+        assert_eq!(extract_json("COMPLETE"), None);
+    }
+
+    #[test]
+    fn test_parse_json_accepts_wrapped_reply() {
+        let verdict: Verdict =
+            parse_json("Here you go:\n```json\n{\"complete\": true, \"reason\": \"ok\"}\n```")
+                .unwrap();
+        assert!(verdict.complete);
+        assert!(parse_json::<Verdict>("{\"complete\": \"yes\"}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chat_json_repairs_invalid_reply() {
+        let provider = MockProvider::scripted(&[
+            "The task is COMPLETE",
+            "{\"complete\": true, \"reason\": \"tests pass\"}",
+        ]);
+
+        let verdict: Verdict = chat_json(&provider, request(), 2).await.unwrap();
+
+        assert_eq!(
+            verdict,
+            Verdict {
+                complete: true,
+                reason: "tests pass".to_string()
+            }
+        );
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        let repair = requests[1].messages.last().unwrap();
+        assert!(repair
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("not valid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_json_gives_up_after_max_repairs() {
+        let provider = MockProvider::scripted(&["nope", "still nope"]);
+
+        let result = chat_json::<Verdict>(&provider, request(), 1).await;
+
+        assert!(matches!(result, Err(ModelError::Serialization(_))));
+        assert_eq!(provider.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_chat_json_with_returns_last_unparsed_reply() {
+        let provider = MockProvider::scripted(&["nope", "still nope"]);
+
+        let result = chat_json_with::<Verdict, _, _, _>(|r| provider.chat(r), request(), 1)
+            .await
+            .unwrap();
+
+        let unparsed = result.unwrap_err();
+        assert_eq!(unparsed.content, "still nope");
+    }
+
+    #[tokio::test]
+    async fn test_chat_json_repairs_schema_violations() {
+        let provider = MockProvider::scripted(&[
+            "{\"complete\": true, \"reason\": \"\", \"confidence\": 7}",
+            "{\"complete\": true, \"reason\": \"tests pass\", \"confidence\": 0.9}",
+        ]);
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "complete": {"type": "boolean"},
+                "reason": {"type": "string"},
+                "confidence": {"type": "number", "minimum": 0.0, "maximum": 1.0}
+            },
+            "required": ["complete", "reason", "confidence"]
+        });
+        let request =
+            request().with_response_format(ResponseFormat::json_schema("verdict", schema));
+
+        let verdict: Verdict = chat_json(&provider, request, 1).await.unwrap();
+
+        assert_eq!(verdict.reason, "tests pass");
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        let repair = requests[1]
+            .messages
+            .last()
+            .unwrap()
+            .content
+            .clone()
+            .unwrap();
+        assert!(repair.contains("reply.confidence: 7 is greater than 1"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Test double shared by the unit tests of this crate.
+
+use crate::admin::{ModelAdmin, ModelDetails, PullProgress, PullStream};
+use crate::embedding::{EmbeddingProvider, EmbeddingResponse};
+use crate::provider::{ModelError, ModelProvider, ModelResult, StreamingModelProvider};
+use crate::stream::{ChatStream, ChatStreamChunk};
+use crate::types::{
+    ChatMessage, ChatRequest, ChatResponse, Choice, FinishReason, KeepAlive, ModelInfo, Usage,
+};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A scripted reply, or the error to fail with instead.
+type Scripted = Result<String, fn() -> ModelError>;
+
+/// What a [`MockProvider`] answers once its script is used up.
+enum Reply {
+    Text(String),
+    /// "reply N" to the Nth chat request
+    Counter,
+}
+
+/// A provider with every capability whose replies, failures, usage and
+/// speed are set up front. It records what it is asked and how many calls
+/// it serves at once.
+pub(crate) struct MockProvider {
+    /// Answers for the first chat requests, in order
+    script: Mutex<VecDeque<Scripted>>,
+    reply: Reply,
+    /// Fails every call, chat or not
+    failure: Option<fn() -> ModelError>,
+    usage: Option<Usage>,
+    delay: Duration,
+    /// Models it lists and manages
+    models: Vec<String>,
+    requests: Mutex<Vec<ChatRequest>>,
+    calls: Mutex<Vec<String>>,
+    active: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MockProvider {
+    fn with_reply(reply: Reply) -> Self {
+        Self {
+            script: Mutex::new(VecDeque::new()),
+            reply,
+            failure: None,
+            usage: None,
+            delay: Duration::ZERO,
+            models: Vec::new(),
+            requests: Mutex::new(Vec::new()),
+            calls: Mutex::new(Vec::new()),
+            active: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Answers `reply` to every chat request.
+    pub(crate) fn replying(reply: &str) -> Self {
+        Self::with_reply(Reply::Text(reply.to_string()))
+    }
+
+    /// Answers "reply N" to the Nth chat request.
+    pub(crate) fn counting() -> Self {
+        Self::with_reply(Reply::Counter)
+    }
+
+    /// Answers `replies` in order, then empty replies.
+    pub(crate) fn scripted(replies: &[&str]) -> Self {
+        let mock = Self::replying("");
+        mock.script
+            .lock()
+            .unwrap()
+            .extend(replies.iter().map(|reply| Ok(reply.to_string())));
+        mock
+    }
+
+    /// Fails every call with `failure`.
+    pub(crate) fn failing(failure: fn() -> ModelError) -> Self {
+        Self {
+            failure: Some(failure),
+            ..Self::replying("")
+        }
+    }
+
+    /// Fail the first chat requests with `failures`, in order.
+    pub(crate) fn with_failures(self, failures: Vec<fn() -> ModelError>) -> Self {
+        self.script
+            .lock()
+            .unwrap()
+            .extend(failures.into_iter().map(Err));
+        self
+    }
+
+    pub(crate) fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Take `delay` over every call.
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// List and manage `models`; deleting any other model fails.
+    pub(crate) fn with_models(mut self, models: &[&str]) -> Self {
+        self.models = models.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    /// Chat requests received, in order.
+    pub(crate) fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Every call received: the model of chat and embedding requests, and
+    /// "<action> <model>" for model management.
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Most calls served at once.
+    pub(crate) fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    /// Record `call`, then take the configured time over it.
+    async fn serve(&self, call: String) -> ModelResult<()> {
+        self.calls.lock().unwrap().push(call);
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(active, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        match self.failure {
+            Some(failure) => Err(failure()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for MockProvider {
+    async fn chat(&self, request: ChatRequest) -> ModelResult<ChatResponse> {
+        let model = request.model.clone();
+        let count = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request);
+            requests.len()
+        };
+        self.serve(model).await?;
+        let scripted = self.script.lock().unwrap().pop_front();
+        let reply = match (scripted, &self.reply) {
+            (Some(scripted), _) => scripted.map_err(|failure| failure())?,
+            (None, Reply::Text(text)) => text.clone(),
+            (None, Reply::Counter) => format!("reply {}", count),
+        };
+        Ok(ChatResponse {
+            choices: vec![Choice {
+                message: ChatMessage::assistant(reply),
+                finish_reason: Some(FinishReason::Stop),
+            }],
+            usage: self.usage.clone(),
+            served_by: None,
+        })
+    }
+
+    async fn list_models(&self) -> ModelResult<Vec<ModelInfo>> {
+        if let Some(failure) = self.failure {
+            return Err(failure());
+        }
+        Ok(self
+            .models
+            .iter()
+            .map(|name| ModelInfo {
+                name: name.clone(),
+                size: None,
+                digest: None,
+                modified_at: None,
+            })
+            .collect())
+    }
+
+    async fn health_check(&self) -> ModelResult<()> {
+        match self.failure {
+            Some(failure) => Err(failure()),
+            None => Ok(()),
+        }
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingModelProvider> {
+        Some(self)
+    }
+
+    fn as_embedding(&self) -> Option<&dyn EmbeddingProvider> {
+        Some(self)
+    }
+
+    fn as_admin(&self) -> Option<&dyn ModelAdmin> {
+        Some(self)
+    }
+}
+
+/// Streams the reply in two halves; the second carries the finish reason
+/// and usage.
+#[async_trait]
+impl StreamingModelProvider for MockProvider {
+    async fn chat_stream(&self, request: ChatRequest) -> ModelResult<ChatStream> {
+        let response = self.chat(request).await?;
+        let text = response.choices[0].message.content.clone().unwrap();
+        let (head, tail) = text.split_at(text.len() / 2);
+        let chunks = vec![
+            Ok(ChatStreamChunk {
+                delta: Some(head.to_string()),
+                ..Default::default()
+            }),
+            Ok(ChatStreamChunk {
+                delta: Some(tail.to_string()),
+                finish_reason: Some(FinishReason::Stop),
+                usage: response.usage,
+                ..Default::default()
+            }),
+        ];
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+}
+
+/// Embeds each input as its length.
+#[async_trait]
+impl EmbeddingProvider for MockProvider {
+    async fn embed(&self, model: &str, inputs: &[String]) -> ModelResult<EmbeddingResponse> {
+        self.serve(model.to_string()).await?;
+        let embeddings = inputs.iter().map(|i| vec![i.len() as f32]).collect();
+        EmbeddingResponse::new(model, embeddings)
+    }
+}
+
+#[async_trait]
+impl ModelAdmin for MockProvider {
+    async fn pull(&self, model: &str) -> ModelResult<PullStream> {
+        self.serve(format!("pull {}", model)).await?;
+        let updates = vec![
+            Ok(PullProgress {
+                status: "pulling manifest".to_string(),
+                ..Default::default()
+            }),
+            Ok(PullProgress {
+                status: "success".to_string(),
+                ..Default::default()
+            }),
+        ];
+        Ok(Box::pin(futures::stream::iter(updates)))
+    }
+
+    async fn show(&self, model: &str) -> ModelResult<ModelDetails> {
+        self.serve(format!("show {}", model)).await?;
+        Ok(ModelDetails {
+            name: model.to_string(),
+            ..Default::default()
+        })
+    }
+
+    async fn delete(&self, model: &str) -> ModelResult<()> {
+        self.serve(format!("delete {}", model)).await?;
+        if !self.models.iter().any(|m| m == model) {
+            return Err(ModelError::ModelNotFound {
+                model: model.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn warm(&self, model: &str, _keep_alive: KeepAlive) -> ModelResult<()> {
+        self.serve(format!("warm {}", model)).await
+    }
+}
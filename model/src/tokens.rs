@@ -0,0 +1,79 @@
+//! Token estimation
+//!
+//! Providers only report exact token counts after a request has been served,
+//! but callers need to know how close a conversation is to the context window
+//! *before* sending it. These helpers give a tokenizer-free estimate of about
+//! three bytes per token. Code, JSON and tool output tokenize densely, close to
+//! that rate; English prose runs nearer four bytes per token, so the estimate
+//! errs on the high side for it.
+
+use crate::types::ChatMessage;
+
+/// Rough per-message cost of role markers and separators in chat templates.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+const BYTES_PER_TOKEN: usize = 3;
+
+/// Estimate the number of tokens in `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(BYTES_PER_TOKEN)
+}
+
+/// Estimate the tokens a single message occupies in the prompt.
+pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
+    let content = message.content.as_deref().map(estimate_tokens).unwrap_or(0);
+    let tool_calls: usize = message
+        .tool_calls
+        .iter()
+        .flatten()
+        .map(|tc| {
+            estimate_tokens(&tc.function.name) + estimate_tokens(&tc.function.arguments.to_string())
+        })
+        .sum();
+    MESSAGE_OVERHEAD_TOKENS + content + tool_calls
+}
+
+/// Estimate the prompt tokens for a whole conversation.
+pub fn estimate_conversation_tokens(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionCall, ToolCall};
+
+    #[test]
+    fn test_estimate_tokens_rounds_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("ab"), 1);
+        assert_eq!(estimate_tokens("abc"), 1);
+        assert_eq!(estimate_tokens("abcd"), 2);
+    }
+
+    #[test]
+    fn test_conversation_estimate_counts_tool_calls() {
+        let plain = ChatMessage::user("x".repeat(30));
+        assert_eq!(
+            estimate_message_tokens(&plain),
+            MESSAGE_OVERHEAD_TOKENS + 10
+        );
+
+        let call = ChatMessage::assistant_with_tools(
+            None,
+            vec![ToolCall {
+                id: "call_0".to_string(),
+                function: FunctionCall {
+                    name: "read_file".to_string(),
+                    arguments: serde_json::json!({"path": "src/main.rs"}),
+                },
+            }],
+        );
+        assert!(estimate_message_tokens(&call) > MESSAGE_OVERHEAD_TOKENS + 5);
+
+        let total = estimate_conversation_tokens(&[plain.clone(), call.clone()]);
+        assert_eq!(
+            total,
+            estimate_message_tokens(&plain) + estimate_message_tokens(&call)
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Tool calls embedded in text
+//!
+//! Small local models often answer a tool-enabled request by writing the call
+//! into the content instead of the structured `tool_calls` field, either
+//! because the backend has no native tool calling for them or because the
+//! chat template does not steer them towards it. [`ToolCallParser`] recognises
+//! the common shapes of such replies and turns them back into [`ToolCall`]s,
+//! so callers can treat them like native calls.
+
+use crate::types::{ChatResponse, FinishReason, FunctionCall, ToolCall, ToolDefinition};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const HERMES_OPEN: &str = "<tool_call>";
+const HERMES_CLOSE: &str = "</tool_call>";
+const FENCE: &str = "```";
+
+/// A way of writing a tool call into message content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextToolFormat {
+    /// `<tool_call>{"name": ..., "arguments": ...}</tool_call>`, as used by
+    /// Hermes, Qwen and many fine-tunes
+    Hermes,
+    /// A call inside a ```` ``` ```` fence, usually tagged `json`
+    FencedJson,
+    /// The whole reply is a call object, an array of calls, or several call
+    /// objects in sequence
+    RawJson,
+}
+
+/// Tool calls recovered from a message's content.
+#[derive(Debug, Clone)]
+pub struct ParsedToolCalls {
+    pub tool_calls: Vec<ToolCall>,
+    /// Text left over once the calls are removed, if any
+    pub content: Option<String>,
+}
+
+/// Extracts tool calls written as text.
+///
+/// Formats are tried in order and the first one that yields a call wins. A
+/// call object needs a string `name` and its arguments under `arguments` or
+/// `parameters` (string-encoded JSON is decoded). OpenAI-style
+/// `{"function": {...}}` wrappers and `{"tool_calls": [...]}` envelopes are
+/// unwrapped.
+#[derive(Debug, Clone)]
+pub struct ToolCallParser {
+    formats: Vec<TextToolFormat>,
+    known_tools: Option<Vec<String>>,
+}
+
+impl Default for ToolCallParser {
+    fn default() -> Self {
+        Self {
+            formats: vec![
+                TextToolFormat::Hermes,
+                TextToolFormat::FencedJson,
+                TextToolFormat::RawJson,
+            ],
+            known_tools: None,
+        }
+    }
+}
+
+impl ToolCallParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_formats(mut self, formats: impl IntoIterator<Item = TextToolFormat>) -> Self {
+        self.formats = formats.into_iter().collect();
+        self
+    }
+
+    /// Only accept calls to these tools.
+    ///
+    /// Strongly recommended with [`TextToolFormat::RawJson`], since an
+    /// ordinary JSON answer can otherwise look like a call.
+    pub fn with_known_tools<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.known_tools = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only accept calls to the tools offered in a request.
+    pub fn for_tools(self, tools: &[ToolDefinition]) -> Self {
+        self.with_known_tools(tools.iter().map(|t| t.function.name.clone()))
+    }
+
+    /// Extract tool calls from `content`, or `None` if it contains none.
+    ///
+    /// Recovered calls get ids `call_0`, `call_1`, ... in order of appearance.
+    pub fn parse(&self, content: &str) -> Option<ParsedToolCalls> {
+        self.formats.iter().find_map(|format| {
+            let (calls, rest) = match format {
+                TextToolFormat::Hermes => self.parse_hermes(content),
+                TextToolFormat::FencedJson => self.parse_fenced(content),
+                TextToolFormat::RawJson => (self.parse_raw(content), String::new()),
+            };
+            if calls.is_empty() {
+                return None;
+            }
+            let tool_calls = calls
+                .into_iter()
+                .enumerate()
+                .map(|(idx, function)| ToolCall {
+                    id: format!("call_{}", idx),
+                    function,
+                })
+                .collect();
+            let rest = rest.trim();
+            Some(ParsedToolCalls {
+                tool_calls,
+                content: (!rest.is_empty()).then(|| rest.to_string()),
+            })
+        })
+    }
+
+    /// Move text tool calls into `tool_calls` for every choice that has none.
+    ///
+    /// Choices that gain calls get [`FinishReason::ToolCalls`]. Returns the
+    /// number of calls recovered.
+    pub fn recover(&self, response: &mut ChatResponse) -> usize {
+        let mut recovered = 0;
+        for choice in &mut response.choices {
+            let message = &mut choice.message;
+            if message.tool_calls.as_ref().is_some_and(|tc| !tc.is_empty()) {
+                continue;
+            }
+            let Some(parsed) = message.content.as_deref().and_then(|c| self.parse(c)) else {
+                continue;
+            };
+            recovered += parsed.tool_calls.len();
+            message.content = parsed.content;
+            message.tool_calls = Some(parsed.tool_calls);
+            choice.finish_reason = Some(FinishReason::ToolCalls);
+        }
+        recovered
+    }
+
+    fn parse_hermes(&self, content: &str) -> (Vec<FunctionCall>, String) {
+        let mut calls = Vec::new();
+        let mut rest = String::new();
+        let mut remaining = content;
+
+        while let Some(open) = remaining.find(HERMES_OPEN) {
+            let body_start = open + HERMES_OPEN.len();
+            let (body, next) = match remaining[body_start..].find(HERMES_CLOSE) {
+                Some(close) => (
+                    &remaining[body_start..body_start + close],
+                    body_start + close + HERMES_CLOSE.len(),
+                ),
+                // Generation can stop before the closing tag
+                None => (&remaining[body_start..], remaining.len()),
+            };
+            match self.calls_from_text(body) {
+                Some(found) => {
+                    rest.push_str(&remaining[..open]);
+                    calls.extend(found);
+                }
+                None => rest.push_str(&remaining[..next]),
+            }
+            remaining = &remaining[next..];
+        }
+        rest.push_str(remaining);
+        (calls, rest)
+    }
+
+    fn parse_fenced(&self, content: &str) -> (Vec<FunctionCall>, String) {
+        let mut calls = Vec::new();
+        let mut rest = String::new();
+        let mut remaining = content;
+
+        while let Some(open) = remaining.find(FENCE) {
+            let after_open = &remaining[open + FENCE.len()..];
+            // Skip the info string (`json`, `tool_call`, ...)
+            let body_start = after_open.find('\n').map(|i| i + 1).unwrap_or(0);
+            let Some(close) = after_open[body_start..].find(FENCE) else {
+                break;
+            };
+            let body = &after_open[body_start..body_start + close];
+            let next = open + FENCE.len() + body_start + close + FENCE.len();
+            match self.calls_from_text(body) {
+                Some(found) => {
+                    rest.push_str(&remaining[..open]);
+                    calls.extend(found);
+                }
+                None => rest.push_str(&remaining[..next]),
+            }
+            remaining = &remaining[next..];
+        }
+        rest.push_str(remaining);
+        (calls, rest)
+    }
+
+    fn parse_raw(&self, content: &str) -> Vec<FunctionCall> {
+        self.calls_from_text(content).unwrap_or_default()
+    }
+
+    /// Calls from one or more whitespace-separated JSON values, all of which
+    /// must be calls.
+    fn calls_from_text(&self, text: &str) -> Option<Vec<FunctionCall>> {
+        let text = text.trim();
+        if !text.starts_with(['{', '[']) {
+            return None;
+        }
+        let mut calls = Vec::new();
+        for value in serde_json::Deserializer::from_str(text).into_iter::<Value>() {
+            calls.extend(self.calls_from_value(value.ok()?)?);
+        }
+        (!calls.is_empty()).then_some(calls)
+    }
+
+    fn calls_from_value(&self, value: Value) -> Option<Vec<FunctionCall>> {
+        match value {
+            Value::Array(items) => {
+                let mut calls = Vec::new();
+                for item in items {
+                    calls.extend(self.calls_from_value(item)?);
+                }
+                Some(calls)
+            }
+            Value::Object(mut map) => {
+                if let Some(envelope) = map.remove("tool_calls") {
+                    return self.calls_from_value(envelope);
+                }
+                if let Some(function @ Value::Object(_)) = map.remove("function") {
+                    return self.calls_from_value(function);
+                }
+                let name = map.get("name")?.as_str()?.to_string();
+                if !self.is_known(&name) {
+                    return None;
+                }
+                let arguments = map
+                    .remove("arguments")
+                    .or_else(|| map.remove("parameters"))?;
+                let arguments = match arguments {
+                    Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+                    Value::Null => Value::Object(Default::default()),
+                    other => other,
+                };
+                Some(vec![FunctionCall { name, arguments }])
+            }
+            _ => None,
+        }
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        self.known_tools
+            .as_ref()
+            .map(|known| known.iter().any(|k| k == name))
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatMessage, Choice};
+    use serde_json::json;
+
+    fn names(parsed: &ParsedToolCalls) -> Vec<&str> {
+        parsed
+            .tool_calls
+            .iter()
+            .map(|tc| tc.function.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_hermes_blocks() {
+        let content = "I'll check.\n<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"a.rs\"}}\n</tool_call>\n<tool_call>{\"name\": \"list_dir\", \"arguments\": \"{\\\"path\\\": \\\".\\\"}\"}";
+        let parsed = ToolCallParser::new().parse(content).unwrap();
+
+        assert_eq!(names(&parsed), vec!["read_file", "list_dir"]);
+        assert_eq!(parsed.tool_calls[0].id, "call_0");
+        assert_eq!(parsed.tool_calls[1].id, "call_1");
+        assert_eq!(parsed.tool_calls[0].function.arguments["path"], "a.rs");
+        assert_eq!(
+            parsed.tool_calls[1].function.arguments,
+            json!({"path": "."})
+        );
+        assert_eq!(parsed.content.as_deref(), Some("I'll check."));
+    }
+
+    #[test]
+    fn test_parse_fenced_and_raw_json() {
+        let fenced = "Running it:\n```json\n{\"function\": {\"name\": \"run_command\", \"arguments\": {\"command\": \"ls\"}}}\n```\n```rust\nfn main() {}\n```";
+        let parsed = ToolCallParser::new().parse(fenced).unwrap();
+        assert_eq!(names(&parsed), vec!["run_command"]);
+        assert_eq!(
+            parsed.content.as_deref(),
+            Some("Running it:\n\n```rust\nfn main() {}\n```")
+        );
+
+        let raw = r#"{"name": "echo", "parameters": {"message": "hi"}}
+{"name": "echo", "parameters": {"message": "again"}}"#;
+        let parsed = ToolCallParser::new().parse(raw).unwrap();
+        assert_eq!(names(&parsed), vec!["echo", "echo"]);
+        assert!(parsed.content.is_none());
+
+        let envelope = json!({"tool_calls": [{"name": "echo", "arguments": null}]}).to_string();
+        let parsed = ToolCallParser::new().parse(&envelope).unwrap();
+        assert_eq!(parsed.tool_calls[0].function.arguments, json!({}));
+    }
+
+    #[test]
+    fn test_parse_rejects_non_calls() {
+        let parser = ToolCallParser::new().with_known_tools(["echo"]);
+        for content in [
+            "The answer is 42.",
+            r#"{"plan": "read the file"}"#,
+            r#"{"name": "Ada"}"#,
+            r#"{"name": "delete_everything", "arguments": {}}"#,
+            "<tool_call>not json</tool_call>",
+        ] {
+            assert!(parser.parse(content).is_none(), "parsed {:?}", content);
+        }
+
+        let hermes_only = ToolCallParser::new().with_formats([TextToolFormat::Hermes]);
+        assert!(hermes_only
+            .parse(r#"{"name": "echo", "arguments": {}}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_recover_rewrites_response() {
+        let mut response = ChatResponse {
+            choices: vec![Choice {
+                message: ChatMessage::assistant(
+                    "<tool_call>{\"name\": \"echo\", \"arguments\": {\"message\": \"hi\"}}</tool_call>",
+                ),
+                finish_reason: Some(FinishReason::Stop),
+            }],
+            usage: None,
+            served_by: None,
+        };
+
+        assert_eq!(ToolCallParser::new().recover(&mut response), 1);
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert!(choice.message.content.is_none());
+        assert_eq!(choice.message.tool_calls.as_ref().unwrap()[0].id, "call_0");
+
+        // Native tool calls are left alone
+        assert_eq!(ToolCallParser::new().recover(&mut response), 0);
+    }
+}
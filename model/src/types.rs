@@ -1,5 +1,10 @@
+use crate::provider::{CancellationToken, ModelError, ModelResult};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -16,6 +21,16 @@ pub struct ChatMessage {
     pub content: Option<String>,
     pub tool_calls: Option<Vec<ToolCall>>,
     pub tool_call_id: Option<String>,
+    /// Chain of thought emitted before the answer by reasoning models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Opaque signature the backend attached to `reasoning`, which it needs
+    /// back unchanged to accept the reasoning in a later request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_signature: Option<String>,
+    /// Images for vision-capable models, on user and tool messages
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageAttachment>,
 }
 
 impl ChatMessage {
@@ -25,6 +40,9 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_calls: None,
             tool_call_id: None,
+            reasoning: None,
+            reasoning_signature: None,
+            images: Vec::new(),
         }
     }
 
@@ -34,6 +52,9 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_calls: None,
             tool_call_id: None,
+            reasoning: None,
+            reasoning_signature: None,
+            images: Vec::new(),
         }
     }
 
@@ -43,6 +64,9 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_calls: None,
             tool_call_id: None,
+            reasoning: None,
+            reasoning_signature: None,
+            images: Vec::new(),
         }
     }
 
@@ -52,6 +76,9 @@ impl ChatMessage {
             content,
             tool_calls: Some(tool_calls),
             tool_call_id: None,
+            reasoning: None,
+            reasoning_signature: None,
+            images: Vec::new(),
         }
     }
 
@@ -61,10 +88,137 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_calls: None,
             tool_call_id: Some(tool_call_id.into()),
+            reasoning: None,
+            reasoning_signature: None,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn with_reasoning(mut self, reasoning: impl Into<String>) -> Self {
+        self.reasoning = Some(reasoning.into());
+        self
+    }
+
+    pub fn with_image(mut self, image: ImageAttachment) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    pub fn with_images(mut self, images: impl IntoIterator<Item = ImageAttachment>) -> Self {
+        self.images.extend(images);
+        self
+    }
+}
+
+/// Image attached to a message, either inline or as a file read when the
+/// request is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageAttachment {
+    /// Base64 data without a `data:` URL prefix
+    Base64 {
+        data: String,
+        /// Detected from the data when absent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        media_type: Option<String>,
+    },
+    Path {
+        path: PathBuf,
+    },
+}
+
+/// Image ready to put on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedImage {
+    pub media_type: String,
+    pub data: String,
+}
+
+impl EncodedImage {
+    /// `data:` URL, as used by OpenAI-style content parts.
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.media_type, self.data)
+    }
+}
+
+impl ImageAttachment {
+    pub fn base64(data: impl Into<String>) -> Self {
+        Self::Base64 {
+            data: data.into(),
+            media_type: None,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::Base64 {
+            data: BASE64.encode(bytes),
+            media_type: sniff_media_type(bytes).map(str::to_string),
+        }
+    }
+
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self::Path { path: path.into() }
+    }
+
+    /// Base64 data and media type, reading the file for path attachments.
+    pub fn encode(&self) -> ModelResult<EncodedImage> {
+        match self {
+            Self::Base64 { data, media_type } => {
+                let media_type = match media_type {
+                    Some(media_type) => media_type.clone(),
+                    None => {
+                        // A few leading bytes are enough to recognize the format
+                        let prefix: String = data.chars().take(16).collect();
+                        let bytes = BASE64.decode(prefix).unwrap_or_default();
+                        sniff_media_type(&bytes).unwrap_or("image/png").to_string()
+                    }
+                };
+                Ok(EncodedImage {
+                    media_type,
+                    data: data.clone(),
+                })
+            }
+            Self::Path { path } => {
+                let bytes = std::fs::read(path).map_err(|e| ModelError::InvalidConfig {
+                    message: format!("Cannot read image {}: {}", path.display(), e),
+                })?;
+                let media_type = sniff_media_type(&bytes)
+                    .or_else(|| {
+                        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+                        match ext.as_str() {
+                            "png" => Some("image/png"),
+                            "jpg" | "jpeg" => Some("image/jpeg"),
+                            "gif" => Some("image/gif"),
+                            "webp" => Some("image/webp"),
+                            _ => None,
+                        }
+                    })
+                    .ok_or_else(|| ModelError::InvalidConfig {
+                        message: format!("Unrecognized image format: {}", path.display()),
+                    })?;
+                Ok(EncodedImage {
+                    media_type: media_type.to_string(),
+                    data: BASE64.encode(&bytes),
+                })
+            }
         }
     }
 }
 
+fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
@@ -99,15 +253,37 @@ pub struct FunctionDefinition {
     pub parameters: JsonSchema,
 }
 
+/// Parameter schema of a tool: a JSON Schema object at the root.
+///
+/// Arguments can be checked against it with [`JsonSchema::validate_value`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonSchema {
     #[serde(rename = "type")]
     pub schema_type: SchemaType,
     pub properties: Option<HashMap<String, PropertySchema>>,
     pub required: Option<Vec<String>>,
+    /// `Some(false)` rejects arguments not listed in `properties`
+    #[serde(
+        default,
+        rename = "additionalProperties",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_properties: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for JsonSchema {
+    /// An object schema with no properties.
+    fn default() -> Self {
+        Self {
+            schema_type: SchemaType::Object,
+            properties: None,
+            required: None,
+            additional_properties: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SchemaType {
     Object,
@@ -118,12 +294,121 @@ pub enum SchemaType {
     Array,
 }
 
+/// Schema of a single value, covering a practical subset of JSON Schema.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertySchema {
-    #[serde(rename = "type")]
-    pub schema_type: SchemaType,
+    /// `None` for schemas that constrain the value only through `one_of`
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub schema_type: Option<SchemaType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Element schema for arrays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub items: Option<Box<PropertySchema>>,
+    /// Allowed values
+    #[serde(default, rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Inclusive lower bound for numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    /// Inclusive upper bound for numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+    /// Nested properties for objects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub properties: Option<HashMap<String, PropertySchema>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required: Option<Vec<String>>,
+    #[serde(
+        default,
+        rename = "additionalProperties",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_properties: Option<bool>,
+    /// Alternatives, exactly one of which must match; replaces the type check
+    #[serde(default, rename = "oneOf", skip_serializing_if = "Option::is_none")]
+    pub one_of: Option<Vec<PropertySchema>>,
+}
+
+impl Default for PropertySchema {
+    /// An unconstrained string.
+    fn default() -> Self {
+        Self::new(SchemaType::String)
+    }
+}
+
+impl PropertySchema {
+    pub fn new(schema_type: SchemaType) -> Self {
+        Self {
+            schema_type: Some(schema_type),
+            description: None,
+            items: None,
+            enum_values: None,
+            default: None,
+            minimum: None,
+            maximum: None,
+            properties: None,
+            required: None,
+            additional_properties: None,
+            one_of: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_items(mut self, items: PropertySchema) -> Self {
+        self.items = Some(Box::new(items));
+        self
+    }
+
+    pub fn with_enum<V: Into<serde_json::Value>>(
+        mut self,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.enum_values = Some(values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_default(mut self, default: impl Into<serde_json::Value>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
+    pub fn with_range(mut self, minimum: Option<f64>, maximum: Option<f64>) -> Self {
+        self.minimum = minimum;
+        self.maximum = maximum;
+        self
+    }
+
+    pub fn with_property(mut self, name: impl Into<String>, schema: PropertySchema) -> Self {
+        self.properties
+            .get_or_insert_with(HashMap::new)
+            .insert(name.into(), schema);
+        self
+    }
+
+    pub fn with_required(mut self, required: &[&str]) -> Self {
+        self.required = Some(required.iter().map(|r| r.to_string()).collect());
+        self
+    }
+
+    pub fn with_additional_properties(mut self, allowed: bool) -> Self {
+        self.additional_properties = Some(allowed);
+        self
+    }
+
+    /// Constrain the value to the alternatives; the schema's own type is
+    /// dropped so it does not contradict them.
+    pub fn with_one_of(mut self, alternatives: Vec<PropertySchema>) -> Self {
+        self.schema_type = None;
+        self.one_of = Some(alternatives);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -134,6 +419,50 @@ pub struct ChatRequest {
     pub tool_choice: Option<ToolChoice>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
+    /// Nucleus sampling: keep the smallest set of tokens whose probabilities
+    /// add up to `top_p`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Sample only among the `top_k` most likely tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// Fixed sampling seed, for reproducible output at a given temperature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Sequences that end generation when produced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Penalty applied to tokens that already appeared; 1.0 disables it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    /// Context window to allocate for this request, overriding the provider
+    /// default. Ignored by OpenAI-compatible servers, which fix it at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    /// How long the server keeps the model loaded after the request. Ignored
+    /// by OpenAI-compatible servers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<KeepAlive>,
+    /// Constrain the reply to JSON, optionally matching a schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Enable (`true`) or disable (`false`) thinking on reasoning models;
+    /// `None` leaves the model's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+    /// Send the `reasoning` of earlier assistant messages back to the model.
+    /// Off by default, since reasoning is rarely useful context and costs tokens
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_reasoning: bool,
+    /// Abandons the request when cancelled, closing the connection so the
+    /// backend stops generating. Not part of the request's identity, so it
+    /// is never serialized
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
+    /// Task the request is sent for. A governed backend takes turns between
+    /// tasks, so this is never serialized either
+    #[serde(skip)]
+    pub task: Option<String>,
 }
 
 impl ChatRequest {
@@ -145,6 +474,18 @@ impl ChatRequest {
             tool_choice: None,
             temperature: None,
             max_tokens: None,
+            top_p: None,
+            top_k: None,
+            seed: None,
+            stop: Vec::new(),
+            repeat_penalty: None,
+            num_ctx: None,
+            keep_alive: None,
+            response_format: None,
+            think: None,
+            include_reasoning: false,
+            cancellation: None,
+            task: None,
         }
     }
 
@@ -163,12 +504,155 @@ impl ChatRequest {
         self.max_tokens = Some(max_tokens);
         self
     }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.stop = stop.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_repeat_penalty(mut self, repeat_penalty: f32) -> Self {
+        self.repeat_penalty = Some(repeat_penalty);
+        self
+    }
+
+    pub fn with_num_ctx(mut self, num_ctx: u32) -> Self {
+        self.num_ctx = Some(num_ctx);
+        self
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+
+    pub fn with_think(mut self, think: bool) -> Self {
+        self.think = Some(think);
+        self
+    }
+
+    pub fn with_reasoning_in_history(mut self) -> Self {
+        self.include_reasoning = true;
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_task(mut self, task: impl Into<String>) -> Self {
+        self.task = Some(task.into());
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+/// How long a server keeps a model in memory after a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepAlive {
+    /// Unload after this long idle; zero unloads right after the request
+    For(Duration),
+    /// Keep loaded until the server restarts
+    Forever,
+}
+
+impl KeepAlive {
+    /// Ollama's encoding: seconds, or a negative number for no expiry.
+    pub fn as_seconds(&self) -> i64 {
+        match self {
+            Self::For(duration) => duration.as_secs() as i64,
+            Self::Forever => -1,
+        }
+    }
+}
+
+impl std::str::FromStr for KeepAlive {
+    type Err = String;
+
+    /// Parse `forever`, `-1`, or a count of seconds with an optional `s`,
+    /// `m` or `h` suffix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("forever") || s == "-1" {
+            return Ok(Self::Forever);
+        }
+        let invalid = || {
+            format!(
+                "Invalid keep-alive '{}': expected e.g. 30s, 5m, 1h or forever",
+                s
+            )
+        };
+        let (digits, unit) = match s.char_indices().last() {
+            Some((i, 's')) => (&s[..i], 1),
+            Some((i, 'm')) => (&s[..i], 60),
+            Some((i, 'h')) => (&s[..i], 60 * 60),
+            _ => (s, 1),
+        };
+        let count: u64 = digits.parse().map_err(|_| invalid())?;
+        // Sent to Ollama as a signed number of seconds
+        let seconds = count
+            .checked_mul(unit)
+            .filter(|seconds| i64::try_from(*seconds).is_ok())
+            .ok_or_else(|| format!("Invalid keep-alive '{}': too long", s))?;
+        Ok(Self::For(Duration::from_secs(seconds)))
+    }
+}
+
+/// Output constraint for a chat request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ResponseFormat {
+    /// Any syntactically valid JSON value
+    Json,
+    /// JSON matching `schema`; `name` identifies the schema to providers that require one
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+    },
+}
+
+impl ResponseFormat {
+    pub fn json_schema(name: impl Into<String>, schema: serde_json::Value) -> Self {
+        Self::JsonSchema {
+            name: name.into(),
+            schema,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub choices: Vec<Choice>,
     pub usage: Option<Usage>,
+    /// Backend that produced this response, set by routing providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -186,11 +670,128 @@ pub enum FinishReason {
     ContentFilter,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Server-side timing breakdown, when the backend reports one
+    pub timings: Option<UsageTimings>,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            timings: None,
+        }
+    }
+
+    pub fn with_timings(mut self, timings: UsageTimings) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Generation throughput, computed from the reported eval duration.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let eval = self.timings.as_ref()?.eval_duration?;
+        if eval.is_zero() {
+            return None;
+        }
+        Some(self.eval_tokens() as f64 / eval.as_secs_f64())
+    }
+
+    /// Prompt processing throughput, computed from the prompt eval duration.
+    pub fn prompt_tokens_per_second(&self) -> Option<f64> {
+        let prompt_eval = self.timings.as_ref()?.prompt_eval_duration?;
+        if prompt_eval.is_zero() {
+            return None;
+        }
+        Some(self.prompt_eval_tokens() as f64 / prompt_eval.as_secs_f64())
+    }
+
+    /// Completion tokens generated within `eval_duration`.
+    fn eval_tokens(&self) -> u32 {
+        match &self.timings {
+            Some(t) if t.eval_duration.is_some() => t.eval_tokens.unwrap_or(self.completion_tokens),
+            _ => 0,
+        }
+    }
+
+    /// Prompt tokens evaluated within `prompt_eval_duration`.
+    fn prompt_eval_tokens(&self) -> u32 {
+        match &self.timings {
+            Some(t) if t.prompt_eval_duration.is_some() => {
+                t.prompt_eval_tokens.unwrap_or(self.prompt_tokens)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Add another response's usage into this running total.
+    ///
+    /// Throughput stays limited to the calls that reported timings, so
+    /// untimed calls do not inflate it.
+    pub fn accumulate(&mut self, other: &Usage) {
+        let eval_tokens = self.eval_tokens() + other.eval_tokens();
+        let prompt_eval_tokens = self.prompt_eval_tokens() + other.prompt_eval_tokens();
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        if let Some(other_timings) = &other.timings {
+            self.timings
+                .get_or_insert_with(UsageTimings::default)
+                .accumulate(other_timings);
+        }
+        if let Some(timings) = &mut self.timings {
+            if timings.eval_duration.is_some() {
+                timings.eval_tokens = Some(eval_tokens);
+            }
+            if timings.prompt_eval_duration.is_some() {
+                timings.prompt_eval_tokens = Some(prompt_eval_tokens);
+            }
+        }
+    }
+}
+
+/// Where time went while serving a request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTimings {
+    /// Wall time spent serving the request
+    pub total_duration: Option<Duration>,
+    /// Time spent loading the model into memory
+    pub load_duration: Option<Duration>,
+    /// Time spent evaluating the prompt
+    pub prompt_eval_duration: Option<Duration>,
+    /// Time spent generating the completion
+    pub eval_duration: Option<Duration>,
+    /// Time spent waiting for a free slot before the request was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_duration: Option<Duration>,
+    /// Completion tokens covered by `eval_duration`, when that is fewer
+    /// than the usage's total because some calls reported no timings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_tokens: Option<u32>,
+    /// Prompt tokens covered by `prompt_eval_duration`, likewise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_tokens: Option<u32>,
+}
+
+impl UsageTimings {
+    pub fn accumulate(&mut self, other: &UsageTimings) {
+        fn add(total: &mut Option<Duration>, other: Option<Duration>) {
+            if let Some(other) = other {
+                *total = Some(total.unwrap_or_default() + other);
+            }
+        }
+        add(&mut self.total_duration, other.total_duration);
+        add(&mut self.load_duration, other.load_duration);
+        add(&mut self.prompt_eval_duration, other.prompt_eval_duration);
+        add(&mut self.eval_duration, other.eval_duration);
+        add(&mut self.queue_duration, other.queue_duration);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -230,6 +831,72 @@ mod tests {
         assert_eq!(request.model, "llama3.1:8b");
         assert_eq!(request.temperature, Some(0.7));
         assert_eq!(request.max_tokens, Some(1000));
+
+        // Unset sampling parameters stay out of the serialized request
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("seed").is_none());
+        assert!(json.get("stop").is_none());
+
+        let seeded = request
+            .with_seed(7)
+            .with_stop(["END"])
+            .with_keep_alive(KeepAlive::For(Duration::from_secs(300)));
+        assert_eq!(seeded.seed, Some(7));
+        assert_eq!(seeded.stop, vec!["END".to_string()]);
+        assert_eq!(seeded.keep_alive.unwrap().as_seconds(), 300);
+        assert_eq!(KeepAlive::Forever.as_seconds(), -1);
+    }
+
+    #[test]
+    fn test_keep_alive_from_str() {
+        let parse = |s: &str| s.parse::<KeepAlive>();
+        assert_eq!(parse("300"), Ok(KeepAlive::For(Duration::from_secs(300))));
+        assert_eq!(parse("30s"), Ok(KeepAlive::For(Duration::from_secs(30))));
+        assert_eq!(parse("5m"), Ok(KeepAlive::For(Duration::from_secs(300))));
+        assert_eq!(parse("2h"), Ok(KeepAlive::For(Duration::from_secs(7200))));
+        assert_eq!(parse("0"), Ok(KeepAlive::For(Duration::ZERO)));
+        assert_eq!(parse("forever"), Ok(KeepAlive::Forever));
+        assert_eq!(parse("-1"), Ok(KeepAlive::Forever));
+        assert!(parse("soon").is_err());
+        assert!(parse("m").is_err());
+        assert!(parse("-5m").is_err());
+        assert!(parse("-2").is_err());
+        assert!(parse("18446744073709551615h").is_err());
+        assert!(parse("9223372036854775808").is_err());
+    }
+
+    #[test]
+    fn test_usage_throughput_and_accumulation() {
+        let mut total = Usage::default();
+        assert!(total.tokens_per_second().is_none());
+
+        let first = Usage::new(100, 50).with_timings(UsageTimings {
+            prompt_eval_duration: Some(Duration::from_millis(500)),
+            eval_duration: Some(Duration::from_secs(2)),
+            ..Default::default()
+        });
+        assert_eq!(first.total_tokens, 150);
+        assert_eq!(first.tokens_per_second(), Some(25.0));
+        assert_eq!(first.prompt_tokens_per_second(), Some(200.0));
+
+        total.accumulate(&first);
+        total.accumulate(&Usage::new(10, 30));
+        total.accumulate(&Usage::new(0, 20).with_timings(UsageTimings {
+            eval_duration: Some(Duration::from_secs(2)),
+            load_duration: Some(Duration::from_secs(1)),
+            ..Default::default()
+        }));
+
+        assert_eq!(total.prompt_tokens, 110);
+        assert_eq!(total.completion_tokens, 100);
+        assert_eq!(total.total_tokens, 210);
+        let timings = total.timings.as_ref().unwrap();
+        assert_eq!(timings.eval_duration, Some(Duration::from_secs(4)));
+        assert_eq!(timings.load_duration, Some(Duration::from_secs(1)));
+        assert!(timings.total_duration.is_none());
+        // The untimed call's 30 tokens do not count towards throughput
+        assert_eq!(total.tokens_per_second(), Some(17.5));
+        assert_eq!(total.prompt_tokens_per_second(), Some(200.0));
     }
 
     #[test]
@@ -240,4 +907,34 @@ mod tests {
         assert_eq!(message.content, deserialized.content);
         assert_eq!(message.role, deserialized.role);
     }
+
+    #[test]
+    fn test_image_attachments() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let encoded = ImageAttachment::from_bytes(png).encode().unwrap();
+        assert_eq!(encoded.media_type, "image/png");
+        assert!(encoded
+            .data_url()
+            .starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+        let sniffed = ImageAttachment::base64(encoded.data.clone())
+            .encode()
+            .unwrap();
+        assert_eq!(sniffed.media_type, "image/png");
+
+        let path = std::env::temp_dir().join(format!("nanna-image-{}.jpg", std::process::id()));
+        std::fs::write(&path, [0xFF, 0xD8, 0xFF, 0xE0]).unwrap();
+        let from_file = ImageAttachment::path(&path).encode().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.media_type, "image/jpeg");
+        assert_eq!(from_file.data, "/9j/4A==");
+
+        let message = ChatMessage::user("Look").with_image(ImageAttachment::path("a.png"));
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["images"][0]["type"], "path");
+        let round_trip: ChatMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(round_trip.images, message.images);
+        let plain = serde_json::to_value(ChatMessage::user("hi")).unwrap();
+        assert!(plain.get("images").is_none());
+    }
 }
@@ -0,0 +1,232 @@
+#![cfg(feature = "anthropic")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{
+    AnthropicConfig, AnthropicProvider, ChatMessage, ChatRequest, FinishReason, FunctionCall,
+    FunctionDefinition, JsonSchema, ModelError, ModelProvider, SchemaType, ToolCall, ToolChoice,
+    ToolDefinition,
+};
+use serde_json::json;
+use std::time::Duration;
+
+fn fixture(status: u16, body: &str) -> StubResponse {
+    StubResponse::text(status, "application/json", body)
+}
+
+fn make_provider(server: &StubServer) -> AnthropicProvider {
+    AnthropicProvider::new(
+        AnthropicConfig::default()
+            .with_base_url(&server.base_url)
+            .with_api_key("test-key"),
+    )
+    .expect("provider creation")
+}
+
+fn echo_tool() -> ToolDefinition {
+    ToolDefinition {
+        function: FunctionDefinition {
+            name: "echo".to_string(),
+            description: "Echo back the provided message".to_string(),
+            parameters: JsonSchema {
+                schema_type: SchemaType::Object,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_chat_round_trips_tool_use_blocks() {
+    let server = StubServer::start(vec![fixture(
+        200,
+        include_str!("fixtures/anthropic/tool_use.json"),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let history = vec![
+        ChatMessage::system("You are helpful"),
+        ChatMessage::user("Say hi"),
+        ChatMessage::assistant_with_tools(
+            None,
+            vec![ToolCall {
+                id: "toolu_0".to_string(),
+                function: FunctionCall {
+                    name: "echo".to_string(),
+                    arguments: json!({"message": "first"}),
+                },
+            }],
+        ),
+        ChatMessage::tool_response("toolu_0", "first"),
+    ];
+    let request = ChatRequest::new("claude-sonnet-4-5", history).with_tools(vec![echo_tool()]);
+
+    let response = provider.chat(request).await.expect("chat");
+
+    let choice = &response.choices[0];
+    assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+    assert_eq!(
+        choice.message.content.as_deref(),
+        Some("I'll echo that back for you.")
+    );
+    let calls = choice.message.tool_calls.as_ref().unwrap();
+    assert_eq!(calls[0].id, "toolu_01A09q90qw90lq917835lq9");
+    assert_eq!(calls[0].function.arguments["message"], "hi");
+    let usage = response.usage.unwrap();
+    assert_eq!(usage.prompt_tokens, 412);
+    assert_eq!(usage.total_tokens, 470);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/v1/messages");
+    assert_eq!(requests[0].header("x-api-key"), Some("test-key"));
+    assert_eq!(requests[0].header("anthropic-version"), Some("2023-06-01"));
+
+    let body = requests[0].json();
+    assert_eq!(body["system"], "You are helpful");
+    assert_eq!(body["tools"][0]["name"], "echo");
+    assert_eq!(body["tool_choice"]["type"], "auto");
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+    assert_eq!(messages[1]["content"][0]["input"]["message"], "first");
+    assert_eq!(messages[2]["role"], "user");
+    assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+    assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_0");
+}
+
+#[tokio::test]
+async fn test_chat_with_thinking() {
+    let server = StubServer::start(vec![fixture(
+        200,
+        include_str!("fixtures/anthropic/thinking.json"),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let request =
+        ChatRequest::new("claude-sonnet-4-5", vec![ChatMessage::user("2+2?")]).with_think(true);
+    let response = provider.chat(request).await.expect("chat");
+
+    let message = &response.choices[0].message;
+    assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+    assert_eq!(message.content.as_deref(), Some("4"));
+    assert_eq!(message.reasoning.as_deref(), Some("Two plus two is four."));
+
+    let body = server.requests()[0].json();
+    assert_eq!(body["thinking"]["type"], "enabled");
+    assert!(body.get("temperature").is_none());
+}
+
+#[tokio::test]
+async fn test_signed_thinking_is_sent_back() {
+    let thinking = include_str!("fixtures/anthropic/thinking.json");
+    let server = StubServer::start(vec![fixture(200, thinking), fixture(200, thinking)]).await;
+    let provider = make_provider(&server);
+
+    let request =
+        ChatRequest::new("claude-sonnet-4-5", vec![ChatMessage::user("2+2?")]).with_think(true);
+    let response = provider.chat(request).await.expect("chat");
+    let answer = response.choices[0].message.clone();
+    assert_eq!(
+        answer.reasoning_signature.as_deref(),
+        Some("EqQBCgIYAhIM1gbcDa9GJwZA2b3hGgxBdjrkzLoky3dl1pkiMOYds")
+    );
+
+    let mut request = ChatRequest::new(
+        "claude-sonnet-4-5",
+        vec![
+            ChatMessage::user("2+2?"),
+            answer,
+            ChatMessage::user("And 3+3?"),
+        ],
+    )
+    .with_tools(vec![echo_tool()])
+    .with_think(true);
+    request.tool_choice = Some(ToolChoice::Required);
+    provider.chat(request).await.expect("chat");
+
+    let body = server.requests()[1].json();
+    let blocks = body["messages"][1]["content"].as_array().unwrap();
+    assert_eq!(blocks[0]["type"], "thinking");
+    assert_eq!(blocks[0]["thinking"], "Two plus two is four.");
+    assert_eq!(
+        blocks[0]["signature"],
+        "EqQBCgIYAhIM1gbcDa9GJwZA2b3hGgxBdjrkzLoky3dl1pkiMOYds"
+    );
+    assert_eq!(blocks[1]["text"], "4");
+    assert!(body.get("tool_choice").is_none());
+}
+
+#[tokio::test]
+async fn test_slow_reply_times_out() {
+    let server = StubServer::start(vec![fixture(
+        200,
+        include_str!("fixtures/anthropic/thinking.json"),
+    )
+    .delayed(Duration::from_millis(500))])
+    .await;
+    let provider = AnthropicProvider::new(
+        AnthropicConfig::default()
+            .with_base_url(&server.base_url)
+            .with_timeout(Duration::from_millis(50)),
+    )
+    .unwrap();
+
+    let err = provider
+        .chat(ChatRequest::new(
+            "claude-sonnet-4-5",
+            vec![ChatMessage::user("hi")],
+        ))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ModelError::Timeout { elapsed } if elapsed == Duration::from_millis(50)),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_http_errors_are_classified() {
+    let server = StubServer::start(vec![
+        fixture(529, include_str!("fixtures/anthropic/overloaded.json")),
+        StubResponse::json(
+            401,
+            json!({"type": "error", "error": {"type": "authentication_error", "message": "invalid x-api-key"}}),
+        ),
+    ])
+    .await;
+    let provider = make_provider(&server);
+    let request = ChatRequest::new("claude-sonnet-4-5", vec![ChatMessage::user("hi")]);
+
+    let err = provider.chat(request.clone()).await.unwrap_err();
+    assert!(matches!(err, ModelError::ServiceUnavailable { .. }));
+
+    let err = provider.chat(request).await.unwrap_err();
+    assert!(matches!(err, ModelError::Authentication));
+}
+
+#[tokio::test]
+async fn test_list_models_and_health_check() {
+    let models = include_str!("fixtures/anthropic/models.json");
+    let server = StubServer::start(vec![fixture(200, models), fixture(200, models)]).await;
+    let provider = make_provider(&server);
+
+    let listed = provider.list_models().await.expect("list_models");
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].name, "claude-sonnet-4-5");
+    assert_eq!(
+        listed[0].modified_at.as_deref(),
+        Some("2025-09-29T00:00:00Z")
+    );
+
+    provider.health_check().await.expect("health_check");
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|r| r.path == "/v1/models"));
+}
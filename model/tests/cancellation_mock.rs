@@ -0,0 +1,99 @@
+#![cfg(feature = "ollama")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{
+    CancellationToken, ChatMessage, ChatRequest, ModelError, ModelProvider, OllamaConfig,
+    OllamaProvider, RetryConfig, RetryingProvider,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn make_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(OllamaConfig::default().with_base_url(&server.base_url))
+        .expect("provider creation")
+}
+
+fn slow_reply() -> StubResponse {
+    StubResponse::json(
+        200,
+        json!({"message": {"role": "assistant", "content": "too late"}, "done": true}),
+    )
+    .delayed(Duration::from_secs(30))
+}
+
+fn cancel_after(token: &CancellationToken, delay: Duration) {
+    let token = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        token.cancel();
+    });
+}
+
+async fn wait_for_disconnect(server: &StubServer) {
+    for _ in 0..100 {
+        if server.disconnects() > 0 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("client never closed the connection");
+}
+
+#[tokio::test]
+async fn test_cancelled_chat_closes_connection() {
+    let server = StubServer::start(vec![slow_reply()]).await;
+    let provider = make_provider(&server);
+    let token = CancellationToken::new();
+    cancel_after(&token, Duration::from_millis(50));
+
+    let started = Instant::now();
+    let request =
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("hi")]).with_cancellation(token);
+    let err = provider.chat(request).await.unwrap_err();
+
+    assert!(matches!(err, ModelError::Cancelled), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    wait_for_disconnect(&server).await;
+}
+
+#[tokio::test]
+async fn test_cancelled_stream_closes_connection() {
+    let server = StubServer::start(vec![slow_reply()]).await;
+    let provider = make_provider(&server);
+    let token = CancellationToken::new();
+    cancel_after(&token, Duration::from_millis(50));
+
+    let request =
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("hi")]).with_cancellation(token);
+    let streaming = provider.as_streaming().expect("ollama streams");
+    let err = match streaming.chat_stream(request).await {
+        Ok(_) => panic!("stream opened after cancellation"),
+        Err(e) => e,
+    };
+
+    assert!(matches!(err, ModelError::Cancelled), "{}", err);
+    wait_for_disconnect(&server).await;
+}
+
+#[tokio::test]
+async fn test_cancellation_is_not_retried() {
+    let server = StubServer::start(vec![slow_reply(), slow_reply()]).await;
+    let provider = RetryingProvider::new(
+        Arc::new(make_provider(&server)),
+        RetryConfig::new().with_max_attempts(3),
+    )
+    .unwrap();
+    let token = CancellationToken::new();
+    cancel_after(&token, Duration::from_millis(50));
+
+    let request =
+        ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("hi")]).with_cancellation(token);
+    let err = provider.chat(request).await.unwrap_err();
+
+    assert!(matches!(err, ModelError::Cancelled), "{}", err);
+    wait_for_disconnect(&server).await;
+    assert_eq!(server.requests().len(), 1);
+}
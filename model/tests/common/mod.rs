@@ -0,0 +1,209 @@
+//! Minimal local HTTP stub used to exercise providers without a model server.
+//!
+//! Each accepted connection serves exactly one request with the next queued
+//! response, then closes. Requests are recorded so tests can assert on the
+//! payload a provider sent over the wire.
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+    /// How long the server "generates" before answering
+    pub delay: Duration,
+    /// Pause before each line of the body after the first
+    pub line_interval: Duration,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            body: body.to_string(),
+            delay: Duration::ZERO,
+            line_interval: Duration::ZERO,
+        }
+    }
+
+    pub fn text(status: u16, content_type: &str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: content_type.to_string(),
+            body: body.into(),
+            delay: Duration::ZERO,
+            line_interval: Duration::ZERO,
+        }
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Send the body one line at a time, `interval` apart.
+    pub fn trickled(mut self, interval: Duration) -> Self {
+        self.line_interval = interval;
+        self
+    }
+}
+
+pub struct StubServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    disconnects: Arc<AtomicUsize>,
+}
+
+impl StubServer {
+    pub async fn start(responses: Vec<StubResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let queue = Arc::new(Mutex::new(VecDeque::from(responses)));
+
+        let disconnects = Arc::new(AtomicUsize::new(0));
+
+        let recorded = Arc::clone(&requests);
+        let dropped = Arc::clone(&disconnects);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let Some(request) = read_request(&mut socket).await else {
+                    continue;
+                };
+                recorded.lock().unwrap().push(request);
+
+                let response =
+                    queue.lock().unwrap().pop_front().unwrap_or_else(|| {
+                        StubResponse::text(500, "text/plain", "no stub response")
+                    });
+                if !response.delay.is_zero() {
+                    // A client that hangs up while waiting counts as a disconnect
+                    let mut probe = [0u8; 1];
+                    let hung_up = tokio::select! {
+                        _ = tokio::time::sleep(response.delay) => false,
+                        read = socket.read(&mut probe) => matches!(read, Ok(0) | Err(_)),
+                    };
+                    if hung_up {
+                        dropped.fetch_add(1, Ordering::SeqCst);
+                        continue;
+                    }
+                }
+                let head = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.status,
+                    response.content_type,
+                    response.body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                for (i, line) in response.body.split_inclusive('\n').enumerate() {
+                    if i > 0 && !response.line_interval.is_zero() {
+                        tokio::time::sleep(response.line_interval).await;
+                    }
+                    let _ = socket.write_all(line.as_bytes()).await;
+                    let _ = socket.flush().await;
+                }
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        Self {
+            base_url: format!("http://{}", addr),
+            requests,
+            disconnects,
+        }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Connections the client closed before their delayed response was sent.
+    pub fn disconnects(&self) -> usize {
+        self.disconnects.load(Ordering::SeqCst)
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            Some((k.trim().to_string(), v.trim().to_string()))
+        })
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let body = String::from_utf8_lossy(&buf[header_end..]).to_string();
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
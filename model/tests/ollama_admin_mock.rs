@@ -0,0 +1,175 @@
+#![cfg(feature = "ollama")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{pull_model, KeepAlive, ModelError, ModelProvider, OllamaConfig, OllamaProvider};
+use serde_json::json;
+use std::time::Duration;
+
+fn make_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(OllamaConfig::default().with_base_url(&server.base_url))
+        .expect("provider creation")
+}
+
+fn ndjson(records: &[serde_json::Value]) -> StubResponse {
+    let body: String = records.iter().map(|r| format!("{}\n", r)).collect();
+    StubResponse::text(200, "application/x-ndjson", body)
+}
+
+#[tokio::test]
+async fn test_pull_reports_progress() {
+    let server = StubServer::start(vec![ndjson(&[
+        json!({"status": "pulling manifest"}),
+        json!({"status": "pulling 8eeb52dfb3bb", "digest": "sha256:8eeb52dfb3bb", "total": 1000, "completed": 250}),
+        json!({"status": "pulling 8eeb52dfb3bb", "digest": "sha256:8eeb52dfb3bb", "total": 1000, "completed": 1000}),
+        json!({"status": "verifying sha256 digest"}),
+        json!({"status": "success"}),
+    ])])
+    .await;
+    let provider = make_provider(&server);
+    let admin = provider.as_admin().expect("ollama manages models");
+
+    let mut fractions = Vec::new();
+    pull_model(admin, "qwen3:0.6b", |progress| {
+        fractions.extend(progress.fraction());
+    })
+    .await
+    .expect("pull");
+
+    assert_eq!(fractions, [0.25, 1.0]);
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/api/pull");
+    assert_eq!(request.json()["model"], "qwen3:0.6b");
+    assert_eq!(request.json()["stream"], true);
+}
+
+#[tokio::test]
+async fn test_pull_surfaces_stream_errors() {
+    let server = StubServer::start(vec![ndjson(&[
+        json!({"status": "pulling manifest"}),
+        json!({"error": "pull model manifest: file does not exist"}),
+    ])])
+    .await;
+    let provider = make_provider(&server);
+
+    let err = pull_model(provider.as_admin().unwrap(), "no-such-model", |_| {})
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("file does not exist"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_show_reports_details_and_capabilities() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        json!({
+            "license": "Apache 2.0",
+            "template": "{{ .Prompt }}",
+            "parameters": "temperature 0.6",
+            "details": {
+                "format": "gguf",
+                "family": "qwen3",
+                "parameter_size": "751.63M",
+                "quantization_level": "Q4_K_M"
+            },
+            "model_info": {"qwen3.context_length": 40960},
+            "capabilities": ["completion", "tools", "thinking"],
+            "modified_at": "2025-05-01T10:00:00Z"
+        }),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let details = provider
+        .as_admin()
+        .unwrap()
+        .show("qwen3:0.6b")
+        .await
+        .unwrap();
+
+    assert_eq!(details.name, "qwen3:0.6b");
+    assert_eq!(details.family.as_deref(), Some("qwen3"));
+    assert_eq!(details.parameter_size.as_deref(), Some("751.63M"));
+    assert_eq!(details.quantization.as_deref(), Some("Q4_K_M"));
+    assert_eq!(details.format.as_deref(), Some("gguf"));
+    assert_eq!(details.license.as_deref(), Some("Apache 2.0"));
+    assert_eq!(details.capabilities.context_length, Some(40960));
+    assert_eq!(details.capabilities.tools, Some(true));
+    assert_eq!(details.capabilities.vision, Some(false));
+    assert_eq!(details.capabilities.temperature, Some(0.6));
+}
+
+#[tokio::test]
+async fn test_delete_and_missing_models() {
+    let server = StubServer::start(vec![
+        StubResponse::text(200, "text/plain", ""),
+        StubResponse::json(404, json!({"error": "model 'gone' not found"})),
+    ])
+    .await;
+    let provider = make_provider(&server);
+    let admin = provider.as_admin().unwrap();
+
+    admin.delete("qwen3:0.6b").await.expect("delete");
+    let err = admin.delete("gone").await.unwrap_err();
+    assert!(matches!(err, ModelError::ModelNotFound { model } if model == "gone"));
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "DELETE");
+    assert_eq!(requests[0].path, "/api/delete");
+    assert_eq!(requests[0].json()["model"], "qwen3:0.6b");
+}
+
+#[tokio::test]
+async fn test_admin_errors_are_classified_like_chat_errors() {
+    let server = StubServer::start(vec![
+        StubResponse::json(503, json!({"error": "server busy"})),
+        StubResponse::json(400, json!({"error": "invalid model name"})),
+    ])
+    .await;
+    let provider = make_provider(&server);
+    let admin = provider.as_admin().unwrap();
+
+    let err = admin.show("qwen3:0.6b").await.unwrap_err();
+    assert!(
+        matches!(err, ModelError::ServiceUnavailable { .. }),
+        "{}",
+        err
+    );
+    let err = admin.delete("bad name").await.unwrap_err();
+    assert!(matches!(err, ModelError::InvalidConfig { .. }), "{}", err);
+}
+
+#[tokio::test]
+async fn test_warm_loads_model_with_keep_alive() {
+    let server = StubServer::start(vec![
+        StubResponse::json(
+            200,
+            json!({"model": "qwen3:0.6b", "response": "", "done": true, "done_reason": "load"}),
+        ),
+        StubResponse::json(
+            200,
+            json!({"model": "qwen3:0.6b", "response": "", "done": true, "done_reason": "load"}),
+        ),
+    ])
+    .await;
+    let provider = make_provider(&server);
+    let admin = provider.as_admin().unwrap();
+
+    admin
+        .warm("qwen3:0.6b", KeepAlive::For(Duration::from_secs(600)))
+        .await
+        .expect("warm");
+    admin
+        .warm("qwen3:0.6b", KeepAlive::Forever)
+        .await
+        .expect("warm");
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/api/generate");
+    let body = requests[0].json();
+    assert_eq!(body["model"], "qwen3:0.6b");
+    assert_eq!(body["keep_alive"], 600);
+    assert!(body.get("prompt").is_none());
+    assert_eq!(requests[1].json()["keep_alive"], -1);
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "ollama")]
+
 use model::{
     ChatMessage, ChatRequest, ChatResponse, FinishReason, FunctionDefinition, JsonSchema,
     ModelProvider, OllamaConfig, OllamaProvider, PropertySchema, SchemaType, ToolDefinition,
@@ -109,9 +111,10 @@ async fn test_chat_with_tool_definitions() {
     properties.insert(
         "location".to_string(),
         PropertySchema {
-            schema_type: SchemaType::String,
+            schema_type: Some(SchemaType::String),
             description: Some("The city and state, e.g. San Francisco, CA".to_string()),
             items: None,
+            ..Default::default()
         },
     );
 
@@ -123,6 +126,7 @@ async fn test_chat_with_tool_definitions() {
                 schema_type: SchemaType::Object,
                 properties: Some(properties),
                 required: Some(vec!["location".to_string()]),
+                ..Default::default()
             },
         },
     };
@@ -246,9 +250,10 @@ async fn test_ollama_tool_calling_roundtrip() {
     properties.insert(
         "city".to_string(),
         PropertySchema {
-            schema_type: SchemaType::String,
+            schema_type: Some(SchemaType::String),
             description: Some("The city to get weather for".to_string()),
             items: None,
+            ..Default::default()
         },
     );
 
@@ -260,6 +265,7 @@ async fn test_ollama_tool_calling_roundtrip() {
                 schema_type: SchemaType::Object,
                 properties: Some(properties),
                 required: Some(vec!["city".to_string()]),
+                ..Default::default()
             },
         },
     };
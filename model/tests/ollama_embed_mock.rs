@@ -0,0 +1,61 @@
+#![cfg(feature = "ollama")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{EmbeddingProvider, ModelError, ModelProvider, OllamaConfig, OllamaProvider};
+
+fn make_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(OllamaConfig::default().with_base_url(&server.base_url))
+        .expect("provider creation")
+}
+
+#[tokio::test]
+async fn test_embed_sends_batch_and_reads_vectors() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        serde_json::json!({
+            "model": "nomic-embed-text",
+            "embeddings": [[0.1, 0.2, 0.3], [0.4, 0.5, 0.6]],
+            "total_duration": 14143917,
+            "load_duration": 1019500,
+            "prompt_eval_count": 8
+        }),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let embedder = provider.as_embedding().expect("ollama embeds");
+    let inputs = vec!["first".to_string(), "second".to_string()];
+    let response = embedder
+        .embed("nomic-embed-text", &inputs)
+        .await
+        .expect("embed");
+
+    assert_eq!(response.dimensions, 3);
+    assert_eq!(response.embeddings[1], vec![0.4, 0.5, 0.6]);
+    assert_eq!(response.usage.unwrap().prompt_tokens, 8);
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/api/embed");
+    assert_eq!(
+        request.json(),
+        serde_json::json!({"model": "nomic-embed-text", "input": ["first", "second"]})
+    );
+}
+
+#[tokio::test]
+async fn test_embed_reports_missing_model() {
+    let server = StubServer::start(vec![StubResponse::json(
+        404,
+        serde_json::json!({"error": "model \"missing\" not found, try pulling it first"}),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let err = provider
+        .embed("missing", &["text".to_string()])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ModelError::ModelNotFound { model } if model == "missing"));
+}
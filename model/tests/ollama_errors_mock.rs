@@ -0,0 +1,153 @@
+#![cfg(feature = "ollama")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{
+    ChatMessage, ChatRequest, ModelError, ModelProvider, OllamaConfig, OllamaProvider, RetryConfig,
+    RetryingProvider, RouteTarget, RouterProvider, RoutingPolicy,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn make_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(OllamaConfig::default().with_base_url(&server.base_url))
+        .expect("provider creation")
+}
+
+fn request() -> ChatRequest {
+    ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("hi")])
+}
+
+fn reply(content: &str) -> StubResponse {
+    StubResponse::json(
+        200,
+        serde_json::json!({
+            "message": {"role": "assistant", "content": content},
+            "done": true,
+            "done_reason": "stop"
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_chat_classifies_error_statuses() {
+    let server = StubServer::start(vec![
+        StubResponse::json(404, serde_json::json!({"error": "model not found"})),
+        StubResponse::json(400, serde_json::json!({"error": "does not support tools"})),
+        StubResponse::json(503, serde_json::json!({"error": "server busy"})),
+    ])
+    .await;
+    let provider = make_provider(&server);
+
+    let err = provider.chat(request()).await.unwrap_err();
+    assert!(matches!(err, ModelError::ModelNotFound { model } if model == "qwen3:0.6b"));
+    let err = provider.chat(request()).await.unwrap_err();
+    assert!(matches!(err, ModelError::InvalidConfig { .. }));
+    let err = provider.chat(request()).await.unwrap_err();
+    assert!(matches!(err, ModelError::ServiceUnavailable { .. }));
+}
+
+#[tokio::test]
+async fn test_slow_reply_times_out() {
+    let server = StubServer::start(vec![reply("late").delayed(Duration::from_millis(500))]).await;
+    let provider = OllamaProvider::new(
+        OllamaConfig::default()
+            .with_base_url(&server.base_url)
+            .with_timeout(Duration::from_millis(50)),
+    )
+    .unwrap();
+
+    let err = provider.chat(request()).await.unwrap_err();
+    assert!(
+        matches!(err, ModelError::Timeout { elapsed } if elapsed == Duration::from_millis(50)),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_retry_sends_missing_model_once() {
+    let server = StubServer::start(vec![
+        StubResponse::json(404, serde_json::json!({"error": "model not found"})),
+        reply("unreachable"),
+    ])
+    .await;
+    let config = RetryConfig::new()
+        .with_max_attempts(3)
+        .with_backoff(Duration::from_millis(1), Duration::from_millis(5));
+    let provider = RetryingProvider::new(Arc::new(make_provider(&server)), config.clone()).unwrap();
+
+    let err = provider.chat(request()).await.unwrap_err();
+    assert!(matches!(err, ModelError::ModelNotFound { .. }));
+    assert_eq!(server.requests().len(), 1);
+
+    let busy = StubServer::start(vec![
+        StubResponse::json(503, serde_json::json!({"error": "server busy"})),
+        reply("hello"),
+    ])
+    .await;
+    let provider = RetryingProvider::new(Arc::new(make_provider(&busy)), config).unwrap();
+
+    let response = provider.chat(request()).await.unwrap();
+    assert_eq!(
+        response.choices[0].message.content.as_deref(),
+        Some("hello")
+    );
+    assert_eq!(busy.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_router_fails_over_on_server_error() {
+    let broken = StubServer::start(vec![StubResponse::json(
+        500,
+        serde_json::json!({"error": "llama runner process has terminated"}),
+    )])
+    .await;
+    let healthy = StubServer::start(vec![reply("hello")]).await;
+
+    let router = RouterProvider::new(RoutingPolicy::Fallback)
+        .with_target(RouteTarget::new("gpu-a", Arc::new(make_provider(&broken))))
+        .with_target(RouteTarget::new("gpu-b", Arc::new(make_provider(&healthy))));
+
+    let response = router.chat(request()).await.expect("failover");
+    assert_eq!(response.served_by.as_deref(), Some("gpu-b"));
+    assert_eq!(broken.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_embed_classifies_error_statuses_and_fails_over() {
+    let broken = StubServer::start(vec![
+        StubResponse::json(400, serde_json::json!({"error": "input too long"})),
+        StubResponse::json(503, serde_json::json!({"error": "server busy"})),
+    ])
+    .await;
+    let provider = make_provider(&broken);
+    let inputs = ["hello".to_string()];
+
+    let err = provider
+        .as_embedding()
+        .unwrap()
+        .embed("nomic-embed-text", &inputs)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ModelError::InvalidConfig { .. }));
+
+    let healthy = StubServer::start(vec![StubResponse::json(
+        200,
+        serde_json::json!({"model": "nomic-embed-text", "embeddings": [[0.1, 0.2]]}),
+    )])
+    .await;
+    let router = RouterProvider::new(RoutingPolicy::Fallback)
+        .with_target(RouteTarget::new("gpu-a", Arc::new(provider)))
+        .with_target(RouteTarget::new("gpu-b", Arc::new(make_provider(&healthy))));
+
+    let response = router
+        .as_embedding()
+        .expect("router forwards embeddings")
+        .embed("nomic-embed-text", &inputs)
+        .await
+        .expect("failover");
+    assert_eq!(response.embeddings, vec![vec![0.1, 0.2]]);
+    assert_eq!(broken.requests().len(), 2);
+}
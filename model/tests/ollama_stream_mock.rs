@@ -0,0 +1,169 @@
+#![cfg(feature = "ollama")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use futures::StreamExt;
+use model::{
+    collect_stream, ChatMessage, ChatRequest, FinishReason, ModelProvider, OllamaConfig,
+    OllamaProvider,
+};
+
+fn make_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(OllamaConfig::default().with_base_url(&server.base_url))
+        .expect("provider creation")
+}
+
+fn ndjson(records: &[serde_json::Value]) -> StubResponse {
+    let body: String = records.iter().map(|r| format!("{}\n", r)).collect();
+    StubResponse::text(200, "application/x-ndjson", body)
+}
+
+#[tokio::test]
+async fn test_chat_stream_yields_deltas_and_usage() {
+    let server = StubServer::start(vec![ndjson(&[
+        serde_json::json!({"message": {"role": "assistant", "content": "The answer"}, "done": false}),
+        serde_json::json!({"message": {"role": "assistant", "content": " is 4."}, "done": false}),
+        serde_json::json!({
+            "message": {"role": "assistant", "content": ""},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 11,
+            "eval_count": 5
+        }),
+    ])])
+    .await;
+    let provider = make_provider(&server);
+
+    let streaming = provider.as_streaming().expect("ollama streams");
+    let request = ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("2+2?")]);
+    let mut stream = streaming.chat_stream(request).await.expect("chat_stream");
+
+    let mut deltas = Vec::new();
+    let mut final_usage = None;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.expect("chunk");
+        if let Some(delta) = chunk.delta {
+            deltas.push(delta);
+        }
+        if chunk.usage.is_some() {
+            final_usage = chunk.usage;
+        }
+    }
+
+    assert_eq!(deltas, vec!["The answer", " is 4."]);
+    let usage = final_usage.expect("final usage");
+    assert_eq!(usage.prompt_tokens, 11);
+    assert_eq!(usage.completion_tokens, 5);
+
+    let body = server.requests()[0].json();
+    assert_eq!(body["stream"], true);
+}
+
+#[tokio::test]
+async fn test_chat_stream_assembles_tool_calls() {
+    let server = StubServer::start(vec![ndjson(&[
+        serde_json::json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "echo", "arguments": {"message": "hi"}}}]
+            },
+            "done": false
+        }),
+        serde_json::json!({"message": {"role": "assistant", "content": ""}, "done": true}),
+    ])])
+    .await;
+    let provider = make_provider(&server);
+
+    let stream = provider
+        .as_streaming()
+        .unwrap()
+        .chat_stream(ChatRequest::new(
+            "qwen3:0.6b",
+            vec![ChatMessage::user("echo hi")],
+        ))
+        .await
+        .expect("chat_stream");
+    let response = collect_stream(stream, |_| {}).await.expect("collect");
+
+    let choice = &response.choices[0];
+    assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+    let calls = choice.message.tool_calls.as_ref().unwrap();
+    assert_eq!(calls[0].function.name, "echo");
+    assert_eq!(calls[0].function.arguments["message"], "hi");
+}
+
+#[tokio::test]
+async fn test_chat_stream_surfaces_midstream_errors() {
+    let server = StubServer::start(vec![ndjson(&[
+        serde_json::json!({"message": {"role": "assistant", "content": "partial"}, "done": false}),
+        serde_json::json!({"error": "model runner crashed"}),
+    ])])
+    .await;
+    let provider = make_provider(&server);
+
+    let stream = provider
+        .as_streaming()
+        .unwrap()
+        .chat_stream(ChatRequest::new(
+            "qwen3:0.6b",
+            vec![ChatMessage::user("hi")],
+        ))
+        .await
+        .expect("chat_stream");
+    let err = collect_stream(stream, |_| {}).await.unwrap_err();
+    assert!(err.to_string().contains("model runner crashed"));
+}
+
+fn slow_provider(server: &StubServer) -> OllamaProvider {
+    OllamaProvider::new(
+        OllamaConfig::default()
+            .with_base_url(&server.base_url)
+            .with_timeout(std::time::Duration::from_millis(300)),
+    )
+    .expect("provider creation")
+}
+
+#[tokio::test]
+async fn test_chat_stream_outlives_request_timeout() {
+    let records: Vec<serde_json::Value> = (0..5)
+        .map(|i| serde_json::json!({"message": {"role": "assistant", "content": format!("{} ", i)}, "done": false}))
+        .chain([serde_json::json!({"message": {"role": "assistant", "content": ""}, "done": true, "done_reason": "stop"})])
+        .collect();
+    let server = StubServer::start(vec![
+        ndjson(&records).trickled(std::time::Duration::from_millis(100))
+    ])
+    .await;
+    let provider = slow_provider(&server);
+
+    let streaming = provider.as_streaming().expect("ollama streams");
+    let request = ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("count")]);
+    let stream = streaming.chat_stream(request).await.expect("chat_stream");
+    let response = collect_stream(stream, |_| {})
+        .await
+        .expect("stream completes");
+
+    assert_eq!(
+        response.choices[0].message.content.as_deref(),
+        Some("0 1 2 3 4 ")
+    );
+}
+
+#[tokio::test]
+async fn test_chat_stream_times_out_when_idle() {
+    let server = StubServer::start(vec![ndjson(&[
+        serde_json::json!({"message": {"role": "assistant", "content": "hi"}, "done": false}),
+        serde_json::json!({"message": {"role": "assistant", "content": ""}, "done": true}),
+    ])
+    .trickled(std::time::Duration::from_secs(2))])
+    .await;
+    let provider = slow_provider(&server);
+
+    let streaming = provider.as_streaming().expect("ollama streams");
+    let request = ChatRequest::new("qwen3:0.6b", vec![ChatMessage::user("hi")]);
+    let stream = streaming.chat_stream(request).await.expect("chat_stream");
+    let err = collect_stream(stream, |_| {}).await.unwrap_err();
+
+    assert!(matches!(err, model::ModelError::Timeout { .. }), "{}", err);
+}
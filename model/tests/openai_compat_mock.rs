@@ -0,0 +1,200 @@
+#![cfg(feature = "openai")]
+
+mod common;
+
+use common::{StubResponse, StubServer};
+use model::{
+    ChatMessage, ChatRequest, FinishReason, FunctionCall, FunctionDefinition, JsonSchema,
+    ModelError, ModelProvider, OpenAiConfig, OpenAiProvider, SchemaType, ToolCall, ToolDefinition,
+};
+use serde_json::json;
+use std::time::Duration;
+
+fn make_provider(server: &StubServer) -> OpenAiProvider {
+    OpenAiProvider::new(
+        OpenAiConfig::default()
+            .with_base_url(&server.base_url)
+            .with_api_key("test-key"),
+    )
+    .expect("provider creation")
+}
+
+fn echo_tool() -> ToolDefinition {
+    ToolDefinition {
+        function: FunctionDefinition {
+            name: "echo".to_string(),
+            description: "Echo back the provided message".to_string(),
+            parameters: JsonSchema {
+                schema_type: SchemaType::Object,
+                properties: None,
+                required: None,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_chat_round_trips_tool_calls() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "model": "qwen3",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "echo", "arguments": "{\"message\":\"hi\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 30, "completion_tokens": 5, "total_tokens": 35}
+        }),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let history = vec![
+        ChatMessage::system("You are helpful"),
+        ChatMessage::user("Say hi"),
+        ChatMessage::assistant_with_tools(
+            None,
+            vec![ToolCall {
+                id: "call_0".to_string(),
+                function: FunctionCall {
+                    name: "echo".to_string(),
+                    arguments: json!({"message": "first"}),
+                },
+            }],
+        ),
+        ChatMessage::tool_response("call_0", "first"),
+    ];
+    let request = ChatRequest::new("qwen3", history).with_tools(vec![echo_tool()]);
+
+    let response = provider.chat(request).await.expect("chat");
+
+    let choice = &response.choices[0];
+    assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+    assert!(choice.message.content.is_none());
+    let calls = choice.message.tool_calls.as_ref().unwrap();
+    assert_eq!(calls[0].id, "call_1");
+    assert_eq!(calls[0].function.arguments["message"], "hi");
+    let usage = response.usage.unwrap();
+    assert_eq!(usage.prompt_tokens, 30);
+    assert_eq!(usage.total_tokens, 35);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer test-key"));
+
+    let body = requests[0].json();
+    assert_eq!(body["tool_choice"], "auto");
+    assert_eq!(body["tools"][0]["function"]["name"], "echo");
+    assert_eq!(body["messages"][2]["tool_calls"][0]["id"], "call_0");
+    assert_eq!(
+        body["messages"][2]["tool_calls"][0]["function"]["arguments"],
+        "{\"message\":\"first\"}"
+    );
+    assert_eq!(body["messages"][3]["role"], "tool");
+    assert_eq!(body["messages"][3]["tool_call_id"], "call_0");
+}
+
+#[tokio::test]
+async fn test_chat_plain_response() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        json!({
+            "choices": [{
+                "message": {"role": "assistant", "content": "4"},
+                "finish_reason": "stop"
+            }]
+        }),
+    )])
+    .await;
+    let provider = make_provider(&server);
+
+    let response = provider
+        .chat(ChatRequest::new("qwen3", vec![ChatMessage::user("2+2?")]))
+        .await
+        .expect("chat");
+
+    assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+    assert_eq!(response.choices[0].message.content.as_deref(), Some("4"));
+    assert!(response.usage.is_none());
+}
+
+#[tokio::test]
+async fn test_http_errors_are_classified() {
+    let server = StubServer::start(vec![
+        StubResponse::json(429, json!({"error": "slow down"})),
+        StubResponse::json(503, json!({"error": "loading"})),
+    ])
+    .await;
+    let provider = make_provider(&server);
+    let request = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]);
+
+    let err = provider.chat(request.clone()).await.unwrap_err();
+    assert!(matches!(err, ModelError::RateLimit));
+
+    let err = provider.chat(request).await.unwrap_err();
+    assert!(matches!(err, ModelError::ServiceUnavailable { .. }));
+}
+
+#[tokio::test]
+async fn test_slow_reply_times_out() {
+    let server = StubServer::start(vec![
+        StubResponse::json(200, json!({"choices": []})).delayed(Duration::from_millis(500))
+    ])
+    .await;
+    let provider = OpenAiProvider::new(
+        OpenAiConfig::default()
+            .with_base_url(&server.base_url)
+            .with_timeout(Duration::from_millis(50)),
+    )
+    .unwrap();
+
+    let err = provider
+        .chat(ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ModelError::Timeout { elapsed } if elapsed == Duration::from_millis(50)),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_list_models_and_health_check() {
+    let models = json!({
+        "object": "list",
+        "data": [
+            {"id": "qwen3-0.6b", "object": "model", "created": 1700000000, "owned_by": "vllm"},
+            {"id": "llama-3.1-8b", "object": "model", "owned_by": "llamacpp"}
+        ]
+    });
+    let server = StubServer::start(vec![
+        StubResponse::json(200, models.clone()),
+        StubResponse::json(200, models),
+    ])
+    .await;
+    let provider = make_provider(&server);
+
+    let listed = provider.list_models().await.expect("list_models");
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].name, "qwen3-0.6b");
+
+    provider.health_check().await.expect("health_check");
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|r| r.path == "/v1/models"));
+}